@@ -0,0 +1,373 @@
+//! Generates the client from `openapi.json`, the spec the server serves at
+//! `/api-docs/openapi.json`. The server's tests keep that copy up to date.
+//!
+//! Component schemas become the types in `types.rs` and every operation a
+//! method of `Client` in `operations.rs`, both written to `OUT_DIR`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+const SPEC: &str = "openapi.json";
+
+/// Methods in the order an operation registered under several of them
+/// (like `PATCH` with a `PUT` alias) is generated for
+const METHODS: [&str; 5] = ["post", "patch", "get", "put", "delete"];
+
+const KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+    "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+    let spec: Value = serde_json::from_str(&fs::read_to_string(SPEC).expect("failed to read the spec")).expect("invalid spec");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("types.rs"), generate_types(&spec)).unwrap();
+    fs::write(Path::new(&out_dir).join("operations.rs"), generate_operations(&spec)).unwrap();
+}
+
+fn generate_types(spec: &Value) -> String {
+    let mut out = String::new();
+    let Some(schemas) = spec.pointer("/components/schemas").and_then(Value::as_object) else {
+        return out;
+    };
+
+    for (name, schema) in schemas {
+        let name = type_name(name);
+        doc(&mut out, "", schema.get("description"));
+
+        if let Some(variants) = schema.get("enum").and_then(Value::as_array) {
+            enumeration(&mut out, &name, variants);
+        } else if let Some(parts) = schema.get("allOf").and_then(Value::as_array) {
+            out += "#[derive(Debug, Clone, Serialize, Deserialize)]\n";
+            writeln!(out, "pub struct {} {{", name).unwrap();
+            for part in parts {
+                match part.get("$ref") {
+                    Some(reference) => {
+                        let base = ref_name(reference);
+                        writeln!(out, "    #[serde(flatten)]\n    pub {}: {},", snake_case(&base), base).unwrap();
+                    }
+                    None => fields(&mut out, part),
+                }
+            }
+            out += "}\n\n";
+        } else if let Some(variants) = schema.get("oneOf").and_then(Value::as_array).filter(|v| v.iter().all(|v| v.get("$ref").is_some())) {
+            out += "#[derive(Debug, Clone, Serialize, Deserialize)]\n#[serde(untagged)]\n";
+            writeln!(out, "pub enum {} {{", name).unwrap();
+            for variant in variants {
+                let variant = ref_name(&variant["$ref"]);
+                writeln!(out, "    {}({}),", variant, variant).unwrap();
+            }
+            out += "}\n\n";
+        } else if schema.get("properties").is_some() {
+            out += "#[derive(Debug, Clone, Serialize, Deserialize)]\n";
+            writeln!(out, "pub struct {} {{", name).unwrap();
+            fields(&mut out, schema);
+            out += "}\n\n";
+        } else {
+            writeln!(out, "pub type {} = {};\n", name, rust_type(schema, "")).unwrap();
+        }
+    }
+    out
+}
+
+fn enumeration(out: &mut String, name: &str, variants: &[Value]) {
+    let variants: Vec<_> = variants.iter().filter_map(Value::as_str).map(|v| (type_name(v), v)).collect();
+
+    *out += "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]\n";
+    writeln!(out, "pub enum {} {{", name).unwrap();
+    for (variant, value) in &variants {
+        writeln!(out, "    #[serde(rename = {:?})]\n    {},", value, variant).unwrap();
+    }
+    *out += "}\n\n";
+
+    writeln!(out, "impl {} {{\n    pub fn as_str(&self) -> &'static str {{\n        match self {{", name).unwrap();
+    for (variant, value) in &variants {
+        writeln!(out, "            Self::{} => {:?},", variant, value).unwrap();
+    }
+    *out += "        }\n    }\n}\n\n";
+    writeln!(
+        out,
+        "impl std::fmt::Display for {} {{\n    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{\n        f.write_str(self.as_str())\n    }}\n}}\n",
+        name
+    )
+    .unwrap();
+}
+
+/// Fields of an object schema. Optional fields are left out when `None`,
+/// required nullable ones are sent as `null`.
+fn fields(out: &mut String, schema: &Value) {
+    let required: Vec<_> = schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).collect();
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return;
+    };
+
+    for (name, property) in properties {
+        doc(out, "    ", property.get("description"));
+        let field = field_name(name);
+        if field.trim_start_matches("r#") != name {
+            writeln!(out, "    #[serde(rename = {:?})]", name).unwrap();
+        }
+        let ty = rust_type(property, "");
+        if !required.contains(&name.as_str()) {
+            let ty = if ty.starts_with("Option<") { ty } else { format!("Option<{}>", ty) };
+            *out += "    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n";
+            writeln!(out, "    pub {}: {},", field, ty).unwrap();
+        } else {
+            if ty.starts_with("Option<") {
+                *out += "    #[serde(default)]\n";
+            }
+            writeln!(out, "    pub {}: {},", field, ty).unwrap();
+        }
+    }
+}
+
+/// Rust type of a schema, with `prefix` before the names of generated types.
+/// Nested objects without a schema of their own stay JSON values.
+fn rust_type(schema: &Value, prefix: &str) -> String {
+    if let Some(reference) = schema.get("$ref") {
+        return format!("{}{}", prefix, ref_name(reference));
+    }
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let (nulls, others): (Vec<_>, Vec<_>) = variants.iter().partition(|v| v.get("type").and_then(Value::as_str) == Some("null"));
+        return match (nulls.len(), others.as_slice()) {
+            (1, [only]) => format!("Option<{}>", rust_type(only, prefix)),
+            _ => "serde_json::Value".to_string(),
+        };
+    }
+
+    let (ty, nullable) = match schema.get("type") {
+        Some(Value::String(ty)) => (ty.as_str(), false),
+        Some(Value::Array(types)) => {
+            let mut types: Vec<_> = types.iter().filter_map(Value::as_str).collect();
+            let nullable = types.contains(&"null");
+            types.retain(|t| *t != "null");
+            match types.as_slice() {
+                [ty] => (*ty, nullable),
+                _ => ("", nullable),
+            }
+        }
+        _ => ("", false),
+    };
+    let format = schema.get("format").and_then(Value::as_str).unwrap_or("");
+    let ty = match (ty, format) {
+        ("string", "uuid") => "uuid::Uuid".to_string(),
+        ("string", "date") => "chrono::NaiveDate".to_string(),
+        ("string", "date-time") => "chrono::NaiveDateTime".to_string(),
+        ("string", _) => "String".to_string(),
+        ("integer", "int32") => "i32".to_string(),
+        ("integer", _) => "i64".to_string(),
+        ("number", _) => "f64".to_string(),
+        ("boolean", _) => "bool".to_string(),
+        ("array", _) => format!("Vec<{}>", schema.get("items").map_or("serde_json::Value".to_string(), |items| rust_type(items, prefix))),
+        _ => "serde_json::Value".to_string(),
+    };
+    if nullable {
+        format!("Option<{}>", ty)
+    } else {
+        ty
+    }
+}
+
+struct Operation<'a> {
+    method: &'a str,
+    path: &'a str,
+    tag: Option<&'a str>,
+    definition: &'a Map<String, Value>,
+}
+
+fn generate_operations(spec: &Value) -> String {
+    // Operations by ID, keeping the first method in `METHODS` order for IDs
+    // registered under several. Deprecated aliases are left out.
+    let mut operations: BTreeMap<&str, Vec<Operation>> = BTreeMap::new();
+    for (path, item) in spec["paths"].as_object().into_iter().flatten() {
+        for method in METHODS {
+            let Some(definition) = item.get(method).and_then(Value::as_object) else {
+                continue;
+            };
+            if definition.get("deprecated").and_then(Value::as_bool) == Some(true) {
+                continue;
+            }
+            let id = definition.get("operationId").and_then(Value::as_str).expect("operation without an ID");
+            let tag = definition.get("tags").and_then(|tags| tags.get(0)).and_then(Value::as_str);
+            let same = operations.entry(id).or_default();
+            if !same.iter().any(|other| other.path == path) {
+                same.push(Operation { method, path, tag, definition });
+            }
+        }
+    }
+
+    let mut out = String::from("impl Client {\n");
+    for (id, operations) in &operations {
+        for operation in operations {
+            // IDs shared by different paths are told apart by their tag
+            let name = match (operations.len(), operation.tag) {
+                (1, _) | (_, None) => snake_case(id),
+                (_, Some(tag)) => format!("{}_{}", snake_case(tag), snake_case(id)),
+            };
+            method(&mut out, &name, operation);
+        }
+    }
+    out += "}\n";
+    out
+}
+
+fn method(out: &mut String, name: &str, operation: &Operation) {
+    let definition = operation.definition;
+    let mut arguments = Vec::new();
+    let mut setup = String::new();
+
+    let mut path = format!("{:?}", operation.path);
+    let mut path_arguments = Vec::new();
+    for parameter in definition.get("parameters").and_then(Value::as_array).into_iter().flatten() {
+        let location = parameter["in"].as_str().unwrap_or("");
+        let wire_name = parameter["name"].as_str().unwrap_or("");
+        let argument = field_name(&snake_case(wire_name));
+        let required = parameter.get("required").and_then(Value::as_bool).unwrap_or(false);
+        let ty = argument_type(&parameter["schema"]);
+
+        match location {
+            "path" => {
+                path = path.replace(&format!("{{{}}}", wire_name), "{}");
+                path_arguments.push(argument.clone());
+                arguments.push(format!("{}: {}", argument, ty));
+            }
+            "query" | "header" => {
+                let set = if location == "query" { "query" } else { "set" };
+                let value = |name: &str| if ty == "&str" { name.to_string() } else { format!("&{}.to_string()", name) };
+                if required {
+                    arguments.push(format!("{}: {}", argument, ty));
+                    writeln!(setup, "        request = request.{}({:?}, {});", set, wire_name, value(&argument)).unwrap();
+                } else {
+                    arguments.push(format!("{}: Option<{}>", argument, ty));
+                    writeln!(setup, "        if let Some(value) = {} {{\n            request = request.{}({:?}, {});\n        }}", argument, set, wire_name, value("value")).unwrap();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let body = definition
+        .get("requestBody")
+        .and_then(|body| body.pointer("/content/application~1json/schema"))
+        .map(|schema| rust_type(schema, "types::"));
+    if let Some(body) = &body {
+        arguments.push(format!("body: &{}", body));
+    }
+
+    let success = definition
+        .get("responses")
+        .and_then(Value::as_object)
+        .and_then(|responses| responses.iter().find(|(status, _)| status.starts_with('2')))
+        .map(|(_, response)| response);
+    let content = success.and_then(|response| response.get("content")).and_then(Value::as_object);
+    let returned = match content {
+        Some(content) if content.contains_key("application/json") => Some(rust_type(&content["application/json"]["schema"], "types::")),
+        Some(content) if content.contains_key("text/plain") => Some("String".to_string()),
+        // Streams are handed over unread
+        Some(content) if !content.is_empty() => Some("ureq::Response".to_string()),
+        _ => None,
+    };
+    let tagged = success.and_then(|response| response.pointer("/headers/ETag")).is_some();
+
+    let (return_type, conversion) = match (&returned, tagged) {
+        (Some(ty), true) if ty.starts_with("types::") => (format!("WithEtag<{}>", ty), "WithEtag::from_response(response)"),
+        (Some(ty), _) if ty == "String" => (ty.clone(), "text(response)"),
+        (Some(ty), _) if ty == "ureq::Response" => (ty.clone(), "Ok(response)"),
+        (Some(ty), _) => (ty.clone(), "json(response)"),
+        (None, _) => ("()".to_string(), "Ok(())"),
+    };
+
+    if let Some(summary) = definition.get("summary") {
+        doc(out, "    ", Some(summary));
+        *out += "    ///\n";
+    }
+    writeln!(out, "    /// `{} {}`", operation.method.to_uppercase(), operation.path).unwrap();
+    arguments.insert(0, "&self".to_string());
+    if arguments.len() > 7 {
+        *out += "    #[allow(clippy::too_many_arguments)]\n";
+    }
+    writeln!(out, "    pub fn {}({}) -> Result<{}, Error> {{", name, arguments.join(", "), return_type).unwrap();
+    let path = if path_arguments.is_empty() { path } else { format!("&format!({}, {})", path, path_arguments.join(", ")) };
+    let mutable = if setup.is_empty() { "" } else { "mut " };
+    writeln!(out, "        let {}request = self.request({:?}, {});", mutable, operation.method.to_uppercase(), path).unwrap();
+    *out += &setup;
+    let response = if return_type == "()" { "" } else { "let response = " };
+    match body {
+        Some(_) => writeln!(out, "        {}send_json(request, body)?;", response).unwrap(),
+        None => writeln!(out, "        {}send(request)?;", response).unwrap(),
+    }
+    writeln!(out, "        {}\n    }}\n", conversion).unwrap();
+}
+
+/// Type of a parameter argument; strings are borrowed
+fn argument_type(schema: &Value) -> String {
+    match rust_type(schema, "types::").trim_start_matches("Option<").trim_end_matches('>') {
+        "String" => "&str".to_string(),
+        ty => ty.to_string(),
+    }
+}
+
+/// First paragraph of a description as a doc comment. Later ones are left
+/// out since they may hold examples rustdoc would try to compile.
+fn doc(out: &mut String, indent: &str, description: Option<&Value>) {
+    let Some(description) = description.and_then(Value::as_str) else {
+        return;
+    };
+    let paragraph: Vec<_> = description.trim().lines().map(str::trim).take_while(|line| !line.is_empty()).collect();
+    if paragraph.is_empty() || paragraph.iter().any(|line| line.starts_with("```")) {
+        return;
+    }
+    for line in paragraph {
+        writeln!(out, "{}/// {}", indent, line).unwrap();
+    }
+}
+
+fn ref_name(reference: &Value) -> String {
+    type_name(reference.as_str().unwrap_or("").rsplit('/').next().unwrap_or(""))
+}
+
+/// `PascalCase` from any mix of separators, keeping inner capitals
+fn type_name(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut previous = '_';
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous.is_ascii_lowercase() || previous.is_ascii_digit() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+        previous = c;
+    }
+    out.trim_matches('_').to_string()
+}
+
+fn field_name(name: &str) -> String {
+    let name = snake_case(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
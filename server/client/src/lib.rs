@@ -0,0 +1,130 @@
+//! Blocking client for the finance API
+//!
+//! The types and the methods of [`Client`] are generated at build time from
+//! `openapi.json`, a copy of the spec the server serves at
+//! `/api-docs/openapi.json`; after changing the API, refresh it by running
+//! the server's tests with `UPDATE_OPENAPI=1`.
+//!
+//! ```no_run
+//! use finstack_client::{types, Client};
+//!
+//! let login = Client::new("http://127.0.0.1:8080").login(&types::LoginRequest {
+//!     email: "user@example.com".to_string(),
+//!     password: "password123".to_string(),
+//! })?;
+//! if let types::LoginResponse::TokenResponse(login) = login {
+//!     let client = Client::new("http://127.0.0.1:8080").with_token(login.token);
+//!     let expenses = client.get_all_expenses()?;
+//!     println!("{} expenses", expenses.len());
+//! }
+//! # Ok::<(), finstack_client::Error>(())
+//! ```
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Request and response bodies, one per schema of the spec
+pub mod types {
+    use serde::{Deserialize, Serialize};
+
+    include!(concat!(env!("OUT_DIR"), "/types.rs"));
+}
+
+include!(concat!(env!("OUT_DIR"), "/operations.rs"));
+
+pub struct Client {
+    base_url: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// A client for the server at `base_url`, like `http://127.0.0.1:8080`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Authenticate every request with a login token or a personal API token
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+}
+
+/// A response body with the `ETag` to send as `If-Match` when changing it
+#[derive(Debug, Clone)]
+pub struct WithEtag<T> {
+    pub value: T,
+    pub etag: Option<String>,
+}
+
+impl<T: DeserializeOwned> WithEtag<T> {
+    fn from_response(response: ureq::Response) -> Result<Self, Error> {
+        let etag = response.header("ETag").map(str::to_string);
+        Ok(Self { value: json(response)?, etag })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server answered with an error status; `body` is its JSON error
+    Status { status: u16, body: String },
+    /// The server could not be reached
+    Transport(Box<ureq::Transport>),
+    /// The response body could not be read or did not match the spec
+    Decode(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Status { status, body } => write!(f, "server answered {}: {}", status, body),
+            Error::Transport(e) => write!(f, "request failed: {}", e),
+            Error::Decode(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+    fn from(error: ureq::Error) -> Self {
+        match error {
+            ureq::Error::Status(status, response) => Error::Status {
+                status,
+                body: response.into_string().unwrap_or_default(),
+            },
+            ureq::Error::Transport(transport) => Error::Transport(Box::new(transport)),
+        }
+    }
+}
+
+fn send(request: ureq::Request) -> Result<ureq::Response, Error> {
+    Ok(request.call()?)
+}
+
+fn send_json(request: ureq::Request, body: &impl Serialize) -> Result<ureq::Response, Error> {
+    Ok(request.send_json(body)?)
+}
+
+fn json<T: DeserializeOwned>(response: ureq::Response) -> Result<T, Error> {
+    response.into_json().map_err(Error::Decode)
+}
+
+fn text(response: ureq::Response) -> Result<String, Error> {
+    response.into_string().map_err(Error::Decode)
+}
@@ -22,10 +22,20 @@ pub enum AppError {
     Validation(String),
     /// Not found errors (resource doesn't exist)
     NotFound(String),
-    /// Authorization errors (permission denied)
+    /// Authentication errors (missing or invalid credentials)
     Unauthorized(String),
+    /// Authorization errors (authenticated but not allowed)
+    Forbidden(String),
     /// Bad request errors (invalid parameters)
     BadRequest(String),
+    /// Conflict with the current state of the resource or a concurrent request
+    Conflict(String),
+    /// Conditional request failed (the resource changed since it was read)
+    PreconditionFailed(String),
+    /// Conditional request header missing where one is mandatory
+    PreconditionRequired(String),
+    /// Well-formed request that cannot be processed as sent
+    UnprocessableEntity(String),
     /// Server errors (internal issues)
     InternalServer(String),
 }
@@ -37,7 +47,12 @@ impl fmt::Display for AppError {
             AppError::Validation(msg) => write!(f, "Validation error: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            AppError::PreconditionFailed(msg) => write!(f, "Precondition failed: {}", msg),
+            AppError::PreconditionRequired(msg) => write!(f, "Precondition required: {}", msg),
+            AppError::UnprocessableEntity(msg) => write!(f, "Unprocessable request: {}", msg),
             AppError::InternalServer(msg) => write!(f, "Internal server error: {}", msg),
         }
     }
@@ -50,7 +65,12 @@ impl ResponseError for AppError {
             AppError::Validation(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
             AppError::InternalServer(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -63,7 +83,12 @@ impl ResponseError for AppError {
             AppError::Validation(_) => "Validation failed",
             AppError::NotFound(_) => "Resource not found",
             AppError::Unauthorized(_) => "Unauthorized access",
+            AppError::Forbidden(_) => "Access denied",
             AppError::BadRequest(_) => "Invalid request",
+            AppError::Conflict(_) => "Conflict",
+            AppError::PreconditionFailed(_) => "Resource was modified",
+            AppError::PreconditionRequired(_) => "Conditional request required",
+            AppError::UnprocessableEntity(_) => "Unprocessable request",
             AppError::InternalServer(_) => "Internal server error",
         };
 
@@ -0,0 +1,56 @@
+//! Entity tags for optimistic concurrency control
+//!
+//! The tag of an income or expense is its `updated_at`, formatted exactly as
+//! in the JSON body, so clients can also build it from a listing. It changes
+//! on every update. Updates must send the tag they last saw in `If-Match`
+//! and fail with `412 Precondition Failed` if the row moved on.
+
+use actix_web::http::header::{EntityTag, IfMatch, ETAG};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::config::errors::AppError;
+
+/// Same format serde uses for `NaiveDateTime`
+const VERSION_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+pub fn entity_tag(updated_at: NaiveDateTime) -> EntityTag {
+    EntityTag::new_strong(updated_at.format(VERSION_FORMAT).to_string())
+}
+
+/// JSON response carrying the entity's `ETag`
+pub fn respond<T: Serialize>(mut builder: HttpResponseBuilder, data: T, updated_at: NaiveDateTime) -> HttpResponse {
+    builder
+        .insert_header((ETAG, entity_tag(updated_at).to_string()))
+        .json(data)
+}
+
+/// Versions accepted by the request's `If-Match` header
+///
+/// `None` stands for `If-Match: *`. Weak or foreign tags never match, so they
+/// are dropped (an empty list always fails the precondition).
+pub fn required_versions(req: &HttpRequest) -> Result<Option<Vec<NaiveDateTime>>, AppError> {
+    match req.get_header::<IfMatch>() {
+        None => Err(AppError::PreconditionRequired(
+            "Send the ETag of the resource in an If-Match header".to_string(),
+        )),
+        Some(IfMatch::Any) => Ok(None),
+        Some(IfMatch::Items(tags)) => Ok(Some(
+            tags.iter()
+                .filter(|tag| !tag.weak)
+                .filter_map(|tag| NaiveDateTime::parse_from_str(tag.tag(), VERSION_FORMAT).ok())
+                .collect(),
+        )),
+    }
+}
+
+/// Check a row's current version against the versions from `If-Match`
+pub fn ensure_version(current: NaiveDateTime, expected: Option<&[NaiveDateTime]>) -> Result<(), AppError> {
+    match expected {
+        Some(versions) if !versions.contains(&current) => Err(AppError::PreconditionFailed(
+            "The resource was modified by another request; reload it and try again".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -2,6 +2,8 @@ use dotenvy::dotenv;
 use std::env;
 
 pub mod errors;
+pub mod etag;
+pub mod jwt;
 
 pub fn get_database_url() -> String {
     dotenv().ok();
@@ -11,4 +13,57 @@ pub fn get_database_url() -> String {
 pub fn get_server_url() -> String {
     dotenv().ok();
     env::var("SERVER_URL").unwrap_or_else(|_| "127.0.0.1:8080".to_string())
-} 
\ No newline at end of file
+} 
+pub fn get_totp_issuer() -> String {
+    dotenv().ok();
+    env::var("TOTP_ISSUER").unwrap_or_else(|_| "FinStack".to_string())
+}
+
+/// Days a deleted income or expense stays in the trash before it is purged
+pub fn get_trash_retention_days() -> i64 {
+    dotenv().ok();
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(30)
+}
+
+/// Hours a response stays available for replay under its `Idempotency-Key`
+pub fn get_idempotency_key_ttl_hours() -> i64 {
+    dotenv().ok();
+    env::var("IDEMPOTENCY_KEY_TTL_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse::<i64>().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(24)
+}
+
+/// Largest number of days between two records with the same amount for them
+/// to be considered duplicates
+pub fn get_duplicate_window_days() -> i32 {
+    dotenv().ok();
+    env::var("DUPLICATE_WINDOW_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i32>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(3)
+}
+
+/// How emails are sent: `log` only writes them to the log, `sendmail` pipes
+/// them to the sendmail program
+pub fn get_mail_transport() -> String {
+    dotenv().ok();
+    env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "log".to_string())
+}
+
+/// Sender address of emails
+pub fn get_mail_from() -> String {
+    dotenv().ok();
+    env::var("MAIL_FROM").unwrap_or_else(|_| "FinStack <noreply@localhost>".to_string())
+}
+
+pub fn get_sendmail_path() -> String {
+    dotenv().ok();
+    env::var("SENDMAIL_PATH").unwrap_or_else(|_| "/usr/sbin/sendmail".to_string())
+}
@@ -0,0 +1,124 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::admin::{AdminUserView, GlobalStatistics, UpdateRoleRequest};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::admin_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List all users
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    responses(
+        (status = 200, description = "List of users", body = Vec<AdminUserView>),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn get_all_users(pool: web::Data<DbPool>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let users: Vec<AdminUserView> = admin_service::get_all_users(&mut conn)?
+        .into_iter()
+        .map(AdminUserView::from)
+        .collect();
+    Ok(response::ok(users))
+}
+
+/// Disable a user account
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/disable",
+    responses(
+        (status = 200, description = "User disabled", body = AdminUserView),
+        (status = 400, description = "Admins cannot disable their own account"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    tag = "admin"
+)]
+pub async fn disable_user(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    if claims.user_id()? == user_id {
+        return Err(AppError::BadRequest("You cannot disable your own account".to_string()));
+    }
+    let mut conn = pool.get()?;
+    let user = admin_service::set_user_disabled(&mut conn, user_id, true, &audit)?;
+    Ok(response::ok(AdminUserView::from(user)))
+}
+
+/// Re-enable a disabled user account
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/enable",
+    responses(
+        (status = 200, description = "User enabled", body = AdminUserView),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    tag = "admin"
+)]
+pub async fn enable_user(pool: web::Data<DbPool>, audit: AuditContext, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let user = admin_service::set_user_disabled(&mut conn, user_id.into_inner(), false, &audit)?;
+    Ok(response::ok(AdminUserView::from(user)))
+}
+
+/// Change a user's role
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{user_id}/role",
+    request_body = UpdateRoleRequest,
+    responses(
+        (status = 200, description = "Role updated", body = AdminUserView),
+        (status = 400, description = "Admins cannot change their own role"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    tag = "admin"
+)]
+pub async fn update_user_role(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, user_id: web::Path<Uuid>, body: web::Json<UpdateRoleRequest>) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    if claims.user_id()? == user_id {
+        return Err(AppError::BadRequest("You cannot change your own role".to_string()));
+    }
+    let mut conn = pool.get()?;
+    let user = admin_service::set_user_role(&mut conn, user_id, body.into_inner().role, &audit)?;
+    Ok(response::ok(AdminUserView::from(user)))
+}
+
+/// Global statistics across all users
+#[utoipa::path(
+    get,
+    path = "/api/admin/statistics",
+    responses(
+        (status = 200, description = "Global statistics", body = GlobalStatistics),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn get_statistics(pool: web::Data<DbPool>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let statistics = admin_service::get_global_statistics(&mut conn)?;
+    Ok(response::ok(statistics))
+}
@@ -0,0 +1,78 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::api_token::{ApiTokenView, CreatedApiToken, NewApiToken};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::api_token_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's API tokens
+#[utoipa::path(
+    get,
+    path = "/api/tokens",
+    responses(
+        (status = 200, description = "List of API tokens", body = Vec<ApiTokenView>),
+        (status = 403, description = "Not available to API tokens"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "tokens"
+)]
+pub async fn get_tokens(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    claims.ensure_session()?;
+    let mut conn = pool.get()?;
+    let tokens: Vec<ApiTokenView> = api_token_service::get_tokens_by_user_id(&mut conn, claims.user_id()?)?
+        .into_iter()
+        .map(ApiTokenView::from)
+        .collect();
+    Ok(response::ok(tokens))
+}
+
+/// Create a personal API token
+///
+/// The token is only returned in this response; store it securely.
+#[utoipa::path(
+    post,
+    path = "/api/tokens",
+    request_body = NewApiToken,
+    responses(
+        (status = 201, description = "API token created", body = CreatedApiToken),
+        (status = 400, description = "Invalid input"),
+        (status = 403, description = "Admin scope requested by a non-admin, or called with an API token"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "tokens"
+)]
+pub async fn create_token(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_token: web::Json<NewApiToken>) -> Result<HttpResponse, AppError> {
+    claims.ensure_session()?;
+    let mut conn = pool.get()?;
+    let token = api_token_service::create_token(&mut conn, claims.user_id()?, claims.role, new_token.into_inner(), &audit)?;
+    Ok(response::created(token))
+}
+
+/// Revoke an API token
+#[utoipa::path(
+    delete,
+    path = "/api/tokens/{token_id}",
+    responses(
+        (status = 200, description = "API token revoked", body = ApiTokenView),
+        (status = 403, description = "Not available to API tokens"),
+        (status = 404, description = "API token not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("token_id" = Uuid, Path, description = "API token ID")
+    ),
+    tag = "tokens"
+)]
+pub async fn revoke_token(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, token_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    claims.ensure_session()?;
+    let mut conn = pool.get()?;
+    let token = api_token_service::revoke_token(&mut conn, claims.user_id()?, token_id.into_inner(), &audit)?;
+    Ok(response::ok(ApiTokenView::from(token)))
+}
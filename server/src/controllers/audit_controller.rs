@@ -0,0 +1,37 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use crate::models::audit::{AuditLogEntry, AuditLogQuery};
+
+use crate::config::errors::AppError;
+use crate::models::auth::Claims;
+use crate::services::audit_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Read the audit log
+///
+/// Users see the changes made to their own data; admins see every entry and
+/// may filter by owner. The total number of matches is returned in the
+/// `X-Total-Count` header.
+#[utoipa::path(
+    get,
+    path = "/api/audit-log",
+    params(AuditLogQuery),
+    responses(
+        (status = 200, description = "Audit log entries, newest first", body = Vec<AuditLogEntry>),
+        (status = 400, description = "Invalid filter"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "audit"
+)]
+pub async fn get_audit_log(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<AuditLogQuery>) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let owner_id = if claims.is_admin() { query.owner_id } else { Some(claims.user_id()?) };
+    let mut conn = pool.get()?;
+    let (entries, total) = audit_service::get_entries(&mut conn, owner_id, &query)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(entries))
+}
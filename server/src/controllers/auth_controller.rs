@@ -1,13 +1,20 @@
 use actix_web::{web, HttpRequest, HttpResponse, Result};
 
-use crate::models::auth::{AuthError, LoginRequest, RegisterRequest, TokenResponse};
+use crate::config::jwt::JwtKeys;
+use crate::models::audit::AuditContext;
+use crate::models::auth::{AuthError, LoginRequest, LoginResponse, RegisterRequest, TokenResponse};
+use crate::models::two_factor::{
+    RecoveryCodesResponse, TotpEnrollmentResponse, TwoFactorCodeRequest, TwoFactorLoginRequest,
+};
 use crate::services::auth_service::{AuthService, DbPool};
+use crate::services::two_factor_service::TwoFactorService;
 
 /// Register a new user
 #[utoipa::path(
     post,
     path = "/api/auth/register",
     tag = "auth",
+    security(()),
     request_body = RegisterRequest,
     responses(
         (status = 201, description = "User registered successfully", body = TokenResponse),
@@ -17,8 +24,9 @@ use crate::services::auth_service::{AuthService, DbPool};
 pub async fn register(
     pool: web::Data<DbPool>,
     register_data: web::Json<RegisterRequest>,
+    audit: AuditContext,
 ) -> Result<HttpResponse> {
-    match AuthService::register_user(pool, register_data.into_inner()).await {
+    match AuthService::register_user(pool, register_data.into_inner(), audit).await {
         Ok(token_response) => Ok(HttpResponse::Created().json(token_response)),
         Err(error) => match error.code.as_str() {
             "EMAIL_EXISTS" => Ok(HttpResponse::Conflict().json(error)),
@@ -33,10 +41,12 @@ pub async fn register(
     post,
     path = "/api/auth/login",
     tag = "auth",
+    security(()),
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "Login successful", body = TokenResponse),
-        (status = 401, description = "Invalid credentials", body = AuthError)
+        (status = 200, description = "Login successful, or a two-factor challenge when 2FA is enabled", body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = AuthError),
+        (status = 403, description = "Account disabled", body = AuthError)
     )
 )]
 pub async fn login(
@@ -47,11 +57,35 @@ pub async fn login(
         Ok(token_response) => Ok(HttpResponse::Ok().json(token_response)),
         Err(error) => match error.code.as_str() {
             "INVALID_CREDENTIALS" => Ok(HttpResponse::Unauthorized().json(error)),
+            "ACCOUNT_DISABLED" => Ok(HttpResponse::Forbidden().json(error)),
             _ => Ok(HttpResponse::InternalServerError().json(error)),
         },
     }
 }
 
+/// Complete a login with a TOTP or recovery code
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/2fa",
+    tag = "auth",
+    security(()),
+    request_body = TwoFactorLoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = TokenResponse),
+        (status = 401, description = "Invalid challenge token or code", body = AuthError),
+        (status = 429, description = "Too many invalid codes; try again later", body = AuthError)
+    )
+)]
+pub async fn login_two_factor(
+    pool: web::Data<DbPool>,
+    login_data: web::Json<TwoFactorLoginRequest>,
+) -> Result<HttpResponse> {
+    match AuthService::complete_two_factor_login(pool, login_data.into_inner()).await {
+        Ok(token_response) => Ok(HttpResponse::Ok().json(token_response)),
+        Err(error) => Ok(two_factor_error_response(error)),
+    }
+}
+
 /// Get current user profile
 #[utoipa::path(
     get,
@@ -61,7 +95,7 @@ pub async fn login(
         ("bearer_auth" = [])
     ),
     responses(
-        (status = 200, description = "Current user profile", body = crate::models::user::User),
+        (status = 200, description = "Current user profile", body = crate::models::auth::UserInfo),
         (status = 401, description = "Unauthorized", body = AuthError)
     )
 )]
@@ -72,18 +106,14 @@ pub async fn me(
     match AuthService::get_current_user(pool, req).await {
         Ok(user) => {
             // Don't return the password in the response
-            let safe_user = crate::models::auth::UserInfo {
-                id: user.id,
-                first_name: user.first_name,
-                last_name: user.last_name,
-                email: user.email,
-            };
+            let safe_user = crate::models::auth::UserInfo::from(user);
             Ok(HttpResponse::Ok().json(safe_user))
         }
         Err(error) => match error.code.as_str() {
             "MISSING_AUTH_HEADER" | "INVALID_TOKEN" | "INVALID_AUTH_HEADER" | "INVALID_AUTH_FORMAT" => {
                 Ok(HttpResponse::Unauthorized().json(error))
             }
+            "ACCOUNT_DISABLED" => Ok(HttpResponse::Forbidden().json(error)),
             "USER_NOT_FOUND" => Ok(HttpResponse::NotFound().json(error)),
             _ => Ok(HttpResponse::InternalServerError().json(error)),
         },
@@ -109,4 +139,143 @@ pub async fn logout() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Logout successful. Please delete the token from client storage."
     })))
-} 
\ No newline at end of file
+}
+
+/// Start TOTP enrollment
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/enroll",
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "TOTP secret and otpauth URI for the authenticator app", body = TotpEnrollmentResponse),
+        (status = 401, description = "Unauthorized", body = AuthError),
+        (status = 409, description = "Two-factor authentication already enabled", body = AuthError)
+    )
+)]
+pub async fn enroll_two_factor(
+    pool: web::Data<DbPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    match TwoFactorService::begin_enrollment(pool, req).await {
+        Ok(enrollment) => Ok(HttpResponse::Ok().json(enrollment)),
+        Err(error) => Ok(two_factor_error_response(error)),
+    }
+}
+
+/// Confirm TOTP enrollment and receive recovery codes
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/verify",
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled", body = RecoveryCodesResponse),
+        (status = 400, description = "Enrollment not started", body = AuthError),
+        (status = 401, description = "Unauthorized or invalid code", body = AuthError),
+        (status = 409, description = "Two-factor authentication already enabled", body = AuthError),
+        (status = 429, description = "Too many invalid codes; try again later", body = AuthError)
+    )
+)]
+pub async fn verify_two_factor(
+    pool: web::Data<DbPool>,
+    req: HttpRequest,
+    body: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse> {
+    match TwoFactorService::confirm_enrollment(pool, req, body.into_inner().code).await {
+        Ok(codes) => Ok(HttpResponse::Ok().json(codes)),
+        Err(error) => Ok(two_factor_error_response(error)),
+    }
+}
+
+/// Disable two-factor authentication
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/disable",
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Two-factor authentication disabled"),
+        (status = 400, description = "Two-factor authentication not enabled", body = AuthError),
+        (status = 401, description = "Unauthorized or invalid code", body = AuthError),
+        (status = 429, description = "Too many invalid codes; try again later", body = AuthError)
+    )
+)]
+pub async fn disable_two_factor(
+    pool: web::Data<DbPool>,
+    req: HttpRequest,
+    body: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse> {
+    match TwoFactorService::disable(pool, req, body.into_inner().code).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "message": "Two-factor authentication disabled"
+        }))),
+        Err(error) => Ok(two_factor_error_response(error)),
+    }
+}
+
+/// Replace all recovery codes
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/recovery-codes",
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes", body = RecoveryCodesResponse),
+        (status = 400, description = "Two-factor authentication not enabled", body = AuthError),
+        (status = 401, description = "Unauthorized or invalid code", body = AuthError),
+        (status = 429, description = "Too many invalid codes; try again later", body = AuthError)
+    )
+)]
+pub async fn regenerate_recovery_codes(
+    pool: web::Data<DbPool>,
+    req: HttpRequest,
+    body: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse> {
+    match TwoFactorService::regenerate_recovery_codes(pool, req, body.into_inner().code).await {
+        Ok(codes) => Ok(HttpResponse::Ok().json(codes)),
+        Err(error) => Ok(two_factor_error_response(error)),
+    }
+}
+
+/// JSON Web Key Set (RFC 7517) with the public keys that verify access tokens
+///
+/// Empty when tokens are signed with a shared HS256 secret.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 200, description = "JSON Web Key Set")
+    )
+)]
+pub async fn jwks() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(JwtKeys::global().jwks()))
+}
+
+fn two_factor_error_response(error: AuthError) -> HttpResponse {
+    match error.code.as_str() {
+        "MISSING_AUTH_HEADER" | "INVALID_TOKEN" | "INVALID_AUTH_HEADER" | "INVALID_AUTH_FORMAT"
+        | "INVALID_CHALLENGE" | "INVALID_TWO_FACTOR_CODE" => HttpResponse::Unauthorized().json(error),
+        "TWO_FACTOR_NOT_ENROLLED" | "TWO_FACTOR_NOT_ENABLED" => HttpResponse::BadRequest().json(error),
+        "TWO_FACTOR_ALREADY_ENABLED" => HttpResponse::Conflict().json(error),
+        "ACCOUNT_DISABLED" => HttpResponse::Forbidden().json(error),
+        "USER_NOT_FOUND" => HttpResponse::NotFound().json(error),
+        "TWO_FACTOR_LOCKED" => HttpResponse::TooManyRequests().json(error),
+        _ => HttpResponse::InternalServerError().json(error),
+    }
+}
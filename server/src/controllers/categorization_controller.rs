@@ -0,0 +1,233 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::category_suggestion::{CategorySuggestionQuery, CategorySuggestions};
+use crate::models::categorization_rule::{ApplyRulesQuery, CategorizationRule, NewCategorizationRule, RuleApplication, RuleMatch, UpdateCategorizationRule};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::{categorization_service, suggestion_service};
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's categorization rules in the order they are applied
+#[utoipa::path(
+    get,
+    path = "/api/categorization-rules",
+    responses(
+        (status = 200, description = "List of rules", body = Vec<CategorizationRule>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn get_rules(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let rules = categorization_service::get_rules_by_user_id(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(rules))
+}
+
+/// Create a categorization rule
+///
+/// New expenses are categorized with the rule from now on; use the apply
+/// endpoints to categorize existing ones.
+#[utoipa::path(
+    post,
+    path = "/api/categorization-rules",
+    request_body = NewCategorizationRule,
+    responses(
+        (status = 201, description = "Rule created", body = CategorizationRule),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn create_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_rule: web::Json<NewCategorizationRule>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let rule = categorization_service::create_rule(&mut conn, claims.user_id()?, new_rule.into_inner(), &audit)?;
+    Ok(response::created(rule))
+}
+
+/// Get a categorization rule
+#[utoipa::path(
+    get,
+    path = "/api/categorization-rules/{rule_id}",
+    responses(
+        (status = 200, description = "Rule found", body = CategorizationRule),
+        (status = 404, description = "Rule not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID")
+    ),
+    tag = "categorization"
+)]
+pub async fn get_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, rule_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let rule = categorization_service::get_rule(&mut conn, claims.user_id()?, rule_id.into_inner())?;
+    Ok(response::ok(rule))
+}
+
+/// Update a categorization rule
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/categorization-rules/{rule_id}",
+    request_body = UpdateCategorizationRule,
+    responses(
+        (status = 200, description = "Rule updated", body = CategorizationRule),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Rule not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID")
+    ),
+    tag = "categorization"
+)]
+pub async fn update_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, rule_id: web::Path<Uuid>, update_rule: web::Json<UpdateCategorizationRule>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let rule = categorization_service::update_rule(&mut conn, claims.user_id()?, rule_id.into_inner(), update_rule.into_inner(), &audit)?;
+    Ok(response::ok(rule))
+}
+
+/// Delete a categorization rule
+///
+/// Expenses categorized by the rule keep their category, tags and display
+/// name.
+#[utoipa::path(
+    delete,
+    path = "/api/categorization-rules/{rule_id}",
+    responses(
+        (status = 200, description = "Rule deleted", body = CategorizationRule),
+        (status = 404, description = "Rule not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID")
+    ),
+    tag = "categorization"
+)]
+pub async fn delete_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, rule_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let rule = categorization_service::delete_rule(&mut conn, claims.user_id()?, rule_id.into_inner(), &audit)?;
+    Ok(response::ok(rule))
+}
+
+/// Preview an unsaved rule
+///
+/// Lists the existing expenses the rule would change and the values they
+/// would get. Nothing is saved.
+#[utoipa::path(
+    post,
+    path = "/api/categorization-rules/dry-run",
+    request_body = NewCategorizationRule,
+    params(ApplyRulesQuery),
+    responses(
+        (status = 200, description = "Expenses the rule would change", body = Vec<RuleMatch>),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn dry_run(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<ApplyRulesQuery>, new_rule: web::Json<NewCategorizationRule>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let matches = categorization_service::dry_run(&mut conn, claims.user_id()?, new_rule.into_inner(), query.overwrite.unwrap_or(false))?;
+    Ok(response::ok(matches))
+}
+
+/// Preview a saved rule
+///
+/// Lists the existing expenses the rule would change if it were applied to
+/// the history. Nothing is saved.
+#[utoipa::path(
+    post,
+    path = "/api/categorization-rules/{rule_id}/dry-run",
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+        ApplyRulesQuery
+    ),
+    responses(
+        (status = 200, description = "Expenses the rule would change", body = Vec<RuleMatch>),
+        (status = 404, description = "Rule not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn dry_run_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, rule_id: web::Path<Uuid>, query: web::Query<ApplyRulesQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let matches = categorization_service::dry_run_rule(&mut conn, claims.user_id()?, rule_id.into_inner(), query.overwrite.unwrap_or(false))?;
+    Ok(response::ok(matches))
+}
+
+/// Apply all enabled rules to existing expenses
+///
+/// Expenses in the trash are left alone. Every changed expense is recorded
+/// in the audit log.
+#[utoipa::path(
+    post,
+    path = "/api/categorization-rules/apply",
+    params(ApplyRulesQuery),
+    responses(
+        (status = 200, description = "Rules applied", body = RuleApplication),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn apply_rules(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, query: web::Query<ApplyRulesQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let application = categorization_service::apply_to_history(&mut conn, claims.user_id()?, None, query.overwrite.unwrap_or(false), &audit)?;
+    Ok(response::ok(application))
+}
+
+/// Apply one rule to existing expenses
+///
+/// The rule is applied even if it is disabled.
+#[utoipa::path(
+    post,
+    path = "/api/categorization-rules/{rule_id}/apply",
+    params(
+        ("rule_id" = Uuid, Path, description = "Rule ID"),
+        ApplyRulesQuery
+    ),
+    responses(
+        (status = 200, description = "Rule applied", body = RuleApplication),
+        (status = 404, description = "Rule not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn apply_rule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, rule_id: web::Path<Uuid>, query: web::Query<ApplyRulesQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let application = categorization_service::apply_to_history(&mut conn, claims.user_id()?, Some(rule_id.into_inner()), query.overwrite.unwrap_or(false), &audit)?;
+    Ok(response::ok(application))
+}
+
+/// Suggest categories for an expense
+///
+/// The suggestions are learned from the words in the item names and
+/// descriptions of the current user's categorized expenses.
+#[utoipa::path(
+    get,
+    path = "/api/category-suggestions",
+    params(CategorySuggestionQuery),
+    responses(
+        (status = 200, description = "Suggested categories, most likely first", body = CategorySuggestions),
+        (status = 400, description = "Invalid query"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "categorization"
+)]
+pub async fn suggest_categories(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<CategorySuggestionQuery>) -> Result<HttpResponse, AppError> {
+    let limit = query.limit.unwrap_or(suggestion_service::DEFAULT_SUGGESTION_LIMIT);
+    if limit == 0 || limit > suggestion_service::MAX_SUGGESTION_LIMIT {
+        return Err(AppError::Validation(format!("Limit must be between 1 and {}", suggestion_service::MAX_SUGGESTION_LIMIT)));
+    }
+
+    let mut conn = pool.get()?;
+    let suggestions = suggestion_service::suggest_categories(&mut conn, claims.user_id()?, &query.item_name, query.description.as_deref(), limit)?;
+    Ok(response::ok(suggestions))
+}
@@ -0,0 +1,97 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::duplicate::{DuplicatePair, DuplicateQuery, DuplicateScan, MergeDuplicate};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::duplicate_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List probable duplicate incomes and expenses of the current user
+///
+/// Pairs are found by a background scan; most likely duplicates come first.
+#[utoipa::path(
+    get,
+    path = "/api/duplicates",
+    params(DuplicateQuery),
+    responses(
+        (status = 200, description = "Duplicate pairs", body = Vec<DuplicatePair>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "duplicates"
+)]
+pub async fn get_duplicates(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<DuplicateQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let pairs = duplicate_service::get_duplicates(&mut conn, claims.user_id()?, &query)?;
+    Ok(response::ok(pairs))
+}
+
+/// Scan the current user's records for duplicates now
+#[utoipa::path(
+    post,
+    path = "/api/duplicates/scan",
+    responses(
+        (status = 200, description = "Number of open pairs", body = DuplicateScan),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "duplicates"
+)]
+pub async fn scan(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let scan = duplicate_service::scan_user(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(scan))
+}
+
+/// Merge a duplicate pair
+///
+/// One record is kept and the other one is moved to the trash. The merge is
+/// recorded in the audit log.
+#[utoipa::path(
+    post,
+    path = "/api/duplicates/{duplicate_id}/merge",
+    request_body = MergeDuplicate,
+    responses(
+        (status = 200, description = "Pair merged", body = DuplicatePair),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Pair not found"),
+        (status = 409, description = "Pair already resolved, a record is in the trash or the record to remove is reconciled"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("duplicate_id" = Uuid, Path, description = "Duplicate pair ID")
+    ),
+    tag = "duplicates"
+)]
+pub async fn merge(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, duplicate_id: web::Path<Uuid>, merge: web::Json<MergeDuplicate>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let pair = duplicate_service::merge(&mut conn, claims.user_id()?, duplicate_id.into_inner(), merge.into_inner(), &audit)?;
+    Ok(response::ok(pair))
+}
+
+/// Dismiss a duplicate pair
+///
+/// The records are left alone and the pair is not reported again.
+#[utoipa::path(
+    post,
+    path = "/api/duplicates/{duplicate_id}/dismiss",
+    responses(
+        (status = 200, description = "Pair dismissed", body = DuplicatePair),
+        (status = 404, description = "Pair not found"),
+        (status = 409, description = "Pair already resolved"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("duplicate_id" = Uuid, Path, description = "Duplicate pair ID")
+    ),
+    tag = "duplicates"
+)]
+pub async fn dismiss(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, duplicate_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let pair = duplicate_service::dismiss(&mut conn, claims.user_id()?, duplicate_id.into_inner())?;
+    Ok(response::ok(pair))
+}
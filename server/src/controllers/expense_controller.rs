@@ -1,37 +1,46 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
 use diesel::PgConnection;
 use r2d2::Pool;
 use diesel::r2d2::ConnectionManager;
 use uuid::Uuid;
+use crate::models::batch::{BatchRequest, BatchResponse};
 use crate::models::expense::{NewExpense, UpdateExpense, Expense};
 
 use crate::config::errors::{AppError, response};
+use crate::config::etag;
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
 use crate::services::expense_service;
 
 type DbPool = Pool<ConnectionManager<PgConnection>>;
 
 /// Get all expenses
+///
+/// Admins receive every user's expenses; other users only their own.
 #[utoipa::path(
     get,
-    path = "/api/expenses",
+    path = "/api/v1/expenses",
     responses(
         (status = 200, description = "List of expenses", body = Vec<Expense>),
         (status = 500, description = "Internal server error")
     ),
     tag = "expenses"
 )]
-pub async fn get_all_expenses(pool: web::Data<DbPool>) -> Result<HttpResponse, AppError> {
+pub async fn get_all_expenses(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    // Admins see every user's expenses, everyone else only their own
+    let owner_id = if claims.is_admin() { None } else { Some(claims.user_id()?) };
     let mut conn = pool.get()?;
-    let expenses = expense_service::get_all_expenses(&mut conn)?;
+    let expenses = expense_service::get_all_expenses(&mut conn, owner_id)?;
     Ok(response::ok(expenses))
 }
 
 /// Get expenses by user ID
 #[utoipa::path(
     get,
-    path = "/api/expenses/user/{user_id}",
+    path = "/api/v1/users/{user_id}/expenses",
     responses(
         (status = 200, description = "List of expenses for user", body = Vec<Expense>),
+        (status = 403, description = "Not allowed to view this user's expenses"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -40,58 +49,133 @@ pub async fn get_all_expenses(pool: web::Data<DbPool>) -> Result<HttpResponse, A
     ),
     tag = "expenses"
 )]
-pub async fn get_expenses_by_user_id(pool: web::Data<DbPool>, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+pub async fn get_expenses_by_user_id(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    claims.ensure_can_access(user_id)?;
     let mut conn = pool.get()?;
-    let expenses = expense_service::get_expenses_by_user_id(&mut conn, user_id.into_inner())?;
+    let expenses = expense_service::get_expenses_by_user_id(&mut conn, user_id)?;
     Ok(response::ok(expenses))
 }
 
+/// Get the current user's expenses
+#[utoipa::path(
+    get,
+    path = "/api/v1/me/expenses",
+    responses(
+        (status = 200, description = "List of the current user's expenses", body = Vec<Expense>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "expenses"
+)]
+pub async fn get_my_expenses(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let user_id = claims.user_id()?;
+    let mut conn = pool.get()?;
+    let expenses = expense_service::get_expenses_by_user_id(&mut conn, user_id)?;
+    Ok(response::ok(expenses))
+}
+
+/// Get an expense by ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/expenses/{expense_id}",
+    responses(
+        (status = 200, description = "Expense found", body = Expense,
+            headers(("ETag" = String, description = "Version of the expense, for If-Match on updates"))),
+        (status = 403, description = "Expense belongs to another user"),
+        (status = 404, description = "Expense not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("expense_id" = Uuid, Path, description = "Expense ID")
+    ),
+    tag = "expenses"
+)]
+pub async fn get_expense(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, expense_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let expense = expense_service::get_expense_by_id(&mut conn, expense_id.into_inner())?;
+    claims.ensure_can_access(expense.user_id)?;
+    let updated_at = expense.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), expense, updated_at))
+}
+
 /// Create new expense
 #[utoipa::path(
     post,
-    path = "/api/expenses",
+    path = "/api/v1/expenses",
     request_body = NewExpense,
     responses(
-        (status = 201, description = "Expense created successfully", body = Expense),
+        (status = 201, description = "Expense created successfully", body = Expense,
+            headers(("ETag" = String, description = "Version of the expense, for If-Match on updates"))),
         (status = 400, description = "Invalid input"),
+        (status = 403, description = "Not allowed to create expenses for this user"),
+        (status = 409, description = "Idempotency key in use by a still running request"),
+        (status = 422, description = "Idempotency key already used for a different request"),
         (status = 500, description = "Internal server error")
     ),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of creating another expense")
+    ),
     tag = "expenses"
 )]
-pub async fn create_expense(pool: web::Data<DbPool>, new_expense: web::Json<NewExpense>) -> Result<HttpResponse, AppError> {
+pub async fn create_expense(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_expense: web::Json<NewExpense>) -> Result<HttpResponse, AppError> {
+    let new_expense = new_expense.into_inner();
+    claims.ensure_can_access(new_expense.user_id)?;
     let mut conn = pool.get()?;
-    let expense = expense_service::create_expense(&mut conn, new_expense.into_inner())?;
-    Ok(response::created(expense))
+    let expense = expense_service::create_expense(&mut conn, new_expense, &audit)?;
+    let updated_at = expense.updated_at;
+    Ok(etag::respond(HttpResponse::Created(), expense, updated_at))
 }
 
 /// Update expense
+///
+/// Only the fields present in the body change; send `"description": null`
+/// to clear the description. `PUT` is accepted as an alias of `PATCH`.
+///
+/// Requires the `ETag` from the last response for this expense in `If-Match`,
+/// so concurrent edits do not silently overwrite each other.
 #[utoipa::path(
-    put,
-    path = "/api/expenses/{expense_id}",
+    method(patch, put),
+    path = "/api/v1/expenses/{expense_id}",
     request_body = UpdateExpense,
     responses(
-        (status = 200, description = "Expense updated successfully", body = Expense),
+        (status = 200, description = "Expense updated successfully", body = Expense,
+            headers(("ETag" = String, description = "New version of the expense"))),
+        (status = 403, description = "Expense belongs to another user"),
         (status = 404, description = "Expense not found"),
+        (status = 409, description = "Expense is reconciled and must be unlocked first"),
+        (status = 412, description = "Expense was modified since the given ETag"),
+        (status = 428, description = "If-Match header missing"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("expense_id" = Uuid, Path, description = "Expense ID")
+        ("expense_id" = Uuid, Path, description = "Expense ID"),
+        ("If-Match" = String, Header, description = "ETag of the expense as last read, or `*`")
     ),
     tag = "expenses"
 )]
-pub async fn update_expense(pool: web::Data<DbPool>, expense_id: web::Path<Uuid>, update_expense: web::Json<UpdateExpense>) -> Result<HttpResponse, AppError> {
+pub async fn update_expense(req: HttpRequest, pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, expense_id: web::Path<Uuid>, update_expense: web::Json<UpdateExpense>) -> Result<HttpResponse, AppError> {
+    let expense_id = expense_id.into_inner();
+    let expected_versions = etag::required_versions(&req)?;
     let mut conn = pool.get()?;
-    let expense = expense_service::update_expense(&mut conn, expense_id.into_inner(), update_expense.into_inner())?;
-    Ok(response::ok(expense))
+    let existing = expense_service::get_expense_by_id(&mut conn, expense_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let expense = expense_service::update_expense(&mut conn, expense_id, update_expense.into_inner(), expected_versions.as_deref(), &audit)?;
+    let updated_at = expense.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), expense, updated_at))
 }
 
 /// Delete expense
+///
+/// The expense is moved to the trash, from where it can be restored until it
+/// is purged after the retention period.
 #[utoipa::path(
     delete,
-    path = "/api/expenses/{expense_id}",
+    path = "/api/v1/expenses/{expense_id}",
     responses(
-        (status = 200, description = "Expense deleted successfully"),
+        (status = 200, description = "Expense moved to the trash", body = Expense),
+        (status = 403, description = "Expense belongs to another user"),
         (status = 404, description = "Expense not found"),
+        (status = 409, description = "Expense is reconciled and must be unlocked first"),
         (status = 500, description = "Internal server error")
     ),
     params(
@@ -99,8 +183,116 @@ pub async fn update_expense(pool: web::Data<DbPool>, expense_id: web::Path<Uuid>
     ),
     tag = "expenses"
 )]
-pub async fn delete_expense(pool: web::Data<DbPool>, expense_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+pub async fn delete_expense(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, expense_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let expense_id = expense_id.into_inner();
     let mut conn = pool.get()?;
-    let expense = expense_service::delete_expense(&mut conn, expense_id.into_inner())?;
+    let existing = expense_service::get_expense_by_id(&mut conn, expense_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let expense = expense_service::delete_expense(&mut conn, expense_id, None, &audit)?;
     Ok(response::ok(expense))
 }
+
+
+/// List deleted expenses
+///
+/// Admins receive every user's deleted expenses; other users only their own.
+#[utoipa::path(
+    get,
+    path = "/api/v1/expenses/trash",
+    responses(
+        (status = 200, description = "Deleted expenses, most recently deleted first", body = Vec<Expense>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "expenses"
+)]
+pub async fn get_deleted_expenses(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let owner_id = if claims.is_admin() { None } else { Some(claims.user_id()?) };
+    let mut conn = pool.get()?;
+    let expenses = expense_service::get_deleted_expenses(&mut conn, owner_id)?;
+    Ok(response::ok(expenses))
+}
+
+/// Restore a deleted expense
+#[utoipa::path(
+    post,
+    path = "/api/v1/expenses/{expense_id}/restore",
+    responses(
+        (status = 200, description = "Expense restored", body = Expense,
+            headers(("ETag" = String, description = "Version of the expense"))),
+        (status = 403, description = "Expense belongs to another user"),
+        (status = 404, description = "Expense not found in the trash"),
+        (status = 409, description = "Expense is reconciled and must be unlocked first"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("expense_id" = Uuid, Path, description = "Expense ID")
+    ),
+    tag = "expenses"
+)]
+pub async fn restore_expense(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, expense_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let expense_id = expense_id.into_inner();
+    let mut conn = pool.get()?;
+    let existing = expense_service::get_deleted_expense_by_id(&mut conn, expense_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let expense = expense_service::restore_expense(&mut conn, expense_id, &audit)?;
+    let updated_at = expense.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), expense, updated_at))
+}
+
+/// Unlock a reconciled expense
+///
+/// Finishing a reconciliation locks its cleared expenses against edits,
+/// deletion and restoring; this allows them again. Expenses in the trash can be
+/// unlocked too.
+#[utoipa::path(
+    post,
+    path = "/api/v1/expenses/{expense_id}/unlock",
+    responses(
+        (status = 200, description = "Expense unlocked", body = Expense,
+            headers(("ETag" = String, description = "New version of the expense"))),
+        (status = 403, description = "Expense belongs to another user"),
+        (status = 404, description = "Expense not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("expense_id" = Uuid, Path, description = "Expense ID")
+    ),
+    tag = "expenses"
+)]
+pub async fn unlock_expense(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, expense_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let expense_id = expense_id.into_inner();
+    let mut conn = pool.get()?;
+    let existing = expense_service::get_expense_including_deleted(&mut conn, expense_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let expense = expense_service::unlock_expense(&mut conn, expense_id, &audit)?;
+    let updated_at = expense.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), expense, updated_at))
+}
+
+/// Create, update and delete expenses in one request
+///
+/// All operations run in a single transaction. In `atomic` mode (the
+/// default) nothing is applied if any operation fails; in `partial` mode the
+/// successful operations are kept. Each operation reports its own status.
+#[utoipa::path(
+    post,
+    path = "/api/v1/expenses/batch",
+    request_body = BatchRequest<NewExpense, UpdateExpense>,
+    responses(
+        (status = 200, description = "Batch committed; see the per-operation results", body = BatchResponse<Expense>),
+        (status = 400, description = "Empty or oversized batch"),
+        (status = 409, description = "Idempotency key in use by a still running request"),
+        (status = 422, description = "Atomic batch rolled back because an operation failed, or idempotency key already used for a different request", body = BatchResponse<Expense>),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of applying the batch again")
+    ),
+    tag = "expenses"
+)]
+pub async fn batch_expenses(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, request: web::Json<BatchRequest<NewExpense, UpdateExpense>>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let result = expense_service::run_batch(&mut conn, request.into_inner(), |owner_id| claims.ensure_can_access(owner_id), &audit)?;
+    let status = if result.committed { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+    Ok(HttpResponse::build(status).json(result))
+}
@@ -0,0 +1,199 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::goal::{Goal, GoalContribution, GoalProgress, NewGoal, NewGoalContribution, UpdateGoal};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::goal_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's savings goals
+#[utoipa::path(
+    get,
+    path = "/api/goals",
+    responses(
+        (status = 200, description = "List of goals", body = Vec<Goal>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "goals"
+)]
+pub async fn get_goals(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let goals = goal_service::get_goals_by_user_id(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(goals))
+}
+
+/// Create a savings goal
+#[utoipa::path(
+    post,
+    path = "/api/goals",
+    request_body = NewGoal,
+    responses(
+        (status = 201, description = "Goal created", body = Goal),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "goals"
+)]
+pub async fn create_goal(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_goal: web::Json<NewGoal>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let goal = goal_service::create_goal(&mut conn, claims.user_id()?, new_goal.into_inner(), &audit)?;
+    Ok(response::created(goal))
+}
+
+/// Get a savings goal
+#[utoipa::path(
+    get,
+    path = "/api/goals/{goal_id}",
+    responses(
+        (status = 200, description = "Goal found", body = Goal),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn get_goal(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, goal_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let goal = goal_service::get_goal(&mut conn, claims.user_id()?, goal_id.into_inner())?;
+    Ok(response::ok(goal))
+}
+
+/// Update a savings goal
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/goals/{goal_id}",
+    request_body = UpdateGoal,
+    responses(
+        (status = 200, description = "Goal updated", body = Goal),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn update_goal(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, goal_id: web::Path<Uuid>, update_goal: web::Json<UpdateGoal>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let goal = goal_service::update_goal(&mut conn, claims.user_id()?, goal_id.into_inner(), update_goal.into_inner(), &audit)?;
+    Ok(response::ok(goal))
+}
+
+/// Delete a savings goal and its contributions
+#[utoipa::path(
+    delete,
+    path = "/api/goals/{goal_id}",
+    responses(
+        (status = 200, description = "Goal deleted", body = Goal),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn delete_goal(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, goal_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let goal = goal_service::delete_goal(&mut conn, claims.user_id()?, goal_id.into_inner(), &audit)?;
+    Ok(response::ok(goal))
+}
+
+/// List the contributions to a savings goal
+#[utoipa::path(
+    get,
+    path = "/api/goals/{goal_id}/contributions",
+    responses(
+        (status = 200, description = "List of contributions", body = Vec<GoalContribution>),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn get_contributions(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, goal_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let contributions = goal_service::get_contributions(&mut conn, claims.user_id()?, goal_id.into_inner())?;
+    Ok(response::ok(contributions))
+}
+
+/// Record a contribution to a savings goal
+#[utoipa::path(
+    post,
+    path = "/api/goals/{goal_id}/contributions",
+    request_body = NewGoalContribution,
+    responses(
+        (status = 201, description = "Contribution recorded", body = GoalContribution),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn add_contribution(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, goal_id: web::Path<Uuid>, new_contribution: web::Json<NewGoalContribution>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let contribution = goal_service::add_contribution(&mut conn, claims.user_id()?, goal_id.into_inner(), new_contribution.into_inner(), &audit)?;
+    Ok(response::created(contribution))
+}
+
+/// Delete a contribution from a savings goal
+#[utoipa::path(
+    delete,
+    path = "/api/goals/{goal_id}/contributions/{contribution_id}",
+    responses(
+        (status = 200, description = "Contribution deleted", body = GoalContribution),
+        (status = 404, description = "Goal or contribution not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID"),
+        ("contribution_id" = Uuid, Path, description = "Contribution ID")
+    ),
+    tag = "goals"
+)]
+pub async fn delete_contribution(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, path: web::Path<(Uuid, Uuid)>) -> Result<HttpResponse, AppError> {
+    let (goal_id, contribution_id) = path.into_inner();
+    let mut conn = pool.get()?;
+    let contribution = goal_service::delete_contribution(&mut conn, claims.user_id()?, goal_id, contribution_id, &audit)?;
+    Ok(response::ok(contribution))
+}
+
+/// Get the progress of a savings goal
+///
+/// Reports how much has been saved, the percentage of the target reached
+/// and, for goals with a deadline, how much needs to be saved each month to
+/// reach the target in time.
+#[utoipa::path(
+    get,
+    path = "/api/goals/{goal_id}/progress",
+    responses(
+        (status = 200, description = "Goal progress", body = GoalProgress),
+        (status = 404, description = "Goal not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("goal_id" = Uuid, Path, description = "Goal ID")
+    ),
+    tag = "goals"
+)]
+pub async fn get_progress(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, goal_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let progress = goal_service::get_progress(&mut conn, claims.user_id()?, goal_id.into_inner())?;
+    Ok(response::ok(progress))
+}
@@ -0,0 +1,40 @@
+use actix_web::{web, HttpResponse};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+
+use crate::graphql::FinanceSchema;
+use crate::models::auth::Claims;
+
+/// Run a GraphQL query
+///
+/// Read-only access to users, incomes, expenses and their summaries with the
+/// same permissions as the REST endpoints. Send `{"query": ..., "variables":
+/// ...}` as JSON, or `query` and `variables` as URL parameters with `GET`,
+/// which API tokens without the `write` scope need to use. Queries may nest
+/// at most 6 levels deep and list fields count `limit` times what they
+/// select towards a complexity limit of 5000.
+#[utoipa::path(
+    method(get, post),
+    path = "/api/graphql",
+    request_body(content = Object, description = "GraphQL request with `query`, `variables` and `operationName`"),
+    responses(
+        (status = 200, description = "GraphQL response with `data` and `errors`; errors carry the HTTP `status` they would have over REST", body = Object),
+        (status = 400, description = "Malformed request")
+    ),
+    tag = "graphql"
+)]
+pub async fn graphql(schema: web::Data<FinanceSchema>, claims: web::ReqData<Claims>, request: GraphQLRequest) -> GraphQLResponse {
+    schema.execute(request.into_inner().data(claims.into_inner())).await.into()
+}
+
+/// Get the GraphQL schema
+#[utoipa::path(
+    get,
+    path = "/api/graphql/schema",
+    responses(
+        (status = 200, description = "Schema in the GraphQL schema definition language", content_type = "text/plain", body = String)
+    ),
+    tag = "graphql"
+)]
+pub async fn get_schema(schema: web::Data<FinanceSchema>) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(schema.sdl())
+}
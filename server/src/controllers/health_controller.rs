@@ -0,0 +1,67 @@
+use actix_web::{web, HttpResponse, Result};
+use serde_json::json;
+
+/// Health check endpoint
+/// 
+/// Returns a simple health status response for Docker health checks
+/// and load balancer health probes.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    security(()),
+    responses(
+        (status = 200, description = "The server is running", body = Object,
+            example = json!({"status": "healthy", "timestamp": "2024-03-20T10:00:00+00:00", "service": "finstack-api", "version": "0.1.0"}))
+    )
+)]
+pub async fn health_check() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "healthy",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "service": "finstack-api",
+        "version": env!("CARGO_PKG_VERSION")
+    })))
+}
+
+/// Health check with database connectivity test
+#[utoipa::path(
+    get,
+    path = "/health/detailed",
+    tag = "health",
+    security(()),
+    responses(
+        (status = 200, description = "The server and its database are healthy", body = Object,
+            example = json!({"status": "healthy", "timestamp": "2024-03-20T10:00:00+00:00", "service": "finstack-api", "version": "0.1.0", "checks": {"database": "healthy"}})),
+        (status = 503, description = "The database cannot be reached", body = Object)
+    )
+)]
+pub async fn health_check_detailed(
+    pool: web::Data<crate::database::db_connection::DbPool>
+) -> Result<HttpResponse> {
+    use crate::database::db_connection::get_connection;
+    
+    // Test database connectivity
+    let db_status = match get_connection(&pool) {
+        Ok(_) => "healthy",
+        Err(_) => "unhealthy"
+    };
+    
+    let overall_status = if db_status == "healthy" { "healthy" } else { "unhealthy" };
+    
+    let response = json!({
+        "status": overall_status,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "service": "finstack-api",
+        "version": env!("CARGO_PKG_VERSION"),
+        "checks": {
+            "database": db_status
+        }
+    });
+    
+    if overall_status == "healthy" {
+        Ok(HttpResponse::Ok().json(response))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(response))
+    }
+}
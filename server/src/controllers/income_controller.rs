@@ -1,38 +1,47 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
 use diesel::PgConnection;
 use r2d2::Pool;
 use diesel::r2d2::ConnectionManager;
 use uuid::Uuid;
+use crate::models::batch::{BatchRequest, BatchResponse};
 use crate::models::income::{NewIncome, UpdateIncome, Income, IncomeWithUser};
 
 use crate::config::errors::{AppError, response};
+use crate::config::etag;
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
 use crate::services::income_service;
 
 
 type DbPool = Pool<ConnectionManager<PgConnection>>;
 
 /// Get all incomes
+///
+/// Admins receive every user's incomes; other users only their own.
 #[utoipa::path(
     get,
-    path = "/api/incomes",
+    path = "/api/v1/incomes",
     responses(
         (status = 200, description = "List of incomes", body = Vec<IncomeWithUser>),
         (status = 500, description = "Internal server error")
     ),
     tag = "incomes"
 )]
-pub async fn get_all_incomes(pool: web::Data<DbPool>) -> Result<HttpResponse, AppError> {
+pub async fn get_all_incomes(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    // Admins see every user's incomes, everyone else only their own
+    let owner_id = if claims.is_admin() { None } else { Some(claims.user_id()?) };
     let mut conn = pool.get()?;
-    let incomes = income_service::get_all_incomes(&mut conn)?;
+    let incomes = income_service::get_all_incomes(&mut conn, owner_id)?;
     Ok(response::ok(incomes))
 }
 
 /// Get incomes by user ID
 #[utoipa::path(
     get,
-    path = "/api/incomes/user/{user_id}",
+    path = "/api/v1/users/{user_id}/incomes",
     responses(
         (status = 200, description = "List of incomes for user", body = Vec<Income>),
+        (status = 403, description = "Not allowed to view this user's incomes"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -41,58 +50,133 @@ pub async fn get_all_incomes(pool: web::Data<DbPool>) -> Result<HttpResponse, Ap
     ),
     tag = "incomes"
 )]
-pub async fn get_incomes_by_user_id(pool: web::Data<DbPool>, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+pub async fn get_incomes_by_user_id(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, user_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    claims.ensure_can_access(user_id)?;
     let mut conn = pool.get()?;
-    let incomes = income_service::get_incomes_by_user_id(&mut conn, user_id.into_inner())?;
+    let incomes = income_service::get_incomes_by_user_id(&mut conn, user_id)?;
     Ok(response::ok(incomes))
 }
 
+/// Get the current user's incomes
+#[utoipa::path(
+    get,
+    path = "/api/v1/me/incomes",
+    responses(
+        (status = 200, description = "List of the current user's incomes", body = Vec<Income>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "incomes"
+)]
+pub async fn get_my_incomes(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let user_id = claims.user_id()?;
+    let mut conn = pool.get()?;
+    let incomes = income_service::get_incomes_by_user_id(&mut conn, user_id)?;
+    Ok(response::ok(incomes))
+}
+
+/// Get an income by ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/incomes/{income_id}",
+    responses(
+        (status = 200, description = "Income found", body = Income,
+            headers(("ETag" = String, description = "Version of the income, for If-Match on updates"))),
+        (status = 403, description = "Income belongs to another user"),
+        (status = 404, description = "Income not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("income_id" = Uuid, Path, description = "Income ID")
+    ),
+    tag = "incomes"
+)]
+pub async fn get_income(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, income_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let income = income_service::get_income_by_id(&mut conn, income_id.into_inner())?;
+    claims.ensure_can_access(income.user_id)?;
+    let updated_at = income.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), income, updated_at))
+}
+
 /// Create new income
 #[utoipa::path(
     post,
-    path = "/api/incomes",
+    path = "/api/v1/incomes",
     request_body = NewIncome,
     responses(
-        (status = 201, description = "Income created successfully", body = Income),
+        (status = 201, description = "Income created successfully", body = Income,
+            headers(("ETag" = String, description = "Version of the income, for If-Match on updates"))),
         (status = 400, description = "Invalid input"),
+        (status = 403, description = "Not allowed to create incomes for this user"),
+        (status = 409, description = "Idempotency key in use by a still running request"),
+        (status = 422, description = "Idempotency key already used for a different request"),
         (status = 500, description = "Internal server error")
     ),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of creating another income")
+    ),
     tag = "incomes"
 )]
-pub async fn create_income(pool: web::Data<DbPool>, new_income: web::Json<NewIncome>) -> Result<HttpResponse, AppError> {
+pub async fn create_income(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_income: web::Json<NewIncome>) -> Result<HttpResponse, AppError> {
+    let new_income = new_income.into_inner();
+    claims.ensure_can_access(new_income.user_id)?;
     let mut conn = pool.get()?;
-    let income = income_service::create_income(&mut conn, new_income.into_inner())?;
-    Ok(response::created(income))
+    let income = income_service::create_income(&mut conn, new_income, &audit)?;
+    let updated_at = income.updated_at;
+    Ok(etag::respond(HttpResponse::Created(), income, updated_at))
 }
 
 /// Update income
+///
+/// Only the fields present in the body change; send `"description": null`
+/// to clear the description. `PUT` is accepted as an alias of `PATCH`.
+///
+/// Requires the `ETag` from the last response for this income in `If-Match`,
+/// so concurrent edits do not silently overwrite each other.
 #[utoipa::path(
-    put,
-    path = "/api/incomes/{income_id}",
+    method(patch, put),
+    path = "/api/v1/incomes/{income_id}",
     request_body = UpdateIncome,
     responses(
-        (status = 200, description = "Income updated successfully", body = Income),
+        (status = 200, description = "Income updated successfully", body = Income,
+            headers(("ETag" = String, description = "New version of the income"))),
+        (status = 403, description = "Income belongs to another user"),
         (status = 404, description = "Income not found"),
+        (status = 409, description = "Income is reconciled and must be unlocked first"),
+        (status = 412, description = "Income was modified since the given ETag"),
+        (status = 428, description = "If-Match header missing"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("income_id" = Uuid, Path, description = "Income ID")
+        ("income_id" = Uuid, Path, description = "Income ID"),
+        ("If-Match" = String, Header, description = "ETag of the income as last read, or `*`")
     ),
     tag = "incomes"
 )]
-pub async fn update_income(pool: web::Data<DbPool>, income_id: web::Path<Uuid>, update_income: web::Json<UpdateIncome>) -> Result<HttpResponse, AppError> {
+pub async fn update_income(req: HttpRequest, pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, income_id: web::Path<Uuid>, update_income: web::Json<UpdateIncome>) -> Result<HttpResponse, AppError> {
+    let income_id = income_id.into_inner();
+    let expected_versions = etag::required_versions(&req)?;
     let mut conn = pool.get()?;
-    let income = income_service::update_income(&mut conn, income_id.into_inner(), update_income.into_inner())?;
-    Ok(response::ok(income))
+    let existing = income_service::get_income_by_id(&mut conn, income_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let income = income_service::update_income(&mut conn, income_id, update_income.into_inner(), expected_versions.as_deref(), &audit)?;
+    let updated_at = income.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), income, updated_at))
 }
 
 /// Delete income
+///
+/// The income is moved to the trash, from where it can be restored until it
+/// is purged after the retention period.
 #[utoipa::path(
     delete,
-    path = "/api/incomes/{income_id}",
+    path = "/api/v1/incomes/{income_id}",
     responses(
-        (status = 200, description = "Income deleted successfully"),
+        (status = 200, description = "Income moved to the trash", body = Income),
+        (status = 403, description = "Income belongs to another user"),
         (status = 404, description = "Income not found"),
+        (status = 409, description = "Income is reconciled and must be unlocked first"),
         (status = 500, description = "Internal server error")
     ),
     params(
@@ -100,8 +184,115 @@ pub async fn update_income(pool: web::Data<DbPool>, income_id: web::Path<Uuid>,
     ),
     tag = "incomes"
 )]
-pub async fn delete_income(pool: web::Data<DbPool>, income_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+pub async fn delete_income(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, income_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let income_id = income_id.into_inner();
     let mut conn = pool.get()?;
-    let income = income_service::delete_income(&mut conn, income_id.into_inner())?;
+    let existing = income_service::get_income_by_id(&mut conn, income_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let income = income_service::delete_income(&mut conn, income_id, None, &audit)?;
     Ok(response::ok(income))
-}
\ No newline at end of file
+}
+
+/// List deleted incomes
+///
+/// Admins receive every user's deleted incomes; other users only their own.
+#[utoipa::path(
+    get,
+    path = "/api/v1/incomes/trash",
+    responses(
+        (status = 200, description = "Deleted incomes, most recently deleted first", body = Vec<Income>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "incomes"
+)]
+pub async fn get_deleted_incomes(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let owner_id = if claims.is_admin() { None } else { Some(claims.user_id()?) };
+    let mut conn = pool.get()?;
+    let incomes = income_service::get_deleted_incomes(&mut conn, owner_id)?;
+    Ok(response::ok(incomes))
+}
+
+/// Restore a deleted income
+#[utoipa::path(
+    post,
+    path = "/api/v1/incomes/{income_id}/restore",
+    responses(
+        (status = 200, description = "Income restored", body = Income,
+            headers(("ETag" = String, description = "Version of the income"))),
+        (status = 403, description = "Income belongs to another user"),
+        (status = 404, description = "Income not found in the trash"),
+        (status = 409, description = "Income is reconciled and must be unlocked first"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("income_id" = Uuid, Path, description = "Income ID")
+    ),
+    tag = "incomes"
+)]
+pub async fn restore_income(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, income_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let income_id = income_id.into_inner();
+    let mut conn = pool.get()?;
+    let existing = income_service::get_deleted_income_by_id(&mut conn, income_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let income = income_service::restore_income(&mut conn, income_id, &audit)?;
+    let updated_at = income.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), income, updated_at))
+}
+
+/// Unlock a reconciled income
+///
+/// Finishing a reconciliation locks its cleared incomes against edits,
+/// deletion and restoring; this allows them again. Incomes in the trash can be
+/// unlocked too.
+#[utoipa::path(
+    post,
+    path = "/api/v1/incomes/{income_id}/unlock",
+    responses(
+        (status = 200, description = "Income unlocked", body = Income,
+            headers(("ETag" = String, description = "New version of the income"))),
+        (status = 403, description = "Income belongs to another user"),
+        (status = 404, description = "Income not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("income_id" = Uuid, Path, description = "Income ID")
+    ),
+    tag = "incomes"
+)]
+pub async fn unlock_income(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, income_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let income_id = income_id.into_inner();
+    let mut conn = pool.get()?;
+    let existing = income_service::get_income_including_deleted(&mut conn, income_id)?;
+    claims.ensure_can_access(existing.user_id)?;
+    let income = income_service::unlock_income(&mut conn, income_id, &audit)?;
+    let updated_at = income.updated_at;
+    Ok(etag::respond(HttpResponse::Ok(), income, updated_at))
+}
+
+/// Create, update and delete incomes in one request
+///
+/// All operations run in a single transaction. In `atomic` mode (the
+/// default) nothing is applied if any operation fails; in `partial` mode the
+/// successful operations are kept. Each operation reports its own status.
+#[utoipa::path(
+    post,
+    path = "/api/v1/incomes/batch",
+    request_body = BatchRequest<NewIncome, UpdateIncome>,
+    responses(
+        (status = 200, description = "Batch committed; see the per-operation results", body = BatchResponse<Income>),
+        (status = 400, description = "Empty or oversized batch"),
+        (status = 409, description = "Idempotency key in use by a still running request"),
+        (status = 422, description = "Atomic batch rolled back because an operation failed, or idempotency key already used for a different request", body = BatchResponse<Income>),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of applying the batch again")
+    ),
+    tag = "incomes"
+)]
+pub async fn batch_incomes(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, request: web::Json<BatchRequest<NewIncome, UpdateIncome>>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let result = income_service::run_batch(&mut conn, request.into_inner(), |owner_id| claims.ensure_can_access(owner_id), &audit)?;
+    let status = if result.committed { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+    Ok(HttpResponse::build(status).json(result))
+}
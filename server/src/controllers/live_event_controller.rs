@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::PgConnection;
+use futures_util::stream::{self, StreamExt};
+use r2d2::Pool;
+use serde::Deserialize;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::live_event::{EventStreamTokenResponse, LiveEvent};
+
+use crate::config::errors::AppError;
+use crate::models::auth::Claims;
+use crate::services::live_event_service::{self, LiveEvents, STREAM_TOKEN_SECONDS};
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Comment sent on an idle stream so that proxies do not close it
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    after: Option<Uuid>,
+}
+
+/// Issue a token that opens the current user's event stream
+///
+/// Browsers' `EventSource` cannot send an `Authorization` header; pass the
+/// token as the `token` query parameter of `GET /api/events` instead. It
+/// expires a minute after it is issued, so get a new one for each
+/// connection.
+#[utoipa::path(
+    post,
+    path = "/api/events/token",
+    responses(
+        (status = 200, description = "Stream token", body = EventStreamTokenResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "API token lacks the write scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "events"
+)]
+pub async fn create_stream_token(claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let user_id = claims.user_id()?;
+    let token = live_event_service::generate_stream_token(user_id)
+        .map_err(|_| AppError::InternalServer("Token generation failed".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(EventStreamTokenResponse { token, expires_in: STREAM_TOKEN_SECONDS }))
+}
+
+/// Stream the current user's income and expense changes
+///
+/// A `text/event-stream` of server-sent events named like the webhook
+/// events (`expense.created`, `income.updated`, ...) with the same JSON body
+/// as a webhook delivery, sent in the order their changes were committed.
+/// Each event's `id` can be sent back as `Last-Event-ID` when reconnecting to
+/// receive the changes missed in between.
+///
+/// Besides the usual bearer token, a token from `POST /api/events/token` is
+/// accepted as the `token` query parameter. As a new `EventSource` cannot
+/// set `Last-Event-ID` either, it can be passed as the `after` query
+/// parameter.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    params(
+        ("Last-Event-ID" = Option<Uuid>, Header, description = "ID of the last event received before reconnecting"),
+        ("token" = Option<String>, Query, description = "Stream token, for clients that cannot send an `Authorization` header"),
+        ("after" = Option<Uuid>, Query, description = "Same as the `Last-Event-ID` header, which takes precedence")
+    ),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream", body = String),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "events"
+)]
+pub async fn stream_events(pool: web::Data<DbPool>, live_events: web::Data<LiveEvents>, claims: web::ReqData<Claims>, query: web::Query<StreamQuery>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = claims.user_id()?;
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value.trim()).ok())
+        .or(query.after);
+
+    // Missed changes are replayed up to the position the stream starts
+    // from, so that none is skipped or sent twice
+    let (position, receiver) = live_events.subscribe(user_id);
+    let missed = match last_event_id {
+        Some(last_event_id) => {
+            let mut conn = pool.get()?;
+            live_event_service::get_changes_after(&mut conn, user_id, last_event_id, position)?
+        }
+        None => Vec::new(),
+    };
+
+    let opening = stream::once(async { Bytes::from_static(b": connected\n\n") });
+    let replay = stream::iter(missed.iter().map(frame).collect::<Vec<_>>());
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        let chunk = match actix_web::rt::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+            Ok(Some(event)) => frame(&event),
+            Ok(None) => return None,
+            Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((chunk, receiver))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(opening.chain(replay).chain(live).map(Ok::<_, actix_web::Error>)))
+}
+
+fn frame(event: &LiveEvent) -> Bytes {
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", event.id, event.event.as_str(), event.payload))
+}
@@ -0,0 +1,222 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::expense::Expense;
+use crate::models::loan::{AmortizationSchedule, LinkLoanPayment, Loan, LoanPayment, LoanStatus, LoanStatusQuery, NewLoan, UpdateLoan};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::loan_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's loans
+#[utoipa::path(
+    get,
+    path = "/api/loans",
+    responses(
+        (status = 200, description = "List of loans", body = Vec<Loan>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "loans"
+)]
+pub async fn get_loans(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let loans = loan_service::get_loans_by_user_id(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(loans))
+}
+
+/// Create a loan
+#[utoipa::path(
+    post,
+    path = "/api/loans",
+    request_body = NewLoan,
+    responses(
+        (status = 201, description = "Loan created", body = Loan),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "loans"
+)]
+pub async fn create_loan(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_loan: web::Json<NewLoan>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let loan = loan_service::create_loan(&mut conn, claims.user_id()?, new_loan.into_inner(), &audit)?;
+    Ok(response::created(loan))
+}
+
+/// Get a loan
+#[utoipa::path(
+    get,
+    path = "/api/loans/{loan_id}",
+    responses(
+        (status = 200, description = "Loan found", body = Loan),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn get_loan(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, loan_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let loan = loan_service::get_loan(&mut conn, claims.user_id()?, loan_id.into_inner())?;
+    Ok(response::ok(loan))
+}
+
+/// Update a loan
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/loans/{loan_id}",
+    request_body = UpdateLoan,
+    responses(
+        (status = 200, description = "Loan updated", body = Loan),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn update_loan(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, loan_id: web::Path<Uuid>, update_loan: web::Json<UpdateLoan>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let loan = loan_service::update_loan(&mut conn, claims.user_id()?, loan_id.into_inner(), update_loan.into_inner(), &audit)?;
+    Ok(response::ok(loan))
+}
+
+/// Delete a loan
+///
+/// Expenses linked as payments are kept.
+#[utoipa::path(
+    delete,
+    path = "/api/loans/{loan_id}",
+    responses(
+        (status = 200, description = "Loan deleted", body = Loan),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn delete_loan(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, loan_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let loan = loan_service::delete_loan(&mut conn, claims.user_id()?, loan_id.into_inner(), &audit)?;
+    Ok(response::ok(loan))
+}
+
+/// Get the amortization schedule of a loan
+#[utoipa::path(
+    get,
+    path = "/api/loans/{loan_id}/schedule",
+    responses(
+        (status = 200, description = "Amortization schedule", body = AmortizationSchedule),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn get_schedule(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, loan_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let schedule = loan_service::get_schedule(&mut conn, claims.user_id()?, loan_id.into_inner())?;
+    Ok(response::ok(schedule))
+}
+
+/// Compare actual repayment of a loan with its schedule
+#[utoipa::path(
+    get,
+    path = "/api/loans/{loan_id}/status",
+    responses(
+        (status = 200, description = "Actual vs. scheduled balance", body = LoanStatus),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID"),
+        LoanStatusQuery
+    ),
+    tag = "loans"
+)]
+pub async fn get_status(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, loan_id: web::Path<Uuid>, query: web::Query<LoanStatusQuery>) -> Result<HttpResponse, AppError> {
+    let as_of = query.as_of.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let mut conn = pool.get()?;
+    let status = loan_service::get_status(&mut conn, claims.user_id()?, loan_id.into_inner(), as_of)?;
+    Ok(response::ok(status))
+}
+
+/// List the expenses linked as payments to a loan
+#[utoipa::path(
+    get,
+    path = "/api/loans/{loan_id}/payments",
+    responses(
+        (status = 200, description = "Expenses paying the loan", body = Vec<Expense>),
+        (status = 404, description = "Loan not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn get_payments(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, loan_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let payments = loan_service::get_payments(&mut conn, claims.user_id()?, loan_id.into_inner())?;
+    Ok(response::ok(payments))
+}
+
+/// Link an expense as a payment towards a loan
+#[utoipa::path(
+    post,
+    path = "/api/loans/{loan_id}/payments",
+    request_body = LinkLoanPayment,
+    responses(
+        (status = 201, description = "Expense linked", body = Expense),
+        (status = 404, description = "Loan or expense not found"),
+        (status = 409, description = "Expense is already linked to a loan"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID")
+    ),
+    tag = "loans"
+)]
+pub async fn link_payment(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, loan_id: web::Path<Uuid>, link: web::Json<LinkLoanPayment>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let expense = loan_service::link_payment(&mut conn, claims.user_id()?, loan_id.into_inner(), link.expense_id, &audit)?;
+    Ok(response::created(expense))
+}
+
+/// Unlink an expense from a loan
+///
+/// The expense itself is kept.
+#[utoipa::path(
+    delete,
+    path = "/api/loans/{loan_id}/payments/{expense_id}",
+    responses(
+        (status = 200, description = "Expense unlinked", body = LoanPayment),
+        (status = 404, description = "Loan or payment not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("loan_id" = Uuid, Path, description = "Loan ID"),
+        ("expense_id" = Uuid, Path, description = "Expense ID")
+    ),
+    tag = "loans"
+)]
+pub async fn unlink_payment(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, path: web::Path<(Uuid, Uuid)>) -> Result<HttpResponse, AppError> {
+    let (loan_id, expense_id) = path.into_inner();
+    let mut conn = pool.get()?;
+    let payment = loan_service::unlink_payment(&mut conn, claims.user_id()?, loan_id, expense_id, &audit)?;
+    Ok(response::ok(payment))
+}
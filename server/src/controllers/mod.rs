@@ -1,3 +1,18 @@
 pub mod income_controller;
 pub mod expense_controller;
-pub mod auth_controller;
\ No newline at end of file
+pub mod auth_controller;
+pub mod admin_controller;
+pub mod api_token_controller;
+pub mod audit_controller;
+pub mod goal_controller;
+pub mod loan_controller;
+pub mod report_controller;
+pub mod net_worth_controller;
+pub mod categorization_controller;
+pub mod duplicate_controller;
+pub mod reconciliation_controller;
+pub mod notification_controller;
+pub mod webhook_controller;
+pub mod live_event_controller;
+pub mod graphql_controller;
+pub mod health_controller;
\ No newline at end of file
@@ -0,0 +1,239 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::net_worth::{
+    NetWorth, NetWorthItem, NetWorthQuery, NetWorthSnapshot, NetWorthSnapshotQuery, NewNetWorthItem, NewValuation, UpdateNetWorthItem, Valuation,
+};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::net_worth_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Get the current user's net worth
+///
+/// Cumulative income minus expenses up to the date, plus the latest
+/// valuation of every asset, minus the latest valuation of every liability.
+#[utoipa::path(
+    get,
+    path = "/api/net-worth",
+    params(NetWorthQuery),
+    responses(
+        (status = 200, description = "Net worth on the date", body = NetWorth),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "net-worth"
+)]
+pub async fn get_net_worth(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<NetWorthQuery>) -> Result<HttpResponse, AppError> {
+    let date = query.date.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let mut conn = pool.get()?;
+    let net_worth = net_worth_service::get_net_worth(&mut conn, claims.user_id()?, date)?;
+    Ok(response::ok(net_worth))
+}
+
+/// Get the current user's month-end net worth over time
+///
+/// One snapshot per completed month since the first transaction or
+/// valuation. Snapshots affected by changes to past data are recomputed
+/// automatically.
+#[utoipa::path(
+    get,
+    path = "/api/net-worth/snapshots",
+    params(NetWorthSnapshotQuery),
+    responses(
+        (status = 200, description = "Month-end snapshots, oldest first", body = Vec<NetWorthSnapshot>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "net-worth"
+)]
+pub async fn get_snapshots(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<NetWorthSnapshotQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let today = chrono::Utc::now().date_naive();
+    let snapshots = net_worth_service::get_snapshots(&mut conn, claims.user_id()?, &query, today)?;
+    Ok(response::ok(snapshots))
+}
+
+/// Recompute all of the current user's month-end snapshots
+#[utoipa::path(
+    post,
+    path = "/api/net-worth/snapshots/recompute",
+    responses(
+        (status = 200, description = "Recomputed snapshots, oldest first", body = Vec<NetWorthSnapshot>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "net-worth"
+)]
+pub async fn recompute_snapshots(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let today = chrono::Utc::now().date_naive();
+    let snapshots = net_worth_service::recompute_snapshots(&mut conn, claims.user_id()?, today)?;
+    Ok(response::ok(snapshots))
+}
+
+/// List the current user's assets and liabilities
+#[utoipa::path(
+    get,
+    path = "/api/net-worth/items",
+    responses(
+        (status = 200, description = "List of assets and liabilities", body = Vec<NetWorthItem>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "net-worth"
+)]
+pub async fn get_items(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let items = net_worth_service::get_items_by_user_id(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(items))
+}
+
+/// Add an asset or liability
+#[utoipa::path(
+    post,
+    path = "/api/net-worth/items",
+    request_body = NewNetWorthItem,
+    responses(
+        (status = 201, description = "Item created", body = NetWorthItem),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "net-worth"
+)]
+pub async fn create_item(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_item: web::Json<NewNetWorthItem>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let item = net_worth_service::create_item(&mut conn, claims.user_id()?, new_item.into_inner(), &audit)?;
+    Ok(response::created(item))
+}
+
+/// Get an asset or liability
+#[utoipa::path(
+    get,
+    path = "/api/net-worth/items/{item_id}",
+    responses(
+        (status = 200, description = "Item found", body = NetWorthItem),
+        (status = 404, description = "Item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn get_item(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, item_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let item = net_worth_service::get_item(&mut conn, claims.user_id()?, item_id.into_inner())?;
+    Ok(response::ok(item))
+}
+
+/// Update an asset or liability
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/net-worth/items/{item_id}",
+    request_body = UpdateNetWorthItem,
+    responses(
+        (status = 200, description = "Item updated", body = NetWorthItem),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn update_item(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, item_id: web::Path<Uuid>, update_item: web::Json<UpdateNetWorthItem>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let item = net_worth_service::update_item(&mut conn, claims.user_id()?, item_id.into_inner(), update_item.into_inner(), &audit)?;
+    Ok(response::ok(item))
+}
+
+/// Delete an asset or liability and its valuations
+#[utoipa::path(
+    delete,
+    path = "/api/net-worth/items/{item_id}",
+    responses(
+        (status = 200, description = "Item deleted", body = NetWorthItem),
+        (status = 404, description = "Item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn delete_item(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, item_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let item = net_worth_service::delete_item(&mut conn, claims.user_id()?, item_id.into_inner(), &audit)?;
+    Ok(response::ok(item))
+}
+
+/// List the valuations of an asset or liability
+#[utoipa::path(
+    get,
+    path = "/api/net-worth/items/{item_id}/valuations",
+    responses(
+        (status = 200, description = "Valuations, most recent first", body = Vec<Valuation>),
+        (status = 404, description = "Item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn get_valuations(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, item_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let valuations = net_worth_service::get_valuations(&mut conn, claims.user_id()?, item_id.into_inner())?;
+    Ok(response::ok(valuations))
+}
+
+/// Record the value of an asset or liability on a date
+///
+/// Replaces an earlier valuation for the same date.
+#[utoipa::path(
+    post,
+    path = "/api/net-worth/items/{item_id}/valuations",
+    request_body = NewValuation,
+    responses(
+        (status = 201, description = "Valuation recorded", body = Valuation),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Item not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn add_valuation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, item_id: web::Path<Uuid>, new_valuation: web::Json<NewValuation>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let valuation = net_worth_service::add_valuation(&mut conn, claims.user_id()?, item_id.into_inner(), new_valuation.into_inner(), &audit)?;
+    Ok(response::created(valuation))
+}
+
+/// Delete a valuation
+#[utoipa::path(
+    delete,
+    path = "/api/net-worth/items/{item_id}/valuations/{valuation_id}",
+    responses(
+        (status = 200, description = "Valuation deleted", body = Valuation),
+        (status = 404, description = "Item or valuation not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("item_id" = Uuid, Path, description = "Item ID"),
+        ("valuation_id" = Uuid, Path, description = "Valuation ID")
+    ),
+    tag = "net-worth"
+)]
+pub async fn delete_valuation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, path: web::Path<(Uuid, Uuid)>) -> Result<HttpResponse, AppError> {
+    let (item_id, valuation_id) = path.into_inner();
+    let mut conn = pool.get()?;
+    let valuation = net_worth_service::delete_valuation(&mut conn, claims.user_id()?, item_id, valuation_id, &audit)?;
+    Ok(response::ok(valuation))
+}
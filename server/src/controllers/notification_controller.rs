@@ -0,0 +1,123 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::notification::{Notification, NotificationPreferences, NotificationQuery, NotificationsRead, UpdateNotificationPreferences};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::notification_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's notifications, newest first
+#[utoipa::path(
+    get,
+    path = "/api/notifications",
+    params(NotificationQuery),
+    responses(
+        (status = 200, description = "Notifications", body = Vec<Notification>),
+        (status = 400, description = "Invalid limit"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "notifications"
+)]
+pub async fn get_notifications(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<NotificationQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let notifications = notification_service::get_notifications(&mut conn, claims.user_id()?, &query)?;
+    Ok(response::ok(notifications))
+}
+
+/// Mark a notification as read
+#[utoipa::path(
+    post,
+    path = "/api/notifications/{notification_id}/read",
+    responses(
+        (status = 200, description = "Notification read", body = Notification),
+        (status = 404, description = "Notification not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("notification_id" = Uuid, Path, description = "Notification ID")
+    ),
+    tag = "notifications"
+)]
+pub async fn mark_read(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, notification_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let notification = notification_service::mark_read(&mut conn, claims.user_id()?, notification_id.into_inner())?;
+    Ok(response::ok(notification))
+}
+
+/// Mark all notifications as read
+#[utoipa::path(
+    post,
+    path = "/api/notifications/read-all",
+    responses(
+        (status = 200, description = "Number of notifications marked as read", body = NotificationsRead),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "notifications"
+)]
+pub async fn mark_all_read(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let read = notification_service::mark_all_read(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(read))
+}
+
+/// Delete a notification
+#[utoipa::path(
+    delete,
+    path = "/api/notifications/{notification_id}",
+    responses(
+        (status = 200, description = "Notification deleted", body = Notification),
+        (status = 404, description = "Notification not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("notification_id" = Uuid, Path, description = "Notification ID")
+    ),
+    tag = "notifications"
+)]
+pub async fn delete_notification(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, notification_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let notification = notification_service::delete_notification(&mut conn, claims.user_id()?, notification_id.into_inner())?;
+    Ok(response::ok(notification))
+}
+
+/// Get the current user's notification preferences
+#[utoipa::path(
+    get,
+    path = "/api/notifications/preferences",
+    responses(
+        (status = 200, description = "Notification preferences", body = NotificationPreferences),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "notifications"
+)]
+pub async fn get_preferences(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let preferences = notification_service::get_preferences(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(preferences))
+}
+
+/// Update the current user's notification preferences
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/notifications/preferences",
+    request_body = UpdateNotificationPreferences,
+    responses(
+        (status = 200, description = "Preferences updated", body = NotificationPreferences),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "notifications"
+)]
+pub async fn update_preferences(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, update: web::Json<UpdateNotificationPreferences>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let preferences = notification_service::update_preferences(&mut conn, claims.user_id()?, update.into_inner(), &audit)?;
+    Ok(response::ok(preferences))
+}
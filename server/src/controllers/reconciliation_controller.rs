@@ -0,0 +1,211 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::reconciliation::{ClearTransactions, NewReconciliation, Reconciliation, ReconciliationSummary, ReconciliationTransaction, UpdateReconciliation};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::reconciliation_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's reconciliations, latest statement first
+#[utoipa::path(
+    get,
+    path = "/api/reconciliations",
+    responses(
+        (status = 200, description = "Reconciliations", body = Vec<Reconciliation>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn get_reconciliations(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let reconciliations = reconciliation_service::get_reconciliations(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(reconciliations))
+}
+
+/// Start reconciling an account against a bank statement
+///
+/// Only one reconciliation per account can be open at a time.
+#[utoipa::path(
+    post,
+    path = "/api/reconciliations",
+    request_body = NewReconciliation,
+    responses(
+        (status = 201, description = "Reconciliation started", body = ReconciliationSummary),
+        (status = 400, description = "Invalid input"),
+        (status = 409, description = "A reconciliation of the account is already open"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn create_reconciliation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_reconciliation: web::Json<NewReconciliation>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::create_reconciliation(&mut conn, claims.user_id()?, new_reconciliation.into_inner(), &audit)?;
+    Ok(response::created(summary))
+}
+
+/// Get a reconciliation with the balance of its cleared transactions
+#[utoipa::path(
+    get,
+    path = "/api/reconciliations/{reconciliation_id}",
+    responses(
+        (status = 200, description = "Reconciliation found", body = ReconciliationSummary),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn get_reconciliation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, reconciliation_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::get_summary(&mut conn, claims.user_id()?, reconciliation_id.into_inner())?;
+    Ok(response::ok(summary))
+}
+
+/// Update the statement of an open reconciliation
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/reconciliations/{reconciliation_id}",
+    request_body = UpdateReconciliation,
+    responses(
+        (status = 200, description = "Reconciliation updated", body = ReconciliationSummary),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 409, description = "Reconciliation already finished"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn update_reconciliation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, reconciliation_id: web::Path<Uuid>, update_reconciliation: web::Json<UpdateReconciliation>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::update_reconciliation(&mut conn, claims.user_id()?, reconciliation_id.into_inner(), update_reconciliation.into_inner(), &audit)?;
+    Ok(response::ok(summary))
+}
+
+/// Abandon an open reconciliation
+///
+/// Cleared marks are discarded; no transaction is locked.
+#[utoipa::path(
+    delete,
+    path = "/api/reconciliations/{reconciliation_id}",
+    responses(
+        (status = 200, description = "Reconciliation deleted", body = Reconciliation),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 409, description = "Reconciliation already finished"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn delete_reconciliation(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, reconciliation_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let reconciliation = reconciliation_service::delete_reconciliation(&mut conn, claims.user_id()?, reconciliation_id.into_inner(), &audit)?;
+    Ok(response::ok(reconciliation))
+}
+
+/// List the transactions of the reconciled account
+///
+/// While open, all unreconciled transactions of the account up to the end of
+/// the statement period; once finished, the transactions cleared in it. Each
+/// carries the running cleared balance.
+#[utoipa::path(
+    get,
+    path = "/api/reconciliations/{reconciliation_id}/transactions",
+    responses(
+        (status = 200, description = "Transactions in date order", body = Vec<ReconciliationTransaction>),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn get_transactions(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, reconciliation_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let transactions = reconciliation_service::get_transactions(&mut conn, claims.user_id()?, reconciliation_id.into_inner())?;
+    Ok(response::ok(transactions))
+}
+
+/// Mark transactions as cleared
+#[utoipa::path(
+    post,
+    path = "/api/reconciliations/{reconciliation_id}/clear",
+    request_body = ClearTransactions,
+    responses(
+        (status = 200, description = "Transactions cleared", body = ReconciliationSummary),
+        (status = 400, description = "A transaction is not an unreconciled transaction of the account in the period"),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 409, description = "Reconciliation already finished"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn clear(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, reconciliation_id: web::Path<Uuid>, transactions: web::Json<ClearTransactions>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::clear(&mut conn, claims.user_id()?, reconciliation_id.into_inner(), transactions.into_inner())?;
+    Ok(response::ok(summary))
+}
+
+/// Remove the cleared mark from transactions
+#[utoipa::path(
+    post,
+    path = "/api/reconciliations/{reconciliation_id}/unclear",
+    request_body = ClearTransactions,
+    responses(
+        (status = 200, description = "Transactions no longer cleared", body = ReconciliationSummary),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 409, description = "Reconciliation already finished"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn unclear(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, reconciliation_id: web::Path<Uuid>, transactions: web::Json<ClearTransactions>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::unclear(&mut conn, claims.user_id()?, reconciliation_id.into_inner(), transactions.into_inner())?;
+    Ok(response::ok(summary))
+}
+
+/// Finish a reconciliation
+///
+/// The cleared balance must match the statement balance. The cleared
+/// transactions are locked against edits until they are unlocked.
+#[utoipa::path(
+    post,
+    path = "/api/reconciliations/{reconciliation_id}/finish",
+    responses(
+        (status = 200, description = "Reconciliation finished", body = ReconciliationSummary),
+        (status = 404, description = "Reconciliation not found"),
+        (status = 409, description = "Already finished or the cleared balance differs from the statement"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("reconciliation_id" = Uuid, Path, description = "Reconciliation ID")
+    ),
+    tag = "reconciliation"
+)]
+pub async fn finish(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, reconciliation_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let summary = reconciliation_service::finish(&mut conn, claims.user_id()?, reconciliation_id.into_inner(), &audit)?;
+    Ok(response::ok(summary))
+}
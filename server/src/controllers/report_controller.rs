@@ -0,0 +1,60 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use crate::models::report::{Forecast, ForecastQuery, SpendingBreakdown, SpendingBreakdownQuery};
+
+use crate::config::errors::{AppError, response};
+use crate::models::auth::Claims;
+use crate::services::report_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Break down the current user's spending
+///
+/// Groups the expenses of a period by payee (`item_name`, ignoring case and
+/// extra whitespace) or by category, lists the groups with the highest
+/// spending with their share of the total, and compares each to the period
+/// of the same length right before.
+#[utoipa::path(
+    get,
+    path = "/api/reports/spending-breakdown",
+    params(SpendingBreakdownQuery),
+    responses(
+        (status = 200, description = "Spending by group", body = SpendingBreakdown),
+        (status = 400, description = "Invalid period or limit"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "reports"
+)]
+pub async fn get_spending_breakdown(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<SpendingBreakdownQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let today = chrono::Utc::now().date_naive();
+    let breakdown = report_service::get_spending_breakdown(&mut conn, claims.user_id()?, &query, today)?;
+    Ok(response::ok(breakdown))
+}
+
+/// Forecast the current user's month
+///
+/// Projects income, expenses and the balance to the end of the month from
+/// what has been recorded so far, the average spending per day of the month
+/// in past months (smoothed with a moving average) and recurring items that
+/// are still expected. Every series has one cumulative point per day with an
+/// 80% range, ready for charting.
+#[utoipa::path(
+    get,
+    path = "/api/reports/forecast",
+    params(ForecastQuery),
+    responses(
+        (status = 200, description = "Projected income, expenses and balance", body = Forecast),
+        (status = 400, description = "Invalid month or history length"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "reports"
+)]
+pub async fn get_forecast(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, query: web::Query<ForecastQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let today = chrono::Utc::now().date_naive();
+    let forecast = report_service::get_forecast(&mut conn, claims.user_id()?, &query, today)?;
+    Ok(response::ok(forecast))
+}
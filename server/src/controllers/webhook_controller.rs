@@ -0,0 +1,163 @@
+use actix_web::{web, HttpResponse};
+use diesel::PgConnection;
+use r2d2::Pool;
+use diesel::r2d2::ConnectionManager;
+use uuid::Uuid;
+use crate::models::webhook::{CreatedWebhook, NewWebhook, UpdateWebhook, Webhook, WebhookDelivery, WebhookDeliveryQuery};
+
+use crate::config::errors::{AppError, response};
+use crate::models::audit::AuditContext;
+use crate::models::auth::Claims;
+use crate::services::webhook_service;
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+/// List the current user's webhooks
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    responses(
+        (status = 200, description = "Webhooks", body = Vec<Webhook>),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "webhooks"
+)]
+pub async fn get_webhooks(pool: web::Data<DbPool>, claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let webhooks = webhook_service::get_webhooks(&mut conn, claims.user_id()?)?;
+    Ok(response::ok(webhooks))
+}
+
+/// Register a webhook
+///
+/// The response contains the signing secret, which cannot be retrieved
+/// later. Every delivery carries a `Webhook-Signature` header of
+/// `sha256=` and the hex HMAC-SHA256 of `<Webhook-Timestamp>.<body>`.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    request_body = NewWebhook,
+    responses(
+        (status = 201, description = "Webhook registered", body = CreatedWebhook),
+        (status = 400, description = "Invalid input"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "webhooks"
+)]
+pub async fn create_webhook(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, new_webhook: web::Json<NewWebhook>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let webhook = webhook_service::create_webhook(&mut conn, claims.user_id()?, new_webhook.into_inner(), &audit)?;
+    Ok(response::created(webhook))
+}
+
+/// Get a webhook
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{webhook_id}",
+    responses(
+        (status = 200, description = "Webhook found", body = Webhook),
+        (status = 404, description = "Webhook not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("webhook_id" = Uuid, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn get_webhook(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, webhook_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let webhook = webhook_service::get_webhook(&mut conn, claims.user_id()?, webhook_id.into_inner())?;
+    Ok(response::ok(webhook))
+}
+
+/// Update a webhook
+///
+/// Only the fields present in the body are changed.
+#[utoipa::path(
+    method(patch, put),
+    path = "/api/webhooks/{webhook_id}",
+    request_body = UpdateWebhook,
+    responses(
+        (status = 200, description = "Webhook updated", body = Webhook),
+        (status = 400, description = "Invalid input"),
+        (status = 404, description = "Webhook not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("webhook_id" = Uuid, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn update_webhook(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, webhook_id: web::Path<Uuid>, update_webhook: web::Json<UpdateWebhook>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let webhook = webhook_service::update_webhook(&mut conn, claims.user_id()?, webhook_id.into_inner(), update_webhook.into_inner(), &audit)?;
+    Ok(response::ok(webhook))
+}
+
+/// Delete a webhook and its delivery log
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{webhook_id}",
+    responses(
+        (status = 200, description = "Webhook deleted", body = Webhook),
+        (status = 404, description = "Webhook not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("webhook_id" = Uuid, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn delete_webhook(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, audit: AuditContext, webhook_id: web::Path<Uuid>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let webhook = webhook_service::delete_webhook(&mut conn, claims.user_id()?, webhook_id.into_inner(), &audit)?;
+    Ok(response::ok(webhook))
+}
+
+/// List the deliveries of a webhook, newest first
+///
+/// Failed attempts are retried with exponential backoff; each delivery shows
+/// the outcome of its last attempt.
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{webhook_id}/deliveries",
+    params(
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+        WebhookDeliveryQuery
+    ),
+    responses(
+        (status = 200, description = "Deliveries", body = Vec<WebhookDelivery>),
+        (status = 400, description = "Invalid limit"),
+        (status = 404, description = "Webhook not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "webhooks"
+)]
+pub async fn get_deliveries(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, webhook_id: web::Path<Uuid>, query: web::Query<WebhookDeliveryQuery>) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let deliveries = webhook_service::get_deliveries(&mut conn, claims.user_id()?, webhook_id.into_inner(), &query)?;
+    Ok(response::ok(deliveries))
+}
+
+/// Send a delivery again
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/{webhook_id}/deliveries/{delivery_id}/retry",
+    responses(
+        (status = 200, description = "Delivery queued", body = WebhookDelivery),
+        (status = 404, description = "Delivery not found"),
+        (status = 409, description = "Delivery is already queued"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("webhook_id" = Uuid, Path, description = "Webhook ID"),
+        ("delivery_id" = Uuid, Path, description = "Delivery ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn retry_delivery(pool: web::Data<DbPool>, claims: web::ReqData<Claims>, path: web::Path<(Uuid, Uuid)>) -> Result<HttpResponse, AppError> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    let mut conn = pool.get()?;
+    let delivery = webhook_service::retry_delivery(&mut conn, claims.user_id()?, webhook_id, delivery_id)?;
+    Ok(response::ok(delivery))
+}
@@ -0,0 +1,4 @@
+ALTER TABLE users
+    DROP CONSTRAINT users_role_check,
+    DROP COLUMN disabled_at,
+    DROP COLUMN role;
@@ -0,0 +1,17 @@
+CREATE TABLE audit_log (
+    id UUID PRIMARY KEY,
+    actor_id UUID,
+    owner_id UUID,
+    action VARCHAR NOT NULL,
+    entity_type VARCHAR NOT NULL,
+    entity_id UUID NOT NULL,
+    before_data JSONB,
+    after_data JSONB,
+    ip_address VARCHAR,
+    user_agent VARCHAR,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- No foreign keys on purpose: entries must outlive the users and rows they describe
+CREATE INDEX idx_audit_log_owner_id_created_at ON audit_log(owner_id, created_at DESC);
+CREATE INDEX idx_audit_log_entity ON audit_log(entity_type, entity_id);
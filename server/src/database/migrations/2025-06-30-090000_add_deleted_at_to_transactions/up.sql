@@ -0,0 +1,6 @@
+ALTER TABLE incomes ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE expenses ADD COLUMN deleted_at TIMESTAMP;
+
+-- The trash listing and the purge job only ever look at deleted rows
+CREATE INDEX idx_incomes_deleted_at ON incomes(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_expenses_deleted_at ON expenses(deleted_at) WHERE deleted_at IS NOT NULL;
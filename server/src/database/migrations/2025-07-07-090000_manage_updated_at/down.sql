@@ -0,0 +1,16 @@
+DROP TRIGGER set_updated_at ON user_totp;
+DROP TRIGGER set_updated_at ON expenses;
+DROP TRIGGER set_updated_at ON incomes;
+DROP TRIGGER set_updated_at ON users;
+
+CREATE OR REPLACE FUNCTION diesel_set_updated_at() RETURNS trigger AS $$
+BEGIN
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at
+    ) THEN
+        NEW.updated_at := current_timestamp;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
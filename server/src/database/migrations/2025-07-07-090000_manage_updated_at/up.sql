@@ -0,0 +1,18 @@
+-- The application stores naive UTC timestamps, so the trigger must not use
+-- the session time zone like the stock helper does
+CREATE OR REPLACE FUNCTION diesel_set_updated_at() RETURNS trigger AS $$
+BEGIN
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at
+    ) THEN
+        NEW.updated_at := timezone('utc', now());
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT diesel_manage_updated_at('users');
+SELECT diesel_manage_updated_at('incomes');
+SELECT diesel_manage_updated_at('expenses');
+SELECT diesel_manage_updated_at('user_totp');
@@ -0,0 +1,29 @@
+CREATE TABLE goals (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    target_amount NUMERIC NOT NULL CHECK (target_amount > 0),
+    deadline DATE,
+    -- Free-form labels linking the goal to where the money is kept or
+    -- which kind of spending it is saved for
+    account VARCHAR,
+    category VARCHAR,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE goal_contributions (
+    id UUID PRIMARY KEY,
+    goal_id UUID NOT NULL,
+    amount NUMERIC NOT NULL,
+    date DATE NOT NULL,
+    note TEXT,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (goal_id) REFERENCES goals(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_goals_user_id ON goals(user_id);
+CREATE INDEX idx_goal_contributions_goal_id ON goal_contributions(goal_id);
+
+SELECT diesel_manage_updated_at('goals');
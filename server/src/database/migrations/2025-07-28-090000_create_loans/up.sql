@@ -0,0 +1,28 @@
+CREATE TABLE loans (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    principal NUMERIC NOT NULL CHECK (principal > 0),
+    -- Nominal annual interest rate in percent, compounded monthly
+    annual_rate NUMERIC NOT NULL CHECK (annual_rate >= 0),
+    term_months INTEGER NOT NULL CHECK (term_months > 0),
+    -- The first payment is due one month after this date
+    start_date DATE NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Expenses that pay off a loan. An expense pays at most one loan.
+CREATE TABLE loan_payments (
+    expense_id UUID PRIMARY KEY,
+    loan_id UUID NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE,
+    FOREIGN KEY (loan_id) REFERENCES loans(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_loans_user_id ON loans(user_id);
+CREATE INDEX idx_loan_payments_loan_id ON loan_payments(loan_id);
+
+SELECT diesel_manage_updated_at('loans');
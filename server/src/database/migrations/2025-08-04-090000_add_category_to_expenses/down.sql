@@ -0,0 +1,2 @@
+DROP INDEX idx_expenses_user_id_date;
+ALTER TABLE expenses DROP COLUMN category;
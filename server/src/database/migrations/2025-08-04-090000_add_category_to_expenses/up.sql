@@ -0,0 +1,4 @@
+ALTER TABLE expenses ADD COLUMN category VARCHAR;
+
+-- Spending reports aggregate a user's expenses over a date range
+CREATE INDEX idx_expenses_user_id_date ON expenses(user_id, date) WHERE deleted_at IS NULL;
@@ -0,0 +1,3 @@
+DROP TABLE net_worth_snapshots;
+DROP TABLE valuations;
+DROP TABLE net_worth_items;
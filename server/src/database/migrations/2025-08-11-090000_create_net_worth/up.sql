@@ -0,0 +1,39 @@
+-- Assets and liabilities tracked by hand, e.g. a house or a mortgage
+CREATE TABLE net_worth_items (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL CHECK (kind IN ('asset', 'liability')),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- What an item was worth on a date; it keeps that value until the next one
+CREATE TABLE valuations (
+    id UUID PRIMARY KEY,
+    item_id UUID NOT NULL,
+    date DATE NOT NULL,
+    value NUMERIC NOT NULL CHECK (value >= 0),
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (item_id) REFERENCES net_worth_items(id) ON DELETE CASCADE,
+    UNIQUE (item_id, date)
+);
+
+-- Derived month-end net worth. Rows are deleted when the data they were
+-- computed from changes and recomputed on the next read.
+CREATE TABLE net_worth_snapshots (
+    user_id UUID NOT NULL,
+    month_end DATE NOT NULL,
+    cash NUMERIC NOT NULL,
+    assets NUMERIC NOT NULL,
+    liabilities NUMERIC NOT NULL,
+    net_worth NUMERIC NOT NULL,
+    computed_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, month_end),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_net_worth_items_user_id ON net_worth_items(user_id);
+
+SELECT diesel_manage_updated_at('net_worth_items');
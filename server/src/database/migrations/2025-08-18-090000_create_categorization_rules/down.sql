@@ -0,0 +1,3 @@
+DROP TABLE categorization_rules;
+ALTER TABLE expenses DROP COLUMN display_name;
+ALTER TABLE expenses DROP COLUMN tags;
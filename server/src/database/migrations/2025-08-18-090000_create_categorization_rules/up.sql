@@ -0,0 +1,29 @@
+ALTER TABLE expenses ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+-- Readable name for raw bank text in item_name, which is kept as is
+ALTER TABLE expenses ADD COLUMN display_name VARCHAR;
+
+CREATE TABLE categorization_rules (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    -- Rules are applied in ascending priority; the first match sets the
+    -- category and display name, tags of all matches are combined
+    priority INTEGER NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    match_field VARCHAR NOT NULL CHECK (match_field IN ('item_name', 'description', 'any')),
+    match_type VARCHAR NOT NULL CHECK (match_type IN ('contains', 'regex')),
+    pattern VARCHAR,
+    min_amount NUMERIC,
+    max_amount NUMERIC,
+    sign VARCHAR CHECK (sign IN ('positive', 'negative')),
+    set_category VARCHAR,
+    add_tags TEXT[] NOT NULL DEFAULT '{}',
+    set_display_name VARCHAR,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_categorization_rules_user_id ON categorization_rules(user_id, priority);
+
+SELECT diesel_manage_updated_at('categorization_rules');
@@ -0,0 +1,17 @@
+-- Pairs of incomes or expenses that look like the same transaction recorded
+-- twice; first_id is the record created first
+CREATE TABLE duplicate_candidates (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    kind VARCHAR NOT NULL CHECK (kind IN ('income', 'expense')),
+    first_id UUID NOT NULL,
+    second_id UUID NOT NULL,
+    score NUMERIC NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'dismissed', 'merged')),
+    detected_at TIMESTAMP NOT NULL,
+    resolved_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (kind, first_id, second_id)
+);
+
+CREATE INDEX idx_duplicate_candidates_user_id ON duplicate_candidates(user_id, status);
@@ -0,0 +1,6 @@
+DROP TABLE reconciliation_items;
+DROP TABLE reconciliations;
+ALTER TABLE expenses DROP COLUMN reconciled_at;
+ALTER TABLE incomes DROP COLUMN reconciled_at;
+ALTER TABLE expenses DROP COLUMN account;
+ALTER TABLE incomes DROP COLUMN account;
@@ -0,0 +1,36 @@
+-- Free-form name of the bank account or card a transaction went through
+ALTER TABLE incomes ADD COLUMN account VARCHAR;
+ALTER TABLE expenses ADD COLUMN account VARCHAR;
+-- Set once the transaction was reconciled against a statement; locks it
+ALTER TABLE incomes ADD COLUMN reconciled_at TIMESTAMP;
+ALTER TABLE expenses ADD COLUMN reconciled_at TIMESTAMP;
+
+CREATE TABLE reconciliations (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    account VARCHAR NOT NULL,
+    period_start DATE NOT NULL,
+    period_end DATE NOT NULL CHECK (period_end >= period_start),
+    opening_balance NUMERIC NOT NULL,
+    statement_balance NUMERIC NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'finished')),
+    finished_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- One session in progress per account
+CREATE UNIQUE INDEX idx_reconciliations_open_account ON reconciliations(user_id, account) WHERE status = 'open';
+
+SELECT diesel_manage_updated_at('reconciliations');
+
+-- Transactions marked as cleared in a session
+CREATE TABLE reconciliation_items (
+    reconciliation_id UUID NOT NULL,
+    kind VARCHAR NOT NULL CHECK (kind IN ('income', 'expense')),
+    record_id UUID NOT NULL,
+    cleared_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (reconciliation_id, kind, record_id),
+    FOREIGN KEY (reconciliation_id) REFERENCES reconciliations(id) ON DELETE CASCADE
+);
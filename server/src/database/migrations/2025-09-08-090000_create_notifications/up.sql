@@ -0,0 +1,37 @@
+-- One row per user once they change a setting; users without a row get the
+-- defaults
+CREATE TABLE notification_preferences (
+    user_id UUID PRIMARY KEY,
+    email_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Notifications are posted as JSON to this URL when set
+    webhook_url VARCHAR,
+    weekly_summary BOOLEAN NOT NULL DEFAULT TRUE,
+    bill_reminders BOOLEAN NOT NULL DEFAULT TRUE,
+    bill_reminder_days INTEGER NOT NULL DEFAULT 3 CHECK (bill_reminder_days BETWEEN 1 AND 14),
+    monthly_budget NUMERIC CHECK (monthly_budget > 0),
+    budget_alert_percent INTEGER NOT NULL DEFAULT 80 CHECK (budget_alert_percent BETWEEN 1 AND 100),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+SELECT diesel_manage_updated_at('notification_preferences');
+
+-- In-app inbox, also the record of what was sent through the other channels
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    kind VARCHAR NOT NULL CHECK (kind IN ('weekly_summary', 'bill_reminder', 'budget_threshold')),
+    -- Identifies the occasion, e.g. the week of a summary, so the scheduler
+    -- notifies about it only once
+    dedup_key VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    body TEXT NOT NULL,
+    data JSONB NOT NULL DEFAULT '{}',
+    read_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, dedup_key)
+);
+
+CREATE INDEX idx_notifications_user_id ON notifications(user_id, created_at DESC);
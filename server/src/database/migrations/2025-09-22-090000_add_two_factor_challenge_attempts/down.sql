@@ -0,0 +1,3 @@
+ALTER TABLE user_totp
+    DROP COLUMN challenge_attempts,
+    DROP COLUMN challenge_id;
@@ -0,0 +1,5 @@
+-- The one challenge a user may currently answer, and how many answers it
+-- has had, so that a challenge token cannot be used to guess codes forever
+ALTER TABLE user_totp
+    ADD COLUMN challenge_id UUID,
+    ADD COLUMN challenge_attempts INTEGER NOT NULL DEFAULT 0;
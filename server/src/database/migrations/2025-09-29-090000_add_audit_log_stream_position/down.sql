@@ -0,0 +1,5 @@
+DROP INDEX idx_audit_log_stream_position;
+
+ALTER TABLE audit_log
+    DROP COLUMN seq,
+    DROP COLUMN tx_id;
@@ -0,0 +1,9 @@
+-- Where each entry falls in commit order, for the live event stream: the
+-- transaction that wrote it, then the order it was written in. A reader
+-- that only looks at transactions older than its snapshot's xmin sees every
+-- entry exactly once, however late its transaction commits.
+ALTER TABLE audit_log
+    ADD COLUMN tx_id BIGINT NOT NULL DEFAULT (pg_current_xact_id()::text::bigint),
+    ADD COLUMN seq BIGSERIAL;
+
+CREATE INDEX idx_audit_log_stream_position ON audit_log(tx_id, seq);
@@ -0,0 +1,16 @@
+DROP INDEX idx_notifications_email_due;
+
+ALTER TABLE notifications
+    DROP COLUMN emailed_at,
+    DROP COLUMN email_next_attempt_at,
+    DROP COLUMN email_attempts;
+
+ALTER TABLE notification_preferences ADD COLUMN webhook_url VARCHAR;
+
+UPDATE notification_preferences
+SET webhook_url = webhooks.url
+FROM webhooks
+WHERE webhooks.user_id = notification_preferences.user_id
+    AND webhooks.events = ARRAY['notification.created'];
+
+DELETE FROM webhooks WHERE events = ARRAY['notification.created'];
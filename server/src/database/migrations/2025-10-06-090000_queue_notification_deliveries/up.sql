@@ -0,0 +1,27 @@
+-- The webhook channel of notifications becomes a webhook subscribed to
+-- notification.created, so its deliveries are signed, checked, retried and
+-- logged like every other one
+INSERT INTO webhooks (id, user_id, url, secret, events, enabled, created_at, updated_at)
+SELECT
+    gen_random_uuid(),
+    user_id,
+    webhook_url,
+    'whsec_' || substr(replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', ''), 1, 32),
+    ARRAY['notification.created'],
+    TRUE,
+    NOW(),
+    NOW()
+FROM notification_preferences
+WHERE webhook_url IS NOT NULL;
+
+ALTER TABLE notification_preferences DROP COLUMN webhook_url;
+
+-- Emails of notifications wait here to be sent by their own job, so a slow
+-- mail server holds up nothing else
+ALTER TABLE notifications
+    ADD COLUMN email_attempts INTEGER NOT NULL DEFAULT 0,
+    -- When the email is tried next; empty once sent or given up on
+    ADD COLUMN email_next_attempt_at TIMESTAMP,
+    ADD COLUMN emailed_at TIMESTAMP;
+
+CREATE INDEX idx_notifications_email_due ON notifications(email_next_attempt_at) WHERE email_next_attempt_at IS NOT NULL;
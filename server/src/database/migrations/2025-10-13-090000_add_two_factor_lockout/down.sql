@@ -0,0 +1,4 @@
+ALTER TABLE user_totp
+    DROP COLUMN locked_until,
+    DROP COLUMN failed_attempts,
+    ADD COLUMN challenge_attempts INTEGER NOT NULL DEFAULT 0;
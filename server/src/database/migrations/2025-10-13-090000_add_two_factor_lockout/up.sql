@@ -0,0 +1,6 @@
+-- Wrong second factor codes since the last correct one, counted per user
+-- rather than per login challenge, and when the next code may be tried
+ALTER TABLE user_totp
+    DROP COLUMN challenge_attempts,
+    ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN locked_until TIMESTAMP;
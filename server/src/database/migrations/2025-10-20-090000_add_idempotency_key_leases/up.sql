@@ -0,0 +1,5 @@
+-- Until when the request that claimed a key holds it. The claim is renewed
+-- while the request runs, so only an abandoned claim runs out.
+ALTER TABLE idempotency_keys ADD COLUMN leased_until TIMESTAMP NOT NULL DEFAULT NOW();
+UPDATE idempotency_keys SET leased_until = created_at + INTERVAL '60 seconds';
+ALTER TABLE idempotency_keys ALTER COLUMN leased_until DROP DEFAULT;
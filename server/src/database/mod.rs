@@ -1,2 +1,4 @@
 pub mod db_migrations;
-pub mod db_connection;
\ No newline at end of file
+pub mod db_connection;
+#[cfg(test)]
+pub mod test_database;
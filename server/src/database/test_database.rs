@@ -0,0 +1,61 @@
+use std::sync::Once;
+
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, Pool};
+use uuid::Uuid;
+
+use crate::database::db_connection::{DbConnection, DbPool};
+use crate::database::db_migrations;
+use crate::models::schema::users;
+use crate::models::user::{NewUser, User};
+
+static MIGRATED: Once = Once::new();
+
+/// Starts the transaction that keeps a test's changes from being committed
+#[derive(Debug)]
+struct TestTransaction;
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for TestTransaction {
+    fn on_acquire(&self, connection: &mut PgConnection) -> Result<(), r2d2::Error> {
+        connection.begin_test_transaction().map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// Connection to the database named by `TEST_DATABASE_URL` for tests that
+/// need PostgreSQL, or `None` when it is not set and the test is skipped.
+/// Everything runs in a transaction that is never committed, so tests do not
+/// see each other's rows.
+pub fn connection() -> Option<DbConnection> {
+    pool().map(|pool| pool.get().expect("Failed to connect to TEST_DATABASE_URL"))
+}
+
+/// Pool of a single connection like the one of [`connection`], for tests of
+/// code that gets its connections from the app's pool
+pub fn pool() -> Option<DbPool> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set; skipping");
+        return None;
+    };
+
+    MIGRATED.call_once(|| {
+        let mut connection = PgConnection::establish(&url).expect("Failed to connect to TEST_DATABASE_URL");
+        db_migrations::run_migrations(&mut connection);
+    });
+
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_customizer(Box::new(TestTransaction))
+        .build(ConnectionManager::<PgConnection>::new(url))
+        .expect("Failed to connect to TEST_DATABASE_URL");
+    Some(pool)
+}
+
+/// A new user with a unique email address
+pub fn user(connection: &mut PgConnection) -> User {
+    let id = Uuid::new_v4();
+    diesel::insert_into(users::table)
+        .values(NewUser::new("Test".to_string(), "User".to_string(), format!("{}@example.com", id), "not a hash".to_string()))
+        .returning(User::as_returning())
+        .get_result(connection)
+        .expect("Failed to create a test user")
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::Context;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbPool;
+use crate::graphql::graphql_error;
+use crate::graphql::types::UserObject;
+use crate::models::user::User;
+use crate::services::admin_service;
+
+/// Loads the users that the `user` fields of a query's incomes and expenses
+/// ask for with one query instead of one per item
+pub struct UserLoader {
+    pool: DbPool,
+}
+
+impl UserLoader {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl Loader<Uuid> for UserLoader {
+    type Value = User;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, User>, Self::Error> {
+        let mut conn = self.pool.get().map_err(|error| Arc::new(AppError::from(error)))?;
+        let users = admin_service::get_users_by_ids(&mut conn, keys).map_err(|error| Arc::new(AppError::from(error)))?;
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+/// The owner of an income or expense the caller was allowed to see
+pub async fn load_user(ctx: &Context<'_>, user_id: Uuid) -> async_graphql::Result<UserObject> {
+    let user = ctx
+        .data::<DataLoader<UserLoader>>()?
+        .load_one(user_id)
+        .await
+        .map_err(|error| graphql_error(&error))?;
+
+    match user {
+        Some(user) => Ok(UserObject(user)),
+        None => Err(graphql_error(&AppError::from(diesel::result::Error::NotFound))),
+    }
+}
@@ -0,0 +1,217 @@
+//! Read-only GraphQL view of users, incomes and expenses
+//!
+//! Resolvers make the same ownership checks as the REST controllers; the
+//! caller's [`Claims`](crate::models::auth::Claims) are attached to every
+//! request by the GraphQL controller. List fields filter and page in SQL,
+//! and the owners of incomes and expenses are loaded in batches.
+
+mod loader;
+mod query;
+mod types;
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, ErrorExtensions, Schema};
+use actix_web::ResponseError;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::{DbConnection, DbPool};
+use crate::models::auth::Claims;
+
+use loader::UserLoader;
+pub use query::QueryRoot;
+
+pub type FinanceSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Deepest selection a query may nest, counting the root field
+const MAX_DEPTH: usize = 6;
+/// Highest complexity a query may have. Every field counts one and list
+/// fields multiply what they select by their `limit`.
+const MAX_COMPLEXITY: usize = 5_000;
+
+pub fn build_schema(pool: DbPool) -> FinanceSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(DataLoader::new(UserLoader::new(pool.clone()), actix_web::rt::spawn))
+        .data(pool)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Run `resolve` with a database connection and the caller's claims.
+/// Application errors become GraphQL errors carrying the HTTP status the
+/// same failure has over REST.
+fn resolve<T>(ctx: &Context<'_>, resolve: impl FnOnce(&mut DbConnection, &Claims) -> Result<T, AppError>) -> async_graphql::Result<T> {
+    let claims = ctx.data::<Claims>()?;
+    let result = ctx
+        .data::<DbPool>()?
+        .get()
+        .map_err(AppError::from)
+        .and_then(|mut conn| resolve(&mut conn, claims));
+
+    result.map_err(|error| graphql_error(&error))
+}
+
+/// GraphQL error carrying the HTTP status of `error`
+fn graphql_error(error: &AppError) -> async_graphql::Error {
+    let status = error.status_code().as_u16();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensions| extensions.set("status", status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+    use crate::models::audit::AuditContext;
+    use crate::models::expense::NewExpense;
+    use crate::models::income::NewIncome;
+    use crate::models::user::{Role, User};
+    use crate::services::{expense_service, income_service};
+    use async_graphql::{Request, Response};
+    use chrono::Utc;
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use rust_decimal::Decimal;
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    /// Schema whose pool never connects, for queries rejected before they run
+    fn offline_schema() -> FinanceSchema {
+        build_schema(Pool::builder().build_unchecked(ConnectionManager::new("postgres://localhost/unused")))
+    }
+
+    fn claims(user: &User) -> Claims {
+        Claims::new(user.id, user.email.clone(), Role::User, usize::MAX)
+    }
+
+    async fn run(schema: &FinanceSchema, claims: Claims, query: String) -> Response {
+        schema.execute(Request::new(query).data(claims)).await
+    }
+
+    fn messages(response: &Response) -> Vec<&str> {
+        response.errors.iter().map(|error| error.message.as_str()).collect()
+    }
+
+    fn statuses(response: &Response) -> Vec<Option<u16>> {
+        response
+            .errors
+            .iter()
+            .map(|error| {
+                let status = error.extensions.as_ref()?.get("status")?.clone();
+                status.into_json().ok()?.as_u64().map(|status| status as u16)
+            })
+            .collect()
+    }
+
+    fn data(response: Response) -> Value {
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()
+    }
+
+    fn claims_of_nobody() -> Claims {
+        Claims::new(Uuid::new_v4(), "nobody@example.com".to_string(), Role::User, usize::MAX)
+    }
+
+    #[actix_web::test]
+    async fn rejects_queries_nested_too_deep() {
+        let query = "{ me { incomes(limit: 1) { user { incomes(limit: 1) { user { incomes(limit: 1) { id } } } } } } }";
+
+        let response = run(&offline_schema(), claims_of_nobody(), query.to_string()).await;
+
+        assert_eq!(messages(&response), ["Query is nested too deep."]);
+    }
+
+    #[actix_web::test]
+    async fn rejects_queries_that_are_too_complex() {
+        let query = "{ incomes(limit: 100) { user { incomes(limit: 100) { id } } } }";
+
+        let response = run(&offline_schema(), claims_of_nobody(), query.to_string()).await;
+
+        assert_eq!(messages(&response), ["Query is too complex."]);
+    }
+
+    fn stored_income(connection: &mut DbConnection, user_id: Uuid, source: &str, amount: i64) -> Uuid {
+        let new_income = NewIncome {
+            user_id,
+            source: source.to_string(),
+            amount: Decimal::from(amount),
+            date: Utc::now().date_naive(),
+            description: None,
+            account: None,
+        };
+        income_service::create_income(connection, new_income, &AuditContext::default()).unwrap().id
+    }
+
+    fn stored_expense(connection: &mut DbConnection, user_id: Uuid) -> Uuid {
+        let new_expense = NewExpense {
+            user_id,
+            item_name: "Groceries".to_string(),
+            amount: Decimal::from(50),
+            description: None,
+            category: None,
+            tags: Vec::new(),
+            display_name: None,
+            account: None,
+        };
+        expense_service::create_expense(connection, new_expense, &AuditContext::default()).unwrap().id
+    }
+
+    #[actix_web::test]
+    async fn users_cannot_read_each_others_records() {
+        let Some(pool) = test_database::pool() else { return };
+        let (me, other, other_income, other_expense) = {
+            let mut conn = pool.get().unwrap();
+            let me = test_database::user(&mut conn);
+            let other = test_database::user(&mut conn);
+            stored_income(&mut conn, me.id, "Salary", 5000);
+            let other_income = stored_income(&mut conn, other.id, "Salary", 7000);
+            let other_expense = stored_expense(&mut conn, other.id);
+            (me, other, other_income, other_expense)
+        };
+        let schema = build_schema(pool);
+
+        for query in [
+            format!(r#"{{ income(id: "{}") {{ id }} }}"#, other_income),
+            format!(r#"{{ expense(id: "{}") {{ id }} }}"#, other_expense),
+            format!(r#"{{ incomes(userId: "{}") {{ id }} }}"#, other.id),
+            format!(r#"{{ expenses(userId: "{}") {{ id }} }}"#, other.id),
+            format!(r#"{{ user(id: "{}") {{ id }} }}"#, other.id),
+            format!(r#"{{ summary(userId: "{}") {{ net }} }}"#, other.id),
+        ] {
+            let response = run(&schema, claims(&me), query.clone()).await;
+            assert_eq!(statuses(&response), [Some(403)], "{}", query);
+        }
+
+        let response = run(&schema, claims(&me), "{ incomes { amount user { id } } expenses { id } }".to_string()).await;
+        assert_eq!(data(response), json!({ "incomes": [{ "amount": "5000", "user": { "id": me.id } }], "expenses": [] }));
+    }
+
+    #[actix_web::test]
+    async fn filters_and_pages_in_the_database() {
+        let Some(pool) = test_database::pool() else { return };
+        let me = {
+            let mut conn = pool.get().unwrap();
+            let me = test_database::user(&mut conn);
+            stored_income(&mut conn, me.id, "Salary", 5000);
+            stored_income(&mut conn, me.id, "Bonus 100%", 300);
+            stored_income(&mut conn, me.id, "Bonus 1000", 200);
+            stored_income(&mut conn, me.id, "Bonus", 100);
+            me
+        };
+        let schema = build_schema(pool);
+
+        let query = r#"{ incomes(filter: { source: "bonus", maxAmount: "250" }) { source } }"#;
+        let response = run(&schema, claims(&me), query.to_string()).await;
+        assert_eq!(data(response)["incomes"].as_array().unwrap().len(), 2);
+
+        let query = r#"{ incomes(filter: { source: "0%" }) { source } }"#;
+        let response = run(&schema, claims(&me), query.to_string()).await;
+        assert_eq!(data(response), json!({ "incomes": [{ "source": "Bonus 100%" }] }));
+
+        let query = "{ incomes(limit: 2, offset: 3) { source } }";
+        let response = run(&schema, claims(&me), query.to_string()).await;
+        assert_eq!(data(response)["incomes"].as_array().unwrap().len(), 1);
+
+        let query = "{ incomes(limit: 0) { source } }";
+        let response = run(&schema, claims(&me), query.to_string()).await;
+        assert_eq!(statuses(&response), [Some(400)]);
+    }
+}
@@ -0,0 +1,113 @@
+use async_graphql::{Context, Object};
+use chrono::NaiveDate;
+use diesel::OptionalExtension;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::graphql::resolve;
+use crate::graphql::types::{
+    load_expenses, load_incomes, load_users, page_complexity, summarize, ExpenseFilter, ExpenseObject, IncomeFilter, IncomeObject, Summary, UserObject,
+    DEFAULT_LIMIT,
+};
+use crate::models::auth::Claims;
+use crate::services::{admin_service, expense_service, income_service};
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The authenticated user
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<UserObject> {
+        resolve(ctx, |conn, claims| Ok(admin_service::get_user(conn, claims.user_id()?)?)).map(UserObject)
+    }
+
+    /// A user by ID; users other than admins can only look up themselves
+    async fn user(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<UserObject>> {
+        resolve(ctx, |conn, claims| {
+            claims.ensure_can_access(id)?;
+            Ok(admin_service::get_user(conn, id).optional()?)
+        })
+        .map(|user| user.map(UserObject))
+    }
+
+    /// Every user for admins, only themselves for everyone else
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn users(&self, ctx: &Context<'_>, #[graphql(default_with = "DEFAULT_LIMIT")] limit: i32, #[graphql(default)] offset: i32) -> async_graphql::Result<Vec<UserObject>> {
+        resolve(ctx, |conn, claims| {
+            let owner_id = if claims.is_admin() { None } else { Some(claims.user_id()?) };
+            load_users(conn, owner_id, limit, offset)
+        })
+        .map(|users| users.into_iter().map(UserObject).collect())
+    }
+
+    /// Incomes, newest first: the given user's, or without `userId` every
+    /// user's for admins and their own for everyone else
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn incomes(&self, ctx: &Context<'_>, user_id: Option<Uuid>, filter: Option<IncomeFilter>, #[graphql(default_with = "DEFAULT_LIMIT")] limit: i32, #[graphql(default)] offset: i32) -> async_graphql::Result<Vec<IncomeObject>> {
+        resolve(ctx, |conn, claims| {
+            let owner_id = owner_filter(claims, user_id)?;
+            load_incomes(conn, owner_id, filter.as_ref(), limit, offset)
+        })
+        .map(|incomes| incomes.into_iter().map(IncomeObject).collect())
+    }
+
+    async fn income(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<IncomeObject>> {
+        resolve(ctx, |conn, claims| {
+            let income = income_service::get_income_by_id(conn, id).optional()?;
+            if let Some(income) = &income {
+                claims.ensure_can_access(income.user_id)?;
+            }
+            Ok(income)
+        })
+        .map(|income| income.map(IncomeObject))
+    }
+
+    /// Expenses, newest first: the given user's, or without `userId` every
+    /// user's for admins and their own for everyone else
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn expenses(&self, ctx: &Context<'_>, user_id: Option<Uuid>, filter: Option<ExpenseFilter>, #[graphql(default_with = "DEFAULT_LIMIT")] limit: i32, #[graphql(default)] offset: i32) -> async_graphql::Result<Vec<ExpenseObject>> {
+        resolve(ctx, |conn, claims| {
+            let owner_id = owner_filter(claims, user_id)?;
+            load_expenses(conn, owner_id, filter.as_ref(), limit, offset)
+        })
+        .map(|expenses| expenses.into_iter().map(ExpenseObject).collect())
+    }
+
+    async fn expense(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<ExpenseObject>> {
+        resolve(ctx, |conn, claims| {
+            let expense = expense_service::get_expense_by_id(conn, id).optional()?;
+            if let Some(expense) = &expense {
+                claims.ensure_can_access(expense.user_id)?;
+            }
+            Ok(expense)
+        })
+        .map(|expense| expense.map(ExpenseObject))
+    }
+
+    /// Totals of a user's incomes and expenses between `from` and `to`,
+    /// by default of the authenticated user
+    async fn summary(&self, ctx: &Context<'_>, user_id: Option<Uuid>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> async_graphql::Result<Summary> {
+        resolve(ctx, |conn, claims| {
+            let user_id = match user_id {
+                Some(user_id) => user_id,
+                None => claims.user_id()?,
+            };
+            claims.ensure_can_access(user_id)?;
+            summarize(conn, user_id, from, to)
+        })
+    }
+}
+
+/// Owner whose items a list shows: `user_id` if the caller may see their
+/// items, otherwise everyone for admins and the caller for everyone else
+fn owner_filter(claims: &Claims, user_id: Option<Uuid>) -> Result<Option<Uuid>, AppError> {
+    match user_id {
+        Some(user_id) => {
+            claims.ensure_can_access(user_id)?;
+            Ok(Some(user_id))
+        }
+        None if claims.is_admin() => Ok(None),
+        None => Ok(Some(claims.user_id()?)),
+    }
+}
+
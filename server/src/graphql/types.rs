@@ -0,0 +1,451 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use async_graphql::{ComplexObject, Context, Enum, InputObject, Object, SimpleObject};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::graphql::loader::load_user;
+use crate::graphql::resolve;
+use crate::models::expense::Expense;
+use crate::models::income::Income;
+use crate::models::schema::{expenses, incomes, users};
+use crate::models::user::{Role, User};
+
+/// Default number of items a list field returns
+pub const DEFAULT_LIMIT: i32 = 100;
+/// Most items a list field returns
+pub const MAX_LIMIT: i32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[graphql(name = "Role")]
+pub enum RoleObject {
+    User,
+    Admin,
+}
+
+impl From<Role> for RoleObject {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::User => RoleObject::User,
+            Role::Admin => RoleObject::Admin,
+        }
+    }
+}
+
+pub struct UserObject(pub User);
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn first_name(&self) -> &str {
+        &self.0.first_name
+    }
+
+    async fn last_name(&self) -> &str {
+        &self.0.last_name
+    }
+
+    async fn email(&self) -> &str {
+        &self.0.email
+    }
+
+    async fn role(&self) -> RoleObject {
+        self.0.role.into()
+    }
+
+    async fn created_at(&self) -> NaiveDateTime {
+        self.0.created_at
+    }
+
+    /// The user's incomes, newest first
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn incomes(&self, ctx: &Context<'_>, filter: Option<IncomeFilter>, #[graphql(default_with = "DEFAULT_LIMIT")] limit: i32, #[graphql(default)] offset: i32) -> async_graphql::Result<Vec<IncomeObject>> {
+        let user_id = self.0.id;
+        resolve(ctx, |conn, _| load_incomes(conn, Some(user_id), filter.as_ref(), limit, offset))
+            .map(|incomes| incomes.into_iter().map(IncomeObject).collect())
+    }
+
+    /// The user's expenses, newest first
+    #[graphql(complexity = "page_complexity(limit, child_complexity)")]
+    async fn expenses(&self, ctx: &Context<'_>, filter: Option<ExpenseFilter>, #[graphql(default_with = "DEFAULT_LIMIT")] limit: i32, #[graphql(default)] offset: i32) -> async_graphql::Result<Vec<ExpenseObject>> {
+        let user_id = self.0.id;
+        resolve(ctx, |conn, _| load_expenses(conn, Some(user_id), filter.as_ref(), limit, offset))
+            .map(|expenses| expenses.into_iter().map(ExpenseObject).collect())
+    }
+
+    /// Totals of the user's incomes and expenses between `from` and `to`
+    async fn summary(&self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> async_graphql::Result<Summary> {
+        let user_id = self.0.id;
+        resolve(ctx, |conn, _| summarize(conn, user_id, from, to))
+    }
+}
+
+pub struct IncomeObject(pub Income);
+
+#[Object(name = "Income")]
+impl IncomeObject {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn user_id(&self) -> Uuid {
+        self.0.user_id
+    }
+
+    async fn source(&self) -> &str {
+        &self.0.source
+    }
+
+    async fn amount(&self) -> Decimal {
+        self.0.amount
+    }
+
+    async fn date(&self) -> NaiveDate {
+        self.0.date
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn account(&self) -> Option<&str> {
+        self.0.account.as_deref()
+    }
+
+    async fn reconciled_at(&self) -> Option<NaiveDateTime> {
+        self.0.reconciled_at
+    }
+
+    async fn created_at(&self) -> NaiveDateTime {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime {
+        self.0.updated_at
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<UserObject> {
+        load_user(ctx, self.0.user_id).await
+    }
+}
+
+pub struct ExpenseObject(pub Expense);
+
+#[Object(name = "Expense")]
+impl ExpenseObject {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn user_id(&self) -> Uuid {
+        self.0.user_id
+    }
+
+    async fn item_name(&self) -> &str {
+        &self.0.item_name
+    }
+
+    /// Readable name for `itemName`
+    async fn display_name(&self) -> Option<&str> {
+        self.0.display_name.as_deref()
+    }
+
+    async fn amount(&self) -> Decimal {
+        self.0.amount
+    }
+
+    async fn date(&self) -> NaiveDate {
+        self.0.date
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    async fn account(&self) -> Option<&str> {
+        self.0.account.as_deref()
+    }
+
+    async fn reconciled_at(&self) -> Option<NaiveDateTime> {
+        self.0.reconciled_at
+    }
+
+    async fn created_at(&self) -> NaiveDateTime {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime {
+        self.0.updated_at
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<UserObject> {
+        load_user(ctx, self.0.user_id).await
+    }
+}
+
+/// Conditions incomes must all meet. Dates and amounts are inclusive.
+#[derive(Debug, Default, InputObject)]
+pub struct IncomeFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Part of the source, ignoring case
+    pub source: Option<String>,
+    pub account: Option<String>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+}
+
+/// Conditions expenses must all meet. Dates and amounts are inclusive.
+#[derive(Debug, Default, InputObject)]
+pub struct ExpenseFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub account: Option<String>,
+    /// Part of the item name, display name or description, ignoring case
+    pub search: Option<String>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+}
+
+/// Totals of incomes and expenses over a period
+#[derive(Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Summary {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub income_total: Decimal,
+    pub income_count: usize,
+    pub expense_total: Decimal,
+    pub expense_count: usize,
+    /// Spending per category, largest first
+    pub by_category: Vec<CategoryTotal>,
+    /// Income and spending per calendar month, oldest first
+    pub by_month: Vec<MonthTotal>,
+}
+
+#[ComplexObject]
+impl Summary {
+    /// Income minus expenses
+    async fn net(&self) -> Decimal {
+        self.income_total - self.expense_total
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct CategoryTotal {
+    /// Empty for uncategorized expenses
+    pub category: Option<String>,
+    pub total: Decimal,
+    pub count: usize,
+}
+
+#[derive(Debug, Default, SimpleObject)]
+pub struct MonthTotal {
+    /// `YYYY-MM`
+    pub month: String,
+    pub income: Decimal,
+    pub expenses: Decimal,
+    pub net: Decimal,
+}
+
+/// Page of incomes matching `filter`, newest first. `owner_id` restricts
+/// them to one user's (`None` is for admins only).
+pub fn load_incomes(conn: &mut DbConnection, owner_id: Option<Uuid>, filter: Option<&IncomeFilter>, limit: i32, offset: i32) -> Result<Vec<Income>, AppError> {
+    check_page(limit, offset)?;
+    Ok(filtered_incomes(owner_id, filter)
+        .order((incomes::date.desc(), incomes::created_at.desc(), incomes::id.desc()))
+        .limit(limit.into())
+        .offset(offset.into())
+        .select(Income::as_select())
+        .load(conn)?)
+}
+
+/// Page of expenses matching `filter`, newest first. `owner_id` restricts
+/// them to one user's (`None` is for admins only).
+pub fn load_expenses(conn: &mut DbConnection, owner_id: Option<Uuid>, filter: Option<&ExpenseFilter>, limit: i32, offset: i32) -> Result<Vec<Expense>, AppError> {
+    check_page(limit, offset)?;
+    Ok(filtered_expenses(owner_id, filter)
+        .order((expenses::date.desc(), expenses::created_at.desc(), expenses::id.desc()))
+        .limit(limit.into())
+        .offset(offset.into())
+        .select(Expense::as_select())
+        .load(conn)?)
+}
+
+/// Page of users, oldest first. `owner_id` restricts them to that user
+/// (`None` is for admins only).
+pub fn load_users(conn: &mut DbConnection, owner_id: Option<Uuid>, limit: i32, offset: i32) -> Result<Vec<User>, AppError> {
+    check_page(limit, offset)?;
+    let mut query = users::table.into_boxed();
+    if let Some(owner_id) = owner_id {
+        query = query.filter(users::id.eq(owner_id));
+    }
+    Ok(query
+        .order((users::created_at.asc(), users::id.asc()))
+        .limit(limit.into())
+        .offset(offset.into())
+        .select(User::as_select())
+        .load(conn)?)
+}
+
+fn filtered_incomes<'a>(owner_id: Option<Uuid>, filter: Option<&IncomeFilter>) -> incomes::BoxedQuery<'a, Pg> {
+    let mut query = incomes::table.filter(incomes::deleted_at.is_null()).into_boxed();
+    if let Some(owner_id) = owner_id {
+        query = query.filter(incomes::user_id.eq(owner_id));
+    }
+    let Some(filter) = filter else {
+        return query;
+    };
+
+    if let Some(from) = filter.from {
+        query = query.filter(incomes::date.ge(from));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(incomes::date.le(to));
+    }
+    if let Some(source) = &filter.source {
+        query = query.filter(incomes::source.ilike(containing(source)));
+    }
+    if let Some(account) = &filter.account {
+        query = query.filter(incomes::account.ilike(escape_like(account)));
+    }
+    if let Some(min_amount) = filter.min_amount {
+        query = query.filter(incomes::amount.ge(min_amount));
+    }
+    if let Some(max_amount) = filter.max_amount {
+        query = query.filter(incomes::amount.le(max_amount));
+    }
+    query
+}
+
+fn filtered_expenses<'a>(owner_id: Option<Uuid>, filter: Option<&ExpenseFilter>) -> expenses::BoxedQuery<'a, Pg> {
+    let mut query = expenses::table.filter(expenses::deleted_at.is_null()).into_boxed();
+    if let Some(owner_id) = owner_id {
+        query = query.filter(expenses::user_id.eq(owner_id));
+    }
+    let Some(filter) = filter else {
+        return query;
+    };
+
+    if let Some(from) = filter.from {
+        query = query.filter(expenses::date.ge(from));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(expenses::date.le(to));
+    }
+    if let Some(category) = &filter.category {
+        query = query.filter(expenses::category.ilike(escape_like(category)));
+    }
+    if let Some(tag) = &filter.tag {
+        query = query.filter(expenses::tags.contains(vec![tag.clone()]));
+    }
+    if let Some(account) = &filter.account {
+        query = query.filter(expenses::account.ilike(escape_like(account)));
+    }
+    if let Some(search) = &filter.search {
+        let pattern = containing(search);
+        query = query.filter(
+            expenses::item_name
+                .ilike(pattern.clone())
+                .nullable()
+                .or(expenses::display_name.ilike(pattern.clone()))
+                .or(expenses::description.ilike(pattern)),
+        );
+    }
+    if let Some(min_amount) = filter.min_amount {
+        query = query.filter(expenses::amount.ge(min_amount));
+    }
+    if let Some(max_amount) = filter.max_amount {
+        query = query.filter(expenses::amount.le(max_amount));
+    }
+    query
+}
+
+/// Check that `limit` and `offset` describe a page
+fn check_page(limit: i32, offset: i32) -> Result<(), AppError> {
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!("limit must be between 1 and {}", MAX_LIMIT)));
+    }
+    if offset < 0 {
+        return Err(AppError::Validation("offset must not be negative".to_string()));
+    }
+    Ok(())
+}
+
+/// Complexity of a list field, assuming it returns a full page
+pub fn page_complexity(limit: i32, child_complexity: usize) -> usize {
+    limit.clamp(1, MAX_LIMIT) as usize * child_complexity
+}
+
+/// Totals of a user's incomes and expenses between `from` and `to`
+pub fn summarize(conn: &mut DbConnection, user_id: Uuid, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Summary, AppError> {
+    let incomes: Vec<Income> = filtered_incomes(Some(user_id), Some(&IncomeFilter { from, to, ..Default::default() }))
+        .select(Income::as_select())
+        .load(conn)?;
+    let expenses: Vec<Expense> = filtered_expenses(Some(user_id), Some(&ExpenseFilter { from, to, ..Default::default() }))
+        .select(Expense::as_select())
+        .load(conn)?;
+
+    let mut categories: BTreeMap<Option<String>, (Decimal, usize)> = BTreeMap::new();
+    let mut months: BTreeMap<String, MonthTotal> = BTreeMap::new();
+    for income in &incomes {
+        let month = months.entry(income.date.format("%Y-%m").to_string()).or_default();
+        month.income += income.amount;
+    }
+    for expense in &expenses {
+        let category = categories.entry(expense.category.clone()).or_default();
+        category.0 += expense.amount;
+        category.1 += 1;
+        let month = months.entry(expense.date.format("%Y-%m").to_string()).or_default();
+        month.expenses += expense.amount;
+    }
+
+    let mut by_category: Vec<CategoryTotal> = categories
+        .into_iter()
+        .map(|(category, (total, count))| CategoryTotal { category, total, count })
+        .collect();
+    by_category.sort_by_key(|category| Reverse(category.total));
+
+    Ok(Summary {
+        from,
+        to,
+        income_total: incomes.iter().map(|income| income.amount).sum(),
+        income_count: incomes.len(),
+        expense_total: expenses.iter().map(|expense| expense.amount).sum(),
+        expense_count: expenses.len(),
+        by_category,
+        by_month: months
+            .into_iter()
+            .map(|(month, total)| MonthTotal { net: total.income - total.expenses, month, ..total })
+            .collect(),
+    })
+}
+
+/// LIKE pattern matching text that contains `part`
+fn containing(part: &str) -> String {
+    format!("%{}%", escape_like(part))
+}
+
+/// `text` with the characters LIKE treats specially escaped
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
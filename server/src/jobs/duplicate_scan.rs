@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use actix_web::web;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::duplicate_service;
+
+/// How often all users' records are scanned for duplicates
+const SCAN_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Look for incomes and expenses that were recorded twice
+pub async fn run(pool: DbPool) {
+    let mut interval = actix_web::rt::time::interval(SCAN_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let scan = duplicate_service::scan_all(&mut conn)?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(scan)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(scan)) if scan.incomes == 0 && scan.expenses == 0 => {}
+            Ok(Ok(scan)) => {
+                log::info!("{} income and {} expense pairs look like duplicates", scan.incomes, scan.expenses);
+            }
+            Ok(Err(e)) => log::error!("Failed to scan for duplicates: {}", e),
+            Err(e) => log::error!("Failed to run the duplicate scan: {}", e),
+        }
+    }
+}
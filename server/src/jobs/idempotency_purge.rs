@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::idempotency_service;
+
+/// How often expired idempotency keys are removed
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delete stored responses whose replay window has passed
+pub async fn run(pool: DbPool) {
+    let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let purged = idempotency_service::purge_expired(&mut conn, Utc::now().naive_utc())?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(purged)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(0)) => {}
+            Ok(Ok(purged)) => log::info!("Purged {} expired idempotency keys", purged),
+            Ok(Err(e)) => log::error!("Failed to purge idempotency keys: {}", e),
+            Err(e) => log::error!("Failed to run the idempotency key purge: {}", e),
+        }
+    }
+}
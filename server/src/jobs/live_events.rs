@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use actix_web::web;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::live_event_service::{self, LiveEvents};
+
+/// How often new changes are looked for
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stream committed income and expense changes to the open event streams
+///
+/// The audit log is read in commit order from the last position published,
+/// up to the oldest transaction still running, so a long-running transaction
+/// holds back the changes committed after it started until it finishes.
+/// While no one is listening the position just follows along without
+/// reading anything.
+pub async fn run(pool: DbPool, live_events: web::Data<LiveEvents>) {
+    let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let shared = live_events.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            if shared.skip_to_if_idle(live_event_service::committed_position(&mut conn)?) {
+                return Ok(None);
+            }
+            let changes = live_event_service::get_changes(&mut conn, shared.position())?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Some(changes))
+        })
+        .await;
+
+        match result {
+            Ok(Ok(Some((events, position)))) => live_events.publish(&events, position),
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => log::error!("Failed to load live events: {}", e),
+            Err(e) => log::error!("Failed to run the live events poll: {}", e),
+        }
+    }
+}
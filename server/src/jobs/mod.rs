@@ -0,0 +1,23 @@
+mod duplicate_scan;
+mod idempotency_purge;
+mod live_events;
+mod notification_emails;
+mod notifications;
+mod trash_purge;
+mod webhook_delivery;
+
+use actix_web::web;
+
+use crate::database::db_connection::DbPool;
+use crate::services::live_event_service::LiveEvents;
+
+/// Start the background jobs that run inside the server process
+pub fn spawn_all(pool: DbPool, live_events: web::Data<LiveEvents>) {
+    actix_web::rt::spawn(trash_purge::run(pool.clone()));
+    actix_web::rt::spawn(idempotency_purge::run(pool.clone()));
+    actix_web::rt::spawn(duplicate_scan::run(pool.clone()));
+    actix_web::rt::spawn(notifications::run(pool.clone()));
+    actix_web::rt::spawn(notification_emails::run(pool.clone()));
+    actix_web::rt::spawn(webhook_delivery::run(pool.clone()));
+    actix_web::rt::spawn(live_events::run(pool, live_events));
+}
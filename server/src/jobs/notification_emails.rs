@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::{mailer, notification_service};
+
+/// How often the email queue is checked
+const EMAIL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Send the queued emails of notifications and retry failed ones when they
+/// are due
+pub async fn run(pool: DbPool) {
+    let mailer = mailer::from_config();
+    let mut interval = actix_web::rt::time::interval(EMAIL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let mailer = mailer.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let run = notification_service::send_due_emails(&mut conn, mailer.as_ref(), Utc::now().naive_utc())?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(run)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(run)) if run.retrying + run.failed > 0 => log::warn!(
+                "Emailed {} notifications, {} will be retried and {} failed for good",
+                run.sent,
+                run.retrying,
+                run.failed
+            ),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => log::error!("Failed to email notifications: {}", e),
+            Err(e) => log::error!("Failed to run the notification emails: {}", e),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::notification_service;
+
+/// How often due notifications are looked for. Each one is only sent once,
+/// so this bounds how late a notification can be.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Create weekly summaries, bill reminders and budget alerts when they are
+/// due. Their emails and webhook deliveries are sent by the jobs of those
+/// queues.
+pub async fn run(pool: DbPool) {
+    let mut interval = actix_web::rt::time::interval(SCHEDULE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let run = notification_service::run_scheduled(&mut conn, Utc::now().naive_utc())?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(run)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(run)) if run.weekly_summaries + run.bill_reminders + run.budget_alerts == 0 => {}
+            Ok(Ok(run)) => log::info!(
+                "Created {} weekly summaries, {} bill reminders and {} budget alerts",
+                run.weekly_summaries,
+                run.bill_reminders,
+                run.budget_alerts
+            ),
+            Ok(Err(e)) => log::error!("Failed to create notifications: {}", e),
+            Err(e) => log::error!("Failed to run the notification scheduler: {}", e),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::config;
+use crate::database::db_connection::{self, DbPool};
+use crate::services::{expense_service, income_service};
+
+/// How often the trash is checked for records past their retention period
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Permanently delete trashed incomes and expenses once they have been in
+/// the trash longer than `TRASH_RETENTION_DAYS`
+pub async fn run(pool: DbPool) {
+    let retention = chrono::Duration::days(config::get_trash_retention_days());
+    let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let deleted_before = Utc::now().naive_utc() - retention;
+            let incomes = income_service::purge_deleted_incomes(&mut conn, deleted_before)?;
+            let expenses = expense_service::purge_deleted_expenses(&mut conn, deleted_before)?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>((incomes, expenses))
+        })
+        .await;
+
+        match result {
+            Ok(Ok((0, 0))) => {}
+            Ok(Ok((incomes, expenses))) => {
+                log::info!("Purged {} incomes and {} expenses from the trash", incomes, expenses);
+            }
+            Ok(Err(e)) => log::error!("Failed to purge the trash: {}", e),
+            Err(e) => log::error!("Failed to run the trash purge: {}", e),
+        }
+    }
+}
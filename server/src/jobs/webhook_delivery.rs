@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::database::db_connection::{self, DbPool};
+use crate::services::webhook_service;
+
+/// How often the delivery queue is checked
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Send queued webhook deliveries and retry failed ones when they are due
+pub async fn run(pool: DbPool) {
+    let mut interval = actix_web::rt::time::interval(DELIVERY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let result = web::block(move || {
+            let mut conn = db_connection::get_connection(&pool)?;
+            let run = webhook_service::deliver_due(&mut conn, Utc::now().naive_utc())?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(run)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(run)) if run.retrying + run.failed > 0 => log::warn!(
+                "Delivered {} webhook events, {} will be retried and {} failed for good",
+                run.delivered,
+                run.retrying,
+                run.failed
+            ),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => log::error!("Failed to deliver webhook events: {}", e),
+            Err(e) => log::error!("Failed to run the webhook delivery: {}", e),
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use actix_web::{App, HttpServer, middleware::Logger};
+use actix_web::{App, HttpServer};
 use actix_web::{web};
 use actix_cors::Cors;
 use dotenvy::dotenv;
 use std::io;
-use utoipa::OpenApi;
+use utoipa::openapi::path::HttpMethod;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::Deprecated;
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
 mod config;
@@ -13,24 +16,127 @@ mod models;
 mod routes;
 mod services;
 mod database;
+mod jobs;
+mod graphql;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        controllers::health_controller::health_check,
+        controllers::health_controller::health_check_detailed,
         controllers::auth_controller::register,
         controllers::auth_controller::login,
         controllers::auth_controller::me,
         controllers::auth_controller::logout,
+        controllers::auth_controller::login_two_factor,
+        controllers::auth_controller::enroll_two_factor,
+        controllers::auth_controller::verify_two_factor,
+        controllers::auth_controller::disable_two_factor,
+        controllers::auth_controller::regenerate_recovery_codes,
+        controllers::auth_controller::jwks,
         controllers::income_controller::get_all_incomes,
         controllers::income_controller::get_incomes_by_user_id,
+        controllers::income_controller::get_my_incomes,
+        controllers::income_controller::get_income,
         controllers::income_controller::create_income,
         controllers::income_controller::update_income,
         controllers::income_controller::delete_income,
+        controllers::income_controller::get_deleted_incomes,
+        controllers::income_controller::restore_income,
+        controllers::income_controller::unlock_income,
+        controllers::income_controller::batch_incomes,
         controllers::expense_controller::get_all_expenses,
         controllers::expense_controller::get_expenses_by_user_id,
+        controllers::expense_controller::get_my_expenses,
+        controllers::expense_controller::get_expense,
         controllers::expense_controller::create_expense,
         controllers::expense_controller::update_expense,
         controllers::expense_controller::delete_expense,
+        controllers::expense_controller::get_deleted_expenses,
+        controllers::expense_controller::restore_expense,
+        controllers::expense_controller::unlock_expense,
+        controllers::expense_controller::batch_expenses,
+        controllers::admin_controller::get_all_users,
+        controllers::admin_controller::disable_user,
+        controllers::admin_controller::enable_user,
+        controllers::admin_controller::update_user_role,
+        controllers::admin_controller::get_statistics,
+        controllers::api_token_controller::get_tokens,
+        controllers::api_token_controller::create_token,
+        controllers::api_token_controller::revoke_token,
+        controllers::audit_controller::get_audit_log,
+        controllers::goal_controller::get_goals,
+        controllers::goal_controller::create_goal,
+        controllers::goal_controller::get_goal,
+        controllers::goal_controller::update_goal,
+        controllers::goal_controller::delete_goal,
+        controllers::goal_controller::get_contributions,
+        controllers::goal_controller::add_contribution,
+        controllers::goal_controller::delete_contribution,
+        controllers::goal_controller::get_progress,
+        controllers::loan_controller::get_loans,
+        controllers::loan_controller::create_loan,
+        controllers::loan_controller::get_loan,
+        controllers::loan_controller::update_loan,
+        controllers::loan_controller::delete_loan,
+        controllers::loan_controller::get_schedule,
+        controllers::loan_controller::get_status,
+        controllers::loan_controller::get_payments,
+        controllers::loan_controller::link_payment,
+        controllers::loan_controller::unlink_payment,
+        controllers::report_controller::get_spending_breakdown,
+        controllers::report_controller::get_forecast,
+        controllers::net_worth_controller::get_net_worth,
+        controllers::net_worth_controller::get_snapshots,
+        controllers::net_worth_controller::recompute_snapshots,
+        controllers::net_worth_controller::get_items,
+        controllers::net_worth_controller::create_item,
+        controllers::net_worth_controller::get_item,
+        controllers::net_worth_controller::update_item,
+        controllers::net_worth_controller::delete_item,
+        controllers::net_worth_controller::get_valuations,
+        controllers::net_worth_controller::add_valuation,
+        controllers::net_worth_controller::delete_valuation,
+        controllers::categorization_controller::get_rules,
+        controllers::categorization_controller::create_rule,
+        controllers::categorization_controller::get_rule,
+        controllers::categorization_controller::update_rule,
+        controllers::categorization_controller::delete_rule,
+        controllers::categorization_controller::dry_run,
+        controllers::categorization_controller::dry_run_rule,
+        controllers::categorization_controller::apply_rules,
+        controllers::categorization_controller::apply_rule,
+        controllers::categorization_controller::suggest_categories,
+        controllers::duplicate_controller::get_duplicates,
+        controllers::duplicate_controller::scan,
+        controllers::duplicate_controller::merge,
+        controllers::duplicate_controller::dismiss,
+        controllers::reconciliation_controller::get_reconciliations,
+        controllers::reconciliation_controller::create_reconciliation,
+        controllers::reconciliation_controller::get_reconciliation,
+        controllers::reconciliation_controller::update_reconciliation,
+        controllers::reconciliation_controller::delete_reconciliation,
+        controllers::reconciliation_controller::get_transactions,
+        controllers::reconciliation_controller::clear,
+        controllers::reconciliation_controller::unclear,
+        controllers::reconciliation_controller::finish,
+        controllers::notification_controller::get_notifications,
+        controllers::notification_controller::mark_read,
+        controllers::notification_controller::mark_all_read,
+        controllers::notification_controller::delete_notification,
+        controllers::notification_controller::get_preferences,
+        controllers::notification_controller::update_preferences,
+        controllers::webhook_controller::get_webhooks,
+        controllers::webhook_controller::create_webhook,
+        controllers::webhook_controller::get_webhook,
+        controllers::webhook_controller::update_webhook,
+        controllers::webhook_controller::delete_webhook,
+        controllers::webhook_controller::get_deliveries,
+        controllers::webhook_controller::retry_delivery,
+        controllers::live_event_controller::stream_events,
+        controllers::live_event_controller::create_stream_token,
+        controllers::graphql_controller::graphql,
+        controllers::graphql_controller::get_schema,
     ),
     components(
         schemas(
@@ -39,6 +145,12 @@ mod database;
             models::auth::TokenResponse,
             models::auth::UserInfo,
             models::auth::AuthError,
+            models::auth::LoginResponse,
+            models::two_factor::TotpEnrollmentResponse,
+            models::two_factor::TwoFactorCodeRequest,
+            models::two_factor::RecoveryCodesResponse,
+            models::two_factor::TwoFactorChallenge,
+            models::two_factor::TwoFactorLoginRequest,
 
             models::income::Income,
             models::income::NewIncome,
@@ -46,23 +158,179 @@ mod database;
             models::income::IncomeWithUser,
             models::expense::Expense,
             models::expense::NewExpense,
-            models::expense::UpdateExpense
+            models::expense::UpdateExpense,
+            models::batch::BatchMode,
+
+            models::user::Role,
+            models::admin::AdminUserView,
+            models::admin::UpdateRoleRequest,
+            models::admin::GlobalStatistics,
+            models::api_token::TokenScope,
+            models::api_token::NewApiToken,
+            models::api_token::ApiTokenView,
+            models::api_token::CreatedApiToken,
+            models::audit::AuditLogEntry,
+            models::goal::Goal,
+            models::goal::NewGoal,
+            models::goal::UpdateGoal,
+            models::goal::GoalContribution,
+            models::goal::NewGoalContribution,
+            models::goal::GoalProgress,
+            models::loan::Loan,
+            models::loan::NewLoan,
+            models::loan::UpdateLoan,
+            models::loan::LoanPayment,
+            models::loan::LinkLoanPayment,
+            models::loan::AmortizationPeriod,
+            models::loan::AmortizationSchedule,
+            models::loan::LoanStatus,
+            models::report::SpendingGroupBy,
+            models::report::SpendingGroup,
+            models::report::SpendingRemainder,
+            models::report::SpendingBreakdown,
+            models::report::RecurringItem,
+            models::report::ForecastPoint,
+            models::report::ForecastSeries,
+            models::report::Forecast,
+            models::net_worth::NetWorthItemKind,
+            models::net_worth::NetWorthItem,
+            models::net_worth::NewNetWorthItem,
+            models::net_worth::UpdateNetWorthItem,
+            models::net_worth::Valuation,
+            models::net_worth::NewValuation,
+            models::net_worth::NetWorthItemValue,
+            models::net_worth::NetWorth,
+            models::net_worth::NetWorthSnapshot,
+            models::categorization_rule::RuleMatchField,
+            models::categorization_rule::RuleMatchType,
+            models::categorization_rule::AmountSign,
+            models::categorization_rule::CategorizationRule,
+            models::categorization_rule::NewCategorizationRule,
+            models::categorization_rule::UpdateCategorizationRule,
+            models::categorization_rule::RuleMatch,
+            models::categorization_rule::RuleApplication,
+            models::category_suggestion::CategorySuggestion,
+            models::category_suggestion::CategorySuggestions,
+            models::duplicate::DuplicateKind,
+            models::duplicate::DuplicateStatus,
+            models::duplicate::DuplicateRecord,
+            models::duplicate::DuplicatePair,
+            models::duplicate::MergeDuplicate,
+            models::duplicate::DuplicateScan,
+            models::reconciliation::TransactionKind,
+            models::reconciliation::ReconciliationStatus,
+            models::reconciliation::Reconciliation,
+            models::reconciliation::NewReconciliation,
+            models::reconciliation::UpdateReconciliation,
+            models::reconciliation::ClearTransactions,
+            models::reconciliation::ReconciliationSummary,
+            models::reconciliation::ReconciliationTransaction,
+            models::notification::NotificationKind,
+            models::notification::Notification,
+            models::notification::NotificationsRead,
+            models::notification::NotificationPreferences,
+            models::notification::UpdateNotificationPreferences,
+            models::webhook::WebhookEvent,
+            models::webhook::DeliveryStatus,
+            models::webhook::Webhook,
+            models::webhook::NewWebhook,
+            models::webhook::CreatedWebhook,
+            models::webhook::UpdateWebhook,
+            models::webhook::WebhookDelivery,
+            models::live_event::EventStreamTokenResponse
         )
     ),
+    modifiers(&SecurityAddon, &DeprecatedPaths),
     tags(
+        (name = "health", description = "Liveness and readiness probes"),
         (name = "auth", description = "Authentication endpoints"),
         (name = "incomes", description = "Income management endpoints"),
-        (name = "expenses", description = "Expense management endpoints")
+        (name = "expenses", description = "Expense management endpoints"),
+        (name = "admin", description = "Administration endpoints (admin role required)"),
+        (name = "tokens", description = "Personal API token management"),
+        (name = "audit", description = "History of changes to user data"),
+        (name = "goals", description = "Savings goals and contributions"),
+        (name = "loans", description = "Loans, amortization schedules and repayments"),
+        (name = "reports", description = "Spending analytics and forecasts"),
+        (name = "net-worth", description = "Assets, liabilities and net worth over time"),
+        (name = "categorization", description = "Rules and learned suggestions that categorize, tag and rename expenses"),
+        (name = "duplicates", description = "Detection and merging of transactions recorded twice"),
+        (name = "reconciliation", description = "Checking accounts against bank statements"),
+        (name = "notifications", description = "Inbox, reminders and summaries, and how they are delivered"),
+        (name = "webhooks", description = "Signed push notifications of income and expense changes"),
+        (name = "events", description = "Live stream of income and expense changes"),
+        (name = "graphql", description = "Read-only GraphQL view of users, incomes and expenses")
     )
 )]
 struct ApiDoc;
 
+/// Declares the `bearer_auth` scheme and requires it for every operation;
+/// public ones opt out with `security(())`
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Token from a login, or a personal API token (`fst_...`)"))
+                    .build(),
+            ),
+        );
+        openapi.security = Some(vec![SecurityRequirement::new("bearer_auth", Vec::<String>::new())]);
+    }
+}
+
+/// Documents the deprecated paths as copies of the operations replacing them
+struct DeprecatedPaths;
+
+impl Modify for DeprecatedPaths {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for routes::DeprecatedOperation { method, path, successor } in routes::deprecated_operations() {
+            let http_method = || match method.as_str() {
+                "GET" => HttpMethod::Get,
+                "POST" => HttpMethod::Post,
+                "PUT" => HttpMethod::Put,
+                "PATCH" => HttpMethod::Patch,
+                "DELETE" => HttpMethod::Delete,
+                _ => unreachable!("unsupported method {}", method),
+            };
+            let mut operation = openapi
+                .paths
+                .get_path_operation(&successor, http_method())
+                .unwrap_or_else(|| panic!("{} {} is not documented", method, successor))
+                .clone();
+            operation.deprecated = Some(Deprecated::True);
+            operation.operation_id = operation.operation_id.map(|id| format!("deprecated_{}", id));
+            operation.description = Some(format!("Deprecated, use `{} {}` instead.", method, successor));
+            openapi.paths.add_path_operation(&path, vec![http_method()], operation);
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> io::Result<()> {
     // Initialize environment
     dotenv().ok();
     env_logger::init();
 
+    // Refuse to start without signing keys rather than issuing tokens
+    // nobody can verify
+    let jwt_keys = config::jwt::JwtKeys::from_env().map_err(|e| {
+        log::error!("Failed to load JWT keys: {}", e);
+        e
+    })?;
+    log::info!(
+        "Signing JWTs with {:?} (kid {})",
+        jwt_keys.algorithm(),
+        jwt_keys.signing_kid()
+    );
+    jwt_keys.install();
+
     let database_url = config::get_database_url();
     let server_url = config::get_server_url();
     log::info!("Starting server at: {}", server_url);
@@ -73,11 +341,17 @@ async fn main() -> io::Result<()> {
         .expect("Failed to get connection from pool");
     database::db_migrations::run_migrations(&mut conn);
 
+    let stream_start = services::live_event_service::committed_position(&mut conn)
+        .expect("Failed to read the live event stream position");
+    let live_events = web::Data::new(services::live_event_service::LiveEvents::starting_at(stream_start));
+    jobs::spawn_all(pool.clone(), live_events.clone());
+
+    let graphql_schema = web::Data::new(graphql::build_schema(pool.clone()));
     let openapi = ApiDoc::openapi();
 
     HttpServer::new(move || {
         // Configure custom logger
-        let logger = Logger::new("%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T");
+        let logger = middleware::access_log::access_log();
 
         // Configure CORS with more comprehensive settings
         let cors = Cors::default()
@@ -86,18 +360,23 @@ async fn main() -> io::Result<()> {
             .allowed_headers(vec![
                 "content-type", 
                 "authorization", 
+                "if-match",
+                "idempotency-key",
+                "last-event-id",
                 "accept",
                 "origin",
                 "x-requested-with",
                 "access-control-request-method",
                 "access-control-request-headers"
             ])
-            .expose_headers(vec!["content-type", "x-total-count"])
+            .expose_headers(vec!["content-type", "x-total-count", "etag", "idempotent-replayed", "deprecation", "sunset", "link"])
             .max_age(3600)
             .supports_credentials();
 
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(live_events.clone())
+            .app_data(graphql_schema.clone())
             .wrap(cors)
             .wrap(logger)
             .app_data(config::errors::json_error_handler())
@@ -111,3 +390,25 @@ async fn main() -> io::Result<()> {
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// The client crate generates its code from a copy of the spec; run with
+    /// `UPDATE_OPENAPI=1` to refresh that copy after changing the API
+    #[test]
+    fn client_spec_is_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("client/openapi.json");
+        let spec = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(&path, spec).unwrap();
+            return;
+        }
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(committed == spec, "{} is out of date, rerun the tests with UPDATE_OPENAPI=1", path.display());
+    }
+}
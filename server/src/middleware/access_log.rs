@@ -0,0 +1,59 @@
+use actix_web::dev::ServiceRequest;
+use actix_web::middleware::Logger;
+
+/// Query parameters whose values are credentials, such as the stream token
+/// of `GET /api/events`
+const SECRET_PARAMETERS: &[&str] = &["token"];
+
+/// Log every request like `%r` would, but with the values of
+/// [`SECRET_PARAMETERS`] left out of the request line
+pub fn access_log() -> Logger {
+    Logger::new("%a \"%{request_line}xi\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T")
+        .custom_request_replace("request_line", request_line)
+}
+
+fn request_line(req: &ServiceRequest) -> String {
+    let target = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), redact(query)),
+    };
+    format!("{} {} {:?}", req.method(), target, req.version())
+}
+
+fn redact(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SECRET_PARAMETERS.contains(&name) => format!("{}=[redacted]", name),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn redacts_stream_tokens() {
+        let req = TestRequest::get().uri("/api/events?token=eyJhbGciOi.abc.def&after=42").to_srv_request();
+
+        assert_eq!(request_line(&req), "GET /api/events?token=[redacted]&after=42 HTTP/1.1");
+    }
+
+    #[test]
+    fn keeps_other_parameters() {
+        let req = TestRequest::get().uri("/api/v1/incomes?page_token=1&tokens=2").to_srv_request();
+
+        assert_eq!(request_line(&req), "GET /api/v1/incomes?page_token=1&tokens=2 HTTP/1.1");
+    }
+
+    #[test]
+    fn logs_paths_without_query() {
+        let req = TestRequest::post().uri("/api/events/token").to_srv_request();
+
+        assert_eq!(request_line(&req), "POST /api/events/token HTTP/1.1");
+    }
+}
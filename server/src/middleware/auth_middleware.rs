@@ -1,7 +1,19 @@
-use actix_web::{dev::ServiceRequest, Error, HttpMessage};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
 use actix_web_httpauth::extractors::bearer::{BearerAuth, Config};
 use actix_web_httpauth::extractors::AuthenticationError;
+use diesel::prelude::*;
+use serde::Deserialize;
+use uuid::Uuid;
 
+use crate::config::errors::AppError;
+use crate::database::db_connection::{DbConnection, DbPool};
+use crate::models::auth::Claims;
+use crate::models::schema::users;
+use crate::models::user::User;
+use crate::services::{api_token_service, live_event_service};
 use crate::services::auth_service::AuthService;
 
 /// JWT token validator middleware
@@ -10,6 +22,9 @@ use crate::services::auth_service::AuthService;
 /// When a valid token is provided, it extracts the user claims and adds them to the request
 /// extensions for use in route handlers.
 /// 
+/// Personal API tokens (`fst_...`) are accepted as well. Their claims carry the token's
+/// scopes: any scope allows reads, while other methods need the `write` scope.
+/// 
 /// Usage example:
 /// ```rust
 /// use actix_web_httpauth::middleware::HttpAuthentication;
@@ -29,9 +44,8 @@ use crate::services::auth_service::AuthService;
 /// ```rust
 /// use crate::models::auth::Claims;
 /// 
-/// pub async fn protected_handler(req: HttpRequest) -> Result<HttpResponse> {
-///     let claims = req.extensions().get::<Claims>().unwrap();
-///     let user_id = claims.sub;
+/// pub async fn protected_handler(claims: web::ReqData<Claims>) -> Result<HttpResponse, AppError> {
+///     let user_id = claims.user_id()?;
 ///     // ... use user_id in your handler
 /// }
 /// ```
@@ -41,20 +55,246 @@ pub async fn jwt_validator(
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
     let token = credentials.token();
     
-    match AuthService::validate_token(token) {
-        Ok(claims) => {
+    let claims = if api_token_service::is_api_token(token) {
+        api_token_claims(&req, token)
+    } else {
+        session_claims(&req, token)
+    };
+
+    match claims {
+        Ok(Some(claims)) => {
+            if !claims.allows_method(req.method()) {
+                let error = AppError::Forbidden("API token lacks the write scope".to_string());
+                return Err((error.into(), req));
+            }
+
             // Add user claims to request extensions for use in handlers
             req.extensions_mut().insert(claims);
             Ok(req)
         }
-        Err(_) => {
-            let config = req
-                .app_data::<Config>()
-                .cloned()
-                .unwrap_or_default()
-                .scope("Bearer");
-            
-            Err((AuthenticationError::from(config).into(), req))
+        Ok(None) => Err((authentication_error(&req), req)),
+        Err(error) => Err((error.into(), req)),
+    }
+}
+
+/// Validator for the live event stream
+///
+/// Browsers' `EventSource` cannot send an `Authorization` header, so besides
+/// everything [`jwt_validator`] accepts, a stream token from
+/// `POST /api/events/token` is accepted in the `token` query parameter. The
+/// token is only checked when the stream is opened, which keeps its lifetime
+/// short without cutting off open streams.
+///
+/// ```rust
+/// let auth = HttpAuthentication::with_fn(event_stream_validator);
+/// ```
+pub async fn event_stream_validator(
+    req: ServiceRequest,
+    credentials: Option<BearerAuth>,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    if let Some(credentials) = credentials {
+        return jwt_validator(req, credentials).await;
+    }
+
+    let token = web::Query::<StreamTokenQuery>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.into_inner().token);
+    let claims = match token {
+        Some(token) => stream_token_claims(&req, &token),
+        None => Ok(None),
+    };
+
+    match claims {
+        Ok(Some(claims)) => {
+            req.extensions_mut().insert(claims);
+            Ok(req)
+        }
+        Ok(None) => Err((authentication_error(&req), req)),
+        Err(error) => Err((error.into(), req)),
+    }
+}
+
+/// Admin role guard
+///
+/// Rejects requests whose claims do not carry the admin role (or, for API
+/// tokens, the `admin` scope) with `403 Forbidden`. It reads the claims
+/// inserted by [`jwt_validator`], so it has to be registered *before* the
+/// bearer middleware (actix runs the last `wrap` first):
+///
+/// ```rust
+/// use actix_web::middleware::from_fn;
+///
+/// cfg.service(
+///     web::scope("/admin")
+///         .wrap(from_fn(require_admin))
+///         .wrap(HttpAuthentication::bearer(jwt_validator))
+///         .route("/users", web::get().to(list_users))
+/// );
+/// ```
+pub async fn require_admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let is_admin = req
+        .extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.is_admin());
+
+    if !is_admin {
+        return Err(AppError::Forbidden("Admin role required".to_string()).into());
+    }
+
+    next.call(req).await
+}
+
+/// Claims of a JWT issued at login
+fn session_claims(req: &ServiceRequest, token: &str) -> Result<Option<Claims>, AppError> {
+    let claims = match AuthService::validate_token(token) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(None),
+    };
+    let user_id = match claims.user_id() {
+        Ok(user_id) => user_id,
+        Err(_) => return Ok(None),
+    };
+
+    // Tokens stay valid until they expire, so look up the account to make
+    // sure it has not been disabled or removed since the token was issued
+    let mut conn = connection(req)?;
+    let disabled_at = users::table
+        .find(user_id)
+        .select(users::disabled_at)
+        .first::<Option<chrono::NaiveDateTime>>(&mut conn)
+        .optional()?;
+
+    Ok(matches!(disabled_at, Some(None)).then_some(claims))
+}
+
+/// Claims of a personal API token, limited to the token's scopes
+fn api_token_claims(req: &ServiceRequest, token: &str) -> Result<Option<Claims>, AppError> {
+    let mut conn = connection(req)?;
+    let authenticated = api_token_service::authenticate(&mut conn, token)?;
+
+    Ok(authenticated.map(|(api_token, user)| Claims::for_api_token(&user, &api_token)))
+}
+
+#[derive(Deserialize)]
+struct StreamTokenQuery {
+    token: Option<String>,
+}
+
+/// Claims of the user a stream token was issued to, as long as the account
+/// is still enabled
+fn stream_token_claims(req: &ServiceRequest, token: &str) -> Result<Option<Claims>, AppError> {
+    let claims = match live_event_service::validate_stream_token(token) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(None),
+    };
+    let user_id = match Uuid::parse_str(&claims.sub) {
+        Ok(user_id) => user_id,
+        Err(_) => return Ok(None),
+    };
+
+    let mut conn = connection(req)?;
+    let user = users::table
+        .find(user_id)
+        .filter(users::disabled_at.is_null())
+        .select(User::as_select())
+        .first(&mut conn)
+        .optional()?;
+
+    Ok(user.map(|user| Claims::new(user.id, user.email, user.role, claims.exp)))
+}
+
+pub(crate) fn connection(req: &ServiceRequest) -> Result<DbConnection, AppError> {
+    let pool = req
+        .app_data::<web::Data<DbPool>>()
+        .ok_or_else(|| AppError::InternalServer("Database pool not configured".to_string()))?;
+    Ok(pool.get()?)
+}
+
+fn authentication_error(req: &ServiceRequest) -> Error {
+    let config = req
+        .app_data::<Config>()
+        .cloned()
+        .unwrap_or_default()
+        .scope("Bearer");
+
+    AuthenticationError::from(config).into()
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{init_service, try_call_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::config::jwt::JwtKeys;
+    use crate::database::test_database;
+    use crate::models::audit::AuditContext;
+    use crate::models::user::Role;
+    use crate::services::admin_service;
+
+    /// Status of a response, also when a middleware refused the request
+    fn status(result: Result<ServiceResponse, Error>) -> StatusCode {
+        match result {
+            Ok(response) => response.status(),
+            Err(error) => error.as_response_error().status_code(),
         }
     }
-} 
\ No newline at end of file
+
+    async fn admin_guard_status(claims: Option<Claims>) -> StatusCode {
+        let app = init_service(
+            App::new().service(
+                web::scope("/admin")
+                    .wrap(from_fn(require_admin))
+                    .wrap_fn(move |req, srv| {
+                        if let Some(claims) = claims.clone() {
+                            req.extensions_mut().insert(claims);
+                        }
+                        actix_web::dev::Service::call(srv, req)
+                    })
+                    .route("", web::get().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        status(try_call_service(&app, TestRequest::get().uri("/admin").to_request()).await)
+    }
+
+    #[actix_web::test]
+    async fn admin_guard_only_lets_admins_through() {
+        let claims = |role| Claims::new(Uuid::new_v4(), "user@example.com".to_string(), role, usize::MAX);
+
+        assert_eq!(admin_guard_status(Some(claims(Role::Admin))).await, StatusCode::OK);
+        assert_eq!(admin_guard_status(Some(claims(Role::User))).await, StatusCode::FORBIDDEN);
+        assert_eq!(admin_guard_status(None).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn rejects_session_tokens_of_disabled_users() {
+        let Some(pool) = test_database::pool() else { return };
+        JwtKeys::install_for_tests();
+        let user = test_database::user(&mut pool.get().unwrap());
+        let token = AuthService::generate_token(&user).unwrap();
+        let app = init_service(
+            App::new().app_data(web::Data::new(pool.clone())).service(
+                web::scope("/me")
+                    .wrap(HttpAuthentication::bearer(jwt_validator))
+                    .route("", web::get().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+        let request = || TestRequest::get().uri("/me").insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+
+        assert_eq!(status(try_call_service(&app, request()).await), StatusCode::OK);
+
+        admin_service::set_user_disabled(&mut pool.get().unwrap(), user.id, true, &AuditContext::default()).unwrap();
+        assert_eq!(status(try_call_service(&app, request()).await), StatusCode::UNAUTHORIZED);
+
+        admin_service::set_user_disabled(&mut pool.get().unwrap(), user.id, false, &AuditContext::default()).unwrap();
+        assert_eq!(status(try_call_service(&app, request()).await), StatusCode::OK);
+    }
+}
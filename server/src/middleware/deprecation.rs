@@ -0,0 +1,69 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use actix_web::http::header::HttpDate;
+use actix_web::middleware::DefaultHeaders;
+
+/// When the income and expense routes outside `/api/v1` were deprecated,
+/// 2026-10-19 as a Unix timestamp
+pub const UNVERSIONED_ROUTES_DEPRECATED_AT: i64 = 1_792_368_000;
+/// When the income and expense routes outside `/api/v1` are going to be
+/// removed, 2027-04-19 as a Unix timestamp
+pub const UNVERSIONED_ROUTES_SUNSET_AT: i64 = 1_808_092_800;
+
+/// Mark every response of a scope that is only kept for old clients
+///
+/// `Deprecation` (RFC 9745) tells when the routes were deprecated, `Sunset`
+/// (RFC 8594) when they will stop working and `Link` points at their
+/// replacement:
+///
+/// ```rust
+/// web::scope("/expenses")
+///     .wrap(deprecated(UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT, "/api/v1/expenses"))
+/// ```
+pub fn deprecated(since: i64, sunset: i64, successor: &str) -> DefaultHeaders {
+    DefaultHeaders::new()
+        .add(("Deprecation", format!("@{}", since)))
+        .add(("Sunset", http_date(sunset)))
+        .add(("Link", format!("<{}>; rel=\"successor-version\"", successor)))
+}
+
+fn http_date(timestamp: i64) -> String {
+    let time = UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64);
+    HttpDate::from(time).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn formats_the_sunset_as_an_http_date() {
+        assert_eq!(http_date(UNVERSIONED_ROUTES_SUNSET_AT), "Mon, 19 Apr 2027 00:00:00 GMT");
+    }
+
+    #[actix_web::test]
+    async fn marks_only_the_deprecated_scope() {
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/v1/incomes").route("", web::get().to(HttpResponse::Ok)))
+                .service(
+                    web::scope("/api/incomes")
+                        .route("", web::get().to(HttpResponse::Ok))
+                        .wrap(deprecated(UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT, "/api/v1/incomes")),
+                ),
+        )
+        .await;
+
+        let old = call_service(&app, TestRequest::get().uri("/api/incomes").to_request()).await;
+        assert_eq!(old.headers().get("Deprecation").unwrap(), "@1792368000");
+        assert_eq!(old.headers().get("Sunset").unwrap(), "Mon, 19 Apr 2027 00:00:00 GMT");
+        assert_eq!(old.headers().get("Link").unwrap(), "</api/v1/incomes>; rel=\"successor-version\"");
+
+        let current = call_service(&app, TestRequest::get().uri("/api/v1/incomes").to_request()).await;
+        for header in ["Deprecation", "Sunset", "Link"] {
+            assert!(!current.headers().contains_key(header), "{}", header);
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{CONTENT_TYPE, ETAG};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{error, web, Error, HttpMessage, HttpResponse};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config;
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbPool;
+use crate::middleware::auth_middleware::connection;
+use crate::models::auth::Claims;
+use crate::models::idempotency::StoredResponse;
+use crate::services::idempotency_service::{self, Claim};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Set on responses that were replayed from an earlier request
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Same limit as the JSON extractor
+const MAX_BODY_SIZE: usize = 2_097_152;
+
+/// Make `POST` requests with an `Idempotency-Key` header safe to retry
+///
+/// The first request with a key is processed normally and its response is
+/// stored per user and key. Retries with the same key and body get the
+/// stored response back instead of being processed again; reusing the key
+/// for a different request is refused. The same request under `/api/v1` and
+/// under a deprecated path counts as the same. Server errors are not stored,
+/// so those requests can be retried.
+///
+/// Needs the caller's claims, so wrap it inside the bearer authentication:
+///
+/// ```rust
+/// web::scope("/expenses")
+///     .wrap(from_fn(idempotency))
+///     .wrap(HttpAuthentication::bearer(jwt_validator))
+/// ```
+pub async fn idempotency(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if req.method() != Method::POST || !req.headers().contains_key(IDEMPOTENCY_KEY_HEADER) {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    }
+
+    let key = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "{} must be 1 to {} visible ASCII characters",
+                IDEMPOTENCY_KEY_HEADER, MAX_KEY_LENGTH
+            ))
+        })?;
+
+    let user_id = req
+        .extensions()
+        .get::<Claims>()
+        .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))?
+        .user_id()?;
+
+    // Buffer the body to fingerprint it, then hand it back to the handler
+    let body = match req.extract::<web::Payload>().await?.to_bytes_limited(MAX_BODY_SIZE).await {
+        Ok(body) => body?,
+        Err(_) => return Err(error::ErrorPayloadTooLarge("Request body is too large")),
+    };
+    let request_hash = hash_request(&req, &body);
+    req.set_payload(Payload::from(body));
+
+    let ttl = Duration::hours(config::get_idempotency_key_ttl_hours());
+    match idempotency_service::claim(&mut connection(&req)?, user_id, &key, &request_hash, ttl, Utc::now().naive_utc())
+        .map_err(AppError::from)?
+    {
+        Claim::Acquired => {}
+        Claim::Replay(stored) => return Ok(req.into_response(replay(stored))),
+        Claim::InProgress => {
+            return Err(AppError::Conflict(format!(
+                "A request with this {} is still being processed",
+                IDEMPOTENCY_KEY_HEADER
+            ))
+            .into())
+        }
+        Claim::Mismatch => {
+            return Err(AppError::UnprocessableEntity(format!(
+                "This {} was already used for a different request",
+                IDEMPOTENCY_KEY_HEADER
+            ))
+            .into())
+        }
+    }
+
+    let pool = req.app_data::<web::Data<DbPool>>().cloned();
+    let release = |pool: &Option<web::Data<DbPool>>| {
+        if let Some(Ok(mut conn)) = pool.as_ref().map(|pool| pool.get()) {
+            if let Err(e) = idempotency_service::release(&mut conn, user_id, &key) {
+                log::error!("Failed to release idempotency key: {}", e);
+            }
+        }
+    };
+
+    let lease = pool.clone().map(|pool| actix_web::rt::spawn(hold_claim(pool, user_id, key.clone())));
+    let res = next.call(req).await;
+    if let Some(lease) = lease {
+        lease.abort();
+    }
+
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            release(&pool);
+            return Err(e);
+        }
+    };
+
+    if res.status().is_server_error() {
+        release(&pool);
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = match body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => {
+            release(&pool);
+            return Err(AppError::InternalServer("Failed to read the response body".to_string()).into());
+        }
+    };
+
+    let stored = StoredResponse {
+        status: res.status().as_u16(),
+        content_type: header_value(res.headers(), CONTENT_TYPE),
+        etag: header_value(res.headers(), ETAG),
+        body: body.to_vec(),
+    };
+    let completed = match &pool {
+        Some(pool) => pool
+            .get()
+            .map_err(AppError::from)
+            .and_then(|mut conn| Ok(idempotency_service::complete(&mut conn, user_id, &key, &stored)?)),
+        None => Err(AppError::InternalServer("Database pool not configured".to_string())),
+    };
+    if let Err(e) = completed {
+        // The change itself went through; a retry would be processed again
+        log::error!("Failed to store the response for an idempotency key: {}", e);
+        release(&pool);
+    }
+
+    Ok(ServiceResponse::new(http_req, res.set_body(body).map_into_boxed_body()))
+}
+
+/// Renew the claim on a key for as long as its request runs, so that a retry
+/// cannot take over a claim whose request is merely slow
+async fn hold_claim(pool: web::Data<DbPool>, user_id: Uuid, key: String) {
+    let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(idempotency_service::LEASE_SECONDS as u64 / 3));
+    // The first tick completes at once, right after the key was claimed
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let key = key.clone();
+        let renewed = web::block(move || {
+            let mut conn = pool.get()?;
+            idempotency_service::renew(&mut conn, user_id, &key, Utc::now().naive_utc()).map_err(AppError::from)
+        })
+        .await;
+        match renewed {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::error!("Failed to renew the claim on an idempotency key: {}", e),
+            Err(e) => log::error!("Failed to renew the claim on an idempotency key: {}", e),
+        }
+    }
+}
+
+fn replay(stored: StoredResponse) -> HttpResponse {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut builder = HttpResponse::build(status);
+    builder.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+    if let Some(content_type) = stored.content_type {
+        builder.insert_header((CONTENT_TYPE, content_type));
+    }
+    if let Some(etag) = stored.etag {
+        builder.insert_header((ETAG, etag));
+    }
+    builder.body(stored.body)
+}
+
+/// Fingerprint of the request, so a key cannot be replayed for another one
+fn hash_request(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str().as_bytes());
+    hasher.update(b" ");
+    hasher.update(api_path(req.path()).as_bytes());
+    if !req.query_string().is_empty() {
+        hasher.update(b"?");
+        hasher.update(req.query_string().as_bytes());
+    }
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Path of a request within the API, the same for `/api/v1/...` and the
+/// deprecated `/api/...` path serving the same operation
+fn api_path(path: &str) -> &str {
+    ["/api/v1", "/api"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix).filter(|rest| rest.is_empty() || rest.starts_with('/')))
+        .unwrap_or(path)
+}
+
+fn header_value(headers: &actix_web::http::header::HeaderMap, name: actix_web::http::header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use actix_web::dev::Service;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, read_body_json, try_call_service, TestRequest};
+    use actix_web::App;
+
+    use super::*;
+    use crate::database::test_database;
+    use crate::models::user::Role;
+
+    /// Counts its calls and answers with the count, a little slowly so that
+    /// a second request can arrive while the first one runs
+    async fn handler(calls: web::Data<AtomicUsize>) -> HttpResponse {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(100)).await;
+        HttpResponse::Created().json(serde_json::json!({ "call": call }))
+    }
+
+    macro_rules! app {
+        () => {{
+            let pool = test_database::pool();
+            let Some(pool) = pool else { return };
+            let user = test_database::user(&mut pool.get().unwrap());
+            let claims = Claims::new(user.id, user.email, Role::User, u32::MAX as usize);
+            let calls = web::Data::new(AtomicUsize::new(0));
+            let scope = |prefix: &str| {
+                let claims = claims.clone();
+                web::scope(prefix)
+                    .wrap(from_fn(idempotency))
+                    .wrap_fn(move |req, srv| {
+                        req.extensions_mut().insert(claims.clone());
+                        srv.call(req)
+                    })
+                    .route("/incomes", web::post().to(handler))
+            };
+            let app = init_service(
+                App::new()
+                    .app_data(web::Data::new(pool))
+                    .app_data(calls.clone())
+                    .service(scope("/api/v1"))
+                    .service(scope("/api")),
+            )
+            .await;
+            (app, calls)
+        }};
+    }
+
+    fn post(path: &str, key: &str, amount: i32) -> TestRequest {
+        TestRequest::post()
+            .uri(path)
+            .insert_header((IDEMPOTENCY_KEY_HEADER, key))
+            .set_json(serde_json::json!({ "amount": amount }))
+    }
+
+    /// Status of the response, or of the error the middleware answered with
+    fn status(result: Result<ServiceResponse, Error>) -> StatusCode {
+        match result {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    #[test]
+    fn api_path_is_the_same_for_versioned_and_deprecated_paths() {
+        assert_eq!(api_path("/api/v1/incomes/batch"), "/incomes/batch");
+        assert_eq!(api_path("/api/incomes/batch"), "/incomes/batch");
+        assert_eq!(api_path("/api"), "");
+        assert_eq!(api_path("/apiary/incomes"), "/apiary/incomes");
+        assert_eq!(api_path("/api/v10/incomes"), "/v10/incomes");
+    }
+
+    #[actix_web::test]
+    async fn first_request_with_a_key_is_processed() {
+        let (app, calls) = app!();
+
+        let res = call_service(&app, post("/api/v1/incomes", "first", 10).to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn retry_gets_the_stored_response() {
+        let (app, calls) = app!();
+
+        let first: serde_json::Value = call_and_read_body_json(&app, post("/api/v1/incomes", "retry", 10).to_request()).await;
+        let res = call_service(&app, post("/api/v1/incomes", "retry", 10).to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(), "true");
+        let replayed: serde_json::Value = read_body_json(res).await;
+        assert_eq!(replayed, first);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn deprecated_path_replays_the_versioned_request() {
+        let (app, calls) = app!();
+
+        call_service(&app, post("/api/v1/incomes", "moved", 10).to_request()).await;
+        let res = call_service(&app, post("/api/incomes", "moved", 10).to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(), "true");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn key_reused_for_another_payload_is_unprocessable() {
+        let (app, calls) = app!();
+
+        call_service(&app, post("/api/v1/incomes", "reused", 10).to_request()).await;
+        let res = try_call_service(&app, post("/api/v1/incomes", "reused", 20).to_request()).await;
+
+        assert_eq!(status(res), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn retry_while_the_first_request_runs_is_a_conflict() {
+        let (app, calls) = app!();
+
+        let (first, second) = futures_util::future::join(call_service(&app, post("/api/v1/incomes", "pending", 10).to_request()), async {
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+            try_call_service(&app, post("/api/v1/incomes", "pending", 10).to_request()).await
+        })
+        .await;
+
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert_eq!(status(second), StatusCode::CONFLICT);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
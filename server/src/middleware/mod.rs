@@ -1 +1,4 @@
-pub mod auth_middleware; 
\ No newline at end of file
+pub mod auth_middleware;
+pub mod idempotency;
+pub mod deprecation;
+pub mod access_log;
@@ -0,0 +1,70 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::user::{Role, User};
+
+/// User account as seen by administrators (without the password hash)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminUserView {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "John")]
+    pub first_name: String,
+    #[schema(example = "Doe")]
+    pub last_name: String,
+    #[schema(example = "john@example.com")]
+    pub email: String,
+    #[schema(example = "user")]
+    pub role: Role,
+    #[schema(example = false)]
+    pub disabled: bool,
+    pub disabled_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+impl From<User> for AdminUserView {
+    fn from(user: User) -> Self {
+        AdminUserView {
+            id: user.id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            email: user.email,
+            role: user.role,
+            disabled: user.disabled_at.is_some(),
+            disabled_at: user.disabled_at,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateRoleRequest {
+    #[schema(example = "admin")]
+    pub role: Role,
+}
+
+/// Totals across all users
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GlobalStatistics {
+    #[schema(example = 42)]
+    pub total_users: i64,
+    #[schema(example = 40)]
+    pub active_users: i64,
+    #[schema(example = 2)]
+    pub disabled_users: i64,
+    #[schema(example = 1)]
+    pub admin_users: i64,
+    #[schema(example = 310)]
+    pub income_count: i64,
+    #[schema(example = 125000.00)]
+    pub income_total: Decimal,
+    #[schema(example = 2750)]
+    pub expense_count: i64,
+    #[schema(example = 98000.00)]
+    pub expense_total: Decimal,
+    #[schema(example = 27000.00)]
+    pub net_balance: Decimal,
+}
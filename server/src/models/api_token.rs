@@ -0,0 +1,122 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::schema::api_tokens;
+
+/// Permission granted to a personal API token
+///
+/// Every scope allows reads. `write` is needed for anything that changes
+/// data and `admin` for the admin endpoints (only for admin accounts).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+            TokenScope::Admin => "admin",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for TokenScope {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Text, Pg> for TokenScope {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "read" => Ok(TokenScope::Read),
+            "write" => Ok(TokenScope::Write),
+            "admin" => Ok(TokenScope::Admin),
+            other => Err(format!("Unrecognized token scope: {}", other).into()),
+        }
+    }
+}
+
+/// Personal access token. Only the SHA-256 hash of the token is stored.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = api_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scopes: Vec<TokenScope>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewApiToken {
+    #[schema(example = "Bank import script")]
+    pub name: String,
+    #[schema(example = json!(["read", "write"]))]
+    pub scopes: Vec<TokenScope>,
+    /// Days until the token expires; omit for a token that never expires
+    #[schema(example = 90)]
+    pub expires_in_days: Option<i64>,
+}
+
+/// API token as listed to its owner
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiTokenView {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "Bank import script")]
+    pub name: String,
+    /// First characters of the token, to recognize it
+    #[schema(example = "fst_k7d2m")]
+    pub token_prefix: String,
+    #[schema(example = json!(["read", "write"]))]
+    pub scopes: Vec<TokenScope>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ApiToken> for ApiTokenView {
+    fn from(token: ApiToken) -> Self {
+        ApiTokenView {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token.scopes,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            revoked_at: token.revoked_at,
+            created_at: token.created_at,
+        }
+    }
+}
+
+/// Response to token creation, the only time the plain token is returned
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    #[schema(example = "fst_k7d2mq9x4pa3hf82wnc6k7d2mq9x4pa3hf82wnc6")]
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiTokenView,
+}
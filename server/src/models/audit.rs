@@ -0,0 +1,288 @@
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::auth::Claims;
+use crate::models::schema::audit_log;
+
+/// What happened to an audited entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+    /// A duplicate was moved to the trash in favour of the record kept
+    Merge,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 6] = [
+        AuditAction::Create,
+        AuditAction::Update,
+        AuditAction::Delete,
+        AuditAction::Restore,
+        AuditAction::Purge,
+        AuditAction::Merge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+            AuditAction::Merge => "merge",
+        }
+    }
+}
+
+/// Kinds of entities that are written to the audit log
+pub mod entity {
+    pub const INCOME: &str = "income";
+    pub const EXPENSE: &str = "expense";
+    pub const USER: &str = "user";
+    pub const TWO_FACTOR: &str = "two_factor";
+    pub const RECOVERY_CODES: &str = "recovery_codes";
+    pub const API_TOKEN: &str = "api_token";
+    pub const GOAL: &str = "goal";
+    pub const GOAL_CONTRIBUTION: &str = "goal_contribution";
+    pub const LOAN: &str = "loan";
+    pub const LOAN_PAYMENT: &str = "loan_payment";
+    pub const NET_WORTH_ITEM: &str = "net_worth_item";
+    pub const VALUATION: &str = "valuation";
+    pub const CATEGORIZATION_RULE: &str = "categorization_rule";
+    pub const RECONCILIATION: &str = "reconciliation";
+    pub const NOTIFICATION_PREFERENCES: &str = "notification_preferences";
+    pub const WEBHOOK: &str = "webhook";
+}
+
+/// Who made a change and where the request came from
+///
+/// Extracted from the request in handlers and passed down to the services,
+/// which record it next to every change they make.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub actor_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl AuditContext {
+    /// Build the context from request metadata. The actor is taken from the
+    /// claims the bearer middleware attached, if any.
+    pub fn from_http_request(req: &HttpRequest) -> Self {
+        let actor_id = req
+            .extensions()
+            .get::<Claims>()
+            .and_then(|claims| Uuid::parse_str(&claims.sub).ok());
+
+        AuditContext {
+            actor_id,
+            ip_address: req.connection_info().realip_remote_addr().map(str::to_string),
+            user_agent: req
+                .headers()
+                .get(actix_web::http::header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+
+    /// Attribute the change to a user known only after the request was
+    /// authenticated by the handler itself (auth endpoints, registration)
+    pub fn with_actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+}
+
+impl FromRequest for AuditContext {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(AuditContext::from_http_request(req)))
+    }
+}
+
+/// A single change to record, with snapshots of the entity before and after
+#[derive(Debug)]
+pub struct AuditChange {
+    pub action: AuditAction,
+    pub entity_type: &'static str,
+    pub entity_id: Uuid,
+    pub owner_id: Option<Uuid>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl AuditChange {
+    pub fn created<T: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, after: &T) -> Self {
+        AuditChange {
+            action: AuditAction::Create,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: None,
+            after: serde_json::to_value(after).ok(),
+        }
+    }
+
+    pub fn updated<B: Serialize, A: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, before: &B, after: &A) -> Self {
+        AuditChange {
+            action: AuditAction::Update,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: serde_json::to_value(before).ok(),
+            after: serde_json::to_value(after).ok(),
+        }
+    }
+
+    pub fn deleted<T: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, before: &T) -> Self {
+        AuditChange {
+            action: AuditAction::Delete,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: serde_json::to_value(before).ok(),
+            after: None,
+        }
+    }
+
+    pub fn restored<T: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, after: &T) -> Self {
+        AuditChange {
+            action: AuditAction::Restore,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: None,
+            after: serde_json::to_value(after).ok(),
+        }
+    }
+
+    pub fn purged<T: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, before: &T) -> Self {
+        AuditChange {
+            action: AuditAction::Purge,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: serde_json::to_value(before).ok(),
+            after: None,
+        }
+    }
+
+    /// `duplicate` was merged into `kept`; recorded on the duplicate
+    pub fn merged<D: Serialize, K: Serialize>(entity_type: &'static str, entity_id: Uuid, owner_id: Uuid, duplicate: &D, kept: &K) -> Self {
+        AuditChange {
+            action: AuditAction::Merge,
+            entity_type,
+            entity_id,
+            owner_id: Some(owner_id),
+            before: serde_json::to_value(duplicate).ok(),
+            after: serde_json::to_value(kept).ok(),
+        }
+    }
+}
+
+/// Row of the append-only audit log
+#[derive(Debug, Serialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditLogEntry {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    /// User who made the change; empty for system changes
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub actor_id: Option<Uuid>,
+    /// User the changed entity belongs to
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub owner_id: Option<Uuid>,
+    #[schema(example = "update")]
+    pub action: String,
+    #[schema(example = "expense")]
+    pub entity_type: String,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub entity_id: Uuid,
+    #[schema(value_type = Option<Object>)]
+    pub before_data: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
+    pub after_data: Option<serde_json::Value>,
+    #[schema(example = "203.0.113.7")]
+    pub ip_address: Option<String>,
+    #[schema(example = "Mozilla/5.0")]
+    pub user_agent: Option<String>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+/// Filters for reading the audit log. Dates are inclusive.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    /// Only entries for this kind of entity, e.g. `expense`
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// `create`, `update`, `delete`, `restore`, `purge` or `merge`
+    pub action: Option<String>,
+    pub actor_id: Option<Uuid>,
+    /// Only honoured for admins; other users always see their own entries
+    pub owner_id: Option<Uuid>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Page size, at most 500 (default 50)
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use crate::models::user::Role;
+
+    #[test]
+    fn takes_the_actor_and_client_from_the_request() {
+        let user_id = Uuid::new_v4();
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.7:4711".parse().unwrap())
+            .insert_header((actix_web::http::header::USER_AGENT, "curl/8.0"))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::new(user_id, "user@example.com".to_string(), Role::User, usize::MAX));
+
+        let context = AuditContext::from_http_request(&req);
+
+        assert_eq!(context.actor_id, Some(user_id));
+        assert_eq!(context.ip_address.as_deref(), Some("203.0.113.7"));
+        assert_eq!(context.user_agent.as_deref(), Some("curl/8.0"));
+    }
+
+    #[test]
+    fn anonymous_requests_have_no_actor_until_one_is_known() {
+        let user_id = Uuid::new_v4();
+        let context = AuditContext::from_http_request(&TestRequest::default().to_http_request());
+
+        assert_eq!(context.actor_id, None);
+        assert_eq!(context.with_actor(user_id).actor_id, Some(user_id));
+    }
+
+    #[test]
+    fn snapshots_only_the_states_that_exist() {
+        let entity_id = Uuid::new_v4();
+        let owner_id = Uuid::new_v4();
+
+        let created = AuditChange::created(entity::INCOME, entity_id, owner_id, &"after");
+        let deleted = AuditChange::deleted(entity::INCOME, entity_id, owner_id, &"before");
+
+        assert_eq!((created.action, created.before, created.after), (AuditAction::Create, None, Some(serde_json::json!("after"))));
+        assert_eq!((deleted.action, deleted.before, deleted.after), (AuditAction::Delete, Some(serde_json::json!("before")), None));
+        assert_eq!(created.owner_id, Some(owner_id));
+    }
+}
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
+use crate::config::errors::AppError;
+use crate::models::api_token::{ApiToken, TokenScope};
+use crate::models::two_factor::TwoFactorChallenge;
+use crate::models::user::{Role, User};
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct LoginRequest {
@@ -35,6 +39,15 @@ pub struct TokenResponse {
     pub user: UserInfo,
 }
 
+/// Result of the password step of a login: either the final token, or a
+/// challenge to be exchanged for one once the second factor is verified
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Token(TokenResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct UserInfo {
     #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
@@ -45,23 +58,108 @@ pub struct UserInfo {
     pub last_name: String,
     #[schema(example = "john@example.com")]
     pub email: String,
+    #[schema(example = "user")]
+    pub role: Role,
+}
+
+impl From<User> for UserInfo {
+    fn from(user: User) -> Self {
+        UserInfo {
+            id: user.id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            email: user.email,
+            role: user.role,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // User ID
     pub email: String,
+    #[serde(default)]
+    pub role: Role,
     pub exp: usize, // Expiration time
     pub iat: usize, // Issued at
+    /// Scopes of the personal API token the request was made with; `None`
+    /// for regular login sessions, which are not restricted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<TokenScope>>,
 }
 
 impl Claims {
-    pub fn new(user_id: Uuid, email: String, exp: usize) -> Self {
+    pub fn new(user_id: Uuid, email: String, role: Role, exp: usize) -> Self {
         Self {
             sub: user_id.to_string(),
             email,
+            role,
             exp,
             iat: chrono::Utc::now().timestamp() as usize,
+            scope: None,
+        }
+    }
+
+    /// Claims for a request authenticated with a personal API token
+    pub fn for_api_token(user: &User, token: &ApiToken) -> Self {
+        Self {
+            sub: user.id.to_string(),
+            email: user.email.clone(),
+            role: user.role,
+            exp: token
+                .expires_at
+                .map_or(usize::MAX, |expires_at| expires_at.and_utc().timestamp() as usize),
+            iat: token.created_at.and_utc().timestamp() as usize,
+            scope: Some(token.scopes.clone()),
+        }
+    }
+
+    /// ID of the authenticated user
+    pub fn user_id(&self) -> Result<Uuid, AppError> {
+        Uuid::parse_str(&self.sub)
+            .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))
+    }
+
+    /// Admin accounts are admins in login sessions, but API tokens only get
+    /// admin rights when they carry the `admin` scope
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin && self.has_scope(TokenScope::Admin)
+    }
+
+    /// Whether the request may perform an operation needing `scope`.
+    /// Login sessions have every scope.
+    pub fn has_scope(&self, scope: TokenScope) -> bool {
+        match &self.scope {
+            None => true,
+            Some(scopes) => scopes.contains(&scope),
+        }
+    }
+
+    /// Reads need any scope, everything else the `write` scope
+    pub fn allows_method(&self, method: &actix_web::http::Method) -> bool {
+        if method.is_safe() {
+            self.scope.as_ref().is_none_or(|scopes| !scopes.is_empty())
+        } else {
+            self.has_scope(TokenScope::Write)
+        }
+    }
+
+    /// Reject API tokens for operations that need an interactive login,
+    /// such as managing API tokens themselves
+    pub fn ensure_session(&self) -> Result<(), AppError> {
+        if self.scope.is_some() {
+            return Err(AppError::Forbidden("This operation is not available to API tokens".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Allow access to a resource owned by `owner_id`: admins may access
+    /// everything, other users only their own data
+    pub fn ensure_can_access(&self, owner_id: Uuid) -> Result<(), AppError> {
+        if self.is_admin() || self.user_id()? == owner_id {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden("You do not have access to this resource".to_string()))
         }
     }
 }
@@ -72,4 +170,102 @@ pub struct AuthError {
     pub message: String,
     #[schema(example = "INVALID_CREDENTIALS")]
     pub code: String,
-} 
\ No newline at end of file
+} 
+
+/// Lets auth flows run inside `connection.transaction`, which needs to
+/// build the closure's error type from a diesel error
+impl From<diesel::result::Error> for AuthError {
+    fn from(_: diesel::result::Error) -> Self {
+        AuthError {
+            message: "Database query failed".to_string(),
+            code: "DB_QUERY_ERROR".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::Method;
+
+    fn claims(role: Role, scope: Option<Vec<TokenScope>>) -> Claims {
+        Claims { scope, ..Claims::new(Uuid::new_v4(), "user@example.com".to_string(), role, usize::MAX) }
+    }
+
+    #[test]
+    fn users_may_only_access_their_own_data() {
+        let claims = claims(Role::User, None);
+
+        assert!(!claims.is_admin());
+        assert!(claims.ensure_can_access(claims.user_id().unwrap()).is_ok());
+        assert!(matches!(claims.ensure_can_access(Uuid::new_v4()), Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn admins_may_access_everyones_data() {
+        let claims = claims(Role::Admin, None);
+
+        assert!(claims.is_admin());
+        assert!(claims.ensure_can_access(Uuid::new_v4()).is_ok());
+    }
+
+    #[test]
+    fn admin_api_tokens_need_the_admin_scope() {
+        let without_scope = claims(Role::Admin, Some(vec![TokenScope::Read, TokenScope::Write]));
+        let with_scope = claims(Role::Admin, Some(vec![TokenScope::Admin]));
+        let of_user = claims(Role::User, Some(vec![TokenScope::Admin]));
+
+        assert!(!without_scope.is_admin());
+        assert!(matches!(without_scope.ensure_can_access(Uuid::new_v4()), Err(AppError::Forbidden(_))));
+        assert!(with_scope.is_admin());
+        assert!(!of_user.is_admin());
+    }
+
+    #[test]
+    fn sessions_allow_every_method() {
+        let claims = claims(Role::User, None);
+
+        for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert!(claims.allows_method(&method), "{}", method);
+        }
+        assert!(claims.has_scope(TokenScope::Admin));
+        assert!(claims.ensure_session().is_ok());
+    }
+
+    #[test]
+    fn read_tokens_only_allow_reads() {
+        let claims = claims(Role::User, Some(vec![TokenScope::Read]));
+
+        assert!(claims.allows_method(&Method::GET));
+        assert!(claims.allows_method(&Method::HEAD));
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert!(!claims.allows_method(&method), "{}", method);
+        }
+        assert!(matches!(claims.ensure_session(), Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn write_tokens_allow_reads_and_changes() {
+        let claims = claims(Role::User, Some(vec![TokenScope::Write]));
+
+        for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert!(claims.allows_method(&method), "{}", method);
+        }
+        assert!(!claims.has_scope(TokenScope::Admin));
+    }
+
+    #[test]
+    fn tokens_without_scopes_allow_nothing() {
+        let claims = claims(Role::User, Some(Vec::new()));
+
+        assert!(!claims.allows_method(&Method::GET));
+        assert!(!claims.allows_method(&Method::POST));
+    }
+
+    #[test]
+    fn rejects_subjects_that_are_not_user_ids() {
+        let claims = Claims { sub: "admin".to_string(), ..claims(Role::Admin, None) };
+
+        assert!(matches!(claims.user_id(), Err(AppError::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// How a batch reacts to failing items
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    /// Apply every item or none of them
+    #[default]
+    Atomic,
+    /// Apply the items that succeed and report the ones that fail
+    Partial,
+}
+
+/// One create, update or delete in a batch
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperation<N, U> {
+    Create {
+        data: N,
+    },
+    Update {
+        id: Uuid,
+        /// `updated_at` of the record as last read, or `*`
+        #[schema(value_type = String, example = "2024-03-20T10:00:00.123456")]
+        version: ExpectedVersion,
+        data: U,
+    },
+    Delete {
+        id: Uuid,
+        /// `updated_at` of the record as last read, or `*`
+        #[schema(value_type = String, example = "2024-03-20T10:00:00.123456")]
+        version: ExpectedVersion,
+    },
+}
+
+/// Version a batch update or delete expects the record at, like `If-Match`
+/// for single requests: its `updated_at` as last read, or `*` to apply the
+/// operation whatever the current version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedVersion {
+    Any,
+    At(NaiveDateTime),
+}
+
+impl ExpectedVersion {
+    /// The accepted versions in the form the services take, `None` for any
+    pub fn versions(self) -> Option<Vec<NaiveDateTime>> {
+        match self {
+            ExpectedVersion::Any => None,
+            ExpectedVersion::At(version) => Some(vec![version]),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ExpectedVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        if version == "*" {
+            return Ok(ExpectedVersion::Any);
+        }
+        version
+            .parse()
+            .map(ExpectedVersion::At)
+            .map_err(|_| serde::de::Error::custom("version must be the record's updated_at or \"*\""))
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchRequest<N, U> {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub operations: Vec<BatchOperation<N, U>>,
+}
+
+/// Outcome of a single batch item, in request order
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchItemResult<T> {
+    #[schema(example = 0)]
+    pub index: usize,
+    /// HTTP status the item would have had as a single request. Items of a
+    /// failed atomic batch that were rolled back report 424.
+    #[schema(example = 201)]
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Validation error: Item name must not be empty")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResponse<T> {
+    pub mode: BatchMode,
+    /// Whether the changes were committed (always true in partial mode)
+    #[schema(example = true)]
+    pub committed: bool,
+    /// Operations that were applied
+    #[schema(example = 2)]
+    pub succeeded: usize,
+    /// Operations that failed themselves (not counting rolled back ones)
+    #[schema(example = 0)]
+    pub failed: usize,
+    pub results: Vec<BatchItemResult<T>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Operation = BatchOperation<serde_json::Value, serde_json::Value>;
+
+    #[test]
+    fn updates_and_deletes_need_a_version() {
+        let update = r#"{ "op": "update", "id": "123e4567-e89b-12d3-a456-426614174000", "data": {} }"#;
+        let delete = r#"{ "op": "delete", "id": "123e4567-e89b-12d3-a456-426614174000" }"#;
+
+        assert!(serde_json::from_str::<Operation>(update).unwrap_err().to_string().contains("missing field `version`"));
+        assert!(serde_json::from_str::<Operation>(delete).unwrap_err().to_string().contains("missing field `version`"));
+    }
+
+    #[test]
+    fn version_is_a_timestamp_or_a_wildcard() {
+        let delete = |version: &str| {
+            serde_json::from_str::<Operation>(&format!(r#"{{ "op": "delete", "id": "123e4567-e89b-12d3-a456-426614174000", "version": "{}" }}"#, version))
+        };
+        let version_of = |operation: Operation| match operation {
+            BatchOperation::Delete { version, .. } => version,
+            other => panic!("expected a delete, got {:?}", other),
+        };
+
+        let at = NaiveDateTime::parse_from_str("2024-03-20T10:00:00.123456", "%Y-%m-%dT%H:%M:%S%.f").unwrap();
+        assert_eq!(version_of(delete("2024-03-20T10:00:00.123456").unwrap()), ExpectedVersion::At(at));
+        assert_eq!(version_of(delete("*").unwrap()), ExpectedVersion::Any);
+        assert!(delete("").is_err());
+        assert!(delete("yesterday").is_err());
+    }
+
+    #[test]
+    fn wildcard_accepts_any_version() {
+        let at = NaiveDateTime::parse_from_str("2024-03-20T10:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        assert_eq!(ExpectedVersion::Any.versions(), None);
+        assert_eq!(ExpectedVersion::At(at).versions(), Some(vec![at]));
+    }
+}
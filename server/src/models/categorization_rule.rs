@@ -0,0 +1,301 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::expense::Expense;
+use crate::models::schema::categorization_rules;
+
+/// Text of an expense a rule's pattern is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMatchField {
+    ItemName,
+    Description,
+    /// Either the item name or the description
+    #[default]
+    Any,
+}
+
+impl RuleMatchField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleMatchField::ItemName => "item_name",
+            RuleMatchField::Description => "description",
+            RuleMatchField::Any => "any",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for RuleMatchField {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for RuleMatchField {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "item_name" => Ok(RuleMatchField::ItemName),
+            "description" => Ok(RuleMatchField::Description),
+            "any" => Ok(RuleMatchField::Any),
+            other => Err(format!("Unrecognized rule match field: {}", other).into()),
+        }
+    }
+}
+
+/// How a rule's pattern is matched; both ignore case
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleMatchType {
+    /// The text contains the pattern
+    #[default]
+    Contains,
+    /// The pattern is a regular expression found somewhere in the text
+    Regex,
+}
+
+impl RuleMatchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleMatchType::Contains => "contains",
+            RuleMatchType::Regex => "regex",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for RuleMatchType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for RuleMatchType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "contains" => Ok(RuleMatchType::Contains),
+            "regex" => Ok(RuleMatchType::Regex),
+            other => Err(format!("Unrecognized rule match type: {}", other).into()),
+        }
+    }
+}
+
+/// Sign of the amount a rule applies to; refunds are negative expenses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum AmountSign {
+    Positive,
+    Negative,
+}
+
+impl AmountSign {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AmountSign::Positive => "positive",
+            AmountSign::Negative => "negative",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for AmountSign {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for AmountSign {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "positive" => Ok(AmountSign::Positive),
+            "negative" => Ok(AmountSign::Negative),
+            other => Err(format!("Unrecognized amount sign: {}", other).into()),
+        }
+    }
+}
+
+/// Conditions an expense has to meet and what to set on it when it does.
+/// All conditions that are present have to match.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = categorization_rules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CategorizationRule {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "Supermarkets")]
+    pub name: String,
+    /// Rules with a lower priority are applied first
+    #[schema(example = 10)]
+    pub priority: i32,
+    pub enabled: bool,
+    pub match_field: RuleMatchField,
+    pub match_type: RuleMatchType,
+    #[schema(example = "^POS \\d+ (\\w+)")]
+    pub pattern: Option<String>,
+    /// Smallest absolute amount, inclusive
+    #[schema(example = 5.00)]
+    pub min_amount: Option<Decimal>,
+    /// Largest absolute amount, inclusive
+    #[schema(example = 250.00)]
+    pub max_amount: Option<Decimal>,
+    pub sign: Option<AmountSign>,
+    #[schema(example = "Groceries")]
+    pub set_category: Option<String>,
+    #[schema(example = json!(["food"]))]
+    pub add_tags: Vec<String>,
+    /// For regex rules `$1` or `${name}` insert the matched groups
+    #[schema(example = "$1")]
+    pub set_display_name: Option<String>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NewCategorizationRule {
+    #[schema(example = "Supermarkets")]
+    pub name: String,
+    #[schema(example = 10)]
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub match_field: RuleMatchField,
+    #[serde(default)]
+    pub match_type: RuleMatchType,
+    #[schema(example = "^POS \\d+ (\\w+)")]
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[schema(example = 5.00)]
+    #[serde(default)]
+    pub min_amount: Option<Decimal>,
+    #[schema(example = 250.00)]
+    #[serde(default)]
+    pub max_amount: Option<Decimal>,
+    #[serde(default)]
+    pub sign: Option<AmountSign>,
+    #[schema(example = "Groceries")]
+    #[serde(default)]
+    pub set_category: Option<String>,
+    #[schema(example = json!(["food"]))]
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[schema(example = "$1")]
+    #[serde(default)]
+    pub set_display_name: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl NewCategorizationRule {
+    pub fn into_rule(self, user_id: Uuid) -> CategorizationRule {
+        let now = chrono::Utc::now().naive_utc();
+        CategorizationRule {
+            id: Uuid::new_v4(),
+            user_id,
+            name: self.name,
+            priority: self.priority,
+            enabled: self.enabled,
+            match_field: self.match_field,
+            match_type: self.match_type,
+            pattern: self.pattern,
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            sign: self.sign,
+            set_category: self.set_category,
+            add_tags: self.add_tags,
+            set_display_name: self.set_display_name,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update of a rule. Omitted fields are left unchanged and optional
+/// conditions and actions can be cleared by sending `null`.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = categorization_rules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateCategorizationRule {
+    #[schema(example = "Supermarkets")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[schema(example = 20)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_field: Option<RuleMatchField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_type: Option<RuleMatchType>,
+    #[schema(value_type = Option<String>, example = "grocer")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Option<String>>,
+    #[schema(value_type = Option<Decimal>, example = 5.00)]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<Option<Decimal>>,
+    #[schema(value_type = Option<Decimal>, example = 250.00)]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<Option<Decimal>>,
+    #[schema(value_type = Option<AmountSign>)]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub sign: Option<Option<AmountSign>>,
+    #[schema(value_type = Option<String>, example = "Groceries")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub set_category: Option<Option<String>>,
+    #[schema(example = json!(["food"]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_tags: Option<Vec<String>>,
+    #[schema(value_type = Option<String>, example = "Corner Grocery")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub set_display_name: Option<Option<String>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApplyRulesQuery {
+    /// Replace categories and display names that are already set instead of
+    /// only filling in empty ones (default false)
+    pub overwrite: Option<bool>,
+}
+
+/// An existing expense a rule would change, with the values it would get
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleMatch {
+    pub expense: Expense,
+    #[schema(example = "Groceries")]
+    pub category: Option<String>,
+    #[schema(example = json!(["food"]))]
+    pub tags: Vec<String>,
+    #[schema(example = "Corner Grocery")]
+    pub display_name: Option<String>,
+}
+
+/// Outcome of applying rules to the user's existing expenses
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleApplication {
+    /// Expenses matched by at least one rule
+    #[schema(example = 42)]
+    pub matched: usize,
+    /// Matched expenses that were changed
+    #[schema(example = 17)]
+    pub updated: usize,
+    /// Reconciled expenses the rules would have changed; unlock them to
+    /// apply the rules
+    pub skipped: Vec<Uuid>,
+}
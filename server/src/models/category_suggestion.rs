@@ -0,0 +1,33 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Text of an expense to suggest a category for
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CategorySuggestionQuery {
+    #[param(example = "POS 4471 CORNERGROCER")]
+    pub item_name: String,
+    pub description: Option<String>,
+    /// Number of suggestions, at most 10 (default 3)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CategorySuggestion {
+    #[schema(example = "Groceries")]
+    pub category: String,
+    /// Estimated probability between 0 and 1 that the category is right
+    #[schema(example = 0.8731)]
+    pub probability: Decimal,
+}
+
+/// Categories learned from the user's own categorized expenses, most likely
+/// first. Empty when none of the words were seen before.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CategorySuggestions {
+    /// Categorized expenses the suggestions were learned from
+    #[schema(example = 184)]
+    pub trained_on: usize,
+    pub suggestions: Vec<CategorySuggestion>,
+}
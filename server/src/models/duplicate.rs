@@ -0,0 +1,171 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::schema::duplicate_candidates;
+
+/// Whether a duplicate pair consists of incomes or expenses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKind {
+    Income,
+    Expense,
+}
+
+impl DuplicateKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateKind::Income => "income",
+            DuplicateKind::Expense => "expense",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for DuplicateKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for DuplicateKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "income" => Ok(DuplicateKind::Income),
+            "expense" => Ok(DuplicateKind::Expense),
+            other => Err(format!("Unrecognized duplicate kind: {}", other).into()),
+        }
+    }
+}
+
+/// Where a duplicate pair is in its review
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateStatus {
+    /// Waiting for review
+    #[default]
+    Open,
+    /// Not a duplicate; the pair is not reported again
+    Dismissed,
+    Merged,
+}
+
+impl DuplicateStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateStatus::Open => "open",
+            DuplicateStatus::Dismissed => "dismissed",
+            DuplicateStatus::Merged => "merged",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for DuplicateStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for DuplicateStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "open" => Ok(DuplicateStatus::Open),
+            "dismissed" => Ok(DuplicateStatus::Dismissed),
+            "merged" => Ok(DuplicateStatus::Merged),
+            other => Err(format!("Unrecognized duplicate status: {}", other).into()),
+        }
+    }
+}
+
+/// Two incomes or two expenses that look like the same transaction
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = duplicate_candidates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DuplicateCandidate {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: DuplicateKind,
+    /// The record created first
+    pub first_id: Uuid,
+    pub second_id: Uuid,
+    pub score: Decimal,
+    pub status: DuplicateStatus,
+    pub detected_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+/// The fields incomes and expenses have in common
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateRecord {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    /// Source of an income or item name of an expense
+    #[schema(example = "Corner Grocery")]
+    pub name: String,
+    #[schema(example = 42.10)]
+    pub amount: Decimal,
+    #[schema(example = "2024-03-20")]
+    pub date: NaiveDate,
+    #[schema(example = "Weekly groceries")]
+    pub description: Option<String>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    /// Set while the record is in the trash, e.g. after a merge
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+/// A probable duplicate with both records
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DuplicatePair {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    pub kind: DuplicateKind,
+    /// Likelihood of a duplicate between 0 and 1, from how similar the texts
+    /// are and how close the dates are
+    #[schema(example = 0.9125)]
+    pub score: Decimal,
+    pub status: DuplicateStatus,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub detected_at: NaiveDateTime,
+    #[schema(example = "2024-03-21T08:30:00")]
+    pub resolved_at: Option<NaiveDateTime>,
+    /// The record created first
+    pub first: DuplicateRecord,
+    pub second: DuplicateRecord,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DuplicateQuery {
+    /// Only incomes or only expenses
+    #[param(inline)]
+    pub kind: Option<DuplicateKind>,
+    /// Defaults to `open`
+    #[param(inline)]
+    pub status: Option<DuplicateStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MergeDuplicate {
+    /// Record to keep; defaults to the one created first. The other one is
+    /// moved to the trash.
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub keep_id: Option<Uuid>,
+}
+
+/// Open duplicate pairs after a scan
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DuplicateScan {
+    #[schema(example = 1)]
+    pub incomes: usize,
+    #[schema(example = 3)]
+    pub expenses: usize,
+}
@@ -16,7 +16,7 @@ pub struct Expense {
     pub user_id: Uuid,
     #[schema(example = "Groceries")]
     pub item_name: String,
-    #[schema(example = "50.00")]
+    #[schema(example = 50.00)]
     pub amount: Decimal,
     #[schema(example = "2024-03-20")]
     pub date: chrono::NaiveDate,
@@ -26,6 +26,20 @@ pub struct Expense {
     pub created_at: NaiveDateTime,
     #[schema(example = "2024-03-20T10:00:00")]
     pub updated_at: NaiveDateTime,
+    /// Set while the record is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
+    #[schema(example = "Food")]
+    pub category: Option<String>,
+    #[schema(example = json!(["household"]))]
+    pub tags: Vec<String>,
+    /// Readable name for the raw text in `item_name`
+    #[schema(example = "Corner Grocery")]
+    pub display_name: Option<String>,
+    #[schema(example = "Credit card")]
+    pub account: Option<String>,
+    /// Set once the expense was reconciled against a statement; it cannot be
+    /// edited until it is unlocked
+    pub reconciled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
@@ -36,10 +50,22 @@ pub struct NewExpense {
     pub user_id: Uuid,
     #[schema(example = "Groceries")]
     pub item_name: String,
-    #[schema(example = "50.00")]
+    #[schema(example = 50.00)]
     pub amount: Decimal,
     #[schema(example = "Weekly groceries")]
     pub description: Option<String>,
+    #[schema(example = "Food")]
+    #[serde(default)]
+    pub category: Option<String>,
+    #[schema(example = json!(["household"]))]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[schema(example = "Corner Grocery")]
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[schema(example = "Credit card")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 impl NewExpense {
@@ -54,21 +80,45 @@ impl NewExpense {
             description: self.description,
             created_at: now,
             updated_at: now,
+            deleted_at: None,
+            category: self.category,
+            tags: self.tags,
+            display_name: self.display_name,
+            account: self.account,
+            reconciled_at: None,
         }
     }
 }
 
+/// Partial update of an expense. Omitted fields are left unchanged and
+/// `description`, `category`, `display_name` and `account` can be cleared by
+/// sending `null`.
 #[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
 #[diesel(table_name = expenses)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct UpdateExpense {
     #[schema(example = "Restaurant")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub item_name: Option<String>,
-    #[schema(example = "75.00")]
+    #[schema(example = 75.00)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub amount: Option<Decimal>,
     #[schema(example = "2024-03-20")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<chrono::NaiveDate>,
-    #[schema(example = "Dinner with friends")]
-    pub description: Option<String>,
-    pub updated_at: Option<NaiveDateTime>,
-} 
\ No newline at end of file
+    #[schema(value_type = Option<String>, example = "Dinner with friends")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
+    #[schema(value_type = Option<String>, example = "Dining out")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub category: Option<Option<String>>,
+    #[schema(example = json!(["household", "shared"]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[schema(value_type = Option<String>, example = "Corner Grocery")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<Option<String>>,
+    #[schema(value_type = Option<String>, example = "Checking")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub account: Option<Option<String>>,
+}
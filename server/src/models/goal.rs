@@ -0,0 +1,161 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::schema::{goal_contributions, goals};
+
+/// Savings goal, optionally tied to the account the money is kept in or the
+/// category it is saved for
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = goals)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Goal {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "Emergency fund")]
+    pub name: String,
+    #[schema(example = 5000.00)]
+    pub target_amount: Decimal,
+    #[schema(example = "2025-12-31")]
+    pub deadline: Option<NaiveDate>,
+    #[schema(example = "Savings account")]
+    pub account: Option<String>,
+    #[schema(example = "Travel")]
+    pub category: Option<String>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewGoal {
+    #[schema(example = "Emergency fund")]
+    pub name: String,
+    #[schema(example = 5000.00)]
+    pub target_amount: Decimal,
+    #[schema(example = "2025-12-31")]
+    pub deadline: Option<NaiveDate>,
+    #[schema(example = "Savings account")]
+    pub account: Option<String>,
+    #[schema(example = "Travel")]
+    pub category: Option<String>,
+}
+
+impl NewGoal {
+    pub fn into_goal(self, user_id: Uuid) -> Goal {
+        let now = chrono::Utc::now().naive_utc();
+        Goal {
+            id: Uuid::new_v4(),
+            user_id,
+            name: self.name,
+            target_amount: self.target_amount,
+            deadline: self.deadline,
+            account: self.account,
+            category: self.category,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update of a goal. Omitted fields are left unchanged; `deadline`,
+/// `account` and `category` can be cleared by sending `null`.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = goals)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateGoal {
+    #[schema(example = "Emergency fund")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[schema(example = 6000.00)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_amount: Option<Decimal>,
+    #[schema(value_type = Option<NaiveDate>, example = "2026-06-30")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<Option<NaiveDate>>,
+    #[schema(value_type = Option<String>, example = "Savings account")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub account: Option<Option<String>>,
+    #[schema(value_type = Option<String>, example = "Travel")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub category: Option<Option<String>>,
+}
+
+/// Money put towards (or, when negative, taken out of) a goal
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = goal_contributions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct GoalContribution {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub goal_id: Uuid,
+    #[schema(example = 250.00)]
+    pub amount: Decimal,
+    #[schema(example = "2024-03-20")]
+    pub date: NaiveDate,
+    #[schema(example = "March savings")]
+    pub note: Option<String>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewGoalContribution {
+    /// Negative amounts record withdrawals
+    #[schema(example = 250.00)]
+    pub amount: Decimal,
+    /// Defaults to today
+    #[schema(example = "2024-03-20")]
+    pub date: Option<NaiveDate>,
+    #[schema(example = "March savings")]
+    pub note: Option<String>,
+}
+
+impl NewGoalContribution {
+    pub fn into_contribution(self, goal_id: Uuid) -> GoalContribution {
+        let now = chrono::Utc::now().naive_utc();
+        GoalContribution {
+            id: Uuid::new_v4(),
+            goal_id,
+            amount: self.amount,
+            date: self.date.unwrap_or(now.date()),
+            note: self.note,
+            created_at: now,
+        }
+    }
+}
+
+/// How far a goal has come and what it takes to reach it in time
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GoalProgress {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub goal_id: Uuid,
+    #[schema(example = 5000.00)]
+    pub target_amount: Decimal,
+    /// Sum of all contributions
+    #[schema(example = 1250.00)]
+    pub saved_amount: Decimal,
+    /// Amount still missing, never negative
+    #[schema(example = 3750.00)]
+    pub remaining_amount: Decimal,
+    /// Share of the target saved so far, capped at 100
+    #[schema(example = 25.00)]
+    pub percent_complete: Decimal,
+    #[schema(example = false)]
+    pub achieved: bool,
+    #[schema(example = "2025-12-31")]
+    pub deadline: Option<NaiveDate>,
+    /// Calendar months left until the deadline, counting the current one
+    #[schema(example = 10)]
+    pub months_remaining: Option<u32>,
+    /// What to save each month to reach the target by the deadline. Once the
+    /// deadline has passed this is the whole remaining amount.
+    #[schema(example = 375.00)]
+    pub required_monthly_saving: Option<Decimal>,
+}
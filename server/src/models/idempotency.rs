@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+use crate::models::schema::idempotency_keys;
+
+/// Stored outcome of a request sent with an `Idempotency-Key` header
+#[derive(Debug, Queryable, Selectable, Insertable)]
+#[diesel(table_name = idempotency_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IdempotencyKey {
+    pub user_id: Uuid,
+    pub idempotency_key: String,
+    /// SHA-256 of method, path within the API and body of the first request
+    pub request_hash: String,
+    pub response_status: Option<i32>,
+    pub response_content_type: Option<String>,
+    pub response_etag: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    /// While no response is stored, the claim of the first request runs out
+    /// at this time unless it is renewed
+    pub leased_until: NaiveDateTime,
+}
+
+/// Response recorded for a completed request
+#[derive(Debug)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub body: Vec<u8>,
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 use rust_decimal::Decimal;
 use crate::models::schema::incomes;
 use diesel::{Queryable, Selectable, Insertable, AsChangeset};
-use crate::models::user::User;
+use crate::models::auth::UserInfo;
 use utoipa::ToSchema;
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
@@ -17,7 +17,7 @@ pub struct Income {
     pub user_id: Uuid,
     #[schema(example = "Salary")]
     pub source: String,
-    #[schema(example = "5000.00")]
+    #[schema(example = 5000.00)]
     #[serde(with = "rust_decimal::serde::float")]
     pub amount: Decimal,
     #[schema(example = "2024-03-20")]
@@ -28,13 +28,21 @@ pub struct Income {
     pub created_at: NaiveDateTime,
     #[schema(example = "2024-03-20T10:00:00")]
     pub updated_at: NaiveDateTime,
+    /// Set while the record is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
+    #[schema(example = "Checking")]
+    pub account: Option<String>,
+    /// Set once the income was reconciled against a statement; it cannot be
+    /// edited until it is unlocked
+    pub reconciled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct IncomeWithUser {
     #[serde(flatten)]
     pub income: Income,
-    pub user: User,
+    /// The owner, without the password hash
+    pub user: UserInfo,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable, ToSchema)]
@@ -45,7 +53,7 @@ pub struct NewIncome {
     pub user_id: Uuid,
     #[schema(example = "Salary")]
     pub source: String,
-    #[schema(example = "5000.00")]
+    #[schema(example = 5000.00)]
     #[serde(with = "rust_decimal::serde::float")]
     #[diesel(sql_type = diesel::sql_types::Numeric)]
     pub amount: Decimal,
@@ -53,19 +61,72 @@ pub struct NewIncome {
     pub date: NaiveDate,
     #[schema(example = "Monthly salary")]
     pub description: Option<String>,
+    #[schema(example = "Checking")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
+/// Partial update of an income. Omitted fields are left unchanged and
+/// `description` and `account` can be cleared by sending `null`.
 #[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
 #[diesel(table_name = incomes)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct UpdateIncome {
     #[schema(example = "Freelance")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
-    #[schema(example = "1000.00")]
+    #[schema(example = 1000.00)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub amount: Option<Decimal>,
     #[schema(example = "2024-03-20")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<NaiveDate>,
-    #[schema(example = "Project payment")]
-    pub description: Option<String>,
-    pub updated_at: Option<NaiveDateTime>,
-}
\ No newline at end of file
+    #[schema(value_type = Option<String>, example = "Project payment")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
+    #[schema(value_type = Option<String>, example = "Savings")]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub account: Option<Option<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::{Role, User};
+    use chrono::Utc;
+
+    #[test]
+    fn income_with_user_omits_password() {
+        let now = Utc::now().naive_utc();
+        let user = User {
+            id: Uuid::new_v4(),
+            first_name: "John".to_string(),
+            last_name: "Doe".to_string(),
+            email: "john@example.com".to_string(),
+            password: "$2b$12$hash".to_string(),
+            created_at: now,
+            updated_at: now,
+            role: Role::User,
+            disabled_at: None,
+        };
+        let income = Income {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            source: "Salary".to_string(),
+            amount: Decimal::new(500000, 2),
+            date: now.date(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            account: None,
+            reconciled_at: None,
+        };
+
+        let json = serde_json::to_value(IncomeWithUser { income, user: user.into() }).unwrap();
+
+        assert_eq!(json["user"]["email"], "john@example.com");
+        assert!(json["user"].get("password").is_none());
+        assert!(!json.to_string().contains("$2b$12$hash"));
+    }
+}
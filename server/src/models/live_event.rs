@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::webhook::WebhookEvent;
+
+/// Where an audit log entry falls in commit order: the ID of the transaction
+/// that wrote it, then the order it was written in
+pub type StreamPosition = (i64, i64);
+
+/// A committed change to one of a user's incomes or expenses, as it is
+/// streamed to live dashboards. `id` is the audit log entry of the change
+/// and doubles as the ID of the server-sent event.
+#[derive(Debug, Clone)]
+pub struct LiveEvent {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub event: WebhookEvent,
+    /// Same body as a webhook delivery of the event
+    pub payload: serde_json::Value,
+}
+
+/// Claims of a stream token. The `purpose` keeps it from being accepted
+/// anywhere else as regular access `Claims`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventStreamClaims {
+    pub sub: String, // User ID
+    pub purpose: String,
+    pub exp: usize,
+    pub iat: usize,
+}
+
+impl EventStreamClaims {
+    pub const PURPOSE: &'static str = "event_stream";
+
+    pub fn new(user_id: Uuid, exp: usize) -> Self {
+        Self {
+            sub: user_id.to_string(),
+            purpose: Self::PURPOSE.to_string(),
+            exp,
+            iat: chrono::Utc::now().timestamp() as usize,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EventStreamTokenResponse {
+    /// Pass as the `token` query parameter of `GET /api/events`
+    #[schema(example = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9...")]
+    pub token: String,
+    /// Seconds left to open the stream with the token
+    #[schema(example = 60)]
+    pub expires_in: i64,
+}
@@ -0,0 +1,173 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::schema::{loan_payments, loans};
+
+/// Fixed-rate loan repaid in equal monthly installments
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = loans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Loan {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "Car loan")]
+    pub name: String,
+    #[schema(example = 20000.00)]
+    pub principal: Decimal,
+    /// Nominal annual interest rate in percent, compounded monthly
+    #[schema(example = 4.5)]
+    pub annual_rate: Decimal,
+    #[schema(example = 60)]
+    pub term_months: i32,
+    /// The first installment is due one month after this date
+    #[schema(example = "2024-01-15")]
+    pub start_date: NaiveDate,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewLoan {
+    #[schema(example = "Car loan")]
+    pub name: String,
+    #[schema(example = 20000.00)]
+    pub principal: Decimal,
+    #[schema(example = 4.5)]
+    pub annual_rate: Decimal,
+    #[schema(example = 60)]
+    pub term_months: i32,
+    #[schema(example = "2024-01-15")]
+    pub start_date: NaiveDate,
+}
+
+impl NewLoan {
+    pub fn into_loan(self, user_id: Uuid) -> Loan {
+        let now = chrono::Utc::now().naive_utc();
+        Loan {
+            id: Uuid::new_v4(),
+            user_id,
+            name: self.name,
+            principal: self.principal,
+            annual_rate: self.annual_rate,
+            term_months: self.term_months,
+            start_date: self.start_date,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update of a loan. Omitted fields are left unchanged.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = loans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateLoan {
+    #[schema(example = "Car loan")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[schema(example = 20000.00)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal: Option<Decimal>,
+    #[schema(example = 3.9)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annual_rate: Option<Decimal>,
+    #[schema(example = 72)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub term_months: Option<i32>,
+    #[schema(example = "2024-01-15")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+}
+
+/// Link between a loan and an expense that paid towards it
+#[derive(Debug, Clone, Serialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = loan_payments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LoanPayment {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub expense_id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub loan_id: Uuid,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LinkLoanPayment {
+    /// Expense that paid towards the loan
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub expense_id: Uuid,
+}
+
+/// One installment of an amortization schedule
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AmortizationPeriod {
+    /// 1 for the first installment
+    #[schema(example = 1)]
+    pub period: u32,
+    #[schema(example = "2024-02-15")]
+    pub due_date: NaiveDate,
+    #[schema(example = 372.86)]
+    pub payment: Decimal,
+    #[schema(example = 75.00)]
+    pub interest: Decimal,
+    #[schema(example = 297.86)]
+    pub principal: Decimal,
+    /// Balance left after this installment
+    #[schema(example = 19702.14)]
+    pub remaining_balance: Decimal,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AmortizationSchedule {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub loan_id: Uuid,
+    /// Regular installment; the last one may differ by the rounding residue
+    #[schema(example = 372.86)]
+    pub monthly_payment: Decimal,
+    #[schema(example = 2371.45)]
+    pub total_interest: Decimal,
+    #[schema(example = 22371.45)]
+    pub total_paid: Decimal,
+    pub periods: Vec<AmortizationPeriod>,
+}
+
+/// Actual repayment of a loan compared to its schedule
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoanStatus {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub loan_id: Uuid,
+    #[schema(example = "2024-06-30")]
+    pub as_of: NaiveDate,
+    /// Installments due on or before `as_of`
+    #[schema(example = 5)]
+    pub periods_elapsed: u32,
+    /// What should have been paid by `as_of`
+    #[schema(example = 1864.30)]
+    pub scheduled_paid: Decimal,
+    /// Sum of the linked payments made by `as_of`
+    #[schema(example = 1800.00)]
+    pub actual_paid: Decimal,
+    #[schema(example = 18560.10)]
+    pub scheduled_balance: Decimal,
+    /// Balance when interest accrues on what is actually owed
+    #[schema(example = 18624.87)]
+    pub actual_balance: Decimal,
+    /// `actual_balance - scheduled_balance`; positive when behind schedule
+    #[schema(example = 64.77)]
+    pub difference: Decimal,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LoanStatusQuery {
+    /// Date to report on; defaults to today
+    pub as_of: Option<NaiveDate>,
+}
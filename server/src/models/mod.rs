@@ -3,3 +3,21 @@ pub mod income;
 pub mod expense;
 pub mod schema;
 pub mod auth;
+pub mod two_factor;
+pub mod admin;
+pub mod api_token;
+pub mod audit;
+pub mod patch;
+pub mod batch;
+pub mod idempotency;
+pub mod goal;
+pub mod loan;
+pub mod report;
+pub mod net_worth;
+pub mod categorization_rule;
+pub mod category_suggestion;
+pub mod duplicate;
+pub mod reconciliation;
+pub mod notification;
+pub mod webhook;
+pub mod live_event;
@@ -0,0 +1,195 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::schema::{net_worth_items, net_worth_snapshots, valuations};
+
+/// Whether an item adds to or subtracts from net worth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum NetWorthItemKind {
+    Asset,
+    Liability,
+}
+
+impl NetWorthItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetWorthItemKind::Asset => "asset",
+            NetWorthItemKind::Liability => "liability",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for NetWorthItemKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for NetWorthItemKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "asset" => Ok(NetWorthItemKind::Asset),
+            "liability" => Ok(NetWorthItemKind::Liability),
+            other => Err(format!("Unrecognized net worth item kind: {}", other).into()),
+        }
+    }
+}
+
+/// Asset or liability whose value is recorded by hand
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = net_worth_items)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NetWorthItem {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "House")]
+    pub name: String,
+    pub kind: NetWorthItemKind,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewNetWorthItem {
+    #[schema(example = "House")]
+    pub name: String,
+    pub kind: NetWorthItemKind,
+}
+
+impl NewNetWorthItem {
+    pub fn into_item(self, user_id: Uuid) -> NetWorthItem {
+        let now = chrono::Utc::now().naive_utc();
+        NetWorthItem {
+            id: Uuid::new_v4(),
+            user_id,
+            name: self.name,
+            kind: self.kind,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update of a net worth item. Omitted fields are left unchanged.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = net_worth_items)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateNetWorthItem {
+    #[schema(example = "Family home")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<NetWorthItemKind>,
+}
+
+/// Value of an item from a date until its next valuation
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = valuations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Valuation {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub item_id: Uuid,
+    #[schema(example = "2024-03-01")]
+    pub date: NaiveDate,
+    /// Always positive; liabilities are subtracted from net worth
+    #[schema(example = 350000.00)]
+    pub value: Decimal,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+/// Recording a valuation for a date that already has one replaces it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewValuation {
+    #[schema(example = "2024-03-01")]
+    pub date: NaiveDate,
+    #[schema(example = 350000.00)]
+    pub value: Decimal,
+}
+
+/// Value of an item on the date of a net worth calculation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetWorthItemValue {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "House")]
+    pub name: String,
+    pub kind: NetWorthItemKind,
+    #[schema(example = 350000.00)]
+    pub value: Decimal,
+    /// Date of the valuation used; empty if the item had none yet
+    #[schema(example = "2024-03-01")]
+    pub valued_on: Option<NaiveDate>,
+}
+
+/// Net worth on a date: cumulative income minus expenses plus assets minus
+/// liabilities
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetWorth {
+    #[schema(example = "2024-03-20")]
+    pub date: NaiveDate,
+    /// All income minus all expenses up to the date
+    #[schema(example = 12450.00)]
+    pub cash: Decimal,
+    #[schema(example = 350000.00)]
+    pub assets: Decimal,
+    #[schema(example = 210000.00)]
+    pub liabilities: Decimal,
+    #[schema(example = 152450.00)]
+    pub net_worth: Decimal,
+    pub items: Vec<NetWorthItemValue>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NetWorthQuery {
+    /// Defaults to today
+    pub date: Option<NaiveDate>,
+}
+
+/// Net worth at the end of a month
+#[derive(Debug, Clone, Serialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = net_worth_snapshots)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NetWorthSnapshot {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    #[schema(example = "2024-02-29")]
+    pub month_end: NaiveDate,
+    #[schema(example = 11800.00)]
+    pub cash: Decimal,
+    #[schema(example = 350000.00)]
+    pub assets: Decimal,
+    #[schema(example = 211000.00)]
+    pub liabilities: Decimal,
+    #[schema(example = 150800.00)]
+    pub net_worth: Decimal,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub computed_at: NaiveDateTime,
+}
+
+/// Range of month-end snapshots, inclusive
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NetWorthSnapshotQuery {
+    /// Only months ending on or after this date
+    pub from: Option<NaiveDate>,
+    /// Only months ending on or before this date
+    pub to: Option<NaiveDate>,
+}
@@ -0,0 +1,189 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::schema::{notification_preferences, notifications};
+
+/// What a notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// Income and spending of the past week
+    WeeklySummary,
+    /// A recurring expense is due soon
+    BillReminder,
+    /// Spending of the month reached a share of the monthly budget
+    BudgetThreshold,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::WeeklySummary => "weekly_summary",
+            NotificationKind::BillReminder => "bill_reminder",
+            NotificationKind::BudgetThreshold => "budget_threshold",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for NotificationKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for NotificationKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "weekly_summary" => Ok(NotificationKind::WeeklySummary),
+            "bill_reminder" => Ok(NotificationKind::BillReminder),
+            "budget_threshold" => Ok(NotificationKind::BudgetThreshold),
+            other => Err(format!("Unrecognized notification kind: {}", other).into()),
+        }
+    }
+}
+
+/// A message in the user's in-app inbox
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = notifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Notification {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    /// Identifies what the notification is about, e.g. `weekly_summary:2024-W11`
+    #[serde(skip)]
+    pub dedup_key: String,
+    #[schema(example = "Rent is due on 2024-04-01")]
+    pub title: String,
+    #[schema(example = "Rent of 1200.00 is expected on 2024-04-01.")]
+    pub body: String,
+    /// Figures behind the message, depending on the kind
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+    #[schema(example = "2024-03-29T08:15:00")]
+    pub read_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-03-29T07:00:00")]
+    pub created_at: NaiveDateTime,
+    #[serde(skip)]
+    pub email_attempts: i32,
+    /// When the email of the notification is tried next
+    #[serde(skip)]
+    pub email_next_attempt_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub emailed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NotificationQuery {
+    /// Only notifications that were not read yet
+    #[serde(default)]
+    pub unread: bool,
+    /// Number of notifications to return, newest first (default 50, at most 200)
+    pub limit: Option<i64>,
+}
+
+/// Notifications marked as read
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NotificationsRead {
+    #[schema(example = 3)]
+    pub read: usize,
+}
+
+/// How and about what a user is notified. The in-app inbox always receives
+/// every notification; email is an optional extra channel, and webhooks
+/// subscribed to `notification.created` get them as well.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = notification_preferences)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NotificationPreferences {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    /// Also send notifications to the account's email address
+    pub email_enabled: bool,
+    /// Summary of the past week, sent on Mondays
+    pub weekly_summary: bool,
+    /// Reminders of recurring expenses that are due soon
+    pub bill_reminders: bool,
+    /// Days ahead of a recurring expense to remind of it
+    #[schema(example = 3)]
+    pub bill_reminder_days: i32,
+    /// Spending limit for a calendar month; no budget alerts without one
+    #[schema(example = 2500.00)]
+    pub monthly_budget: Option<Decimal>,
+    /// Share of the monthly budget at which to warn, in percent. Exceeding
+    /// the budget is always reported.
+    #[schema(example = 80)]
+    pub budget_alert_percent: i32,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+impl NotificationPreferences {
+    /// Preferences of a user who never changed them
+    pub fn defaults(user_id: Uuid, now: NaiveDateTime) -> NotificationPreferences {
+        NotificationPreferences {
+            user_id,
+            email_enabled: false,
+            weekly_summary: true,
+            bill_reminders: true,
+            bill_reminder_days: 3,
+            monthly_budget: None,
+            budget_alert_percent: 80,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update of the notification preferences. Omitted fields are left
+/// unchanged; send `"monthly_budget": null` to clear the budget.
+#[derive(Debug, Default, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = notification_preferences)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateNotificationPreferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekly_summary: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bill_reminders: Option<bool>,
+    #[schema(example = 3)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bill_reminder_days: Option<i32>,
+    #[schema(value_type = Option<Decimal>, example = 2500.00)]
+    #[serde(default, with = "crate::models::patch::double_option", skip_serializing_if = "Option::is_none")]
+    pub monthly_budget: Option<Option<Decimal>>,
+    #[schema(example = 80)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_alert_percent: Option<i32>,
+}
+
+/// Notifications created by one run of the scheduler
+#[derive(Debug, Default, Serialize)]
+pub struct NotificationRun {
+    pub weekly_summaries: usize,
+    pub bill_reminders: usize,
+    pub budget_alerts: usize,
+}
+
+/// Outcome of one run of the notification email job
+#[derive(Debug, Default, Serialize)]
+pub struct EmailRun {
+    pub sent: usize,
+    pub retrying: usize,
+    pub failed: usize,
+}
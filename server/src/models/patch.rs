@@ -0,0 +1,64 @@
+//! Serde support for three-state PATCH fields
+//!
+//! A nullable column in a PATCH body can be absent (leave it alone), `null`
+//! (clear it) or a value (set it). Model it as `Option<Option<T>>`, which
+//! Diesel's `AsChangeset` already understands, and annotate the field with
+//! `#[serde(default, with = "crate::models::patch::double_option")]`.
+
+pub mod double_option {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        // Only called when the field is present; absent fields use `default`
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+
+    pub fn serialize<T, S>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(inner) => inner.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Patch {
+        #[serde(default, with = "super::double_option", skip_serializing_if = "Option::is_none")]
+        note: Option<Option<String>>,
+    }
+
+    fn parse(json: &str) -> Option<Option<String>> {
+        serde_json::from_str::<Patch>(json).unwrap().note
+    }
+
+    #[test]
+    fn tells_absent_null_and_values_apart() {
+        assert_eq!(parse("{}"), None);
+        assert_eq!(parse(r#"{"note": null}"#), Some(None));
+        assert_eq!(parse(r#"{"note": "rent"}"#), Some(Some("rent".to_string())));
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        assert!(serde_json::from_str::<Patch>(r#"{"note": 1}"#).is_err());
+    }
+
+    #[test]
+    fn serializes_back_to_the_same_body() {
+        for json in [r#"{}"#, r#"{"note":null}"#, r#"{"note":"rent"}"#] {
+            assert_eq!(serde_json::to_string(&serde_json::from_str::<Patch>(json).unwrap()).unwrap(), json);
+        }
+    }
+}
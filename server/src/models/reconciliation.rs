@@ -0,0 +1,209 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::schema::{reconciliation_items, reconciliations};
+
+/// Whether a transaction is an income or an expense
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    Income,
+    Expense,
+}
+
+impl TransactionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::Income => "income",
+            TransactionKind::Expense => "expense",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for TransactionKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for TransactionKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "income" => Ok(TransactionKind::Income),
+            "expense" => Ok(TransactionKind::Expense),
+            other => Err(format!("Unrecognized transaction kind: {}", other).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconciliationStatus {
+    /// Transactions are still being cleared
+    Open,
+    /// The cleared transactions matched the statement and are locked
+    Finished,
+}
+
+impl ReconciliationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReconciliationStatus::Open => "open",
+            ReconciliationStatus::Finished => "finished",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for ReconciliationStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for ReconciliationStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "open" => Ok(ReconciliationStatus::Open),
+            "finished" => Ok(ReconciliationStatus::Finished),
+            other => Err(format!("Unrecognized reconciliation status: {}", other).into()),
+        }
+    }
+}
+
+/// Check of an account's transactions against one bank statement
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = reconciliations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Reconciliation {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "Checking")]
+    pub account: String,
+    #[schema(example = "2024-03-01")]
+    pub period_start: NaiveDate,
+    #[schema(example = "2024-03-31")]
+    pub period_end: NaiveDate,
+    /// Balance at the start of the statement
+    #[schema(example = 1520.00)]
+    pub opening_balance: Decimal,
+    /// Closing balance printed on the statement
+    #[schema(example = 1874.35)]
+    pub statement_balance: Decimal,
+    pub status: ReconciliationStatus,
+    #[schema(example = "2024-04-02T18:00:00")]
+    pub finished_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-04-02T17:30:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-04-02T17:30:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewReconciliation {
+    #[schema(example = "Checking")]
+    pub account: String,
+    #[schema(example = "2024-03-01")]
+    pub period_start: NaiveDate,
+    #[schema(example = "2024-03-31")]
+    pub period_end: NaiveDate,
+    /// Defaults to the statement balance of the account's last finished
+    /// reconciliation, or zero for the first one
+    #[schema(example = 1520.00)]
+    pub opening_balance: Option<Decimal>,
+    #[schema(example = 1874.35)]
+    pub statement_balance: Decimal,
+}
+
+/// Partial update of an open reconciliation. Omitted fields are left
+/// unchanged.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = reconciliations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateReconciliation {
+    #[schema(example = "2024-03-01")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period_start: Option<NaiveDate>,
+    #[schema(example = "2024-03-31")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period_end: Option<NaiveDate>,
+    #[schema(example = 1520.00)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_balance: Option<Decimal>,
+    #[schema(example = 1874.35)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement_balance: Option<Decimal>,
+}
+
+/// A transaction marked as cleared in a reconciliation
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = reconciliation_items)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ReconciliationItem {
+    pub reconciliation_id: Uuid,
+    pub kind: TransactionKind,
+    pub record_id: Uuid,
+    pub cleared_at: NaiveDateTime,
+}
+
+/// Transactions to mark as cleared or not cleared
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ClearTransactions {
+    #[serde(default)]
+    pub incomes: Vec<Uuid>,
+    #[serde(default)]
+    pub expenses: Vec<Uuid>,
+}
+
+/// A reconciliation with the balance of its cleared transactions
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReconciliationSummary {
+    #[serde(flatten)]
+    pub reconciliation: Reconciliation,
+    #[schema(example = 14)]
+    pub cleared_count: usize,
+    #[schema(example = 2450.00)]
+    pub cleared_incomes: Decimal,
+    #[schema(example = 2095.65)]
+    pub cleared_expenses: Decimal,
+    /// Opening balance plus cleared incomes minus cleared expenses
+    #[schema(example = 1874.35)]
+    pub cleared_balance: Decimal,
+    /// Statement balance minus cleared balance; zero when the account is
+    /// reconciled
+    #[schema(example = 0.00)]
+    pub difference: Decimal,
+}
+
+/// A transaction of the reconciled account, in date order
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReconciliationTransaction {
+    pub kind: TransactionKind,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    /// Source of an income or item name of an expense
+    #[schema(example = "Corner Grocery")]
+    pub name: String,
+    /// Positive for incomes, negative for expenses
+    #[schema(example = -42.10)]
+    pub amount: Decimal,
+    #[schema(example = "2024-03-20")]
+    pub date: NaiveDate,
+    #[schema(example = "Weekly groceries")]
+    pub description: Option<String>,
+    pub cleared: bool,
+    /// Opening balance plus the cleared transactions up to this one
+    #[schema(example = 1477.90)]
+    pub cleared_balance: Decimal,
+}
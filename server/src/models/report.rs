@@ -0,0 +1,168 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// What expenses are grouped by in a spending breakdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendingGroupBy {
+    /// Payee or item, ignoring case and extra whitespace
+    #[default]
+    ItemName,
+    Category,
+}
+
+/// Period and grouping of a spending breakdown. Dates are inclusive.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SpendingBreakdownQuery {
+    /// First day of the period; defaults to the first day of the current month
+    pub from: Option<NaiveDate>,
+    /// Last day of the period; defaults to today
+    pub to: Option<NaiveDate>,
+    /// `item_name` (default) or `category`
+    #[param(inline)]
+    pub group_by: Option<SpendingGroupBy>,
+    /// Number of groups to list, at most 100 (default 10); the rest is
+    /// summed up in `other`
+    pub limit: Option<i64>,
+}
+
+/// Spending of one group in the period and in the period before
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SpendingGroup {
+    /// Normalized grouping key; empty for expenses without a category
+    #[schema(example = "groceries")]
+    pub key: String,
+    /// Most common spelling of the group in the period
+    #[schema(example = "Groceries")]
+    pub label: String,
+    #[schema(example = 412.50)]
+    pub total: Decimal,
+    #[schema(example = 6)]
+    pub count: i64,
+    /// Percentage of the period's total spending
+    #[schema(example = 27.35)]
+    pub share: Decimal,
+    #[schema(example = 380.00)]
+    pub previous_total: Decimal,
+    #[schema(example = 32.50)]
+    pub change: Decimal,
+    /// Change relative to the previous period in percent; empty when
+    /// nothing was spent on the group before
+    #[schema(example = 8.55)]
+    pub change_percent: Option<Decimal>,
+}
+
+/// Spending of the groups that did not make the top list
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SpendingRemainder {
+    #[schema(example = 12)]
+    pub groups: usize,
+    #[schema(example = 210.00)]
+    pub total: Decimal,
+    #[schema(example = 15)]
+    pub count: i64,
+    #[schema(example = 13.92)]
+    pub share: Decimal,
+}
+
+/// Where the money went in a period, compared to the period of the same
+/// length right before it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SpendingBreakdown {
+    pub group_by: SpendingGroupBy,
+    #[schema(example = "2024-03-01")]
+    pub from: NaiveDate,
+    #[schema(example = "2024-03-31")]
+    pub to: NaiveDate,
+    #[schema(example = "2024-01-30")]
+    pub previous_from: NaiveDate,
+    #[schema(example = "2024-02-29")]
+    pub previous_to: NaiveDate,
+    #[schema(example = 1508.20)]
+    pub total: Decimal,
+    #[schema(example = 1390.75)]
+    pub previous_total: Decimal,
+    /// Groups with the highest spending, largest first
+    pub groups: Vec<SpendingGroup>,
+    pub other: SpendingRemainder,
+}
+
+/// Month to forecast and how much history to learn from
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForecastQuery {
+    /// Month to forecast as `YYYY-MM`; defaults to the current month
+    #[param(example = "2024-03")]
+    pub month: Option<String>,
+    /// Number of full months before it to learn from, 1 to 24 (default 6)
+    pub history_months: Option<u32>,
+}
+
+/// A transaction that occurred in each of the last months and is expected
+/// again this month
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecurringItem {
+    #[schema(example = "Rent")]
+    pub label: String,
+    #[schema(example = "2024-03-01")]
+    pub expected_date: NaiveDate,
+    #[schema(example = 1200.00)]
+    pub amount: Decimal,
+}
+
+/// Cumulative amount for one day of the forecast month
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ForecastPoint {
+    #[schema(example = "2024-03-20")]
+    pub date: NaiveDate,
+    /// Recorded so far; only present up to `as_of`
+    #[schema(example = 1830.40)]
+    pub actual: Option<Decimal>,
+    #[schema(example = 1830.40)]
+    pub projected: Decimal,
+    /// Lower end of the 80% range
+    #[schema(example = 1830.40)]
+    pub low: Decimal,
+    /// Upper end of the 80% range
+    #[schema(example = 1830.40)]
+    pub high: Decimal,
+}
+
+/// Projection of income, expenses or the balance to the end of the month
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ForecastSeries {
+    #[schema(example = 1830.40)]
+    pub actual_to_date: Decimal,
+    #[schema(example = 2710.00)]
+    pub projected_total: Decimal,
+    #[schema(example = 2480.15)]
+    pub low: Decimal,
+    #[schema(example = 2939.85)]
+    pub high: Decimal,
+    /// Recurring items still expected after `as_of`
+    pub upcoming_recurring: Vec<RecurringItem>,
+    /// One point per day of the month
+    pub points: Vec<ForecastPoint>,
+}
+
+/// Where the month is heading, based on what has been recorded so far,
+/// day-of-month averages of past months and recurring items
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Forecast {
+    #[schema(example = "2024-03-01")]
+    pub month_start: NaiveDate,
+    #[schema(example = "2024-03-31")]
+    pub month_end: NaiveDate,
+    /// Last day with actual data
+    #[schema(example = "2024-03-20")]
+    pub as_of: NaiveDate,
+    #[schema(example = 6)]
+    pub history_months: u32,
+    pub income: ForecastSeries,
+    pub expenses: ForecastSeries,
+    /// Income minus expenses; the range combines the ranges of both
+    pub balance: ForecastSeries,
+}
@@ -1,5 +1,73 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        token_prefix -> Varchar,
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Uuid,
+        actor_id -> Nullable<Uuid>,
+        owner_id -> Nullable<Uuid>,
+        action -> Varchar,
+        entity_type -> Varchar,
+        entity_id -> Uuid,
+        before_data -> Nullable<Jsonb>,
+        after_data -> Nullable<Jsonb>,
+        ip_address -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        tx_id -> Int8,
+        seq -> Int8,
+    }
+}
+
+diesel::table! {
+    categorization_rules (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        priority -> Int4,
+        enabled -> Bool,
+        match_field -> Varchar,
+        match_type -> Varchar,
+        pattern -> Nullable<Varchar>,
+        min_amount -> Nullable<Numeric>,
+        max_amount -> Nullable<Numeric>,
+        sign -> Nullable<Varchar>,
+        set_category -> Nullable<Varchar>,
+        add_tags -> Array<Text>,
+        set_display_name -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    duplicate_candidates (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        kind -> Varchar,
+        first_id -> Uuid,
+        second_id -> Uuid,
+        score -> Numeric,
+        status -> Varchar,
+        detected_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     expenses (id) {
         id -> Uuid,
@@ -10,6 +78,52 @@ diesel::table! {
         description -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+        category -> Nullable<Varchar>,
+        tags -> Array<Text>,
+        display_name -> Nullable<Varchar>,
+        account -> Nullable<Varchar>,
+        reconciled_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    goal_contributions (id) {
+        id -> Uuid,
+        goal_id -> Uuid,
+        amount -> Numeric,
+        date -> Date,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    goals (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        target_amount -> Numeric,
+        deadline -> Nullable<Date>,
+        account -> Nullable<Varchar>,
+        category -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    idempotency_keys (user_id, idempotency_key) {
+        user_id -> Uuid,
+        idempotency_key -> Varchar,
+        request_hash -> Varchar,
+        response_status -> Nullable<Int4>,
+        response_content_type -> Nullable<Varchar>,
+        response_etag -> Nullable<Varchar>,
+        response_body -> Nullable<Bytea>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        leased_until -> Timestamp,
     }
 }
 
@@ -23,6 +137,134 @@ diesel::table! {
         description -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+        account -> Nullable<Varchar>,
+        reconciled_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    loan_payments (expense_id) {
+        expense_id -> Uuid,
+        loan_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    loans (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        principal -> Numeric,
+        annual_rate -> Numeric,
+        term_months -> Int4,
+        start_date -> Date,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    net_worth_items (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        kind -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    net_worth_snapshots (user_id, month_end) {
+        user_id -> Uuid,
+        month_end -> Date,
+        cash -> Numeric,
+        assets -> Numeric,
+        liabilities -> Numeric,
+        net_worth -> Numeric,
+        computed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notification_preferences (user_id) {
+        user_id -> Uuid,
+        email_enabled -> Bool,
+        weekly_summary -> Bool,
+        bill_reminders -> Bool,
+        bill_reminder_days -> Int4,
+        monthly_budget -> Nullable<Numeric>,
+        budget_alert_percent -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        kind -> Varchar,
+        dedup_key -> Varchar,
+        title -> Varchar,
+        body -> Text,
+        data -> Jsonb,
+        read_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        email_attempts -> Int4,
+        email_next_attempt_at -> Nullable<Timestamp>,
+        emailed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    reconciliation_items (reconciliation_id, kind, record_id) {
+        reconciliation_id -> Uuid,
+        kind -> Varchar,
+        record_id -> Uuid,
+        cleared_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reconciliations (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        account -> Varchar,
+        period_start -> Date,
+        period_end -> Date,
+        opening_balance -> Numeric,
+        statement_balance -> Numeric,
+        status -> Varchar,
+        finished_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_totp (user_id) {
+        user_id -> Uuid,
+        secret -> Varchar,
+        enabled -> Bool,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        challenge_id -> Nullable<Uuid>,
+        failed_attempts -> Int4,
+        locked_until -> Nullable<Timestamp>,
     }
 }
 
@@ -35,14 +277,96 @@ diesel::table! {
         password -> Varchar,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        role -> Varchar,
+        disabled_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    valuations (id) {
+        id -> Uuid,
+        item_id -> Uuid,
+        date -> Date,
+        value -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        webhook_id -> Uuid,
+        event -> Text,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamp>,
+        last_attempt_at -> Nullable<Timestamp>,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Varchar>,
+        delivered_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        url -> Varchar,
+        secret -> Varchar,
+        events -> Array<Text>,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
+diesel::joinable!(api_tokens -> users (user_id));
+diesel::joinable!(categorization_rules -> users (user_id));
+diesel::joinable!(duplicate_candidates -> users (user_id));
 diesel::joinable!(expenses -> users (user_id));
+diesel::joinable!(goal_contributions -> goals (goal_id));
+diesel::joinable!(goals -> users (user_id));
+diesel::joinable!(idempotency_keys -> users (user_id));
 diesel::joinable!(incomes -> users (user_id));
+diesel::joinable!(loan_payments -> expenses (expense_id));
+diesel::joinable!(loan_payments -> loans (loan_id));
+diesel::joinable!(loans -> users (user_id));
+diesel::joinable!(net_worth_items -> users (user_id));
+diesel::joinable!(net_worth_snapshots -> users (user_id));
+diesel::joinable!(notification_preferences -> users (user_id));
+diesel::joinable!(notifications -> users (user_id));
+diesel::joinable!(reconciliation_items -> reconciliations (reconciliation_id));
+diesel::joinable!(reconciliations -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(user_totp -> users (user_id));
+diesel::joinable!(valuations -> net_worth_items (item_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhooks -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    audit_log,
+    categorization_rules,
+    duplicate_candidates,
     expenses,
+    goal_contributions,
+    goals,
+    idempotency_keys,
     incomes,
+    loan_payments,
+    loans,
+    net_worth_items,
+    net_worth_snapshots,
+    notification_preferences,
+    notifications,
+    reconciliation_items,
+    reconciliations,
+    recovery_codes,
+    user_totp,
     users,
+    valuations,
+    webhook_deliveries,
+    webhooks,
 );
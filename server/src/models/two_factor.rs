@@ -0,0 +1,106 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::models::schema::{recovery_codes, user_totp};
+
+/// TOTP secret for a user. The row exists as soon as enrollment starts but
+/// `enabled` only flips once the user proves they can generate codes.
+#[derive(Debug, Queryable, Selectable, Insertable)]
+#[diesel(table_name = user_totp)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserTotp {
+    pub user_id: Uuid,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// The challenge issued by the latest password login, cleared once it is
+    /// answered
+    pub challenge_id: Option<Uuid>,
+    /// Wrong codes since the last correct one, over all logins and every
+    /// other check of a code
+    pub failed_attempts: i32,
+    /// No code is checked before this time
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+/// One-time recovery code, stored as a SHA-256 hash of the normalized code
+#[derive(Debug, Queryable, Selectable, Insertable)]
+#[diesel(table_name = recovery_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpEnrollmentResponse {
+    #[schema(example = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP")]
+    pub secret: String,
+    #[schema(example = "otpauth://totp/FinStack:john%40example.com?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=FinStack")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TwoFactorCodeRequest {
+    /// Six digit TOTP code or an unused recovery code
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    #[schema(example = json!(["k7d2m-q9x4p", "a3hf8-2wnc6"]))]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwoFactorChallenge {
+    #[schema(example = true)]
+    pub two_factor_required: bool,
+    #[schema(example = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9...")]
+    pub challenge_token: String,
+    #[schema(example = 300)]
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TwoFactorLoginRequest {
+    #[schema(example = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9...")]
+    pub challenge_token: String,
+    /// Six digit TOTP code or an unused recovery code
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+/// Claims of the short-lived token handed out between the password and
+/// second factor steps of a login. It has no `email`, so it never decodes
+/// as regular access `Claims`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String, // User ID
+    pub jti: String, // Challenge ID
+    pub purpose: String,
+    pub exp: usize,
+    pub iat: usize,
+}
+
+impl ChallengeClaims {
+    pub const PURPOSE: &'static str = "two_factor";
+
+    pub fn new(user_id: Uuid, challenge_id: Uuid, exp: usize) -> Self {
+        Self {
+            sub: user_id.to_string(),
+            jti: challenge_id.to_string(),
+            purpose: Self::PURPOSE.to_string(),
+            exp,
+            iat: chrono::Utc::now().timestamp() as usize,
+        }
+    }
+}
@@ -1,12 +1,52 @@
 use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
 use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Varchar;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use utoipa::ToSchema;
 use crate::models::schema::users;
 use crate::models::income::Income;
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
+/// Authorization role of a user, stored as text in `users.role`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for Role {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "user" => Ok(Role::User),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("Unrecognized role: {}", other).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema)]
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct User {
@@ -24,9 +64,13 @@ pub struct User {
     pub created_at: NaiveDateTime,
     #[schema(example = "2024-03-20T10:00:00")]
     pub updated_at: NaiveDateTime,
+    #[schema(example = "user")]
+    pub role: Role,
+    pub disabled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
+#[allow(dead_code)]
 pub struct UserWithIncomes {
     #[serde(flatten)]
     pub user: User,
@@ -73,6 +117,8 @@ impl NewUser {
             password: self.password,
             created_at: self.created_at,
             updated_at: self.updated_at,
+            role: Role::User,
+            disabled_at: None,
         }
     }
 }
@@ -0,0 +1,254 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::{Text, Varchar};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::audit::{entity, AuditAction};
+use crate::models::schema::{webhook_deliveries, webhooks};
+
+/// A change to an income or expense that webhooks can subscribe to.
+/// `deleted` means moved to the trash, `restored` back out of it.
+/// `notification.created` carries every new notification of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Text)]
+pub enum WebhookEvent {
+    #[serde(rename = "income.created")]
+    IncomeCreated,
+    #[serde(rename = "income.updated")]
+    IncomeUpdated,
+    #[serde(rename = "income.deleted")]
+    IncomeDeleted,
+    #[serde(rename = "income.restored")]
+    IncomeRestored,
+    #[serde(rename = "expense.created")]
+    ExpenseCreated,
+    #[serde(rename = "expense.updated")]
+    ExpenseUpdated,
+    #[serde(rename = "expense.deleted")]
+    ExpenseDeleted,
+    #[serde(rename = "expense.restored")]
+    ExpenseRestored,
+    #[serde(rename = "notification.created")]
+    NotificationCreated,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 9] = [
+        WebhookEvent::IncomeCreated,
+        WebhookEvent::IncomeUpdated,
+        WebhookEvent::IncomeDeleted,
+        WebhookEvent::IncomeRestored,
+        WebhookEvent::ExpenseCreated,
+        WebhookEvent::ExpenseUpdated,
+        WebhookEvent::ExpenseDeleted,
+        WebhookEvent::ExpenseRestored,
+        WebhookEvent::NotificationCreated,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::IncomeCreated => "income.created",
+            WebhookEvent::IncomeUpdated => "income.updated",
+            WebhookEvent::IncomeDeleted => "income.deleted",
+            WebhookEvent::IncomeRestored => "income.restored",
+            WebhookEvent::ExpenseCreated => "expense.created",
+            WebhookEvent::ExpenseUpdated => "expense.updated",
+            WebhookEvent::ExpenseDeleted => "expense.deleted",
+            WebhookEvent::ExpenseRestored => "expense.restored",
+            WebhookEvent::NotificationCreated => "notification.created",
+        }
+    }
+
+    /// The event of an audited change, if it is one. A duplicate that was
+    /// merged into another record went to the trash, so it counts as deleted.
+    pub fn for_change(entity_type: &str, action: AuditAction) -> Option<WebhookEvent> {
+        let event = match (entity_type, action) {
+            (entity::INCOME, AuditAction::Create) => WebhookEvent::IncomeCreated,
+            (entity::INCOME, AuditAction::Update) => WebhookEvent::IncomeUpdated,
+            (entity::INCOME, AuditAction::Delete | AuditAction::Merge) => WebhookEvent::IncomeDeleted,
+            (entity::INCOME, AuditAction::Restore) => WebhookEvent::IncomeRestored,
+            (entity::EXPENSE, AuditAction::Create) => WebhookEvent::ExpenseCreated,
+            (entity::EXPENSE, AuditAction::Update) => WebhookEvent::ExpenseUpdated,
+            (entity::EXPENSE, AuditAction::Delete | AuditAction::Merge) => WebhookEvent::ExpenseDeleted,
+            (entity::EXPENSE, AuditAction::Restore) => WebhookEvent::ExpenseRestored,
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    pub fn is_update(&self) -> bool {
+        matches!(self, WebhookEvent::IncomeUpdated | WebhookEvent::ExpenseUpdated)
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        matches!(self, WebhookEvent::IncomeDeleted | WebhookEvent::ExpenseDeleted)
+    }
+}
+
+impl ToSql<Text, Pg> for WebhookEvent {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Text, Pg> for WebhookEvent {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|event| event.as_str() == value)
+            .ok_or_else(|| format!("Unrecognized webhook event: {}", value).into())
+    }
+}
+
+/// Where a delivery is in the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, ToSchema)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first or next attempt
+    Pending,
+    /// The receiver answered with a 2xx status
+    Delivered,
+    /// Every attempt failed; the delivery can be retried by hand
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for DeliveryStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Varchar, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl FromSql<Varchar, Pg> for DeliveryStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Varchar, Pg>>::from_sql(bytes)?.as_str() {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            other => Err(format!("Unrecognized delivery status: {}", other).into()),
+        }
+    }
+}
+
+/// A URL that is sent the user's income and expense events. The signing
+/// secret is only returned when the webhook is created.
+#[derive(Debug, Clone, Serialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    #[schema(example = "https://example.com/hooks/transactions")]
+    pub url: String,
+    #[serde(skip_serializing)]
+    #[schema(ignore)]
+    pub secret: String,
+    #[schema(example = json!(["expense.created", "expense.updated"]))]
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewWebhook {
+    /// http or https URL that events are posted to. Its host must resolve to
+    /// public addresses only.
+    #[schema(example = "https://example.com/hooks/transactions")]
+    pub url: String,
+    #[schema(example = json!(["expense.created", "expense.updated"]))]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// Response to webhook creation, the only time the secret is returned
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedWebhook {
+    /// Key of the HMAC-SHA256 signature in the `Webhook-Signature` header
+    #[schema(example = "whsec_4pa3hf82wnc6k7d2mq9x4pa3hf82wnc6")]
+    pub secret: String,
+    #[serde(flatten)]
+    pub webhook: Webhook,
+}
+
+/// Partial update of a webhook. Omitted fields are left unchanged.
+#[derive(Debug, Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateWebhook {
+    #[schema(example = "https://example.com/hooks/transactions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[schema(example = json!(["expense.created"]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<WebhookEvent>>,
+    /// Disabled webhooks get no new deliveries and pending ones wait
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+/// One event sent, or to be sent, to a webhook
+#[derive(Debug, Clone, Serialize, Queryable, Selectable, Insertable, ToSchema)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDelivery {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub id: Uuid,
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    /// Body that is posted
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    #[schema(example = 1)]
+    pub attempts: i32,
+    #[schema(example = "2024-03-20T10:01:00")]
+    pub next_attempt_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-03-20T10:00:30")]
+    pub last_attempt_at: Option<NaiveDateTime>,
+    /// HTTP status of the last response
+    #[schema(example = 503)]
+    pub response_status: Option<i32>,
+    #[schema(example = "Receiver answered with status 503")]
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+    #[schema(example = "2024-03-20T10:00:00")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WebhookDeliveryQuery {
+    #[param(inline)]
+    pub status: Option<DeliveryStatus>,
+    /// Number of deliveries to return, newest first (default 50, at most 200)
+    pub limit: Option<i64>,
+}
+
+/// Deliveries attempted by one run of the delivery job
+#[derive(Debug, Default)]
+pub struct DeliveryRun {
+    pub delivered: usize,
+    pub retrying: usize,
+    pub failed: usize,
+}
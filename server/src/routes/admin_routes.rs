@@ -0,0 +1,29 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::admin_controller;
+use crate::middleware::auth_middleware::{jwt_validator, require_admin};
+use crate::routes::{Endpoint, RouteTable};
+
+const ADMIN: RouteTable = RouteTable {
+    prefix: "/admin",
+    endpoints: &[
+        Endpoint::get("/users", |route| route.to(admin_controller::get_all_users)),
+        Endpoint::post("/users/{user_id}/disable", |route| route.to(admin_controller::disable_user)),
+        Endpoint::post("/users/{user_id}/enable", |route| route.to(admin_controller::enable_user)),
+        Endpoint::put("/users/{user_id}/role", |route| route.to(admin_controller::update_user_role)),
+        Endpoint::get("/statistics", |route| route.to(admin_controller::get_statistics)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[ADMIN];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        ADMIN.scope()
+            .wrap(from_fn(require_admin))
+            .wrap(auth)
+    );
+}
@@ -0,0 +1,25 @@
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::api_token_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::routes::{Endpoint, RouteTable};
+
+const TOKENS: RouteTable = RouteTable {
+    prefix: "/tokens",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(api_token_controller::get_tokens)),
+        Endpoint::post("", |route| route.to(api_token_controller::create_token)),
+        Endpoint::delete("/{token_id}", |route| route.to(api_token_controller::revoke_token)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[TOKENS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        TOKENS.scope()
+            .wrap(auth)
+    );
+}
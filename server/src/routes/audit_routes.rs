@@ -0,0 +1,23 @@
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::audit_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::routes::{Endpoint, RouteTable};
+
+const AUDIT_LOG: RouteTable = RouteTable {
+    prefix: "/audit-log",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(audit_controller::get_audit_log)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[AUDIT_LOG];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        AUDIT_LOG.scope()
+            .wrap(auth)
+    );
+}
@@ -1,12 +1,37 @@
 use actix_web::web;
 use crate::controllers::auth_controller;
+use crate::routes::{Endpoint, RouteTable};
+
+const AUTH: RouteTable = RouteTable {
+    prefix: "/auth",
+    endpoints: &[
+        Endpoint::post("/register", |route| route.to(auth_controller::register)),
+        Endpoint::post("/login", |route| route.to(auth_controller::login)),
+        Endpoint::post("/login/2fa", |route| route.to(auth_controller::login_two_factor)),
+        Endpoint::get("/me", |route| route.to(auth_controller::me)),
+        Endpoint::post("/logout", |route| route.to(auth_controller::logout)),
+        Endpoint::post("/2fa/enroll", |route| route.to(auth_controller::enroll_two_factor)),
+        Endpoint::post("/2fa/verify", |route| route.to(auth_controller::verify_two_factor)),
+        Endpoint::post("/2fa/disable", |route| route.to(auth_controller::disable_two_factor)),
+        Endpoint::post("/2fa/recovery-codes", |route| route.to(auth_controller::regenerate_recovery_codes)),
+    ],
+};
+
+/// Discovery documents served outside of `/api`
+const WELL_KNOWN: RouteTable = RouteTable {
+    prefix: "/.well-known",
+    endpoints: &[
+        Endpoint::get("/jwks.json", |route| route.to(auth_controller::jwks)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[AUTH];
+pub const WELL_KNOWN_ROUTES: &[RouteTable] = &[WELL_KNOWN];
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/auth")
-            .route("/register", web::post().to(auth_controller::register))
-            .route("/login", web::post().to(auth_controller::login))
-            .route("/me", web::get().to(auth_controller::me))
-            .route("/logout", web::post().to(auth_controller::logout)),
-    );
-} 
\ No newline at end of file
+    cfg.service(AUTH.scope());
+}
+
+pub fn configure_well_known(cfg: &mut web::ServiceConfig) {
+    WELL_KNOWN.register(cfg);
+}
@@ -0,0 +1,47 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::categorization_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const RULES: RouteTable = RouteTable {
+    prefix: "/categorization-rules",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(categorization_controller::get_rules)),
+        Endpoint::post("", |route| route.to(categorization_controller::create_rule)),
+        Endpoint::post("/dry-run", |route| route.to(categorization_controller::dry_run)),
+        Endpoint::post("/apply", |route| route.to(categorization_controller::apply_rules)),
+        Endpoint::get("/{rule_id}", |route| route.to(categorization_controller::get_rule)),
+        Endpoint::patch("/{rule_id}", |route| route.to(categorization_controller::update_rule)),
+        Endpoint::put("/{rule_id}", |route| route.to(categorization_controller::update_rule)),
+        Endpoint::delete("/{rule_id}", |route| route.to(categorization_controller::delete_rule)),
+        Endpoint::post("/{rule_id}/dry-run", |route| route.to(categorization_controller::dry_run_rule)),
+        Endpoint::post("/{rule_id}/apply", |route| route.to(categorization_controller::apply_rule)),
+    ],
+};
+
+const SUGGESTIONS: RouteTable = RouteTable {
+    prefix: "/category-suggestions",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(categorization_controller::suggest_categories)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[RULES, SUGGESTIONS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        RULES.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth.clone())
+    );
+
+    cfg.service(
+        SUGGESTIONS.scope()
+            .wrap(auth)
+    );
+}
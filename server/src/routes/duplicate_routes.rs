@@ -0,0 +1,29 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::duplicate_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const DUPLICATES: RouteTable = RouteTable {
+    prefix: "/duplicates",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(duplicate_controller::get_duplicates)),
+        Endpoint::post("/scan", |route| route.to(duplicate_controller::scan)),
+        Endpoint::post("/{duplicate_id}/merge", |route| route.to(duplicate_controller::merge)),
+        Endpoint::post("/{duplicate_id}/dismiss", |route| route.to(duplicate_controller::dismiss)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[DUPLICATES];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        DUPLICATES.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
@@ -1,18 +1,90 @@
+use actix_web::middleware::from_fn;
 use actix_web::web;
 use actix_web_httpauth::middleware::HttpAuthentication;
 use crate::controllers::expense_controller;
 use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::deprecation::{deprecated, UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT};
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const EXPENSES: RouteTable = RouteTable {
+    prefix: "/expenses",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(expense_controller::get_all_expenses)),
+        Endpoint::post("", |route| route.to(expense_controller::create_expense)),
+        Endpoint::post("/batch", |route| route.to(expense_controller::batch_expenses)),
+        // Must come before `/{expense_id}`, which would otherwise match it
+        Endpoint::get("/trash", |route| route.to(expense_controller::get_deleted_expenses)),
+        Endpoint::get("/{expense_id}", |route| route.to(expense_controller::get_expense)),
+        Endpoint::patch("/{expense_id}", |route| route.to(expense_controller::update_expense)),
+        Endpoint::put("/{expense_id}", |route| route.to(expense_controller::update_expense)),
+        Endpoint::delete("/{expense_id}", |route| route.to(expense_controller::delete_expense)),
+        Endpoint::post("/{expense_id}/restore", |route| route.to(expense_controller::restore_expense)),
+        Endpoint::post("/{expense_id}/unlock", |route| route.to(expense_controller::unlock_expense)),
+    ],
+};
+
+const USER_EXPENSES: RouteTable = RouteTable {
+    prefix: "/users/{user_id}/expenses",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(expense_controller::get_expenses_by_user_id)),
+    ],
+};
+
+const MY_EXPENSES: RouteTable = RouteTable {
+    prefix: "/me/expenses",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(expense_controller::get_my_expenses)),
+    ],
+};
+
+const DEPRECATED_EXPENSES: RouteTable = RouteTable {
+    prefix: "/expenses",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(expense_controller::get_all_expenses)),
+        Endpoint::post("", |route| route.to(expense_controller::create_expense)),
+        Endpoint::post("/batch", |route| route.to(expense_controller::batch_expenses)),
+        // Must come before `/{user_id}`, which would otherwise match it
+        Endpoint::get("/trash", |route| route.to(expense_controller::get_deleted_expenses)),
+        Endpoint::get("/{user_id}", |route| route.to(expense_controller::get_expenses_by_user_id)).moved_to("/users/{user_id}/expenses"),
+        Endpoint::patch("/{expense_id}", |route| route.to(expense_controller::update_expense)),
+        Endpoint::put("/{expense_id}", |route| route.to(expense_controller::update_expense)),
+        Endpoint::delete("/{expense_id}", |route| route.to(expense_controller::delete_expense)),
+        Endpoint::post("/{expense_id}/restore", |route| route.to(expense_controller::restore_expense)),
+        Endpoint::post("/{expense_id}/unlock", |route| route.to(expense_controller::unlock_expense)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[EXPENSES, USER_EXPENSES, MY_EXPENSES];
+pub const DEPRECATED_ROUTES: &[RouteTable] = &[DEPRECATED_EXPENSES];
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     let auth = HttpAuthentication::bearer(jwt_validator);
-    
+
+    cfg.service(
+        EXPENSES.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth.clone())
+    )
+    .service(
+        USER_EXPENSES.scope()
+            .wrap(auth.clone())
+    )
+    .service(
+        MY_EXPENSES.scope()
+            .wrap(auth)
+    );
+}
+
+/// The routes from before `/api/v1`, where `GET /{user_id}` lists a user's
+/// expenses instead of fetching one
+pub fn configure_deprecated(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
     cfg.service(
-        web::scope("/expenses")
+        DEPRECATED_EXPENSES.scope()
+            .wrap(from_fn(idempotency))
             .wrap(auth)
-            .route("", web::get().to(expense_controller::get_all_expenses))
-            .route("", web::post().to(expense_controller::create_expense))
-            .route("/{user_id}", web::get().to(expense_controller::get_expenses_by_user_id))
-            .route("/{expense_id}", web::put().to(expense_controller::update_expense))
-            .route("/{expense_id}", web::delete().to(expense_controller::delete_expense))
+            .wrap(deprecated(UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT, "/api/v1/expenses"))
     );
 }
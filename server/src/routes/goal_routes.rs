@@ -0,0 +1,35 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::goal_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const GOALS: RouteTable = RouteTable {
+    prefix: "/goals",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(goal_controller::get_goals)),
+        Endpoint::post("", |route| route.to(goal_controller::create_goal)),
+        Endpoint::get("/{goal_id}", |route| route.to(goal_controller::get_goal)),
+        Endpoint::patch("/{goal_id}", |route| route.to(goal_controller::update_goal)),
+        Endpoint::put("/{goal_id}", |route| route.to(goal_controller::update_goal)),
+        Endpoint::delete("/{goal_id}", |route| route.to(goal_controller::delete_goal)),
+        Endpoint::get("/{goal_id}/contributions", |route| route.to(goal_controller::get_contributions)),
+        Endpoint::post("/{goal_id}/contributions", |route| route.to(goal_controller::add_contribution)),
+        Endpoint::delete("/{goal_id}/contributions/{contribution_id}", |route| route.to(goal_controller::delete_contribution)),
+        Endpoint::get("/{goal_id}/progress", |route| route.to(goal_controller::get_progress)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[GOALS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        GOALS.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
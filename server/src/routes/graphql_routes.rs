@@ -0,0 +1,25 @@
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::graphql_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::routes::{Endpoint, RouteTable};
+
+const GRAPHQL: RouteTable = RouteTable {
+    prefix: "/graphql",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(graphql_controller::graphql)),
+        Endpoint::post("", |route| route.to(graphql_controller::graphql)),
+        Endpoint::get("/schema", |route| route.to(graphql_controller::get_schema)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[GRAPHQL];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        GRAPHQL.scope()
+            .wrap(auth)
+    );
+}
@@ -1,18 +1,90 @@
+use actix_web::middleware::from_fn;
 use actix_web::web;
 use actix_web_httpauth::middleware::HttpAuthentication;
 use crate::controllers::income_controller;
 use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::deprecation::{deprecated, UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT};
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const INCOMES: RouteTable = RouteTable {
+    prefix: "/incomes",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(income_controller::get_all_incomes)),
+        Endpoint::post("", |route| route.to(income_controller::create_income)),
+        Endpoint::post("/batch", |route| route.to(income_controller::batch_incomes)),
+        // Must come before `/{income_id}`, which would otherwise match it
+        Endpoint::get("/trash", |route| route.to(income_controller::get_deleted_incomes)),
+        Endpoint::get("/{income_id}", |route| route.to(income_controller::get_income)),
+        Endpoint::patch("/{income_id}", |route| route.to(income_controller::update_income)),
+        Endpoint::put("/{income_id}", |route| route.to(income_controller::update_income)),
+        Endpoint::delete("/{income_id}", |route| route.to(income_controller::delete_income)),
+        Endpoint::post("/{income_id}/restore", |route| route.to(income_controller::restore_income)),
+        Endpoint::post("/{income_id}/unlock", |route| route.to(income_controller::unlock_income)),
+    ],
+};
+
+const USER_INCOMES: RouteTable = RouteTable {
+    prefix: "/users/{user_id}/incomes",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(income_controller::get_incomes_by_user_id)),
+    ],
+};
+
+const MY_INCOMES: RouteTable = RouteTable {
+    prefix: "/me/incomes",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(income_controller::get_my_incomes)),
+    ],
+};
+
+const DEPRECATED_INCOMES: RouteTable = RouteTable {
+    prefix: "/incomes",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(income_controller::get_all_incomes)),
+        // Must come before `/{user_id}`, which would otherwise match it
+        Endpoint::get("/trash", |route| route.to(income_controller::get_deleted_incomes)),
+        Endpoint::get("/{user_id}", |route| route.to(income_controller::get_incomes_by_user_id)).moved_to("/users/{user_id}/incomes"),
+        Endpoint::post("", |route| route.to(income_controller::create_income)),
+        Endpoint::post("/batch", |route| route.to(income_controller::batch_incomes)),
+        Endpoint::patch("/{income_id}", |route| route.to(income_controller::update_income)),
+        Endpoint::put("/{income_id}", |route| route.to(income_controller::update_income)),
+        Endpoint::delete("/{income_id}", |route| route.to(income_controller::delete_income)),
+        Endpoint::post("/{income_id}/restore", |route| route.to(income_controller::restore_income)),
+        Endpoint::post("/{income_id}/unlock", |route| route.to(income_controller::unlock_income)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[INCOMES, USER_INCOMES, MY_INCOMES];
+pub const DEPRECATED_ROUTES: &[RouteTable] = &[DEPRECATED_INCOMES];
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     let auth = HttpAuthentication::bearer(jwt_validator);
-    
+
+    cfg.service(
+        INCOMES.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth.clone())
+    )
+    .service(
+        USER_INCOMES.scope()
+            .wrap(auth.clone())
+    )
+    .service(
+        MY_INCOMES.scope()
+            .wrap(auth)
+    );
+}
+
+/// The routes from before `/api/v1`, where `GET /{user_id}` lists a user's
+/// incomes instead of fetching one
+pub fn configure_deprecated(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
     cfg.service(
-        web::scope("/incomes")
+        DEPRECATED_INCOMES.scope()
+            .wrap(from_fn(idempotency))
             .wrap(auth)
-            .route("", web::get().to(income_controller::get_all_incomes))
-            .route("/{user_id}", web::get().to(income_controller::get_incomes_by_user_id))
-            .route("", web::post().to(income_controller::create_income))
-            .route("/{income_id}", web::put().to(income_controller::update_income))
-            .route("/{income_id}", web::delete().to(income_controller::delete_income))
+            .wrap(deprecated(UNVERSIONED_ROUTES_DEPRECATED_AT, UNVERSIONED_ROUTES_SUNSET_AT, "/api/v1/incomes"))
     );
-} 
\ No newline at end of file
+}
@@ -0,0 +1,34 @@
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::live_event_controller;
+use crate::middleware::auth_middleware::{event_stream_validator, jwt_validator};
+use crate::routes::{Endpoint, RouteTable};
+
+const STREAM_TOKEN: RouteTable = RouteTable {
+    prefix: "/events/token",
+    endpoints: &[
+        Endpoint::post("", |route| route.to(live_event_controller::create_stream_token)),
+    ],
+};
+
+const EVENTS: RouteTable = RouteTable {
+    prefix: "/events",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(live_event_controller::stream_events)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[STREAM_TOKEN, EVENTS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Before `/events`, which would otherwise match its requests
+    cfg.service(
+        STREAM_TOKEN.scope()
+            .wrap(HttpAuthentication::bearer(jwt_validator))
+    );
+
+    cfg.service(
+        EVENTS.scope()
+            .wrap(HttpAuthentication::with_fn(event_stream_validator))
+    );
+}
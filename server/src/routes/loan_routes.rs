@@ -0,0 +1,36 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::loan_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const LOANS: RouteTable = RouteTable {
+    prefix: "/loans",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(loan_controller::get_loans)),
+        Endpoint::post("", |route| route.to(loan_controller::create_loan)),
+        Endpoint::get("/{loan_id}", |route| route.to(loan_controller::get_loan)),
+        Endpoint::patch("/{loan_id}", |route| route.to(loan_controller::update_loan)),
+        Endpoint::put("/{loan_id}", |route| route.to(loan_controller::update_loan)),
+        Endpoint::delete("/{loan_id}", |route| route.to(loan_controller::delete_loan)),
+        Endpoint::get("/{loan_id}/schedule", |route| route.to(loan_controller::get_schedule)),
+        Endpoint::get("/{loan_id}/status", |route| route.to(loan_controller::get_status)),
+        Endpoint::get("/{loan_id}/payments", |route| route.to(loan_controller::get_payments)),
+        Endpoint::post("/{loan_id}/payments", |route| route.to(loan_controller::link_payment)),
+        Endpoint::delete("/{loan_id}/payments/{expense_id}", |route| route.to(loan_controller::unlink_payment)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[LOANS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        LOANS.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
@@ -2,15 +2,397 @@ mod income_routes;
 mod expense_routes;
 mod health_routes;
 mod auth_routes;
+mod admin_routes;
+mod api_token_routes;
+mod audit_routes;
+mod goal_routes;
+mod loan_routes;
+mod report_routes;
+mod net_worth_routes;
+mod categorization_routes;
+mod duplicate_routes;
+mod reconciliation_routes;
+mod notification_routes;
+mod webhook_routes;
+mod live_event_routes;
+mod graphql_routes;
 
-use actix_web::web;
+use actix_web::http::Method;
+use actix_web::{web, Route};
+
+/// One operation of a routes table: its method, its path within the
+/// table's scope, and how its route is handled
+pub struct Endpoint {
+    pub method: Method,
+    pub path: &'static str,
+    to: fn(Route) -> Route,
+    /// Where a deprecated endpoint moved to under `/api/v1`, when that is
+    /// not its own path
+    successor: Option<&'static str>,
+}
+
+impl Endpoint {
+    pub const fn get(path: &'static str, to: fn(Route) -> Route) -> Self {
+        Self { method: Method::GET, path, to, successor: None }
+    }
+
+    pub const fn post(path: &'static str, to: fn(Route) -> Route) -> Self {
+        Self { method: Method::POST, path, to, successor: None }
+    }
+
+    pub const fn put(path: &'static str, to: fn(Route) -> Route) -> Self {
+        Self { method: Method::PUT, path, to, successor: None }
+    }
+
+    pub const fn patch(path: &'static str, to: fn(Route) -> Route) -> Self {
+        Self { method: Method::PATCH, path, to, successor: None }
+    }
+
+    pub const fn delete(path: &'static str, to: fn(Route) -> Route) -> Self {
+        Self { method: Method::DELETE, path, to, successor: None }
+    }
+
+    /// The same endpoint, moved to `successor` under `/api/v1`
+    pub const fn moved_to(mut self, successor: &'static str) -> Self {
+        self.successor = Some(successor);
+        self
+    }
+
+    fn route(&self) -> Route {
+        (self.to)(web::method(self.method.clone()))
+    }
+}
+
+/// Endpoints served under one path prefix, in the order they are matched
+///
+/// Routes modules register their endpoints only through these tables, which
+/// are also what the OpenAPI spec is checked against.
+pub struct RouteTable {
+    pub prefix: &'static str,
+    pub endpoints: &'static [Endpoint],
+}
+
+impl RouteTable {
+    /// A scope at the prefix serving the endpoints, ready for its middleware
+    pub fn scope(&self) -> actix_web::Scope {
+        self.endpoints
+            .iter()
+            .fold(web::scope(self.prefix), |scope, endpoint| scope.route(endpoint.path, endpoint.route()))
+    }
+
+    /// Register the endpoints directly, without a scope of their own
+    pub fn register(&self, cfg: &mut web::ServiceConfig) {
+        for endpoint in self.endpoints {
+            cfg.route(&format!("{}{}", self.prefix, endpoint.path), endpoint.route());
+        }
+    }
+}
+
+/// A routes module's `configure` function with every table it registers
+struct Mount {
+    configure: fn(&mut web::ServiceConfig),
+    // Only read by the tests checking the routing and the OpenAPI spec
+    #[cfg_attr(not(test), allow(dead_code))]
+    tables: &'static [RouteTable],
+}
+
+/// Routes kept at their paths from before `/api/v1`, which they are served
+/// under too
+const DEPRECATED: [&[RouteTable]; 2] = [income_routes::DEPRECATED_ROUTES, expense_routes::DEPRECATED_ROUTES];
+
+/// A deprecated operation: its method, its path and the path of the
+/// operation replacing it
+pub struct DeprecatedOperation {
+    pub method: Method,
+    pub path: String,
+    pub successor: String,
+}
+
+/// Every operation of the [`DEPRECATED`] routes
+pub fn deprecated_operations() -> Vec<DeprecatedOperation> {
+    DEPRECATED
+        .into_iter()
+        .flatten()
+        .flat_map(|table| {
+            table.endpoints.iter().map(|endpoint| {
+                let path = format!("{}{}", table.prefix, endpoint.path);
+                DeprecatedOperation {
+                    method: endpoint.method.clone(),
+                    successor: format!("/api/v1{}", endpoint.successor.unwrap_or(&path)),
+                    path: format!("/api{}", path),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Where each routes module is served, in the order the prefixes are matched
+const MOUNTS: [(&str, &[Mount]); 3] = [
+    (
+        "",
+        &[
+            Mount { configure: health_routes::configure, tables: health_routes::ROUTES },
+            Mount { configure: auth_routes::configure_well_known, tables: auth_routes::WELL_KNOWN_ROUTES },
+        ],
+    ),
+    // Before `/api`, which would otherwise match its requests
+    (
+        "/api/v1",
+        &[
+            Mount { configure: income_routes::configure, tables: income_routes::ROUTES },
+            Mount { configure: expense_routes::configure, tables: expense_routes::ROUTES },
+        ],
+    ),
+    (
+        "/api",
+        &[
+            Mount { configure: auth_routes::configure, tables: auth_routes::ROUTES },
+            Mount { configure: income_routes::configure_deprecated, tables: income_routes::DEPRECATED_ROUTES },
+            Mount { configure: expense_routes::configure_deprecated, tables: expense_routes::DEPRECATED_ROUTES },
+            Mount { configure: admin_routes::configure, tables: admin_routes::ROUTES },
+            Mount { configure: api_token_routes::configure, tables: api_token_routes::ROUTES },
+            Mount { configure: audit_routes::configure, tables: audit_routes::ROUTES },
+            Mount { configure: goal_routes::configure, tables: goal_routes::ROUTES },
+            Mount { configure: loan_routes::configure, tables: loan_routes::ROUTES },
+            Mount { configure: report_routes::configure, tables: report_routes::ROUTES },
+            Mount { configure: net_worth_routes::configure, tables: net_worth_routes::ROUTES },
+            Mount { configure: categorization_routes::configure, tables: categorization_routes::ROUTES },
+            Mount { configure: duplicate_routes::configure, tables: duplicate_routes::ROUTES },
+            Mount { configure: reconciliation_routes::configure, tables: reconciliation_routes::ROUTES },
+            Mount { configure: notification_routes::configure, tables: notification_routes::ROUTES },
+            Mount { configure: webhook_routes::configure, tables: webhook_routes::ROUTES },
+            Mount { configure: live_event_routes::configure, tables: live_event_routes::ROUTES },
+            Mount { configure: graphql_routes::configure, tables: graphql_routes::ROUTES },
+        ],
+    ),
+];
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.configure(health_routes::configure)
-        .service(
-            web::scope("/api")
-                .configure(auth_routes::configure)
-                .configure(income_routes::configure)
-                .configure(expense_routes::configure)
+    for (prefix, mounts) in MOUNTS {
+        let configure_all = |cfg: &mut web::ServiceConfig| {
+            for mount in mounts {
+                cfg.configure(mount.configure);
+            }
+        };
+        if prefix.is_empty() {
+            configure_all(cfg);
+        } else {
+            cfg.service(web::scope(prefix).configure(configure_all));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use regex::Regex;
+    use utoipa::OpenApi;
+
+    use actix_web::http::StatusCode;
+    use diesel::prelude::*;
+    use actix_web::test::{call_service, init_service, read_body, try_call_service, TestRequest};
+    use actix_web::{web, App};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::{configure, deprecated_operations, MOUNTS};
+    use crate::config::jwt::JwtKeys;
+    use crate::models::schema::users;
+    use crate::database::test_database;
+    use crate::models::api_token::{NewApiToken, TokenScope};
+    use crate::models::audit::AuditContext;
+    use crate::models::income::NewIncome;
+    use crate::models::user::{Role, User};
+    use crate::services::auth_service::AuthService;
+    use crate::services::{api_token_service, income_service};
+
+    /// A method and path, with every path parameter written as `{}`
+    type Operation = (String, String);
+
+    fn normalize(path: &str) -> String {
+        let path = Regex::new(r"\{[^}]*\}").unwrap().replace_all(path, "{}");
+        match path.strip_suffix('/') {
+            Some(trimmed) if !trimmed.is_empty() => trimmed.to_string(),
+            _ => path.into_owned(),
+        }
+    }
+
+    /// Every operation [`super::configure`] registers, read from the route
+    /// tables it registers them from
+    fn routed_operations() -> BTreeSet<Operation> {
+        let mut routed = BTreeSet::new();
+        for (prefix, mounts) in MOUNTS {
+            for table in mounts.iter().flat_map(|mount| mount.tables) {
+                for endpoint in table.endpoints {
+                    let path = format!("{}{}{}", prefix, table.prefix, endpoint.path);
+                    routed.insert((endpoint.method.as_str().to_lowercase(), normalize(&path)));
+                }
+            }
+        }
+        routed
+    }
+
+    fn documented_operations() -> BTreeSet<Operation> {
+        let value = serde_json::to_value(crate::ApiDoc::openapi()).unwrap();
+        let mut documented = BTreeSet::new();
+        for (path, item) in value["paths"].as_object().unwrap() {
+            for method in ["get", "post", "put", "patch", "delete"] {
+                if item.get(method).is_some() {
+                    documented.insert((method.to_string(), normalize(path)));
+                }
+            }
+        }
+        documented
+    }
+
+    #[test]
+    fn openapi_spec_matches_route_table() {
+        let routed = routed_operations();
+        let documented = documented_operations();
+        assert!(!routed.is_empty(), "no routes found");
+
+        let format = |operations: Vec<&Operation>| {
+            operations.iter().map(|(method, path)| format!("  {} {}", method.to_uppercase(), path)).collect::<Vec<_>>().join("\n")
+        };
+        let undocumented: Vec<_> = routed.difference(&documented).collect();
+        let unrouted: Vec<_> = documented.difference(&routed).collect();
+        assert!(
+            undocumented.is_empty() && unrouted.is_empty(),
+            "The OpenAPI spec and the route table disagree.\nRouted but not documented:\n{}\nDocumented but not routed:\n{}",
+            format(undocumented),
+            format(unrouted)
         );
-} 
\ No newline at end of file
+    }
+
+    /// Every endpoint of the route tables is served by a resource with the
+    /// same pattern, rather than by one registered earlier or by none
+    #[actix_web::test]
+    async fn route_tables_match_the_registered_resources() {
+        let Some(pool) = test_database::pool() else { return };
+        // Session tokens and the well-known endpoints need installed keys
+        JwtKeys::install_for_tests();
+        let token = {
+            let mut conn = pool.get().unwrap();
+            let user = test_database::user(&mut conn);
+            let admin = diesel::update(users::table.find(user.id))
+                .set(users::role.eq(Role::Admin))
+                .returning(User::as_returning())
+                .get_result(&mut conn)
+                .unwrap();
+            AuthService::generate_token(&admin).unwrap()
+        };
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(crate::config::errors::json_error_handler())
+                .configure(configure),
+        )
+        .await;
+
+        let parameters = Regex::new(r"\{([^}:]*)[^}]*\}").unwrap();
+        for (prefix, mounts) in MOUNTS {
+            for table in mounts.iter().flat_map(|mount| mount.tables) {
+                for endpoint in table.endpoints {
+                    let pattern = format!("{}{}{}", prefix, table.prefix, endpoint.path);
+                    let name = format!("{} {}", endpoint.method, pattern);
+                    // Parameters that are not IDs fail to parse, so no request
+                    // gets past extracting them
+                    let request = TestRequest::default()
+                        .method(endpoint.method.clone())
+                        .uri(&parameters.replace_all(&pattern, "0"))
+                        .insert_header(("Authorization", format!("Bearer {}", token)))
+                        .to_request();
+                    let response = match try_call_service(&app, request).await {
+                        Ok(response) => response,
+                        Err(error) => panic!("{} was refused: {}", name, error),
+                    };
+
+                    let expected: BTreeSet<&str> = parameters.captures_iter(&pattern).map(|captures| captures.get(1).unwrap().as_str()).collect();
+                    let matched: BTreeSet<&str> = response.request().match_info().iter().map(|(parameter, _)| parameter).collect();
+                    assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} is not routed", name);
+                    assert_eq!(response.request().match_info().unprocessed(), "", "{} is not routed", name);
+                    assert_eq!(matched, expected, "{} reaches another resource", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deprecated_operations_are_routed_and_replaced() {
+        let routed = routed_operations();
+        let deprecated = deprecated_operations();
+        assert!(!deprecated.is_empty(), "no deprecated routes found");
+
+        for operation in deprecated {
+            let method = operation.method.as_str().to_lowercase();
+            assert!(routed.contains(&(method.clone(), normalize(&operation.path))), "{} {} is not routed", method, operation.path);
+            assert!(operation.successor.starts_with("/api/v1/"), "{} moved outside /api/v1", operation.path);
+            assert!(routed.contains(&(method.clone(), normalize(&operation.successor))), "{} {} is not routed", method, operation.successor);
+        }
+    }
+
+    /// The deprecated paths answer exactly like the paths they moved to, and
+    /// only they carry the deprecation headers
+    #[actix_web::test]
+    async fn deprecated_paths_reach_the_same_handlers() {
+        let Some(pool) = test_database::pool() else { return };
+        let (user_id, token) = {
+            let mut conn = pool.get().unwrap();
+            let user = test_database::user(&mut conn);
+            let new_income = NewIncome {
+                user_id: user.id,
+                source: "Salary".to_string(),
+                amount: 5000.into(),
+                date: chrono::Utc::now().date_naive(),
+                description: None,
+                account: None,
+            };
+            income_service::create_income(&mut conn, new_income, &AuditContext::default()).unwrap();
+            let new_token = NewApiToken { name: "Test".to_string(), scopes: vec![TokenScope::Read, TokenScope::Write], expires_in_days: None };
+            let token = api_token_service::create_token(&mut conn, user.id, Role::User, new_token, &AuditContext::default()).unwrap().token;
+            (user.id, token)
+        };
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(crate::config::errors::json_error_handler())
+                .configure(configure),
+        )
+        .await;
+
+        // Other IDs name nothing, so that no request changes anything
+        let other_id = Uuid::new_v4().to_string();
+        let parameters = Regex::new(r"\{[^}]*\}").unwrap();
+        let uri = |path: &str| parameters.replace_all(&path.replace("{user_id}", &user_id.to_string()), other_id.as_str()).into_owned();
+
+        for operation in deprecated_operations() {
+            let mut responses = Vec::new();
+            for path in [&operation.path, &operation.successor] {
+                let request = TestRequest::default()
+                    .method(operation.method.clone())
+                    .uri(&uri(path))
+                    .insert_header(("Authorization", format!("Bearer {}", token)))
+                    .set_json(json!({}))
+                    .to_request();
+                let response = call_service(&app, request).await;
+                let deprecation = response.headers().get("Deprecation").cloned();
+                responses.push((response.status(), deprecation, read_body(response).await));
+            }
+            let (old, new) = (&responses[0], &responses[1]);
+
+            let name = format!("{} {}", operation.method, operation.path);
+            assert_eq!((old.0, &old.2), (new.0, &new.2), "{} answers unlike {}", name, operation.successor);
+            assert!(old.1.is_some(), "{} has no Deprecation header", name);
+            assert!(new.1.is_none(), "{} has a Deprecation header", operation.successor);
+        }
+
+        let listed = call_service(
+            &app,
+            TestRequest::get().uri(&format!("/api/incomes/{}", user_id)).insert_header(("Authorization", format!("Bearer {}", token))).to_request(),
+        )
+        .await;
+        let incomes: serde_json::Value = serde_json::from_slice(&read_body(listed).await).unwrap();
+        assert_eq!(incomes.as_array().map(Vec::len), Some(1));
+    }
+}
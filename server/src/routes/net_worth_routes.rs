@@ -0,0 +1,37 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::net_worth_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const NET_WORTH: RouteTable = RouteTable {
+    prefix: "/net-worth",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(net_worth_controller::get_net_worth)),
+        Endpoint::get("/snapshots", |route| route.to(net_worth_controller::get_snapshots)),
+        Endpoint::post("/snapshots/recompute", |route| route.to(net_worth_controller::recompute_snapshots)),
+        Endpoint::get("/items", |route| route.to(net_worth_controller::get_items)),
+        Endpoint::post("/items", |route| route.to(net_worth_controller::create_item)),
+        Endpoint::get("/items/{item_id}", |route| route.to(net_worth_controller::get_item)),
+        Endpoint::patch("/items/{item_id}", |route| route.to(net_worth_controller::update_item)),
+        Endpoint::put("/items/{item_id}", |route| route.to(net_worth_controller::update_item)),
+        Endpoint::delete("/items/{item_id}", |route| route.to(net_worth_controller::delete_item)),
+        Endpoint::get("/items/{item_id}/valuations", |route| route.to(net_worth_controller::get_valuations)),
+        Endpoint::post("/items/{item_id}/valuations", |route| route.to(net_worth_controller::add_valuation)),
+        Endpoint::delete("/items/{item_id}/valuations/{valuation_id}", |route| route.to(net_worth_controller::delete_valuation)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[NET_WORTH];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        NET_WORTH.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
@@ -0,0 +1,32 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::notification_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const NOTIFICATIONS: RouteTable = RouteTable {
+    prefix: "/notifications",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(notification_controller::get_notifications)),
+        Endpoint::post("/read-all", |route| route.to(notification_controller::mark_all_read)),
+        Endpoint::get("/preferences", |route| route.to(notification_controller::get_preferences)),
+        Endpoint::patch("/preferences", |route| route.to(notification_controller::update_preferences)),
+        Endpoint::put("/preferences", |route| route.to(notification_controller::update_preferences)),
+        Endpoint::post("/{notification_id}/read", |route| route.to(notification_controller::mark_read)),
+        Endpoint::delete("/{notification_id}", |route| route.to(notification_controller::delete_notification)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[NOTIFICATIONS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        NOTIFICATIONS.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
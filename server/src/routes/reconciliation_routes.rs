@@ -0,0 +1,35 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::reconciliation_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const RECONCILIATIONS: RouteTable = RouteTable {
+    prefix: "/reconciliations",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(reconciliation_controller::get_reconciliations)),
+        Endpoint::post("", |route| route.to(reconciliation_controller::create_reconciliation)),
+        Endpoint::get("/{reconciliation_id}", |route| route.to(reconciliation_controller::get_reconciliation)),
+        Endpoint::patch("/{reconciliation_id}", |route| route.to(reconciliation_controller::update_reconciliation)),
+        Endpoint::put("/{reconciliation_id}", |route| route.to(reconciliation_controller::update_reconciliation)),
+        Endpoint::delete("/{reconciliation_id}", |route| route.to(reconciliation_controller::delete_reconciliation)),
+        Endpoint::get("/{reconciliation_id}/transactions", |route| route.to(reconciliation_controller::get_transactions)),
+        Endpoint::post("/{reconciliation_id}/clear", |route| route.to(reconciliation_controller::clear)),
+        Endpoint::post("/{reconciliation_id}/unclear", |route| route.to(reconciliation_controller::unclear)),
+        Endpoint::post("/{reconciliation_id}/finish", |route| route.to(reconciliation_controller::finish)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[RECONCILIATIONS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        RECONCILIATIONS.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
@@ -0,0 +1,24 @@
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::report_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::routes::{Endpoint, RouteTable};
+
+const REPORTS: RouteTable = RouteTable {
+    prefix: "/reports",
+    endpoints: &[
+        Endpoint::get("/spending-breakdown", |route| route.to(report_controller::get_spending_breakdown)),
+        Endpoint::get("/forecast", |route| route.to(report_controller::get_forecast)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[REPORTS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        REPORTS.scope()
+            .wrap(auth)
+    );
+}
@@ -0,0 +1,33 @@
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use crate::controllers::webhook_controller;
+use crate::middleware::auth_middleware::jwt_validator;
+use crate::middleware::idempotency::idempotency;
+use crate::routes::{Endpoint, RouteTable};
+
+const WEBHOOKS: RouteTable = RouteTable {
+    prefix: "/webhooks",
+    endpoints: &[
+        Endpoint::get("", |route| route.to(webhook_controller::get_webhooks)),
+        Endpoint::post("", |route| route.to(webhook_controller::create_webhook)),
+        Endpoint::get("/{webhook_id}", |route| route.to(webhook_controller::get_webhook)),
+        Endpoint::patch("/{webhook_id}", |route| route.to(webhook_controller::update_webhook)),
+        Endpoint::put("/{webhook_id}", |route| route.to(webhook_controller::update_webhook)),
+        Endpoint::delete("/{webhook_id}", |route| route.to(webhook_controller::delete_webhook)),
+        Endpoint::get("/{webhook_id}/deliveries", |route| route.to(webhook_controller::get_deliveries)),
+        Endpoint::post("/{webhook_id}/deliveries/{delivery_id}/retry", |route| route.to(webhook_controller::retry_delivery)),
+    ],
+};
+
+pub const ROUTES: &[RouteTable] = &[WEBHOOKS];
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(jwt_validator);
+
+    cfg.service(
+        WEBHOOKS.scope()
+            .wrap(from_fn(idempotency))
+            .wrap(auth)
+    );
+}
@@ -0,0 +1,162 @@
+use diesel::dsl::{self, count_star};
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::models::admin::{AdminUserView, GlobalStatistics};
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::schema::{expenses, incomes, users};
+use crate::models::user::{Role, User};
+use crate::database::db_connection::DbConnection;
+use crate::services::audit_service;
+
+pub fn get_all_users(connection: &mut DbConnection) -> Result<Vec<User>, diesel::result::Error> {
+    users::table
+        .order(users::created_at.asc())
+        .select(User::as_select())
+        .load(connection)
+}
+
+pub fn get_user(connection: &mut DbConnection, user_id: Uuid) -> Result<User, diesel::result::Error> {
+    users::table
+        .find(user_id)
+        .select(User::as_select())
+        .first(connection)
+}
+
+/// The users with the given IDs, in no particular order; unknown IDs are
+/// left out
+pub fn get_users_by_ids(connection: &mut DbConnection, user_ids: &[Uuid]) -> Result<Vec<User>, diesel::result::Error> {
+    users::table
+        .filter(users::id.eq_any(user_ids))
+        .select(User::as_select())
+        .load(connection)
+}
+
+/// Disable or re-enable an account. Disabled users can neither log in nor
+/// use tokens issued before they were disabled.
+pub fn set_user_disabled(connection: &mut DbConnection, user_id: Uuid, disabled: bool, audit: &AuditContext) -> Result<User, diesel::result::Error> {
+    let now = Utc::now().naive_utc();
+    update_user(connection, user_id, audit, |connection| {
+        diesel::update(users::table.find(user_id))
+            .set((
+                users::disabled_at.eq(disabled.then_some(now)),
+                users::updated_at.eq(now),
+            ))
+            .returning(User::as_returning())
+            .get_result(connection)
+    })
+}
+
+pub fn set_user_role(connection: &mut DbConnection, user_id: Uuid, role: Role, audit: &AuditContext) -> Result<User, diesel::result::Error> {
+    update_user(connection, user_id, audit, |connection| {
+        diesel::update(users::table.find(user_id))
+            .set((
+                users::role.eq(role),
+                users::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .returning(User::as_returning())
+            .get_result(connection)
+    })
+}
+
+/// Run an update of a user account and record it in the audit log
+fn update_user<F>(connection: &mut DbConnection, user_id: Uuid, audit: &AuditContext, update: F) -> Result<User, diesel::result::Error>
+where
+    F: FnOnce(&mut DbConnection) -> Result<User, diesel::result::Error>,
+{
+    connection.transaction(|connection| {
+        let before = users::table
+            .find(user_id)
+            .select(User::as_select())
+            .first(connection)?;
+        let user = update(connection)?;
+
+        audit_service::record(
+            connection,
+            audit,
+            AuditChange::updated(entity::USER, user.id, user.id, &AdminUserView::from(before), &AdminUserView::from(user.clone())),
+        )?;
+        Ok(user)
+    })
+}
+
+pub fn get_global_statistics(connection: &mut DbConnection) -> Result<GlobalStatistics, diesel::result::Error> {
+    let total_users = users::table.select(count_star()).first::<i64>(connection)?;
+    let disabled_users = users::table
+        .filter(users::disabled_at.is_not_null())
+        .select(count_star())
+        .first::<i64>(connection)?;
+    let admin_users = users::table
+        .filter(users::role.eq(Role::Admin))
+        .select(count_star())
+        .first::<i64>(connection)?;
+
+    let (income_count, income_total) = incomes::table
+        .filter(incomes::deleted_at.is_null())
+        .select((count_star(), dsl::sum(incomes::amount)))
+        .first::<(i64, Option<Decimal>)>(connection)?;
+    let (expense_count, expense_total) = expenses::table
+        .filter(expenses::deleted_at.is_null())
+        .select((count_star(), dsl::sum(expenses::amount)))
+        .first::<(i64, Option<Decimal>)>(connection)?;
+
+    let income_total = income_total.unwrap_or(Decimal::ZERO);
+    let expense_total = expense_total.unwrap_or(Decimal::ZERO);
+
+    Ok(GlobalStatistics {
+        total_users,
+        active_users: total_users - disabled_users,
+        disabled_users,
+        admin_users,
+        income_count,
+        income_total,
+        expense_count,
+        expense_total,
+        net_balance: income_total - expense_total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+    use crate::models::audit::AuditLogQuery;
+
+    #[test]
+    fn disabling_a_user_is_counted_and_audited() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let admin = test_database::user(&mut conn);
+        let before = get_global_statistics(&mut conn).unwrap();
+
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext { actor_id: Some(admin.id), ..Default::default() };
+        let disabled = set_user_disabled(&mut conn, user.id, true, &audit).unwrap();
+        let after = get_global_statistics(&mut conn).unwrap();
+
+        assert!(disabled.disabled_at.is_some());
+        assert_eq!(after.total_users, before.total_users + 1);
+        assert_eq!(after.disabled_users, before.disabled_users + 1);
+        assert_eq!(after.active_users, before.active_users);
+
+        let query = AuditLogQuery { entity_id: Some(user.id), ..Default::default() };
+        let (entries, total) = audit_service::get_entries(&mut conn, None, &query).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].actor_id, Some(admin.id));
+        assert_eq!(entries[0].before_data.as_ref().unwrap()["disabled_at"], serde_json::Value::Null);
+        assert!(entries[0].after_data.as_ref().unwrap()["disabled_at"].is_string());
+    }
+
+    #[test]
+    fn promotes_users_to_admins() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let before = get_global_statistics(&mut conn).unwrap();
+
+        let promoted = set_user_role(&mut conn, user.id, Role::Admin, &AuditContext::default()).unwrap();
+
+        assert_eq!(promoted.role, Role::Admin);
+        assert_eq!(get_global_statistics(&mut conn).unwrap().admin_users, before.admin_users + 1);
+    }
+}
@@ -0,0 +1,262 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::api_token::{ApiToken, ApiTokenView, CreatedApiToken, NewApiToken, TokenScope};
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::schema::{api_tokens, users};
+use crate::models::user::{Role, User};
+use crate::services::audit_service;
+
+/// Personal API tokens start with this marker so the bearer middleware can
+/// tell them apart from JWTs without a database lookup
+pub const API_TOKEN_PREFIX: &str = "fst_";
+
+const TOKEN_RANDOM_LENGTH: usize = 40;
+const TOKEN_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const DISPLAY_PREFIX_LENGTH: usize = 9;
+
+/// How stale `last_used_at` may get before a request refreshes it, so busy
+/// scripts do not write to the row on every call
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(API_TOKEN_PREFIX)
+}
+
+pub fn get_tokens_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<ApiToken>, diesel::result::Error> {
+    api_tokens::table
+        .filter(api_tokens::user_id.eq(user_id))
+        .order(api_tokens::created_at.desc())
+        .select(ApiToken::as_select())
+        .load(connection)
+}
+
+pub fn create_token(connection: &mut DbConnection, user_id: Uuid, role: Role, new_token: NewApiToken, audit: &AuditContext) -> Result<CreatedApiToken, AppError> {
+    let name = new_token.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Validation("Token name must not be empty".to_string()));
+    }
+    if new_token.scopes.is_empty() {
+        return Err(AppError::Validation("At least one scope is required".to_string()));
+    }
+    if new_token.scopes.contains(&TokenScope::Admin) && role != Role::Admin {
+        return Err(AppError::Forbidden("Only admins can create tokens with the admin scope".to_string()));
+    }
+    if new_token.expires_in_days.is_some_and(|days| days <= 0) {
+        return Err(AppError::Validation("expires_in_days must be positive".to_string()));
+    }
+
+    let mut scopes: Vec<TokenScope> = Vec::new();
+    for scope in new_token.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let now = Utc::now().naive_utc();
+    let plain_token = generate_token();
+    let token = ApiToken {
+        id: Uuid::new_v4(),
+        user_id,
+        name,
+        token_prefix: plain_token[..DISPLAY_PREFIX_LENGTH].to_string(),
+        token_hash: hash_token(&plain_token),
+        scopes,
+        expires_at: new_token.expires_in_days.map(|days| now + Duration::days(days)),
+        last_used_at: None,
+        revoked_at: None,
+        created_at: now,
+    };
+
+    let details = connection.transaction(|connection| {
+        let token = diesel::insert_into(api_tokens::table)
+            .values(&token)
+            .returning(ApiToken::as_returning())
+            .get_result(connection)?;
+
+        let details = ApiTokenView::from(token);
+        audit_service::record(connection, audit, AuditChange::created(entity::API_TOKEN, details.id, user_id, &details))?;
+        Ok::<_, diesel::result::Error>(details)
+    })?;
+
+    Ok(CreatedApiToken {
+        token: plain_token,
+        details,
+    })
+}
+
+/// Revoke one of the user's active tokens. Revoked tokens stay listed so
+/// their history remains visible.
+pub fn revoke_token(connection: &mut DbConnection, user_id: Uuid, token_id: Uuid, audit: &AuditContext) -> Result<ApiToken, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let token = diesel::update(
+            api_tokens::table
+                .filter(api_tokens::id.eq(token_id))
+                .filter(api_tokens::user_id.eq(user_id))
+                .filter(api_tokens::revoked_at.is_null()),
+        )
+        .set(api_tokens::revoked_at.eq(Utc::now().naive_utc()))
+        .returning(ApiToken::as_returning())
+        .get_result(connection)?;
+
+        let after = ApiTokenView::from(token.clone());
+        let before = ApiTokenView { revoked_at: None, ..after.clone() };
+        audit_service::record(connection, audit, AuditChange::updated(entity::API_TOKEN, token.id, user_id, &before, &after))?;
+        Ok(token)
+    })
+}
+
+/// Resolve a plain token to the token row and its (active) owner
+///
+/// Returns `None` for unknown, revoked or expired tokens and for disabled
+/// accounts. Records when the token was last used.
+pub fn authenticate(connection: &mut DbConnection, plain_token: &str) -> Result<Option<(ApiToken, User)>, diesel::result::Error> {
+    let now = Utc::now().naive_utc();
+
+    let found = api_tokens::table
+        .inner_join(users::table)
+        .filter(api_tokens::token_hash.eq(hash_token(plain_token)))
+        .filter(api_tokens::revoked_at.is_null())
+        .filter(api_tokens::expires_at.is_null().or(api_tokens::expires_at.gt(now)))
+        .filter(users::disabled_at.is_null())
+        .select((ApiToken::as_select(), User::as_select()))
+        .first::<(ApiToken, User)>(connection)
+        .optional()?;
+
+    if let Some((token, _)) = &found {
+        let stale_before = now - Duration::seconds(LAST_USED_RESOLUTION_SECONDS);
+        diesel::update(
+            api_tokens::table
+                .filter(api_tokens::id.eq(token.id))
+                .filter(api_tokens::last_used_at.is_null().or(api_tokens::last_used_at.lt(stale_before))),
+        )
+        .set(api_tokens::last_used_at.eq(now))
+        .execute(connection)?;
+    }
+
+    Ok(found)
+}
+
+fn generate_token() -> String {
+    let mut rng = rand::rng();
+    let random: String = (0..TOKEN_RANDOM_LENGTH)
+        .map(|_| TOKEN_ALPHABET[rng.random_range(0..TOKEN_ALPHABET.len())] as char)
+        .collect();
+    format!("{}{}", API_TOKEN_PREFIX, random)
+}
+
+/// Tokens are long random strings, so an unsalted SHA-256 is sufficient and
+/// allows looking them up by hash
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+    use crate::services::admin_service;
+
+    fn new_token(scopes: Vec<TokenScope>, expires_in_days: Option<i64>) -> NewApiToken {
+        NewApiToken { name: " Import script ".to_string(), scopes, expires_in_days }
+    }
+
+    #[test]
+    fn stores_only_the_hash_of_the_token() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+
+        let created = create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Read, TokenScope::Read], None), &AuditContext::default()).unwrap();
+        let stored = get_tokens_by_user_id(&mut conn, user.id).unwrap();
+
+        assert!(is_api_token(&created.token));
+        assert_eq!(created.details.name, "Import script");
+        assert_eq!(created.details.scopes, vec![TokenScope::Read]);
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].token_hash, hash_token(&created.token));
+        assert_ne!(stored[0].token_hash, created.token);
+        assert!(created.token.starts_with(&stored[0].token_prefix));
+    }
+
+    #[test]
+    fn validates_new_tokens() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+
+        let unnamed = NewApiToken { name: " ".to_string(), ..new_token(vec![TokenScope::Read], None) };
+        assert!(matches!(create_token(&mut conn, user.id, user.role, unnamed, &audit), Err(AppError::Validation(_))));
+        assert!(matches!(create_token(&mut conn, user.id, user.role, new_token(Vec::new(), None), &audit), Err(AppError::Validation(_))));
+        assert!(matches!(create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Read], Some(0)), &audit), Err(AppError::Validation(_))));
+        assert!(matches!(
+            create_token(&mut conn, user.id, Role::User, new_token(vec![TokenScope::Admin], None), &audit),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(create_token(&mut conn, user.id, Role::Admin, new_token(vec![TokenScope::Admin], None), &audit).is_ok());
+    }
+
+    #[test]
+    fn authenticates_active_tokens_and_records_their_use() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let created = create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Write], Some(30)), &AuditContext::default()).unwrap();
+
+        let (token, owner) = authenticate(&mut conn, &created.token).unwrap().unwrap();
+
+        assert_eq!(token.id, created.details.id);
+        assert_eq!(owner.id, user.id);
+        assert!(get_tokens_by_user_id(&mut conn, user.id).unwrap()[0].last_used_at.is_some());
+        assert!(authenticate(&mut conn, &format!("{}unknown", API_TOKEN_PREFIX)).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_revoked_and_expired_tokens() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+
+        let revoked = create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Read], None), &audit).unwrap();
+        revoke_token(&mut conn, user.id, revoked.details.id, &audit).unwrap();
+        assert!(authenticate(&mut conn, &revoked.token).unwrap().is_none());
+        assert!(matches!(
+            revoke_token(&mut conn, user.id, revoked.details.id, &audit),
+            Err(diesel::result::Error::NotFound)
+        ));
+
+        let expired = create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Read], Some(1)), &audit).unwrap();
+        diesel::update(api_tokens::table.find(expired.details.id))
+            .set(api_tokens::expires_at.eq(Utc::now().naive_utc() - Duration::seconds(1)))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(authenticate(&mut conn, &expired.token).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_tokens_of_disabled_users() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+        let created = create_token(&mut conn, user.id, user.role, new_token(vec![TokenScope::Read], None), &audit).unwrap();
+
+        admin_service::set_user_disabled(&mut conn, user.id, true, &audit).unwrap();
+
+        assert!(authenticate(&mut conn, &created.token).unwrap().is_none());
+    }
+
+    #[test]
+    fn users_cannot_revoke_others_tokens() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let owner = test_database::user(&mut conn);
+        let other = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+        let created = create_token(&mut conn, owner.id, owner.role, new_token(vec![TokenScope::Read], None), &audit).unwrap();
+
+        assert!(revoke_token(&mut conn, other.id, created.details.id, &audit).is_err());
+        assert!(authenticate(&mut conn, &created.token).unwrap().is_some());
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{Days, Utc};
+use diesel::dsl::count_star;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{AuditChange, AuditContext, AuditLogEntry, AuditLogQuery};
+use crate::models::schema::audit_log;
+use crate::services::webhook_service;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Append a change to the audit log and queue its webhook deliveries
+///
+/// Call this on the same connection (and inside the same transaction) as the
+/// change itself so that a change is never committed without its entry.
+pub fn record(connection: &mut PgConnection, context: &AuditContext, change: AuditChange) -> Result<(), diesel::result::Error> {
+    webhook_service::enqueue(connection, &change)?;
+
+    let entry = AuditLogEntry {
+        id: Uuid::new_v4(),
+        actor_id: context.actor_id,
+        owner_id: change.owner_id,
+        action: change.action.as_str().to_string(),
+        entity_type: change.entity_type.to_string(),
+        entity_id: change.entity_id,
+        before_data: change.before,
+        after_data: change.after,
+        ip_address: context.ip_address.clone(),
+        user_agent: context.user_agent.clone(),
+        created_at: Utc::now().naive_utc(),
+    };
+
+    diesel::insert_into(audit_log::table)
+        .values(&entry)
+        .execute(connection)?;
+
+    Ok(())
+}
+
+/// Page of audit entries, newest first, together with the total number of
+/// matching entries. `owner_id` restricts the result to one user's data
+/// (`None` is for admins only).
+pub fn get_entries(connection: &mut DbConnection, owner_id: Option<Uuid>, query: &AuditLogQuery) -> Result<(Vec<AuditLogEntry>, i64), diesel::result::Error> {
+    let total = filtered(owner_id, query)
+        .select(count_star())
+        .first::<i64>(connection)?;
+
+    let entries = filtered(owner_id, query)
+        .order((audit_log::created_at.desc(), audit_log::id.desc()))
+        .limit(query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE))
+        .offset(query.offset.unwrap_or(0).max(0))
+        .select(AuditLogEntry::as_select())
+        .load(connection)?;
+
+    Ok((entries, total))
+}
+
+fn filtered<'a>(owner_id: Option<Uuid>, query: &AuditLogQuery) -> audit_log::BoxedQuery<'a, Pg> {
+    let mut statement = audit_log::table.into_boxed();
+
+    if let Some(owner_id) = owner_id {
+        statement = statement.filter(audit_log::owner_id.eq(owner_id));
+    }
+    if let Some(entity_type) = &query.entity_type {
+        statement = statement.filter(audit_log::entity_type.eq(entity_type.clone()));
+    }
+    if let Some(entity_id) = query.entity_id {
+        statement = statement.filter(audit_log::entity_id.eq(entity_id));
+    }
+    if let Some(action) = &query.action {
+        statement = statement.filter(audit_log::action.eq(action.clone()));
+    }
+    if let Some(actor_id) = query.actor_id {
+        statement = statement.filter(audit_log::actor_id.eq(actor_id));
+    }
+    if let Some(from) = query.from {
+        statement = statement.filter(audit_log::created_at.ge(from.and_hms_opt(0, 0, 0).expect("valid time")));
+    }
+    if let Some(to) = query.to.and_then(|to| to.checked_add_days(Days::new(1))) {
+        statement = statement.filter(audit_log::created_at.lt(to.and_hms_opt(0, 0, 0).expect("valid time")));
+    }
+
+    statement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use crate::database::test_database;
+    use crate::models::audit::entity;
+    use crate::models::income::{Income, NewIncome, UpdateIncome};
+    use crate::services::income_service;
+
+    fn stored_income(connection: &mut DbConnection, user_id: Uuid, audit: &AuditContext) -> Income {
+        let new_income = NewIncome {
+            user_id,
+            source: "Salary".to_string(),
+            amount: Decimal::from(5000),
+            date: NaiveDate::from_ymd_opt(2024, 3, 20).unwrap(),
+            description: None,
+            account: None,
+        };
+        income_service::create_income(connection, new_income, audit).unwrap()
+    }
+
+    fn entries_of(connection: &mut DbConnection, entity_id: Uuid) -> Vec<AuditLogEntry> {
+        let query = AuditLogQuery { entity_id: Some(entity_id), ..Default::default() };
+        let (mut entries, _) = get_entries(connection, None, &query).unwrap();
+        entries.reverse();
+        entries
+    }
+
+    #[test]
+    fn records_every_change_with_its_snapshots_and_actor() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext {
+            actor_id: Some(user.id),
+            ip_address: Some("203.0.113.7".to_string()),
+            user_agent: Some("curl/8.0".to_string()),
+        };
+
+        let income = stored_income(&mut conn, user.id, &audit);
+        let update = UpdateIncome { source: None, amount: Some(Decimal::from(6000)), date: None, description: None, account: None };
+        income_service::update_income(&mut conn, income.id, update, None, &audit).unwrap();
+        income_service::delete_income(&mut conn, income.id, None, &audit).unwrap();
+
+        let entries = entries_of(&mut conn, income.id);
+        let actions: Vec<&str> = entries.iter().map(|entry| entry.action.as_str()).collect();
+        assert_eq!(actions, ["create", "update", "delete"]);
+
+        for entry in &entries {
+            assert_eq!(entry.entity_type, entity::INCOME);
+            assert_eq!(entry.owner_id, Some(user.id));
+            assert_eq!(entry.actor_id, Some(user.id));
+            assert_eq!(entry.ip_address.as_deref(), Some("203.0.113.7"));
+            assert_eq!(entry.user_agent.as_deref(), Some("curl/8.0"));
+        }
+
+        assert!(entries[0].before_data.is_none());
+        assert_eq!(entries[0].after_data.as_ref().unwrap()["source"], "Salary");
+        assert_eq!(entries[1].before_data.as_ref().unwrap()["amount"], 5000.0);
+        assert_eq!(entries[1].after_data.as_ref().unwrap()["amount"], 6000.0);
+        assert_eq!(entries[2].before_data.as_ref().unwrap()["amount"], 6000.0);
+        assert!(entries[2].after_data.is_none());
+    }
+
+    #[test]
+    fn filters_entries_by_owner_and_entity() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let owner = test_database::user(&mut conn);
+        let other = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+        let own = stored_income(&mut conn, owner.id, &audit);
+        let others = stored_income(&mut conn, other.id, &audit);
+
+        let (entries, total) = get_entries(&mut conn, Some(owner.id), &AuditLogQuery::default()).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].entity_id, own.id);
+
+        let query = AuditLogQuery { entity_id: Some(others.id), ..Default::default() };
+        assert_eq!(get_entries(&mut conn, Some(owner.id), &query).unwrap().1, 0);
+        assert_eq!(get_entries(&mut conn, None, &query).unwrap().1, 1);
+
+        let query = AuditLogQuery { entity_type: Some(entity::EXPENSE.to_string()), ..Default::default() };
+        assert_eq!(get_entries(&mut conn, Some(owner.id), &query).unwrap().1, 0);
+
+        let query = AuditLogQuery { action: Some("delete".to_string()), ..Default::default() };
+        assert_eq!(get_entries(&mut conn, Some(owner.id), &query).unwrap().1, 0);
+    }
+
+    #[test]
+    fn pages_entries_newest_first() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+        let first = stored_income(&mut conn, user.id, &audit);
+        let second = stored_income(&mut conn, user.id, &audit);
+
+        let query = AuditLogQuery { limit: Some(1), ..Default::default() };
+        let (page, total) = get_entries(&mut conn, Some(user.id), &query).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].entity_id, second.id);
+
+        let query = AuditLogQuery { limit: Some(1), offset: Some(1), ..Default::default() };
+        assert_eq!(get_entries(&mut conn, Some(user.id), &query).unwrap().0[0].entity_id, first.id);
+    }
+}
@@ -2,18 +2,25 @@ use actix_web::{web, HttpRequest};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use std::env;
 use uuid::Uuid;
 
-use crate::models::auth::{AuthError, Claims, LoginRequest, RegisterRequest, TokenResponse, UserInfo};
+use crate::config::jwt::JwtKeys;
+use crate::models::admin::AdminUserView;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::auth::{AuthError, Claims, LoginRequest, LoginResponse, RegisterRequest, TokenResponse, UserInfo};
 use crate::models::schema::users;
+use crate::models::two_factor::{ChallengeClaims, TwoFactorChallenge, TwoFactorLoginRequest};
 use crate::models::user::{NewUser, User};
+use crate::services::audit_service;
+use crate::services::two_factor_service::TwoFactorService;
 
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 
 pub struct AuthService;
 
+/// Lifetime of the token handed out between the password and second factor steps
+const CHALLENGE_TOKEN_SECONDS: i64 = 5 * 60;
+
 impl AuthService {
     /// Hash a password using bcrypt
     pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
@@ -27,38 +34,51 @@ impl AuthService {
 
     /// Generate JWT token for user
     pub fn generate_token(user: &User) -> Result<String, jsonwebtoken::errors::Error> {
-        let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
         let expiration = chrono::Utc::now()
             .checked_add_signed(chrono::Duration::hours(24))
             .expect("valid timestamp")
             .timestamp() as usize;
 
-        let claims = Claims::new(user.id, user.email.clone(), expiration);
+        let claims = Claims::new(user.id, user.email.clone(), user.role, expiration);
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(secret.as_ref()),
-        )
+        JwtKeys::global().encode(&claims)
     }
 
     /// Validate JWT token and extract claims
     pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-        let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-        let validation = Validation::new(Algorithm::HS256);
+        JwtKeys::global().decode::<Claims>(token)
+    }
 
-        decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &validation,
-        )
-        .map(|data| data.claims)
+    /// Generate the short-lived token that stands in for a login until the
+    /// second factor has been verified
+    pub fn generate_challenge_token(user: &User, challenge_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
+        let expiration = chrono::Utc::now()
+            .checked_add_signed(chrono::Duration::seconds(CHALLENGE_TOKEN_SECONDS))
+            .expect("valid timestamp")
+            .timestamp() as usize;
+
+        let claims = ChallengeClaims::new(user.id, challenge_id, expiration);
+
+        JwtKeys::global().encode(&claims)
+    }
+
+    /// Validate a two-factor challenge token and extract its claims
+    pub fn validate_challenge_token(token: &str) -> Result<ChallengeClaims, AuthError> {
+        JwtKeys::global()
+            .decode::<ChallengeClaims>(token)
+            .ok()
+            .filter(|claims| claims.purpose == ChallengeClaims::PURPOSE)
+            .ok_or_else(|| AuthError {
+                message: "Invalid or expired challenge token".to_string(),
+                code: "INVALID_CHALLENGE".to_string(),
+            })
     }
 
     /// Register a new user
     pub async fn register_user(
         pool: web::Data<DbPool>,
         register_data: RegisterRequest,
+        audit: AuditContext,
     ) -> Result<TokenResponse, AuthError> {
         let mut conn = pool.get().map_err(|_| AuthError {
             message: "Database connection failed".to_string(),
@@ -105,40 +125,37 @@ impl AuthService {
             hashed_password,
         );
 
-        let user = diesel::insert_into(users::table)
-            .values(&new_user)
-            .returning(User::as_returning())
-            .get_result(&mut conn)
-            .map_err(|_| AuthError {
-                message: "Failed to create user".to_string(),
-                code: "USER_CREATION_ERROR".to_string(),
-            })?;
-
-        // Generate token
-        let token = Self::generate_token(&user)
-            .map_err(|_| AuthError {
-                message: "Token generation failed".to_string(),
-                code: "TOKEN_ERROR".to_string(),
-            })?;
+        let user = conn.transaction(|conn| {
+            let user = diesel::insert_into(users::table)
+                .values(&new_user)
+                .returning(User::as_returning())
+                .get_result(conn)
+                .map_err(|_| AuthError {
+                    message: "Failed to create user".to_string(),
+                    code: "USER_CREATION_ERROR".to_string(),
+                })?;
+
+            let snapshot = AdminUserView::from(user.clone());
+            audit_service::record(
+                conn,
+                &audit.with_actor(user.id),
+                AuditChange::created(entity::USER, user.id, user.id, &snapshot),
+            )?;
+            Ok::<_, AuthError>(user)
+        })?;
 
-        Ok(TokenResponse {
-            token,
-            token_type: "Bearer".to_string(),
-            expires_in: 24 * 3600, // 24 hours
-            user: UserInfo {
-                id: user.id,
-                first_name: user.first_name,
-                last_name: user.last_name,
-                email: user.email,
-            },
-        })
+        Self::build_token_response(user)
     }
 
     /// Login user
+    ///
+    /// Users with two-factor authentication enabled get a challenge token
+    /// instead of an access token; exchange it through
+    /// [`Self::complete_two_factor_login`].
     pub async fn login_user(
         pool: web::Data<DbPool>,
         login_data: LoginRequest,
-    ) -> Result<TokenResponse, AuthError> {
+    ) -> Result<LoginResponse, AuthError> {
         let mut conn = pool.get().map_err(|_| AuthError {
             message: "Database connection failed".to_string(),
             code: "DB_CONNECTION_ERROR".to_string(),
@@ -173,7 +190,75 @@ impl AuthService {
             });
         }
 
-        // Generate token
+        Self::ensure_active(&user)?;
+
+        if TwoFactorService::is_enabled(&mut conn, user.id)? {
+            let challenge_id = TwoFactorService::start_challenge(&mut conn, user.id)?;
+            let challenge_token = Self::generate_challenge_token(&user, challenge_id)
+                .map_err(|_| AuthError {
+                    message: "Token generation failed".to_string(),
+                    code: "TOKEN_ERROR".to_string(),
+                })?;
+
+            return Ok(LoginResponse::TwoFactorRequired(TwoFactorChallenge {
+                two_factor_required: true,
+                challenge_token,
+                expires_in: CHALLENGE_TOKEN_SECONDS,
+            }));
+        }
+
+        Self::build_token_response(user).map(LoginResponse::Token)
+    }
+
+    /// Second step of a login for users with two-factor authentication
+    pub async fn complete_two_factor_login(
+        pool: web::Data<DbPool>,
+        login_data: TwoFactorLoginRequest,
+    ) -> Result<TokenResponse, AuthError> {
+        let claims = Self::validate_challenge_token(&login_data.challenge_token)?;
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| AuthError {
+                message: "Invalid user ID in token".to_string(),
+                code: "INVALID_USER_ID".to_string(),
+            })?;
+        let challenge_id = Uuid::parse_str(&claims.jti)
+            .map_err(|_| AuthError {
+                message: "Invalid or expired challenge token".to_string(),
+                code: "INVALID_CHALLENGE".to_string(),
+            })?;
+
+        let mut conn = pool.get().map_err(|_| AuthError {
+            message: "Database connection failed".to_string(),
+            code: "DB_CONNECTION_ERROR".to_string(),
+        })?;
+
+        let user = users::table
+            .find(user_id)
+            .first::<User>(&mut conn)
+            .optional()
+            .map_err(|_| AuthError {
+                message: "Database query failed".to_string(),
+                code: "DB_QUERY_ERROR".to_string(),
+            })?
+            .ok_or_else(|| AuthError {
+                message: "Invalid or expired challenge token".to_string(),
+                code: "INVALID_CHALLENGE".to_string(),
+            })?;
+
+        Self::ensure_active(&user)?;
+
+        if !TwoFactorService::answer_challenge(&mut conn, &user, challenge_id, &login_data.code)? {
+            return Err(AuthError {
+                message: "Invalid two-factor code".to_string(),
+                code: "INVALID_TWO_FACTOR_CODE".to_string(),
+            });
+        }
+
+        Self::build_token_response(user)
+    }
+
+    /// Issue an access token for an authenticated user
+    fn build_token_response(user: User) -> Result<TokenResponse, AuthError> {
         let token = Self::generate_token(&user)
             .map_err(|_| AuthError {
                 message: "Token generation failed".to_string(),
@@ -184,12 +269,7 @@ impl AuthService {
             token,
             token_type: "Bearer".to_string(),
             expires_in: 24 * 3600, // 24 hours
-            user: UserInfo {
-                id: user.id,
-                first_name: user.first_name,
-                last_name: user.last_name,
-                email: user.email,
-            },
+            user: UserInfo::from(user),
         })
     }
 
@@ -225,10 +305,24 @@ impl AuthService {
                 code: "DB_QUERY_ERROR".to_string(),
             })?;
 
-        user.ok_or_else(|| AuthError {
+        let user = user.ok_or_else(|| AuthError {
             message: "User not found".to_string(),
             code: "USER_NOT_FOUND".to_string(),
-        })
+        })?;
+
+        Self::ensure_active(&user)?;
+        Ok(user)
+    }
+
+    /// Reject accounts an administrator has disabled
+    fn ensure_active(user: &User) -> Result<(), AuthError> {
+        if user.disabled_at.is_some() {
+            return Err(AuthError {
+                message: "Account is disabled".to_string(),
+                code: "ACCOUNT_DISABLED".to_string(),
+            });
+        }
+        Ok(())
     }
 
     /// Extract token from Authorization header
@@ -257,4 +351,32 @@ impl AuthService {
 
         Ok(auth_str[7..].to_string())
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+
+    fn keys() -> JwtKeys {
+        JwtKeys::from_secrets("test-secret-that-is-long-enough-for-hs256", Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn challenge_token_is_not_an_access_token() {
+        let keys = keys();
+        let challenge = keys.encode(&ChallengeClaims::new(Uuid::new_v4(), Uuid::new_v4(), u32::MAX as usize)).unwrap();
+
+        assert!(keys.decode::<ChallengeClaims>(&challenge).is_ok());
+        assert!(keys.decode::<Claims>(&challenge).is_err());
+    }
+
+    #[test]
+    fn access_token_is_not_a_challenge_token() {
+        let keys = keys();
+        let claims = Claims::new(Uuid::new_v4(), "john@example.com".to_string(), Role::User, u32::MAX as usize);
+        let access = keys.encode(&claims).unwrap();
+
+        assert!(keys.decode::<Claims>(&access).is_ok());
+        assert!(keys.decode::<ChallengeClaims>(&access).is_err());
+    }
+}
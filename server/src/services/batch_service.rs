@@ -0,0 +1,89 @@
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use diesel::prelude::*;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::batch::{BatchItemResult, BatchMode, BatchResponse};
+
+/// Upper bound on operations per batch, to keep transactions short
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+/// Why the batch transaction was not committed
+enum Abort {
+    ItemsFailed,
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for Abort {
+    fn from(error: diesel::result::Error) -> Self {
+        Abort::Database(error)
+    }
+}
+
+/// Apply batch operations in one transaction
+///
+/// Every item runs in its own savepoint, so a failing item never leaves
+/// partial changes behind and the following items still run and report
+/// their own outcome. In atomic mode the whole transaction is rolled back as
+/// soon as one item failed; in partial mode the successful items are
+/// committed.
+pub fn run<O, T, F>(connection: &mut DbConnection, mode: BatchMode, operations: Vec<O>, mut apply: F) -> Result<BatchResponse<T>, AppError>
+where
+    F: FnMut(&mut DbConnection, O) -> Result<(StatusCode, T), AppError>,
+{
+    if operations.is_empty() {
+        return Err(AppError::Validation("A batch needs at least one operation".to_string()));
+    }
+    if operations.len() > MAX_BATCH_SIZE {
+        return Err(AppError::Validation(format!("A batch can hold at most {} operations", MAX_BATCH_SIZE)));
+    }
+
+    let mut results = Vec::with_capacity(operations.len());
+    let outcome = connection.transaction::<_, Abort, _>(|connection| {
+        for (index, operation) in operations.into_iter().enumerate() {
+            let result = match connection.transaction(|connection| apply(connection, operation)) {
+                Ok((status, data)) => BatchItemResult {
+                    index,
+                    status: status.as_u16(),
+                    data: Some(data),
+                    error: None,
+                },
+                Err(error) => BatchItemResult {
+                    index,
+                    status: error.status_code().as_u16(),
+                    data: None,
+                    error: Some(error.to_string()),
+                },
+            };
+            results.push(result);
+        }
+
+        if mode == BatchMode::Atomic && results.iter().any(|result| result.error.is_some()) {
+            return Err(Abort::ItemsFailed);
+        }
+        Ok(())
+    });
+
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    let committed = match outcome {
+        Ok(()) => true,
+        Err(Abort::ItemsFailed) => {
+            for result in results.iter_mut().filter(|result| result.error.is_none()) {
+                result.status = StatusCode::FAILED_DEPENDENCY.as_u16();
+                result.data = None;
+                result.error = Some("Rolled back because another operation in the batch failed".to_string());
+            }
+            false
+        }
+        Err(Abort::Database(error)) => return Err(error.into()),
+    };
+
+    Ok(BatchResponse {
+        mode,
+        committed,
+        succeeded: if committed { results.len() - failed } else { 0 },
+        failed,
+        results,
+    })
+}
@@ -0,0 +1,581 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use regex::{Regex, RegexBuilder};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::categorization_rule::{AmountSign, CategorizationRule, NewCategorizationRule, RuleApplication, RuleMatch, RuleMatchField, RuleMatchType, UpdateCategorizationRule};
+use crate::models::expense::{Expense, NewExpense};
+use crate::models::schema::{categorization_rules, expenses};
+use crate::services::audit_service;
+
+/// Upper bound on the compiled size of a rule's regular expression
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// The user's rules in the order they are applied
+pub fn get_rules_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<CategorizationRule>, diesel::result::Error> {
+    categorization_rules::table
+        .filter(categorization_rules::user_id.eq(user_id))
+        .order((categorization_rules::priority.asc(), categorization_rules::created_at.asc(), categorization_rules::id.asc()))
+        .select(CategorizationRule::as_select())
+        .load(connection)
+}
+
+/// One of the user's rules; rules of other users are reported as not found
+pub fn get_rule(connection: &mut DbConnection, user_id: Uuid, rule_id: Uuid) -> Result<CategorizationRule, diesel::result::Error> {
+    categorization_rules::table
+        .find(rule_id)
+        .filter(categorization_rules::user_id.eq(user_id))
+        .select(CategorizationRule::as_select())
+        .first(connection)
+}
+
+pub fn create_rule(connection: &mut DbConnection, user_id: Uuid, new_rule: NewCategorizationRule, audit: &AuditContext) -> Result<CategorizationRule, AppError> {
+    let mut rule = new_rule.into_rule(user_id);
+    rule.add_tags = normalize_tags(rule.add_tags);
+    CompiledRule::compile(&rule)?;
+
+    connection.transaction(|connection| {
+        let rule = diesel::insert_into(categorization_rules::table)
+            .values(rule)
+            .returning(CategorizationRule::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::CATEGORIZATION_RULE, rule.id, user_id, &rule))?;
+        Ok(rule)
+    })
+}
+
+pub fn update_rule(connection: &mut DbConnection, user_id: Uuid, rule_id: Uuid, mut update_rule: UpdateCategorizationRule, audit: &AuditContext) -> Result<CategorizationRule, AppError> {
+    update_rule.add_tags = update_rule.add_tags.map(normalize_tags);
+
+    connection.transaction(|connection| {
+        let before = categorization_rules::table
+            .find(rule_id)
+            .filter(categorization_rules::user_id.eq(user_id))
+            .select(CategorizationRule::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let mut updated = before.clone();
+        if let Some(name) = &update_rule.name {
+            updated.name = name.clone();
+        }
+        updated.match_field = update_rule.match_field.unwrap_or(updated.match_field);
+        updated.match_type = update_rule.match_type.unwrap_or(updated.match_type);
+        if let Some(pattern) = &update_rule.pattern {
+            updated.pattern = pattern.clone();
+        }
+        updated.min_amount = update_rule.min_amount.unwrap_or(updated.min_amount);
+        updated.max_amount = update_rule.max_amount.unwrap_or(updated.max_amount);
+        updated.sign = update_rule.sign.unwrap_or(updated.sign);
+        if let Some(set_category) = &update_rule.set_category {
+            updated.set_category = set_category.clone();
+        }
+        if let Some(add_tags) = &update_rule.add_tags {
+            updated.add_tags = add_tags.clone();
+        }
+        if let Some(set_display_name) = &update_rule.set_display_name {
+            updated.set_display_name = set_display_name.clone();
+        }
+        CompiledRule::compile(&updated)?;
+
+        let now = Utc::now().naive_utc();
+        let rule = diesel::update(categorization_rules::table.find(rule_id))
+            .set((update_rule, categorization_rules::updated_at.eq(now)))
+            .returning(CategorizationRule::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::CATEGORIZATION_RULE, rule.id, user_id, &before, &rule))?;
+        Ok(rule)
+    })
+}
+
+pub fn delete_rule(connection: &mut DbConnection, user_id: Uuid, rule_id: Uuid, audit: &AuditContext) -> Result<CategorizationRule, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let rule = diesel::delete(categorization_rules::table.find(rule_id).filter(categorization_rules::user_id.eq(user_id)))
+            .returning(CategorizationRule::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::CATEGORIZATION_RULE, rule.id, user_id, &rule))?;
+        Ok(rule)
+    })
+}
+
+/// Fill in the category, display name and tags of a new expense from the
+/// owner's enabled rules. Values sent by the client are kept.
+pub fn categorize_new_expense(connection: &mut DbConnection, mut new_expense: NewExpense) -> Result<NewExpense, AppError> {
+    let rules = load_enabled_rules(connection, new_expense.user_id)?;
+    if rules.is_empty() {
+        return Ok(new_expense);
+    }
+
+    let outcome = categorize(&rules, &new_expense.item_name, new_expense.description.as_deref(), new_expense.amount);
+    if new_expense.category.is_none() {
+        new_expense.category = outcome.category;
+    }
+    if new_expense.display_name.is_none() {
+        new_expense.display_name = outcome.display_name;
+    }
+    new_expense.tags = merge_tags(&new_expense.tags, &outcome.tags);
+    Ok(new_expense)
+}
+
+/// Existing expenses the rule would change, without changing them. The rule
+/// does not have to be saved or enabled.
+pub fn dry_run(connection: &mut DbConnection, user_id: Uuid, new_rule: NewCategorizationRule, overwrite: bool) -> Result<Vec<RuleMatch>, AppError> {
+    let mut rule = new_rule.into_rule(user_id);
+    rule.add_tags = normalize_tags(rule.add_tags);
+    let rules = vec![CompiledRule::compile(&rule)?];
+
+    let expenses = load_expenses(connection, user_id)?;
+    let matches = expenses
+        .into_iter()
+        .filter_map(|expense| {
+            let outcome = categorize(&rules, &expense.item_name, expense.description.as_deref(), expense.amount);
+            let change = outcome.changes(&expense, overwrite)?;
+            Some(RuleMatch {
+                category: change.category,
+                tags: change.tags,
+                display_name: change.display_name,
+                expense,
+            })
+        })
+        .collect();
+
+    Ok(matches)
+}
+
+/// Dry run of one of the user's saved rules
+pub fn dry_run_rule(connection: &mut DbConnection, user_id: Uuid, rule_id: Uuid, overwrite: bool) -> Result<Vec<RuleMatch>, AppError> {
+    let rule = get_rule(connection, user_id, rule_id)?;
+    dry_run(connection, user_id, rule.into(), overwrite)
+}
+
+/// Apply the user's enabled rules, or only `rule_id`, to all expenses not in
+/// the trash. Every changed expense is recorded in the audit log; reconciled
+/// ones are left as they are and reported as skipped.
+pub fn apply_to_history(connection: &mut DbConnection, user_id: Uuid, rule_id: Option<Uuid>, overwrite: bool, audit: &AuditContext) -> Result<RuleApplication, AppError> {
+    connection.transaction(|connection| {
+        let rules = match rule_id {
+            Some(rule_id) => vec![CompiledRule::compile(&get_rule(connection, user_id, rule_id)?)?],
+            None => load_enabled_rules(connection, user_id)?,
+        };
+
+        let mut application = RuleApplication { matched: 0, updated: 0, skipped: Vec::new() };
+        for before in load_expenses(connection, user_id)? {
+            let outcome = categorize(&rules, &before.item_name, before.description.as_deref(), before.amount);
+            if !outcome.matched {
+                continue;
+            }
+            application.matched += 1;
+
+            let Some(change) = outcome.changes(&before, overwrite) else {
+                continue;
+            };
+            // The filter also catches expenses reconciled since they were
+            // loaded
+            let updated = diesel::update(expenses::table.find(before.id).filter(expenses::reconciled_at.is_null()))
+                .set((
+                    expenses::category.eq(change.category),
+                    expenses::tags.eq(change.tags),
+                    expenses::display_name.eq(change.display_name),
+                    expenses::updated_at.eq(Utc::now().naive_utc()),
+                ))
+                .returning(Expense::as_returning())
+                .get_result(connection)
+                .optional()?;
+            let Some(expense) = updated else {
+                application.skipped.push(before.id);
+                continue;
+            };
+
+            audit_service::record(connection, audit, AuditChange::updated(entity::EXPENSE, expense.id, user_id, &before, &expense))?;
+            application.updated += 1;
+        }
+
+        Ok(application)
+    })
+}
+
+impl From<CategorizationRule> for NewCategorizationRule {
+    fn from(rule: CategorizationRule) -> Self {
+        NewCategorizationRule {
+            name: rule.name,
+            priority: rule.priority,
+            enabled: rule.enabled,
+            match_field: rule.match_field,
+            match_type: rule.match_type,
+            pattern: rule.pattern,
+            min_amount: rule.min_amount,
+            max_amount: rule.max_amount,
+            sign: rule.sign,
+            set_category: rule.set_category,
+            add_tags: rule.add_tags,
+            set_display_name: rule.set_display_name,
+        }
+    }
+}
+
+fn load_enabled_rules(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<CompiledRule>, AppError> {
+    let rules = categorization_rules::table
+        .filter(categorization_rules::user_id.eq(user_id))
+        .filter(categorization_rules::enabled.eq(true))
+        .select(CategorizationRule::as_select())
+        .load(connection)?;
+
+    Ok(compile_rules(rules))
+}
+
+/// Rules in the order they are applied: by priority, then oldest first, with
+/// the ID settling rules created at the same time
+fn compile_rules(mut rules: Vec<CategorizationRule>) -> Vec<CompiledRule> {
+    rules.sort_by_key(|rule| (rule.priority, rule.created_at, rule.id));
+
+    // Patterns are checked when a rule is saved, so this only skips rules
+    // saved before a change to the checks
+    rules
+        .iter()
+        .filter_map(|rule| match CompiledRule::compile(rule) {
+            Ok(compiled) => Some(compiled),
+            Err(e) => {
+                log::warn!("Skipping categorization rule {}: {}", rule.id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn load_expenses(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Expense>, diesel::result::Error> {
+    expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .order((expenses::date.asc(), expenses::created_at.asc()))
+        .select(Expense::as_select())
+        .load(connection)
+}
+
+/// A rule ready to be matched, with its pattern lowercased or compiled
+struct CompiledRule {
+    match_field: RuleMatchField,
+    matcher: Option<Matcher>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    sign: Option<AmountSign>,
+    set_category: Option<String>,
+    add_tags: Vec<String>,
+    set_display_name: Option<String>,
+}
+
+enum Matcher {
+    Contains(String),
+    Regex(Regex),
+}
+
+impl CompiledRule {
+    /// Check a rule and prepare it for matching
+    fn compile(rule: &CategorizationRule) -> Result<CompiledRule, AppError> {
+        if rule.name.trim().is_empty() {
+            return Err(AppError::Validation("Rule name must not be empty".to_string()));
+        }
+
+        let pattern = rule.pattern.as_deref().filter(|pattern| !pattern.is_empty());
+        let matcher = match (pattern, rule.match_type) {
+            (None, _) => None,
+            (Some(pattern), RuleMatchType::Contains) => Some(Matcher::Contains(pattern.to_lowercase())),
+            (Some(pattern), RuleMatchType::Regex) => {
+                let regex = RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| AppError::Validation(format!("Invalid pattern: {}", e)))?;
+                Some(Matcher::Regex(regex))
+            }
+        };
+
+        if matcher.is_none() && rule.min_amount.is_none() && rule.max_amount.is_none() && rule.sign.is_none() {
+            return Err(AppError::Validation("A rule needs a pattern, an amount range or a sign to match on".to_string()));
+        }
+        if rule.min_amount.is_some_and(|min| min < Decimal::ZERO) || rule.max_amount.is_some_and(|max| max < Decimal::ZERO) {
+            return Err(AppError::Validation("Amount limits apply to the absolute amount and must not be negative".to_string()));
+        }
+        if let (Some(min), Some(max)) = (rule.min_amount, rule.max_amount) {
+            if min > max {
+                return Err(AppError::Validation("Minimum amount must not be greater than the maximum".to_string()));
+            }
+        }
+
+        let set_category = non_blank(rule.set_category.as_deref());
+        let set_display_name = non_blank(rule.set_display_name.as_deref());
+        if set_category.is_none() && set_display_name.is_none() && rule.add_tags.is_empty() {
+            return Err(AppError::Validation("A rule needs a category, tags or a display name to set".to_string()));
+        }
+
+        Ok(CompiledRule {
+            match_field: rule.match_field,
+            matcher,
+            min_amount: rule.min_amount,
+            max_amount: rule.max_amount,
+            sign: rule.sign,
+            set_category,
+            add_tags: rule.add_tags.clone(),
+            set_display_name,
+        })
+    }
+
+    /// Whether the expense matches; returns the display name to set, with
+    /// regex groups expanded, if it does
+    fn apply(&self, item_name: &str, description: Option<&str>, amount: Decimal) -> Option<Option<String>> {
+        match self.sign {
+            Some(AmountSign::Positive) if amount <= Decimal::ZERO => return None,
+            Some(AmountSign::Negative) if amount >= Decimal::ZERO => return None,
+            _ => {}
+        }
+        if self.min_amount.is_some_and(|min| amount.abs() < min) || self.max_amount.is_some_and(|max| amount.abs() > max) {
+            return None;
+        }
+
+        let Some(matcher) = &self.matcher else {
+            return Some(self.set_display_name.clone());
+        };
+        let texts = match self.match_field {
+            RuleMatchField::ItemName => [Some(item_name), None],
+            RuleMatchField::Description => [description, None],
+            RuleMatchField::Any => [Some(item_name), description],
+        };
+        texts.into_iter().flatten().find_map(|text| match matcher {
+            Matcher::Contains(pattern) => text.to_lowercase().contains(pattern.as_str()).then(|| self.set_display_name.clone()),
+            Matcher::Regex(regex) => regex.captures(text).map(|captures| {
+                self.set_display_name.as_deref().map(|template| {
+                    let mut display_name = String::new();
+                    captures.expand(template, &mut display_name);
+                    display_name.trim().to_string()
+                })
+            }),
+        })
+    }
+}
+
+/// What the matching rules set on an expense. The first matching rule with a
+/// category or display name wins; tags of all matching rules are combined.
+struct Categorization {
+    matched: bool,
+    category: Option<String>,
+    display_name: Option<String>,
+    tags: Vec<String>,
+}
+
+/// Values of an expense after applying a categorization
+struct CategoryChange {
+    category: Option<String>,
+    tags: Vec<String>,
+    display_name: Option<String>,
+}
+
+impl Categorization {
+    /// The new values for an existing expense, or `None` if nothing changes
+    fn changes(self, expense: &Expense, overwrite: bool) -> Option<CategoryChange> {
+        if !self.matched {
+            return None;
+        }
+
+        let pick = |current: &Option<String>, new: Option<String>| match (current, new) {
+            (Some(_), Some(new)) if overwrite => Some(new),
+            (None, new) => new,
+            (current, _) => current.clone(),
+        };
+        let change = CategoryChange {
+            category: pick(&expense.category, self.category),
+            tags: merge_tags(&expense.tags, &self.tags),
+            display_name: pick(&expense.display_name, self.display_name),
+        };
+
+        let unchanged = change.category == expense.category && change.tags == expense.tags && change.display_name == expense.display_name;
+        (!unchanged).then_some(change)
+    }
+}
+
+fn categorize(rules: &[CompiledRule], item_name: &str, description: Option<&str>, amount: Decimal) -> Categorization {
+    let mut categorization = Categorization { matched: false, category: None, display_name: None, tags: Vec::new() };
+
+    for rule in rules {
+        let Some(display_name) = rule.apply(item_name, description, amount) else {
+            continue;
+        };
+        categorization.matched = true;
+        if categorization.category.is_none() {
+            categorization.category = rule.set_category.clone();
+        }
+        if categorization.display_name.is_none() {
+            categorization.display_name = display_name.filter(|name| !name.is_empty());
+        }
+        categorization.tags = merge_tags(&categorization.tags, &rule.add_tags);
+    }
+
+    categorization
+}
+
+/// `tags` followed by the `added` tags it does not have yet
+fn merge_tags(tags: &[String], added: &[String]) -> Vec<String> {
+    let mut merged = tags.to_vec();
+    for tag in added {
+        if !merged.contains(tag) {
+            merged.push(tag.clone());
+        }
+    }
+    merged
+}
+
+/// Trimmed, non-empty tags without duplicates
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let tags: Vec<String> = tags.into_iter().map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect();
+    merge_tags(&[], &tags)
+}
+
+fn non_blank(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::database::test_database;
+    use crate::models::expense::NewExpense;
+    use crate::services::expense_service;
+
+    fn at(seconds: i64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap().naive_utc()
+    }
+
+    fn rule(id: u128, priority: i32, created_at: NaiveDateTime, pattern: &str, category: &str, tags: &[&str]) -> CategorizationRule {
+        CategorizationRule {
+            id: Uuid::from_u128(id),
+            user_id: Uuid::nil(),
+            name: format!("Rule {}", id),
+            priority,
+            enabled: true,
+            match_field: RuleMatchField::Any,
+            match_type: RuleMatchType::Contains,
+            pattern: Some(pattern.to_string()),
+            min_amount: None,
+            max_amount: None,
+            sign: None,
+            set_category: Some(category.to_string()),
+            add_tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            set_display_name: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn money(amount: i64) -> Decimal {
+        Decimal::from(amount)
+    }
+
+    #[test]
+    fn lower_priority_number_wins_whatever_the_load_order() {
+        let rules = compile_rules(vec![
+            rule(1, 10, at(0), "coffee", "Dining", &["food"]),
+            rule(2, 1, at(60), "starbucks", "Coffee shops", &["caffeine", "food"]),
+        ]);
+
+        let categorization = categorize(&rules, "Starbucks coffee", None, money(5));
+
+        assert!(categorization.matched);
+        assert_eq!(categorization.category.as_deref(), Some("Coffee shops"));
+        // Tags of every matching rule, in rule order and without duplicates
+        assert_eq!(categorization.tags, vec!["caffeine", "food"]);
+    }
+
+    #[test]
+    fn ties_go_to_the_older_rule_then_the_lower_id() {
+        let older = compile_rules(vec![rule(1, 5, at(60), "coffee", "Newer", &[]), rule(2, 5, at(0), "coffee", "Older", &[])]);
+        assert_eq!(categorize(&older, "Coffee", None, money(5)).category.as_deref(), Some("Older"));
+
+        let same_time = compile_rules(vec![rule(2, 5, at(0), "coffee", "Second", &[]), rule(1, 5, at(0), "coffee", "First", &[])]);
+        assert_eq!(categorize(&same_time, "Coffee", None, money(5)).category.as_deref(), Some("First"));
+    }
+
+    #[test]
+    fn later_rules_fill_in_what_earlier_ones_leave_unset() {
+        let mut rename = rule(1, 1, at(0), r"^amzn\s+(\w+)", "", &[]);
+        rename.match_type = RuleMatchType::Regex;
+        rename.set_category = None;
+        rename.set_display_name = Some("Amazon $1".to_string());
+        let rules = compile_rules(vec![rename, rule(2, 2, at(0), "amzn", "Shopping", &[])]);
+
+        let categorization = categorize(&rules, "AMZN Mktp", None, money(25));
+
+        assert_eq!(categorization.display_name.as_deref(), Some("Amazon Mktp"));
+        assert_eq!(categorization.category.as_deref(), Some("Shopping"));
+    }
+
+    #[test]
+    fn matches_amount_limits_on_the_absolute_amount() {
+        let mut refunds = rule(1, 1, at(0), "", "Refunds", &[]);
+        refunds.pattern = None;
+        refunds.sign = Some(AmountSign::Negative);
+        refunds.min_amount = Some(money(10));
+        refunds.max_amount = Some(money(100));
+        let rules = compile_rules(vec![refunds]);
+
+        assert!(categorize(&rules, "Shop", None, money(-50)).matched);
+        assert!(categorize(&rules, "Shop", None, money(-100)).matched);
+        assert!(!categorize(&rules, "Shop", None, money(-5)).matched);
+        assert!(!categorize(&rules, "Shop", None, money(-150)).matched);
+        assert!(!categorize(&rules, "Shop", None, money(50)).matched);
+    }
+
+    #[test]
+    fn matches_only_the_configured_field() {
+        let mut by_description = rule(1, 1, at(0), "rent", "Housing", &[]);
+        by_description.match_field = RuleMatchField::Description;
+        let rules = compile_rules(vec![by_description]);
+
+        assert!(categorize(&rules, "Transfer", Some("March RENT"), money(900)).matched);
+        assert!(!categorize(&rules, "Rent", None, money(900)).matched);
+    }
+
+    #[test]
+    fn applying_rules_skips_reconciled_expenses() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let audit = AuditContext::default();
+        let mut expense = |item_name: &str| {
+            let new_expense = NewExpense {
+                user_id: user.id,
+                item_name: item_name.to_string(),
+                amount: money(40),
+                description: None,
+                category: None,
+                tags: Vec::new(),
+                display_name: None,
+                account: None,
+            };
+            expense_service::create_expense(&mut conn, new_expense, &audit).unwrap()
+        };
+        let open = expense("Corner market");
+        let reconciled = expense("Farmers market");
+        let reconciled_at = Utc::now().naive_utc();
+        diesel::update(expenses::table.find(reconciled.id))
+            .set(expenses::reconciled_at.eq(reconciled_at))
+            .execute(&mut conn)
+            .unwrap();
+        // Saved after the expenses, which would otherwise be categorized as
+        // they are created
+        let saved = create_rule(&mut conn, user.id, rule(1, 0, at(0), "market", "Groceries", &["food"]).into(), &audit).unwrap();
+
+        let application = apply_to_history(&mut conn, user.id, Some(saved.id), false, &audit).unwrap();
+
+        assert_eq!((application.matched, application.updated), (2, 1));
+        assert_eq!(application.skipped, vec![reconciled.id]);
+        let category = |conn: &mut DbConnection, id: Uuid| expenses::table.find(id).select(expenses::category).first::<Option<String>>(conn).unwrap();
+        assert_eq!(category(&mut conn, open.id).as_deref(), Some("Groceries"));
+        assert_eq!(category(&mut conn, reconciled.id), None);
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config;
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::duplicate::{DuplicateCandidate, DuplicateKind, DuplicatePair, DuplicateQuery, DuplicateRecord, DuplicateScan, DuplicateStatus, MergeDuplicate};
+use crate::models::expense::Expense;
+use crate::models::income::Income;
+use crate::models::schema::{duplicate_candidates, expenses, incomes, users};
+use crate::services::suggestion_service::tokenize;
+use crate::services::{audit_service, expense_service, income_service};
+
+/// Smallest word overlap between two texts for them to count as the same
+const MIN_TEXT_SIMILARITY: f64 = 0.5;
+
+/// Share of the score that comes from the texts; the rest comes from how
+/// close the dates are
+const TEXT_WEIGHT: f64 = 0.7;
+
+/// Scan every user's incomes and expenses, see [`scan_user`]
+pub fn scan_all(connection: &mut DbConnection) -> Result<DuplicateScan, diesel::result::Error> {
+    let user_ids = users::table.select(users::id).load::<Uuid>(connection)?;
+
+    let mut total = DuplicateScan { incomes: 0, expenses: 0 };
+    for user_id in user_ids {
+        let scan = scan_user(connection, user_id)?;
+        total.incomes += scan.incomes;
+        total.expenses += scan.expenses;
+    }
+    Ok(total)
+}
+
+/// Look for records with the same amount, dates at most
+/// `DUPLICATE_WINDOW_DAYS` apart and similar texts. New pairs are opened,
+/// open pairs that no longer look alike are dropped and dismissed or merged
+/// pairs are left alone.
+pub fn scan_user(connection: &mut DbConnection, user_id: Uuid) -> Result<DuplicateScan, diesel::result::Error> {
+    let window_days = config::get_duplicate_window_days();
+
+    connection.transaction(|connection| {
+        Ok(DuplicateScan {
+            incomes: scan_kind(connection, user_id, DuplicateKind::Income, window_days)?,
+            expenses: scan_kind(connection, user_id, DuplicateKind::Expense, window_days)?,
+        })
+    })
+}
+
+/// The user's duplicate pairs, most likely duplicates first. Open pairs
+/// with a record in the trash are left out until the next scan drops them.
+pub fn get_duplicates(connection: &mut DbConnection, user_id: Uuid, query: &DuplicateQuery) -> Result<Vec<DuplicatePair>, diesel::result::Error> {
+    let status = query.status.unwrap_or_default();
+    let mut statement = duplicate_candidates::table
+        .filter(duplicate_candidates::user_id.eq(user_id))
+        .filter(duplicate_candidates::status.eq(status))
+        .order((duplicate_candidates::score.desc(), duplicate_candidates::detected_at.asc()))
+        .select(DuplicateCandidate::as_select())
+        .into_boxed();
+    if let Some(kind) = query.kind {
+        statement = statement.filter(duplicate_candidates::kind.eq(kind));
+    }
+    let candidates = statement.load(connection)?;
+
+    // A record can be part of several pairs
+    let records = load_records(connection, &candidates)?;
+    Ok(candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let first = records.get(&candidate.first_id)?.clone();
+            let second = records.get(&candidate.second_id)?.clone();
+            if candidate.status == DuplicateStatus::Open && (first.deleted_at.is_some() || second.deleted_at.is_some()) {
+                return None;
+            }
+            Some(to_pair(candidate, first, second))
+        })
+        .collect())
+}
+
+/// Keep one record of an open pair and move the other one to the trash. The
+/// merge is recorded in the audit log on the record that was removed, which
+/// must not be reconciled.
+pub fn merge(connection: &mut DbConnection, user_id: Uuid, candidate_id: Uuid, merge: MergeDuplicate, audit: &AuditContext) -> Result<DuplicatePair, AppError> {
+    connection.transaction(|connection| {
+        let candidate = get_open_candidate(connection, user_id, candidate_id)?;
+        let keep_id = merge.keep_id.unwrap_or(candidate.first_id);
+        let remove_id = if keep_id == candidate.first_id {
+            candidate.second_id
+        } else if keep_id == candidate.second_id {
+            candidate.first_id
+        } else {
+            return Err(AppError::Validation("The record to keep must be one of the pair".to_string()));
+        };
+
+        match candidate.kind {
+            DuplicateKind::Income => {
+                let kept = income_service::get_income_by_id(connection, keep_id).map_err(record_gone)?;
+                let remove = income_service::get_income_by_id(connection, remove_id).map_err(record_gone)?;
+                income_service::ensure_unlocked(&remove, "merging it into its duplicate")?;
+                let removed = income_service::delete_income(connection, remove_id, None, audit)?;
+                audit_service::record(connection, audit, AuditChange::merged(entity::INCOME, removed.id, user_id, &removed, &kept))?;
+            }
+            DuplicateKind::Expense => {
+                let kept = expense_service::get_expense_by_id(connection, keep_id).map_err(record_gone)?;
+                let remove = expense_service::get_expense_by_id(connection, remove_id).map_err(record_gone)?;
+                expense_service::ensure_unlocked(&remove, "merging it into its duplicate")?;
+                let removed = expense_service::delete_expense(connection, remove_id, None, audit)?;
+                audit_service::record(connection, audit, AuditChange::merged(entity::EXPENSE, removed.id, user_id, &removed, &kept))?;
+            }
+        }
+
+        let candidate = resolve(connection, candidate.id, DuplicateStatus::Merged)?;
+        Ok(load_pair(connection, candidate)?)
+    })
+}
+
+/// Mark an open pair as not being a duplicate
+pub fn dismiss(connection: &mut DbConnection, user_id: Uuid, candidate_id: Uuid) -> Result<DuplicatePair, AppError> {
+    connection.transaction(|connection| {
+        let candidate = get_open_candidate(connection, user_id, candidate_id)?;
+        let candidate = resolve(connection, candidate.id, DuplicateStatus::Dismissed)?;
+        Ok(load_pair(connection, candidate)?)
+    })
+}
+
+fn get_open_candidate(connection: &mut DbConnection, user_id: Uuid, candidate_id: Uuid) -> Result<DuplicateCandidate, AppError> {
+    let candidate = duplicate_candidates::table
+        .find(candidate_id)
+        .filter(duplicate_candidates::user_id.eq(user_id))
+        .select(DuplicateCandidate::as_select())
+        .for_update()
+        .first(connection)?;
+
+    if candidate.status != DuplicateStatus::Open {
+        return Err(AppError::Conflict(format!("Duplicate pair is already {}", candidate.status.as_str())));
+    }
+    Ok(candidate)
+}
+
+fn resolve(connection: &mut DbConnection, candidate_id: Uuid, status: DuplicateStatus) -> Result<DuplicateCandidate, diesel::result::Error> {
+    diesel::update(duplicate_candidates::table.find(candidate_id))
+        .set((
+            duplicate_candidates::status.eq(status),
+            duplicate_candidates::resolved_at.eq(Utc::now().naive_utc()),
+        ))
+        .returning(DuplicateCandidate::as_returning())
+        .get_result(connection)
+}
+
+fn record_gone(error: diesel::result::Error) -> AppError {
+    match error {
+        diesel::result::Error::NotFound => AppError::Conflict("A record of the pair is in the trash or was deleted".to_string()),
+        other => other.into(),
+    }
+}
+
+/// Pair of records found by the scan query
+#[derive(QueryableByName)]
+struct PairRow {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    first_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    second_id: Uuid,
+    #[diesel(sql_type = Text)]
+    first_name: String,
+    #[diesel(sql_type = Text)]
+    second_name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    first_description: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    second_description: Option<String>,
+    #[diesel(sql_type = Integer)]
+    days_apart: i32,
+}
+
+/// Number of open pairs of the kind after the scan
+fn scan_kind(connection: &mut DbConnection, user_id: Uuid, kind: DuplicateKind, window_days: i32) -> Result<usize, diesel::result::Error> {
+    let (table, name) = match kind {
+        DuplicateKind::Income => ("incomes", "source"),
+        DuplicateKind::Expense => ("expenses", "item_name"),
+    };
+    // The record created first is always `a`, so every pair is found once
+    let sql = format!(
+        "SELECT a.id AS first_id, b.id AS second_id,
+                a.{name} AS first_name, b.{name} AS second_name,
+                a.description AS first_description, b.description AS second_description,
+                abs(a.date - b.date) AS days_apart
+         FROM {table} a
+         JOIN {table} b
+           ON b.user_id = a.user_id
+          AND b.amount = a.amount
+          AND (a.created_at, a.id) < (b.created_at, b.id)
+          AND abs(a.date - b.date) <= $2
+         WHERE a.user_id = $1 AND a.deleted_at IS NULL AND b.deleted_at IS NULL"
+    );
+    let rows: Vec<PairRow> = diesel::sql_query(sql)
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Integer, _>(window_days)
+        .load(connection)?;
+
+    let found: HashMap<(Uuid, Uuid), Decimal> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let similarity = text_similarity(&row.first_name, row.first_description.as_deref(), &row.second_name, row.second_description.as_deref());
+            duplicate_score(similarity, row.days_apart, window_days).map(|score| ((row.first_id, row.second_id), score))
+        })
+        .collect();
+
+    let existing = duplicate_candidates::table
+        .filter(duplicate_candidates::user_id.eq(user_id))
+        .filter(duplicate_candidates::kind.eq(kind))
+        .select(DuplicateCandidate::as_select())
+        .load(connection)?;
+    let mut known = HashSet::new();
+    let now = Utc::now().naive_utc();
+    for candidate in existing {
+        let key = (candidate.first_id, candidate.second_id);
+        known.insert(key);
+        if candidate.status != DuplicateStatus::Open {
+            continue;
+        }
+        match found.get(&key) {
+            Some(score) => {
+                diesel::update(duplicate_candidates::table.find(candidate.id))
+                    .set(duplicate_candidates::score.eq(score))
+                    .execute(connection)?;
+            }
+            None => {
+                diesel::delete(duplicate_candidates::table.find(candidate.id)).execute(connection)?;
+            }
+        }
+    }
+
+    let new_candidates: Vec<DuplicateCandidate> = found
+        .iter()
+        .filter(|(key, _)| !known.contains(*key))
+        .map(|(&(first_id, second_id), &score)| DuplicateCandidate {
+            id: Uuid::new_v4(),
+            user_id,
+            kind,
+            first_id,
+            second_id,
+            score,
+            status: DuplicateStatus::Open,
+            detected_at: now,
+            resolved_at: None,
+        })
+        .collect();
+    diesel::insert_into(duplicate_candidates::table)
+        .values(&new_candidates)
+        .execute(connection)?;
+
+    Ok(found.len())
+}
+
+/// Score of two records with the same amount, or `None` if their texts are
+/// too different for them to be duplicates
+fn duplicate_score(similarity: f64, days_apart: i32, window_days: i32) -> Option<Decimal> {
+    if similarity < MIN_TEXT_SIMILARITY {
+        return None;
+    }
+    let closeness = 1.0 - days_apart as f64 / (window_days as f64 + 1.0);
+    let score = TEXT_WEIGHT * similarity + (1.0 - TEXT_WEIGHT) * closeness;
+    Some(Decimal::from_f64(score).unwrap_or_default().round_dp(4))
+}
+
+/// Share of words the two records have in common (Jaccard index). Texts
+/// without any words only match when they are equal.
+fn text_similarity(first_name: &str, first_description: Option<&str>, second_name: &str, second_description: Option<&str>) -> f64 {
+    let first: HashSet<String> = tokenize(first_name, first_description).into_iter().collect();
+    let second: HashSet<String> = tokenize(second_name, second_description).into_iter().collect();
+    if first.is_empty() && second.is_empty() {
+        return if first_name.trim().eq_ignore_ascii_case(second_name.trim()) { 1.0 } else { 0.0 };
+    }
+    first.intersection(&second).count() as f64 / first.union(&second).count() as f64
+}
+
+/// Both records of every candidate, including records in the trash
+fn load_records(connection: &mut DbConnection, candidates: &[DuplicateCandidate]) -> Result<HashMap<Uuid, DuplicateRecord>, diesel::result::Error> {
+    let ids_of = |kind: DuplicateKind| -> Vec<Uuid> {
+        candidates
+            .iter()
+            .filter(|candidate| candidate.kind == kind)
+            .flat_map(|candidate| [candidate.first_id, candidate.second_id])
+            .collect()
+    };
+
+    let mut records = HashMap::new();
+    let income_ids = ids_of(DuplicateKind::Income);
+    if !income_ids.is_empty() {
+        let found = incomes::table
+            .filter(incomes::id.eq_any(income_ids))
+            .select(Income::as_select())
+            .load(connection)?;
+        records.extend(found.into_iter().map(|income| (income.id, DuplicateRecord::from(income))));
+    }
+    let expense_ids = ids_of(DuplicateKind::Expense);
+    if !expense_ids.is_empty() {
+        let found = expenses::table
+            .filter(expenses::id.eq_any(expense_ids))
+            .select(Expense::as_select())
+            .load(connection)?;
+        records.extend(found.into_iter().map(|expense| (expense.id, DuplicateRecord::from(expense))));
+    }
+    Ok(records)
+}
+
+fn load_pair(connection: &mut DbConnection, candidate: DuplicateCandidate) -> Result<DuplicatePair, diesel::result::Error> {
+    let mut records = load_records(connection, std::slice::from_ref(&candidate))?;
+    let first = records.remove(&candidate.first_id).ok_or(diesel::result::Error::NotFound)?;
+    let second = records.remove(&candidate.second_id).ok_or(diesel::result::Error::NotFound)?;
+    Ok(to_pair(candidate, first, second))
+}
+
+fn to_pair(candidate: DuplicateCandidate, first: DuplicateRecord, second: DuplicateRecord) -> DuplicatePair {
+    DuplicatePair {
+        id: candidate.id,
+        kind: candidate.kind,
+        score: candidate.score,
+        status: candidate.status,
+        detected_at: candidate.detected_at,
+        resolved_at: candidate.resolved_at,
+        first,
+        second,
+    }
+}
+
+impl From<Income> for DuplicateRecord {
+    fn from(income: Income) -> Self {
+        DuplicateRecord {
+            id: income.id,
+            name: income.source,
+            amount: income.amount,
+            date: income.date,
+            description: income.description,
+            created_at: income.created_at,
+            deleted_at: income.deleted_at,
+        }
+    }
+}
+
+impl From<Expense> for DuplicateRecord {
+    fn from(expense: Expense) -> Self {
+        DuplicateRecord {
+            id: expense.id,
+            name: expense.item_name,
+            amount: expense.amount,
+            date: expense.date,
+            description: expense.description,
+            created_at: expense.created_at,
+            deleted_at: expense.deleted_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_identical_records_on_the_same_day_highest() {
+        assert_eq!(duplicate_score(1.0, 0, 3), Some(Decimal::ONE));
+    }
+
+    #[test]
+    fn lowers_the_score_with_the_days_apart() {
+        // 0.7 * 1 + 0.3 * (1 - 3/4)
+        assert_eq!(duplicate_score(1.0, 3, 3), Some(Decimal::new(7750, 4)));
+        // 0.7 * 1 + 0.3 * (1 - 1/8)
+        assert_eq!(duplicate_score(1.0, 1, 7), Some(Decimal::new(9625, 4)));
+    }
+
+    #[test]
+    fn requires_half_of_the_words_in_common() {
+        // 0.7 * 0.5 + 0.3 * 1
+        assert_eq!(duplicate_score(0.5, 0, 3), Some(Decimal::new(6500, 4)));
+        assert_eq!(duplicate_score(0.49, 0, 3), None);
+    }
+
+    #[test]
+    fn compares_words_regardless_of_case_punctuation_and_numbers() {
+        assert_eq!(text_similarity("Netflix.com 4829", None, "NETFLIX COM", None), 1.0);
+        assert_eq!(text_similarity("Coffee shop", None, "Coffee", None), 0.5);
+        assert_eq!(text_similarity("Coffee", Some("downtown shop"), "Coffee", None), 1.0 / 3.0);
+        assert_eq!(text_similarity("Rent", None, "Salary", Some("March")), 0.0);
+    }
+
+    #[test]
+    fn compares_texts_without_words_as_a_whole() {
+        assert_eq!(text_similarity(" #1 ", None, "#1", None), 1.0);
+        assert_eq!(text_similarity("#1", None, "#2", None), 0.0);
+    }
+}
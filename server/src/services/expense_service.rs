@@ -1,54 +1,359 @@
+use actix_web::http::StatusCode;
 use diesel::prelude::*;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 
+use crate::config::errors::AppError;
+use crate::config::etag;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::batch::{BatchOperation, BatchRequest, BatchResponse};
 use crate::models::expense::{Expense, NewExpense, UpdateExpense};
 use crate::models::schema::expenses;
 use crate::database::db_connection::DbConnection;
+use crate::services::{audit_service, batch_service, categorization_service, net_worth_service};
+use crate::services::suggestion_service::CategoryPrefill;
 
-pub fn get_all_expenses(connection: &mut DbConnection) -> Result<Vec<Expense>, diesel::result::Error> {
-    expenses::table
+/// Expenses, optionally restricted to a single user
+pub fn get_all_expenses(connection: &mut DbConnection, owner_id: Option<Uuid>) -> Result<Vec<Expense>, diesel::result::Error> {
+    let mut query = expenses::table
+        .filter(expenses::deleted_at.is_null())
         .select(Expense::as_select())
-        .load::<Expense>(connection)
+        .into_boxed();
+
+    if let Some(owner_id) = owner_id {
+        query = query.filter(expenses::user_id.eq(owner_id));
+    }
+
+    query.load::<Expense>(connection)
 }
 
 pub fn get_expenses_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Expense>, diesel::result::Error> {
     expenses::table
         .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
         .select(Expense::as_select())
         .load::<Expense>(connection)
 }
 
-pub fn create_expense(connection: &mut DbConnection, new_expense: NewExpense) -> Result<Expense, diesel::result::Error> {
-    let now = Utc::now().naive_utc();
-    let expense = diesel::insert_into(expenses::table)
-        .values((
-            expenses::id.eq(Uuid::new_v4()),
-            expenses::user_id.eq(new_expense.user_id),
-            expenses::item_name.eq(new_expense.item_name),
-            expenses::amount.eq(new_expense.amount),
-            expenses::date.eq(now.date()),
-            expenses::description.eq(new_expense.description),
-            expenses::created_at.eq(now),
-            expenses::updated_at.eq(now),
-        ))
-        .get_result::<Expense>(connection)?;
+pub fn get_expense_by_id(connection: &mut DbConnection, expense_id: Uuid) -> Result<Expense, diesel::result::Error> {
+    expenses::table
+        .find(expense_id)
+        .filter(expenses::deleted_at.is_null())
+        .select(Expense::as_select())
+        .first(connection)
+}
+
+/// Deleted expenses that have not been purged yet, most recently deleted
+/// first, optionally restricted to a single user
+pub fn get_deleted_expenses(connection: &mut DbConnection, owner_id: Option<Uuid>) -> Result<Vec<Expense>, diesel::result::Error> {
+    let mut query = expenses::table
+        .filter(expenses::deleted_at.is_not_null())
+        .order(expenses::deleted_at.desc())
+        .select(Expense::as_select())
+        .into_boxed();
+
+    if let Some(owner_id) = owner_id {
+        query = query.filter(expenses::user_id.eq(owner_id));
+    }
+
+    query.load::<Expense>(connection)
+}
+
+pub fn get_deleted_expense_by_id(connection: &mut DbConnection, expense_id: Uuid) -> Result<Expense, diesel::result::Error> {
+    expenses::table
+        .find(expense_id)
+        .filter(expenses::deleted_at.is_not_null())
+        .select(Expense::as_select())
+        .first(connection)
+}
+
+/// An expense whether or not it is in the trash
+pub fn get_expense_including_deleted(connection: &mut DbConnection, expense_id: Uuid) -> Result<Expense, diesel::result::Error> {
+    expenses::table
+        .find(expense_id)
+        .select(Expense::as_select())
+        .first(connection)
+}
+
+pub fn create_expense(connection: &mut DbConnection, new_expense: NewExpense, audit: &AuditContext) -> Result<Expense, AppError> {
+    insert_expense(connection, new_expense, None, audit)
+}
+
+/// Create an expense, categorized by the owner's rules and, when `prefill`
+/// is given and no rule set a category, by what was learned from their
+/// history
+fn insert_expense(connection: &mut DbConnection, new_expense: NewExpense, prefill: Option<&mut CategoryPrefill>, audit: &AuditContext) -> Result<Expense, AppError> {
+    validate_item_name(&new_expense.item_name)?;
+
+    connection.transaction(|connection| {
+        let mut new_expense = categorization_service::categorize_new_expense(connection, new_expense)?;
+        if let Some(prefill) = prefill {
+            prefill.apply(connection, &mut new_expense)?;
+        }
+        let now = Utc::now().naive_utc();
+        let expense = diesel::insert_into(expenses::table)
+            .values((
+                expenses::id.eq(Uuid::new_v4()),
+                expenses::user_id.eq(new_expense.user_id),
+                expenses::item_name.eq(new_expense.item_name),
+                expenses::amount.eq(new_expense.amount),
+                expenses::date.eq(now.date()),
+                expenses::description.eq(new_expense.description),
+                expenses::category.eq(new_expense.category),
+                expenses::tags.eq(new_expense.tags),
+                expenses::display_name.eq(new_expense.display_name),
+                expenses::account.eq(new_expense.account),
+                expenses::created_at.eq(now),
+                expenses::updated_at.eq(now),
+            ))
+            .get_result::<Expense>(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, expense.user_id, expense.date)?;
+        audit_service::record(connection, audit, AuditChange::created(entity::EXPENSE, expense.id, expense.user_id, &expense))?;
+        Ok(expense)
+    })
+}
+
+/// Update an expense if it is still at one of the `expected_versions`
+/// (its `updated_at`, see [`etag`]); `None` skips the check
+pub fn update_expense(connection: &mut DbConnection, expense_id: Uuid, update_expense: UpdateExpense, expected_versions: Option<&[NaiveDateTime]>, audit: &AuditContext) -> Result<Expense, AppError> {
+    if let Some(item_name) = &update_expense.item_name {
+        validate_item_name(item_name)?;
+    }
+
+    connection.transaction(|connection| {
+        // Lock the row so the version check and the update cannot interleave
+        // with a concurrent update
+        let before = expenses::table
+            .find(expense_id)
+            .filter(expenses::deleted_at.is_null())
+            .select(Expense::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "editing")?;
+        etag::ensure_version(before.updated_at, expected_versions)?;
+
+        let expense: Expense = diesel::update(expenses::table.find(expense_id))
+            .set((update_expense, expenses::updated_at.eq(Utc::now().naive_utc())))
+            .get_result(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, expense.user_id, before.date.min(expense.date))?;
+        audit_service::record(connection, audit, AuditChange::updated(entity::EXPENSE, expense.id, expense.user_id, &before, &expense))?;
+        Ok(expense)
+    })
+}
+
+/// Move an expense to the trash if it is still at one of the
+/// `expected_versions` (`None` skips the check). It can be restored until it
+/// is purged.
+pub fn delete_expense(connection: &mut DbConnection, expense_id: Uuid, expected_versions: Option<&[NaiveDateTime]>, audit: &AuditContext) -> Result<Expense, AppError> {
+    connection.transaction(|connection| {
+        let before: Expense = expenses::table
+            .find(expense_id)
+            .filter(expenses::deleted_at.is_null())
+            .select(Expense::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "deleting")?;
+        etag::ensure_version(before.updated_at, expected_versions)?;
+
+        let expense: Expense = diesel::update(expenses::table.find(expense_id))
+            .set(expenses::deleted_at.eq(Utc::now().naive_utc()))
+            .get_result(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, expense.user_id, expense.date)?;
+        audit_service::record(connection, audit, AuditChange::deleted(entity::EXPENSE, expense.id, expense.user_id, &expense))?;
+        Ok(expense)
+    })
+}
+
+pub fn restore_expense(connection: &mut DbConnection, expense_id: Uuid, audit: &AuditContext) -> Result<Expense, AppError> {
+    connection.transaction(|connection| {
+        let before: Expense = expenses::table
+            .find(expense_id)
+            .filter(expenses::deleted_at.is_not_null())
+            .select(Expense::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "restoring")?;
+
+        let expense: Expense = diesel::update(expenses::table.find(expense_id))
+            .set(expenses::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result(connection)?;
 
-    Ok(expense)
+        net_worth_service::invalidate_snapshots(connection, expense.user_id, expense.date)?;
+        audit_service::record(connection, audit, AuditChange::restored(entity::EXPENSE, expense.id, expense.user_id, &expense))?;
+        Ok(expense)
+    })
 }
 
-pub fn update_expense(connection: &mut DbConnection, expense_id: Uuid, mut update_expense: UpdateExpense) -> Result<Expense, diesel::result::Error> {
+/// Allow changes to a reconciled expense again. It stays cleared in its
+/// reconciliation. Expenses in the trash can be unlocked too, so they can be
+/// restored.
+pub fn unlock_expense(connection: &mut DbConnection, expense_id: Uuid, audit: &AuditContext) -> Result<Expense, diesel::result::Error> {
     connection.transaction(|connection| {
-        update_expense.updated_at = Some(Utc::now().naive_utc());
-        diesel::update(expenses::table.find(expense_id))
-            .set(update_expense)
-            .get_result(connection)
+        let before: Expense = expenses::table
+            .find(expense_id)
+            .select(Expense::as_select())
+            .for_update()
+            .first(connection)?;
+        if before.reconciled_at.is_none() {
+            return Ok(before);
+        }
+
+        let expense: Expense = diesel::update(expenses::table.find(expense_id))
+            .set((expenses::reconciled_at.eq(None::<NaiveDateTime>), expenses::updated_at.eq(Utc::now().naive_utc())))
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::EXPENSE, expense.id, expense.user_id, &before, &expense))?;
+        Ok(expense)
     })
 }
 
-pub fn delete_expense(connection: &mut DbConnection, expense_id: Uuid) -> Result<Expense, diesel::result::Error> {
+/// Permanently remove expenses that were deleted before `deleted_before`
+pub fn purge_deleted_expenses(connection: &mut DbConnection, deleted_before: NaiveDateTime) -> Result<usize, diesel::result::Error> {
     connection.transaction(|connection| {
-        diesel::delete(expenses::table.find(expense_id))
-            .get_result(connection)
+        let purged: Vec<Expense> = diesel::delete(expenses::table.filter(expenses::deleted_at.lt(deleted_before)))
+            .get_results(connection)?;
+
+        for expense in &purged {
+            audit_service::record(connection, &AuditContext::default(), AuditChange::purged(entity::EXPENSE, expense.id, expense.user_id, expense))?;
+        }
+        Ok(purged.len())
     })
-}
\ No newline at end of file
+}
+
+/// Apply a batch of creates, updates and deletes, see [`batch_service::run`]
+///
+/// Created expenses that neither the client nor a rule categorized get the
+/// category learned from the owner's history when it is clear enough.
+///
+/// `authorize` is called with the owner of every expense an operation touches
+/// and fails the operation if the caller may not access it.
+pub fn run_batch<A>(connection: &mut DbConnection, request: BatchRequest<NewExpense, UpdateExpense>, authorize: A, audit: &AuditContext) -> Result<BatchResponse<Expense>, AppError>
+where
+    A: Fn(Uuid) -> Result<(), AppError>,
+{
+    let mut prefill = CategoryPrefill::default();
+    batch_service::run(connection, request.mode, request.operations, |connection, operation| match operation {
+        BatchOperation::Create { data } => {
+            authorize(data.user_id)?;
+            Ok((StatusCode::CREATED, insert_expense(connection, data, Some(&mut prefill), audit)?))
+        }
+        BatchOperation::Update { id, version, data } => {
+            authorize(get_expense_by_id(connection, id)?.user_id)?;
+            Ok((StatusCode::OK, update_expense(connection, id, data, version.versions().as_deref(), audit)?))
+        }
+        BatchOperation::Delete { id, version } => {
+            authorize(get_expense_by_id(connection, id)?.user_id)?;
+            Ok((StatusCode::OK, delete_expense(connection, id, version.versions().as_deref(), audit)?))
+        }
+    })
+}
+
+/// Reconciled expenses cannot change until they are unlocked, see [`unlock_expense`]
+pub fn ensure_unlocked(expense: &Expense, action: &str) -> Result<(), AppError> {
+    if expense.reconciled_at.is_some() {
+        return Err(AppError::Conflict(format!("Expense is reconciled; unlock it before {}", action)));
+    }
+    Ok(())
+}
+
+fn validate_item_name(item_name: &str) -> Result<(), AppError> {
+    if item_name.trim().is_empty() {
+        return Err(AppError::Validation("Item name must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+    use rust_decimal::Decimal;
+
+    fn expense(reconciled_at: Option<NaiveDateTime>) -> Expense {
+        let now = Utc::now().naive_utc();
+        Expense {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            item_name: "Coffee".to_string(),
+            amount: Decimal::new(350, 2),
+            date: now.date(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            category: None,
+            tags: Vec::new(),
+            display_name: None,
+            account: Some("Checking".to_string()),
+            reconciled_at,
+        }
+    }
+
+    #[test]
+    fn refuses_changes_to_reconciled_expenses() {
+        let error = ensure_unlocked(&expense(Some(Utc::now().naive_utc())), "deleting").unwrap_err();
+
+        assert!(matches!(&error, AppError::Conflict(message) if message == "Expense is reconciled; unlock it before deleting"));
+        assert_eq!(actix_web::ResponseError::status_code(&error), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn allows_changes_to_unreconciled_expenses() {
+        assert!(ensure_unlocked(&expense(None), "restoring").is_ok());
+    }
+
+    fn stored_expense(connection: &mut DbConnection, user_id: Uuid) -> Expense {
+        let new_expense = NewExpense {
+            user_id,
+            item_name: "Coffee".to_string(),
+            amount: Decimal::new(350, 2),
+            description: None,
+            category: None,
+            tags: Vec::new(),
+            display_name: None,
+            account: None,
+        };
+        create_expense(connection, new_expense, &AuditContext::default()).unwrap()
+    }
+
+    #[test]
+    fn deleted_expenses_stay_in_the_trash_until_restored() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let expense = stored_expense(&mut conn, user.id);
+        let audit = AuditContext::default();
+
+        delete_expense(&mut conn, expense.id, None, &audit).unwrap();
+
+        assert!(get_expenses_by_user_id(&mut conn, user.id).unwrap().is_empty());
+        assert!(get_all_expenses(&mut conn, Some(user.id)).unwrap().is_empty());
+        assert_eq!(get_deleted_expenses(&mut conn, Some(user.id)).unwrap()[0].id, expense.id);
+
+        restore_expense(&mut conn, expense.id, &audit).unwrap();
+
+        assert_eq!(get_expenses_by_user_id(&mut conn, user.id).unwrap()[0].id, expense.id);
+        assert!(get_deleted_expenses(&mut conn, Some(user.id)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn purges_only_expenses_deleted_before_the_retention_cutoff() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let (expired, recent) = (stored_expense(&mut conn, user.id), stored_expense(&mut conn, user.id));
+        let now = Utc::now().naive_utc();
+        for expense in [&expired, &recent] {
+            delete_expense(&mut conn, expense.id, None, &AuditContext::default()).unwrap();
+        }
+        diesel::update(expenses::table.find(expired.id))
+            .set(expenses::deleted_at.eq(now - chrono::Duration::days(31)))
+            .execute(&mut conn)
+            .unwrap();
+
+        assert_eq!(purge_deleted_expenses(&mut conn, now - chrono::Duration::days(30)).unwrap(), 1);
+        assert!(get_expense_including_deleted(&mut conn, expired.id).is_err());
+        assert!(get_deleted_expense_by_id(&mut conn, recent.id).is_ok());
+    }
+}
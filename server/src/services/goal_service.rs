@@ -0,0 +1,272 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::goal::{Goal, GoalContribution, GoalProgress, NewGoal, NewGoalContribution, UpdateGoal};
+use crate::models::schema::{goal_contributions, goals};
+use crate::services::audit_service;
+
+pub fn get_goals_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Goal>, diesel::result::Error> {
+    goals::table
+        .filter(goals::user_id.eq(user_id))
+        .order(goals::created_at.asc())
+        .select(Goal::as_select())
+        .load(connection)
+}
+
+/// One of the user's goals; goals of other users are reported as not found
+pub fn get_goal(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid) -> Result<Goal, diesel::result::Error> {
+    goals::table
+        .find(goal_id)
+        .filter(goals::user_id.eq(user_id))
+        .select(Goal::as_select())
+        .first(connection)
+}
+
+pub fn create_goal(connection: &mut DbConnection, user_id: Uuid, new_goal: NewGoal, audit: &AuditContext) -> Result<Goal, AppError> {
+    validate_name(&new_goal.name)?;
+    validate_target(new_goal.target_amount)?;
+
+    let goal = connection.transaction(|connection| {
+        let goal = diesel::insert_into(goals::table)
+            .values(new_goal.into_goal(user_id))
+            .returning(Goal::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::GOAL, goal.id, user_id, &goal))?;
+        Ok::<_, diesel::result::Error>(goal)
+    })?;
+
+    Ok(goal)
+}
+
+pub fn update_goal(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid, update_goal: UpdateGoal, audit: &AuditContext) -> Result<Goal, AppError> {
+    if let Some(name) = &update_goal.name {
+        validate_name(name)?;
+    }
+    if let Some(target_amount) = update_goal.target_amount {
+        validate_target(target_amount)?;
+    }
+
+    let goal = connection.transaction(|connection| {
+        let before = goals::table
+            .find(goal_id)
+            .filter(goals::user_id.eq(user_id))
+            .select(Goal::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let now = Utc::now().naive_utc();
+        let goal = diesel::update(goals::table.find(goal_id))
+            .set((update_goal, goals::updated_at.eq(now)))
+            .returning(Goal::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::GOAL, goal.id, user_id, &before, &goal))?;
+        Ok::<_, diesel::result::Error>(goal)
+    })?;
+
+    Ok(goal)
+}
+
+/// Delete a goal together with its contributions
+pub fn delete_goal(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid, audit: &AuditContext) -> Result<Goal, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let goal = diesel::delete(goals::table.find(goal_id).filter(goals::user_id.eq(user_id)))
+            .returning(Goal::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::GOAL, goal.id, user_id, &goal))?;
+        Ok(goal)
+    })
+}
+
+/// Contributions to one of the user's goals, oldest first
+pub fn get_contributions(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid) -> Result<Vec<GoalContribution>, diesel::result::Error> {
+    let goal = get_goal(connection, user_id, goal_id)?;
+
+    goal_contributions::table
+        .filter(goal_contributions::goal_id.eq(goal.id))
+        .order((goal_contributions::date.asc(), goal_contributions::created_at.asc()))
+        .select(GoalContribution::as_select())
+        .load(connection)
+}
+
+pub fn add_contribution(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid, new_contribution: NewGoalContribution, audit: &AuditContext) -> Result<GoalContribution, AppError> {
+    if new_contribution.amount.is_zero() {
+        return Err(AppError::Validation("Contribution amount must not be zero".to_string()));
+    }
+
+    let contribution = connection.transaction(|connection| {
+        let goal = get_goal(connection, user_id, goal_id)?;
+
+        let contribution = diesel::insert_into(goal_contributions::table)
+            .values(new_contribution.into_contribution(goal.id))
+            .returning(GoalContribution::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::GOAL_CONTRIBUTION, contribution.id, user_id, &contribution))?;
+        Ok::<_, diesel::result::Error>(contribution)
+    })?;
+
+    Ok(contribution)
+}
+
+pub fn delete_contribution(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid, contribution_id: Uuid, audit: &AuditContext) -> Result<GoalContribution, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let goal = get_goal(connection, user_id, goal_id)?;
+
+        let contribution = diesel::delete(
+            goal_contributions::table
+                .find(contribution_id)
+                .filter(goal_contributions::goal_id.eq(goal.id)),
+        )
+        .returning(GoalContribution::as_returning())
+        .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::GOAL_CONTRIBUTION, contribution.id, user_id, &contribution))?;
+        Ok(contribution)
+    })
+}
+
+/// Progress of one of the user's goals as of today
+pub fn get_progress(connection: &mut DbConnection, user_id: Uuid, goal_id: Uuid) -> Result<GoalProgress, diesel::result::Error> {
+    let goal = get_goal(connection, user_id, goal_id)?;
+
+    let saved_amount = goal_contributions::table
+        .filter(goal_contributions::goal_id.eq(goal.id))
+        .select(dsl::sum(goal_contributions::amount))
+        .first::<Option<Decimal>>(connection)?
+        .unwrap_or(Decimal::ZERO);
+
+    Ok(calculate_progress(&goal, saved_amount, Utc::now().date_naive()))
+}
+
+fn calculate_progress(goal: &Goal, saved_amount: Decimal, today: NaiveDate) -> GoalProgress {
+    let remaining_amount = (goal.target_amount - saved_amount).max(Decimal::ZERO);
+    let percent_complete = (saved_amount.max(Decimal::ZERO) * Decimal::ONE_HUNDRED / goal.target_amount)
+        .min(Decimal::ONE_HUNDRED)
+        .round_dp(2);
+
+    let months_remaining = goal.deadline.map(|deadline| months_until(today, deadline));
+    let required_monthly_saving = months_remaining.map(|months| {
+        if months == 0 {
+            remaining_amount
+        } else {
+            // Round up to the cent so saving this much every month always
+            // reaches the target
+            (remaining_amount / Decimal::from(months)).round_dp_with_strategy(2, RoundingStrategy::AwayFromZero)
+        }
+    });
+
+    GoalProgress {
+        goal_id: goal.id,
+        target_amount: goal.target_amount,
+        saved_amount,
+        remaining_amount,
+        percent_complete,
+        achieved: remaining_amount.is_zero(),
+        deadline: goal.deadline,
+        months_remaining,
+        required_monthly_saving,
+    }
+}
+
+/// Calendar months from `today` up to and including the deadline's month,
+/// or 0 once the deadline has passed
+fn months_until(today: NaiveDate, deadline: NaiveDate) -> u32 {
+    if deadline < today {
+        return 0;
+    }
+    let months = (deadline.year() - today.year()) * 12 + deadline.month() as i32 - today.month() as i32;
+    months as u32 + 1
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::Validation("Goal name must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_target(target_amount: Decimal) -> Result<(), AppError> {
+    if target_amount <= Decimal::ZERO {
+        return Err(AppError::Validation("Target amount must be positive".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn goal(target_amount: Decimal, deadline: Option<NaiveDate>) -> Goal {
+        let now = Utc::now().naive_utc();
+        Goal {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "Emergency fund".to_string(),
+            target_amount,
+            deadline,
+            account: None,
+            category: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn splits_remaining_amount_over_months_left() {
+        let progress = calculate_progress(&goal(Decimal::new(1000, 0), Some(date(2025, 6, 30))), Decimal::new(250, 0), date(2025, 1, 15));
+
+        assert_eq!(progress.remaining_amount, Decimal::new(750, 0));
+        assert_eq!(progress.percent_complete, Decimal::new(2500, 2));
+        assert_eq!(progress.months_remaining, Some(6));
+        assert_eq!(progress.required_monthly_saving, Some(Decimal::new(12500, 2)));
+        assert!(!progress.achieved);
+    }
+
+    #[test]
+    fn rounds_monthly_saving_up_to_the_cent() {
+        let progress = calculate_progress(&goal(Decimal::new(1000, 0), Some(date(2025, 3, 1))), Decimal::ZERO, date(2025, 1, 31));
+
+        assert_eq!(progress.months_remaining, Some(3));
+        assert_eq!(progress.required_monthly_saving, Some(Decimal::new(33334, 2)));
+        assert_eq!(progress.percent_complete, Decimal::ZERO);
+    }
+
+    #[test]
+    fn caps_progress_once_target_is_reached() {
+        let progress = calculate_progress(&goal(Decimal::new(1000, 0), None), Decimal::new(1200, 0), date(2025, 1, 15));
+
+        assert_eq!(progress.remaining_amount, Decimal::ZERO);
+        assert_eq!(progress.percent_complete, Decimal::ONE_HUNDRED);
+        assert!(progress.achieved);
+        assert_eq!(progress.months_remaining, None);
+        assert_eq!(progress.required_monthly_saving, None);
+    }
+
+    #[test]
+    fn asks_for_the_whole_remainder_after_the_deadline() {
+        let progress = calculate_progress(&goal(Decimal::new(1000, 0), Some(date(2024, 12, 31))), Decimal::new(400, 0), date(2025, 1, 15));
+
+        assert_eq!(progress.months_remaining, Some(0));
+        assert_eq!(progress.required_monthly_saving, Some(Decimal::new(600, 0)));
+    }
+
+    #[test]
+    fn counts_months_across_year_boundaries() {
+        assert_eq!(months_until(date(2024, 11, 20), date(2025, 2, 1)), 4);
+        assert_eq!(months_until(date(2025, 1, 15), date(2025, 1, 15)), 1);
+        assert_eq!(months_until(date(2025, 1, 15), date(2025, 1, 14)), 0);
+    }
+}
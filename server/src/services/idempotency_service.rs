@@ -0,0 +1,167 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::database::db_connection::DbConnection;
+use crate::models::idempotency::{IdempotencyKey, StoredResponse};
+use crate::models::schema::idempotency_keys;
+
+/// How long a claim on a key holds without being renewed. The request that
+/// claimed it renews it while it runs, see [`renew`], so only the claim of a
+/// request that never completed (e.g. the server stopped mid-request) runs
+/// out and lets the client retry.
+pub const LEASE_SECONDS: i64 = 60;
+
+/// What to do with a request that carries an idempotency key
+#[derive(Debug)]
+pub enum Claim {
+    /// First use of the key: process the request and store its response
+    Acquired,
+    /// The key was used before for the same request: send this response again
+    Replay(StoredResponse),
+    /// The first request with this key is still being processed
+    InProgress,
+    /// The key was used before for a different request
+    Mismatch,
+}
+
+/// Claim a key for a request, or find out how it was used before
+pub fn claim(connection: &mut DbConnection, user_id: Uuid, key: &str, request_hash: &str, ttl: Duration, now: NaiveDateTime) -> Result<Claim, diesel::result::Error> {
+    connection.transaction(|connection| {
+        // Expired keys and abandoned claims are free to be used again
+        diesel::delete(
+            idempotency_keys::table
+                .filter(idempotency_keys::user_id.eq(user_id))
+                .filter(idempotency_keys::idempotency_key.eq(key))
+                .filter(
+                    idempotency_keys::expires_at
+                        .lt(now)
+                        .or(idempotency_keys::response_status.is_null().and(idempotency_keys::leased_until.lt(now))),
+                ),
+        )
+        .execute(connection)?;
+
+        let inserted = diesel::insert_into(idempotency_keys::table)
+            .values(&IdempotencyKey {
+                user_id,
+                idempotency_key: key.to_string(),
+                request_hash: request_hash.to_string(),
+                response_status: None,
+                response_content_type: None,
+                response_etag: None,
+                response_body: None,
+                created_at: now,
+                expires_at: now + ttl,
+                leased_until: now + Duration::seconds(LEASE_SECONDS),
+            })
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+
+        if inserted > 0 {
+            return Ok(Claim::Acquired);
+        }
+
+        let existing = idempotency_keys::table
+            .find((user_id, key))
+            .select(IdempotencyKey::as_select())
+            .first(connection)?;
+
+        if existing.request_hash != request_hash {
+            return Ok(Claim::Mismatch);
+        }
+
+        Ok(match (existing.response_status, existing.response_body) {
+            (Some(status), Some(body)) => Claim::Replay(StoredResponse {
+                status: status as u16,
+                content_type: existing.response_content_type,
+                etag: existing.response_etag,
+                body,
+            }),
+            _ => Claim::InProgress,
+        })
+    })
+}
+
+/// Extend the claim of a request that is still running
+pub fn renew(connection: &mut DbConnection, user_id: Uuid, key: &str, now: NaiveDateTime) -> Result<(), diesel::result::Error> {
+    diesel::update(
+        idempotency_keys::table
+            .find((user_id, key))
+            .filter(idempotency_keys::response_status.is_null()),
+    )
+    .set(idempotency_keys::leased_until.eq(now + Duration::seconds(LEASE_SECONDS)))
+    .execute(connection)?;
+    Ok(())
+}
+
+/// Store the response of the request that claimed the key
+pub fn complete(connection: &mut DbConnection, user_id: Uuid, key: &str, response: &StoredResponse) -> Result<(), diesel::result::Error> {
+    diesel::update(idempotency_keys::table.find((user_id, key)))
+        .set((
+            idempotency_keys::response_status.eq(response.status as i32),
+            idempotency_keys::response_content_type.eq(&response.content_type),
+            idempotency_keys::response_etag.eq(&response.etag),
+            idempotency_keys::response_body.eq(&response.body),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Give up a claim without storing a response, so a retry is processed anew
+pub fn release(connection: &mut DbConnection, user_id: Uuid, key: &str) -> Result<(), diesel::result::Error> {
+    diesel::delete(idempotency_keys::table.find((user_id, key))).execute(connection)?;
+    Ok(())
+}
+
+/// Remove keys whose replay window ended before `now`
+pub fn purge_expired(connection: &mut DbConnection, now: NaiveDateTime) -> Result<usize, diesel::result::Error> {
+    diesel::delete(idempotency_keys::table.filter(idempotency_keys::expires_at.lt(now))).execute(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+
+    fn response() -> StoredResponse {
+        StoredResponse {
+            status: 201,
+            content_type: Some("application/json".to_string()),
+            etag: None,
+            body: b"{}".to_vec(),
+        }
+    }
+
+    #[test]
+    fn claim_is_held_while_it_is_renewed() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let ttl = Duration::hours(24);
+        let start = chrono::Utc::now().naive_utc();
+        let lease = Duration::seconds(LEASE_SECONDS);
+
+        assert!(matches!(claim(&mut conn, user.id, "slow", "hash", ttl, start).unwrap(), Claim::Acquired));
+        renew(&mut conn, user.id, "slow", start + lease / 2).unwrap();
+        assert!(matches!(claim(&mut conn, user.id, "slow", "hash", ttl, start + lease + Duration::seconds(1)).unwrap(), Claim::InProgress));
+
+        // Once the request stops renewing it, the claim runs out
+        let abandoned = start + lease * 2;
+        assert!(matches!(claim(&mut conn, user.id, "slow", "hash", ttl, abandoned).unwrap(), Claim::Acquired));
+    }
+
+    #[test]
+    fn completed_claim_is_replayed_until_it_expires() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let ttl = Duration::hours(24);
+        let start = chrono::Utc::now().naive_utc();
+
+        assert!(matches!(claim(&mut conn, user.id, "done", "hash", ttl, start).unwrap(), Claim::Acquired));
+        complete(&mut conn, user.id, "done", &response()).unwrap();
+
+        let later = start + Duration::hours(1);
+        assert!(matches!(claim(&mut conn, user.id, "done", "hash", ttl, later).unwrap(), Claim::Replay(stored) if stored.status == 201));
+        assert!(matches!(claim(&mut conn, user.id, "done", "other", ttl, later).unwrap(), Claim::Mismatch));
+        assert!(matches!(claim(&mut conn, user.id, "done", "other", ttl, start + ttl + Duration::seconds(1)).unwrap(), Claim::Acquired));
+    }
+}
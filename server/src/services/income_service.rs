@@ -1,24 +1,39 @@
+use actix_web::http::StatusCode;
 use diesel::prelude::*;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use crate::models::user::User;
 use diesel::result::Error;
 
+use crate::config::errors::AppError;
+use crate::config::etag;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::batch::{BatchOperation, BatchRequest, BatchResponse};
 use crate::models::income::{Income, NewIncome, UpdateIncome, IncomeWithUser};
 use crate::models::schema::{incomes, users};
 use crate::database::db_connection::DbConnection;
+use crate::services::{audit_service, batch_service, net_worth_service};
 
-pub fn get_all_incomes(connection: &mut DbConnection) -> Result<Vec<IncomeWithUser>, Error> {
-    incomes::table
+/// Incomes joined with their owner, optionally restricted to a single user
+pub fn get_all_incomes(connection: &mut DbConnection, owner_id: Option<Uuid>) -> Result<Vec<IncomeWithUser>, Error> {
+    let mut query = incomes::table
         .inner_join(users::table)
+        .filter(incomes::deleted_at.is_null())
         .select((incomes::all_columns, users::all_columns))
+        .into_boxed();
+
+    if let Some(owner_id) = owner_id {
+        query = query.filter(incomes::user_id.eq(owner_id));
+    }
+
+    query
         .load::<(Income, User)>(connection)
         .map(|results| {
             results
                 .into_iter()
                 .map(|(income, user)| IncomeWithUser {
                     income,
-                    user,
+                    user: user.into(),
                 })
                 .collect()
         })
@@ -27,37 +42,466 @@ pub fn get_all_incomes(connection: &mut DbConnection) -> Result<Vec<IncomeWithUs
 pub fn get_incomes_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Income>, diesel::result::Error> {
     incomes::table
         .filter(incomes::user_id.eq(user_id))
+        .filter(incomes::deleted_at.is_null())
         .select(Income::as_select())
         .load(connection)
 }
 
-pub fn create_income(connection: &mut DbConnection, new_income: NewIncome) -> Result<Income, diesel::result::Error> {
-    let now = Utc::now().naive_utc();
-    let income = diesel::insert_into(incomes::table)
-        .values((
-            incomes::id.eq(Uuid::new_v4()),
-            incomes::user_id.eq(new_income.user_id),
-            incomes::source.eq(new_income.source),
-            incomes::amount.eq(new_income.amount),
-            incomes::date.eq(new_income.date),
-            incomes::description.eq(new_income.description),
-            incomes::created_at.eq(now),
-            incomes::updated_at.eq(now),
-        ))
-        .get_result::<Income>(connection)?;
-
-    Ok(income)
-}
-
-pub fn update_income(connection: &mut DbConnection, income_id: Uuid, update_income: UpdateIncome) -> Result<Income, diesel::result::Error> {
-    diesel::update(incomes::table)
-        .filter(incomes::id.eq(income_id))
-        .set(update_income)
-        .get_result(connection)
-}
-
-pub fn delete_income(connection: &mut DbConnection, income_id: Uuid) -> Result<Income, diesel::result::Error> {
-    diesel::delete(incomes::table)
-        .filter(incomes::id.eq(income_id))
-        .get_result(connection)
-}
\ No newline at end of file
+pub fn get_income_by_id(connection: &mut DbConnection, income_id: Uuid) -> Result<Income, diesel::result::Error> {
+    incomes::table
+        .find(income_id)
+        .filter(incomes::deleted_at.is_null())
+        .select(Income::as_select())
+        .first(connection)
+}
+
+/// Deleted incomes that have not been purged yet, most recently deleted
+/// first, optionally restricted to a single user
+pub fn get_deleted_incomes(connection: &mut DbConnection, owner_id: Option<Uuid>) -> Result<Vec<Income>, diesel::result::Error> {
+    let mut query = incomes::table
+        .filter(incomes::deleted_at.is_not_null())
+        .order(incomes::deleted_at.desc())
+        .select(Income::as_select())
+        .into_boxed();
+
+    if let Some(owner_id) = owner_id {
+        query = query.filter(incomes::user_id.eq(owner_id));
+    }
+
+    query.load::<Income>(connection)
+}
+
+pub fn get_deleted_income_by_id(connection: &mut DbConnection, income_id: Uuid) -> Result<Income, diesel::result::Error> {
+    incomes::table
+        .find(income_id)
+        .filter(incomes::deleted_at.is_not_null())
+        .select(Income::as_select())
+        .first(connection)
+}
+
+/// An income whether or not it is in the trash
+pub fn get_income_including_deleted(connection: &mut DbConnection, income_id: Uuid) -> Result<Income, diesel::result::Error> {
+    incomes::table
+        .find(income_id)
+        .select(Income::as_select())
+        .first(connection)
+}
+
+pub fn create_income(connection: &mut DbConnection, new_income: NewIncome, audit: &AuditContext) -> Result<Income, AppError> {
+    validate_source(&new_income.source)?;
+
+    connection.transaction(|connection| {
+        let now = Utc::now().naive_utc();
+        let income = diesel::insert_into(incomes::table)
+            .values((
+                incomes::id.eq(Uuid::new_v4()),
+                incomes::user_id.eq(new_income.user_id),
+                incomes::source.eq(new_income.source),
+                incomes::amount.eq(new_income.amount),
+                incomes::date.eq(new_income.date),
+                incomes::description.eq(new_income.description),
+                incomes::account.eq(new_income.account),
+                incomes::created_at.eq(now),
+                incomes::updated_at.eq(now),
+            ))
+            .get_result::<Income>(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, income.user_id, income.date)?;
+        audit_service::record(connection, audit, AuditChange::created(entity::INCOME, income.id, income.user_id, &income))?;
+        Ok(income)
+    })
+}
+
+/// Update an income if it is still at one of the `expected_versions`
+/// (its `updated_at`, see [`etag`]); `None` skips the check
+pub fn update_income(connection: &mut DbConnection, income_id: Uuid, update_income: UpdateIncome, expected_versions: Option<&[NaiveDateTime]>, audit: &AuditContext) -> Result<Income, AppError> {
+    if let Some(source) = &update_income.source {
+        validate_source(source)?;
+    }
+
+    connection.transaction(|connection| {
+        // Lock the row so the version check and the update cannot interleave
+        // with a concurrent update
+        let before = incomes::table
+            .find(income_id)
+            .filter(incomes::deleted_at.is_null())
+            .select(Income::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "editing")?;
+        etag::ensure_version(before.updated_at, expected_versions)?;
+
+        let income: Income = diesel::update(incomes::table.find(income_id))
+            .set((update_income, incomes::updated_at.eq(Utc::now().naive_utc())))
+            .get_result(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, income.user_id, before.date.min(income.date))?;
+        audit_service::record(connection, audit, AuditChange::updated(entity::INCOME, income.id, income.user_id, &before, &income))?;
+        Ok(income)
+    })
+}
+
+/// Move an income to the trash if it is still at one of the
+/// `expected_versions` (`None` skips the check). It can be restored until it
+/// is purged.
+pub fn delete_income(connection: &mut DbConnection, income_id: Uuid, expected_versions: Option<&[NaiveDateTime]>, audit: &AuditContext) -> Result<Income, AppError> {
+    connection.transaction(|connection| {
+        let before: Income = incomes::table
+            .find(income_id)
+            .filter(incomes::deleted_at.is_null())
+            .select(Income::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "deleting")?;
+        etag::ensure_version(before.updated_at, expected_versions)?;
+
+        let income: Income = diesel::update(incomes::table.find(income_id))
+            .set(incomes::deleted_at.eq(Utc::now().naive_utc()))
+            .get_result(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, income.user_id, income.date)?;
+        audit_service::record(connection, audit, AuditChange::deleted(entity::INCOME, income.id, income.user_id, &income))?;
+        Ok(income)
+    })
+}
+
+pub fn restore_income(connection: &mut DbConnection, income_id: Uuid, audit: &AuditContext) -> Result<Income, AppError> {
+    connection.transaction(|connection| {
+        let before: Income = incomes::table
+            .find(income_id)
+            .filter(incomes::deleted_at.is_not_null())
+            .select(Income::as_select())
+            .for_update()
+            .first(connection)?;
+        ensure_unlocked(&before, "restoring")?;
+
+        let income: Income = diesel::update(incomes::table.find(income_id))
+            .set(incomes::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result(connection)?;
+
+        net_worth_service::invalidate_snapshots(connection, income.user_id, income.date)?;
+        audit_service::record(connection, audit, AuditChange::restored(entity::INCOME, income.id, income.user_id, &income))?;
+        Ok(income)
+    })
+}
+
+/// Allow changes to a reconciled income again. It stays cleared in its
+/// reconciliation. Incomes in the trash can be unlocked too, so they can be
+/// restored.
+pub fn unlock_income(connection: &mut DbConnection, income_id: Uuid, audit: &AuditContext) -> Result<Income, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let before: Income = incomes::table
+            .find(income_id)
+            .select(Income::as_select())
+            .for_update()
+            .first(connection)?;
+        if before.reconciled_at.is_none() {
+            return Ok(before);
+        }
+
+        let income: Income = diesel::update(incomes::table.find(income_id))
+            .set((incomes::reconciled_at.eq(None::<NaiveDateTime>), incomes::updated_at.eq(Utc::now().naive_utc())))
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::INCOME, income.id, income.user_id, &before, &income))?;
+        Ok(income)
+    })
+}
+
+/// Permanently remove incomes that were deleted before `deleted_before`
+pub fn purge_deleted_incomes(connection: &mut DbConnection, deleted_before: NaiveDateTime) -> Result<usize, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let purged: Vec<Income> = diesel::delete(incomes::table.filter(incomes::deleted_at.lt(deleted_before)))
+            .get_results(connection)?;
+
+        for income in &purged {
+            audit_service::record(connection, &AuditContext::default(), AuditChange::purged(entity::INCOME, income.id, income.user_id, income))?;
+        }
+        Ok(purged.len())
+    })
+}
+
+/// Apply a batch of creates, updates and deletes, see [`batch_service::run`]
+///
+/// `authorize` is called with the owner of every income an operation touches
+/// and fails the operation if the caller may not access it.
+pub fn run_batch<A>(connection: &mut DbConnection, request: BatchRequest<NewIncome, UpdateIncome>, authorize: A, audit: &AuditContext) -> Result<BatchResponse<Income>, AppError>
+where
+    A: Fn(Uuid) -> Result<(), AppError>,
+{
+    batch_service::run(connection, request.mode, request.operations, |connection, operation| match operation {
+        BatchOperation::Create { data } => {
+            authorize(data.user_id)?;
+            Ok((StatusCode::CREATED, create_income(connection, data, audit)?))
+        }
+        BatchOperation::Update { id, version, data } => {
+            authorize(get_income_by_id(connection, id)?.user_id)?;
+            Ok((StatusCode::OK, update_income(connection, id, data, version.versions().as_deref(), audit)?))
+        }
+        BatchOperation::Delete { id, version } => {
+            authorize(get_income_by_id(connection, id)?.user_id)?;
+            Ok((StatusCode::OK, delete_income(connection, id, version.versions().as_deref(), audit)?))
+        }
+    })
+}
+
+/// Reconciled incomes cannot change until they are unlocked, see [`unlock_income`]
+pub fn ensure_unlocked(income: &Income, action: &str) -> Result<(), AppError> {
+    if income.reconciled_at.is_some() {
+        return Err(AppError::Conflict(format!("Income is reconciled; unlock it before {}", action)));
+    }
+    Ok(())
+}
+
+fn validate_source(source: &str) -> Result<(), AppError> {
+    if source.trim().is_empty() {
+        return Err(AppError::Validation("Source must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+    use crate::models::batch::{BatchMode, ExpectedVersion};
+    use rust_decimal::Decimal;
+
+    fn income(reconciled_at: Option<NaiveDateTime>) -> Income {
+        let now = Utc::now().naive_utc();
+        Income {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            source: "Salary".to_string(),
+            amount: Decimal::new(500000, 2),
+            date: now.date(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            account: Some("Checking".to_string()),
+            reconciled_at,
+        }
+    }
+
+    #[test]
+    fn refuses_changes_to_reconciled_incomes() {
+        let error = ensure_unlocked(&income(Some(Utc::now().naive_utc())), "deleting").unwrap_err();
+
+        assert!(matches!(&error, AppError::Conflict(message) if message == "Income is reconciled; unlock it before deleting"));
+        assert_eq!(actix_web::ResponseError::status_code(&error), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn allows_changes_to_unreconciled_incomes() {
+        assert!(ensure_unlocked(&income(None), "restoring").is_ok());
+    }
+
+    fn stored_income(connection: &mut DbConnection, user_id: Uuid) -> Income {
+        let new_income = NewIncome {
+            user_id,
+            source: "Salary".to_string(),
+            amount: Decimal::from(5000),
+            date: Utc::now().date_naive(),
+            description: None,
+            account: None,
+        };
+        create_income(connection, new_income, &AuditContext::default()).unwrap()
+    }
+
+    #[test]
+    fn lists_everyones_incomes_only_without_an_owner() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let owner = test_database::user(&mut conn);
+        let other = test_database::user(&mut conn);
+        let own = stored_income(&mut conn, owner.id);
+        let others = stored_income(&mut conn, other.id);
+
+        let listed: Vec<Uuid> = get_all_incomes(&mut conn, Some(owner.id)).unwrap().iter().map(|income| income.income.id).collect();
+        assert_eq!(listed, [own.id]);
+
+        let listed: Vec<Uuid> = get_all_incomes(&mut conn, None).unwrap().iter().map(|income| income.income.id).collect();
+        assert!(listed.contains(&own.id) && listed.contains(&others.id));
+    }
+
+    #[test]
+    fn deleted_incomes_stay_in_the_trash_until_restored() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let owner = test_database::user(&mut conn);
+        let other = test_database::user(&mut conn);
+        let income = stored_income(&mut conn, owner.id);
+        let audit = AuditContext::default();
+
+        let deleted = delete_income(&mut conn, income.id, None, &audit).unwrap();
+
+        assert!(deleted.deleted_at.is_some());
+        assert!(get_incomes_by_user_id(&mut conn, owner.id).unwrap().is_empty());
+        assert!(get_all_incomes(&mut conn, None).unwrap().iter().all(|listed| listed.income.id != income.id));
+        assert!(matches!(get_income_by_id(&mut conn, income.id), Err(Error::NotFound)));
+        assert!(matches!(delete_income(&mut conn, income.id, None, &audit), Err(AppError::NotFound(_))));
+        assert_eq!(get_deleted_incomes(&mut conn, Some(owner.id)).unwrap()[0].id, income.id);
+        assert!(get_deleted_incomes(&mut conn, Some(other.id)).unwrap().is_empty());
+
+        let restored = restore_income(&mut conn, income.id, &audit).unwrap();
+
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(get_incomes_by_user_id(&mut conn, owner.id).unwrap()[0].id, income.id);
+        assert!(get_deleted_incomes(&mut conn, Some(owner.id)).unwrap().is_empty());
+        assert!(matches!(restore_income(&mut conn, income.id, &audit), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn purges_only_incomes_deleted_before_the_retention_cutoff() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let (expired, recent) = (stored_income(&mut conn, user.id), stored_income(&mut conn, user.id));
+        let kept = stored_income(&mut conn, user.id);
+        let now = Utc::now().naive_utc();
+        for income in [&expired, &recent] {
+            delete_income(&mut conn, income.id, None, &AuditContext::default()).unwrap();
+        }
+        diesel::update(incomes::table.find(expired.id))
+            .set(incomes::deleted_at.eq(now - chrono::Duration::days(31)))
+            .execute(&mut conn)
+            .unwrap();
+
+        let purged = purge_deleted_incomes(&mut conn, now - chrono::Duration::days(30)).unwrap();
+
+        assert_eq!(purged, 1);
+        assert!(matches!(get_income_including_deleted(&mut conn, expired.id), Err(Error::NotFound)));
+        assert!(get_deleted_income_by_id(&mut conn, recent.id).is_ok());
+        assert!(get_income_by_id(&mut conn, kept.id).is_ok());
+    }
+
+    #[test]
+    fn patches_leave_absent_fields_and_clear_null_ones() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let income = stored_income(&mut conn, user.id);
+        let audit = AuditContext::default();
+        let mut patch = |json: &str| update_income(&mut conn, income.id, serde_json::from_str(json).unwrap(), None, &audit).unwrap();
+
+        let set = patch(r#"{"description": "March", "account": "Checking"}"#);
+        assert_eq!((set.description.as_deref(), set.account.as_deref()), (Some("March"), Some("Checking")));
+
+        let untouched = patch(r#"{"amount": 6000}"#);
+        assert_eq!((untouched.description.as_deref(), untouched.account.as_deref()), (Some("March"), Some("Checking")));
+        assert_eq!(untouched.amount, Decimal::from(6000));
+
+        let cleared = patch(r#"{"description": null}"#);
+        assert_eq!((cleared.description, cleared.account.as_deref()), (None, Some("Checking")));
+    }
+
+    #[test]
+    fn the_database_stamps_updated_at() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let income = stored_income(&mut conn, user.id);
+
+        let unchanged: Income = diesel::update(incomes::table.find(income.id))
+            .set(incomes::source.eq(&income.source))
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(unchanged.updated_at, income.updated_at);
+
+        let changed: Income = diesel::update(incomes::table.find(income.id))
+            .set(incomes::source.eq("Bonus"))
+            .get_result(&mut conn)
+            .unwrap();
+        assert_ne!(changed.updated_at, income.updated_at);
+
+        let explicit = income.updated_at - chrono::Duration::days(1);
+        let stamped: Income = diesel::update(incomes::table.find(income.id))
+            .set((incomes::source.eq("Salary"), incomes::updated_at.eq(explicit)))
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(stamped.updated_at, explicit);
+    }
+
+    fn raise(amount: i64) -> UpdateIncome {
+        UpdateIncome {
+            source: None,
+            amount: Some(Decimal::from(amount)),
+            date: None,
+            description: None,
+            account: None,
+        }
+    }
+
+    /// An update at the current version, a delete at a stale one and a create
+    fn mixed_batch(mode: BatchMode, first: &Income, second: &Income) -> BatchRequest<NewIncome, UpdateIncome> {
+        BatchRequest {
+            mode,
+            operations: vec![
+                BatchOperation::Update { id: first.id, version: ExpectedVersion::At(first.updated_at), data: raise(6000) },
+                BatchOperation::Delete { id: second.id, version: ExpectedVersion::At(second.updated_at - chrono::Duration::seconds(1)) },
+                BatchOperation::Create {
+                    data: NewIncome {
+                        user_id: first.user_id,
+                        source: "Bonus".to_string(),
+                        amount: Decimal::from(100),
+                        date: Utc::now().date_naive(),
+                        description: None,
+                        account: None,
+                    },
+                },
+            ],
+        }
+    }
+
+    fn statuses(response: &BatchResponse<Income>) -> Vec<u16> {
+        response.results.iter().map(|result| result.status).collect()
+    }
+
+    #[test]
+    fn atomic_batch_rolls_back_when_an_item_fails() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let (first, second) = (stored_income(&mut conn, user.id), stored_income(&mut conn, user.id));
+
+        let response = run_batch(&mut conn, mixed_batch(BatchMode::Atomic, &first, &second), |_| Ok(()), &AuditContext::default()).unwrap();
+
+        assert!(!response.committed);
+        assert_eq!(statuses(&response), vec![424, 412, 424]);
+        assert_eq!((response.succeeded, response.failed), (0, 1));
+        assert_eq!(get_income_by_id(&mut conn, first.id).unwrap().amount, Decimal::from(5000));
+        assert_eq!(get_all_incomes(&mut conn, Some(user.id)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn partial_batch_reports_each_item() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let (first, second) = (stored_income(&mut conn, user.id), stored_income(&mut conn, user.id));
+
+        let response = run_batch(&mut conn, mixed_batch(BatchMode::Partial, &first, &second), |_| Ok(()), &AuditContext::default()).unwrap();
+
+        assert!(response.committed);
+        assert_eq!(statuses(&response), vec![200, 412, 201]);
+        assert_eq!((response.succeeded, response.failed), (2, 1));
+        assert!(response.results[1].error.as_deref().unwrap().contains("modified by another request"));
+        assert_eq!(get_income_by_id(&mut conn, first.id).unwrap().amount, Decimal::from(6000));
+        assert!(get_income_by_id(&mut conn, second.id).unwrap().deleted_at.is_none());
+        assert_eq!(get_all_incomes(&mut conn, Some(user.id)).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn stale_batch_versions_fail_per_item() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let user = test_database::user(&mut conn);
+        let income = stored_income(&mut conn, user.id);
+        let read = income.updated_at;
+
+        let update = |version| BatchRequest {
+            mode: BatchMode::Partial,
+            operations: vec![BatchOperation::Update { id: income.id, version, data: raise(6000) }],
+        };
+        let first = run_batch(&mut conn, update(ExpectedVersion::At(read)), |_| Ok(()), &AuditContext::default()).unwrap();
+        let again = run_batch(&mut conn, update(ExpectedVersion::At(read)), |_| Ok(()), &AuditContext::default()).unwrap();
+        let any = run_batch(&mut conn, update(ExpectedVersion::Any), |_| Ok(()), &AuditContext::default()).unwrap();
+
+        assert_eq!(statuses(&first), vec![200]);
+        assert_eq!(statuses(&again), vec![412]);
+        assert_eq!(statuses(&any), vec![200]);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::config::jwt::JwtKeys;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditAction, AuditLogEntry};
+use crate::models::auth::AuthError;
+use crate::models::live_event::{EventStreamClaims, LiveEvent, StreamPosition};
+use crate::models::schema::audit_log;
+use crate::models::webhook::WebhookEvent;
+use crate::services::webhook_service;
+
+/// Events a stream may fall behind by before it is closed. The client then
+/// reconnects and catches up through `Last-Event-ID`.
+const STREAM_BUFFER: usize = 256;
+/// Most changes read by one poll of the live events job
+const MAX_BATCH: i64 = 500;
+/// Most changes sent to a reconnecting client
+const MAX_REPLAY: i64 = 500;
+/// How long a stream token can be used to open a stream
+pub const STREAM_TOKEN_SECONDS: i64 = 60;
+
+struct State {
+    /// Every change up to here has been published
+    position: StreamPosition,
+    subscribers: HashMap<Uuid, Vec<mpsc::Sender<LiveEvent>>>,
+}
+
+/// The open event streams of each user
+///
+/// Changes are not published by the services writing them but by the live
+/// events job once their audit entries are committed, so a change that is
+/// rolled back is never announced. The job reads the audit log in commit
+/// order from the position it last published, which is shared with the
+/// streams under one lock: a stream gets everything after the position it
+/// subscribed at, and nothing before.
+pub struct LiveEvents {
+    state: Mutex<State>,
+}
+
+impl LiveEvents {
+    /// Streams of the changes after `position`
+    pub fn starting_at(position: StreamPosition) -> Self {
+        Self {
+            state: Mutex::new(State {
+                position,
+                subscribers: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Open a stream of the user's changes after the returned position
+    pub fn subscribe(&self, user_id: Uuid) -> (StreamPosition, mpsc::Receiver<LiveEvent>) {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        let mut state = self.state.lock().expect("live events lock");
+        state.subscribers.entry(user_id).or_default().push(sender);
+        (state.position, receiver)
+    }
+
+    /// Position the job should read on from
+    pub fn position(&self) -> StreamPosition {
+        self.state.lock().expect("live events lock").position
+    }
+
+    /// Move to `position` without reading what lies before it, unless a
+    /// stream is open. Returns whether it moved.
+    pub fn skip_to_if_idle(&self, position: StreamPosition) -> bool {
+        let mut state = self.state.lock().expect("live events lock");
+        state.subscribers.retain(|_, streams| {
+            streams.retain(|stream| !stream.is_closed());
+            !streams.is_empty()
+        });
+        if !state.subscribers.is_empty() {
+            return false;
+        }
+        state.position = position;
+        true
+    }
+
+    /// Send the changes read up to `position` to their owners' open
+    /// streams, closing those that fell too far behind
+    pub fn publish(&self, events: &[LiveEvent], position: StreamPosition) {
+        let mut state = self.state.lock().expect("live events lock");
+        for event in events {
+            if let Some(streams) = state.subscribers.get_mut(&event.owner_id) {
+                streams.retain(|stream| stream.try_send(event.clone()).is_ok());
+            }
+        }
+        state.position = position;
+    }
+}
+
+/// Position just before the oldest transaction that may still be running,
+/// so that everything up to it is committed or rolled back
+pub fn committed_position(connection: &mut DbConnection) -> Result<StreamPosition, diesel::result::Error> {
+    let xmin = diesel::select(sql::<BigInt>("pg_snapshot_xmin(pg_current_snapshot())::text::bigint")).get_result::<i64>(connection)?;
+    Ok((xmin - 1, i64::MAX))
+}
+
+/// Income and expense changes after `after` by finished transactions, in
+/// commit order, with the position they reach. At most a batch is read at a
+/// time; the position says where to read on from.
+pub fn get_changes(connection: &mut DbConnection, after: StreamPosition) -> Result<(Vec<LiveEvent>, StreamPosition), diesel::result::Error> {
+    let committed = committed_position(connection)?;
+    let entries = audit_log::table
+        .filter(audit_log::tx_id.gt(after.0).or(audit_log::tx_id.eq(after.0).and(audit_log::seq.gt(after.1))))
+        .filter(audit_log::tx_id.le(committed.0))
+        .filter(audit_log::entity_type.eq_any([entity::INCOME, entity::EXPENSE]))
+        .order((audit_log::tx_id.asc(), audit_log::seq.asc()))
+        .limit(MAX_BATCH)
+        .select((AuditLogEntry::as_select(), audit_log::tx_id, audit_log::seq))
+        .load::<(AuditLogEntry, i64, i64)>(connection)?;
+
+    let position = match entries.last() {
+        Some((_, tx_id, seq)) if entries.len() as i64 == MAX_BATCH => (*tx_id, *seq),
+        _ => after.max(committed),
+    };
+    Ok((entries.into_iter().filter_map(|(entry, _, _)| to_live_event(entry)).collect(), position))
+}
+
+/// The user's changes after the one with ID `last_event_id` up to `until`,
+/// for a client that reconnects. Nothing is replayed for an unknown ID.
+pub fn get_changes_after(connection: &mut DbConnection, owner_id: Uuid, last_event_id: Uuid, until: StreamPosition) -> Result<Vec<LiveEvent>, diesel::result::Error> {
+    let last_seen = audit_log::table
+        .filter(audit_log::id.eq(last_event_id))
+        .filter(audit_log::owner_id.eq(owner_id))
+        .select((audit_log::tx_id, audit_log::seq))
+        .first::<StreamPosition>(connection)
+        .optional()?;
+    let Some((tx_id, seq)) = last_seen else {
+        return Ok(Vec::new());
+    };
+
+    let entries = audit_log::table
+        .filter(audit_log::owner_id.eq(owner_id))
+        .filter(audit_log::tx_id.gt(tx_id).or(audit_log::tx_id.eq(tx_id).and(audit_log::seq.gt(seq))))
+        .filter(audit_log::tx_id.lt(until.0).or(audit_log::tx_id.eq(until.0).and(audit_log::seq.le(until.1))))
+        .filter(audit_log::entity_type.eq_any([entity::INCOME, entity::EXPENSE]))
+        .order((audit_log::tx_id.asc(), audit_log::seq.asc()))
+        .limit(MAX_REPLAY)
+        .select(AuditLogEntry::as_select())
+        .load(connection)?;
+
+    Ok(entries.into_iter().filter_map(to_live_event).collect())
+}
+
+/// Short-lived token that opens the user's event stream when passed as the
+/// `token` query parameter, for browsers' `EventSource`, which cannot send an
+/// `Authorization` header
+pub fn generate_stream_token(user_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::seconds(STREAM_TOKEN_SECONDS))
+        .expect("valid timestamp")
+        .timestamp() as usize;
+
+    JwtKeys::global().encode(&EventStreamClaims::new(user_id, expiration))
+}
+
+/// Validate a stream token and extract its claims
+pub fn validate_stream_token(token: &str) -> Result<EventStreamClaims, AuthError> {
+    JwtKeys::global()
+        .decode::<EventStreamClaims>(token)
+        .ok()
+        .filter(|claims| claims.purpose == EventStreamClaims::PURPOSE)
+        .ok_or_else(|| AuthError {
+            message: "Invalid or expired stream token".to_string(),
+            code: "INVALID_TOKEN".to_string(),
+        })
+}
+
+fn to_live_event(entry: AuditLogEntry) -> Option<LiveEvent> {
+    let action = AuditAction::ALL.into_iter().find(|action| action.as_str() == entry.action)?;
+    let event = WebhookEvent::for_change(&entry.entity_type, action)?;
+    Some(LiveEvent {
+        id: entry.id,
+        owner_id: entry.owner_id?,
+        event,
+        payload: webhook_service::payload(entry.id, event, entry.created_at, &entry.before_data, &entry.after_data),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(owner_id: Uuid) -> LiveEvent {
+        LiveEvent {
+            id: Uuid::new_v4(),
+            owner_id,
+            event: WebhookEvent::ExpenseCreated,
+            payload: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn subscribe_starts_at_the_published_position() {
+        let live_events = LiveEvents::starting_at((10, 3));
+        let (position, _receiver) = live_events.subscribe(Uuid::new_v4());
+        assert_eq!(position, (10, 3));
+
+        live_events.publish(&[], (12, i64::MAX));
+        let (position, _receiver) = live_events.subscribe(Uuid::new_v4());
+        assert_eq!(position, (12, i64::MAX));
+    }
+
+    #[test]
+    fn publish_sends_each_change_to_its_owner_only() {
+        let live_events = LiveEvents::starting_at((10, 0));
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let (_, mut alice_stream) = live_events.subscribe(alice);
+        let (_, mut bob_stream) = live_events.subscribe(bob);
+
+        let change = event(alice);
+        live_events.publish(std::slice::from_ref(&change), (11, 1));
+
+        assert_eq!(alice_stream.try_recv().unwrap().id, change.id);
+        assert!(alice_stream.try_recv().is_err());
+        assert!(bob_stream.try_recv().is_err());
+        assert_eq!(live_events.position(), (11, 1));
+    }
+
+    #[test]
+    fn position_skips_ahead_only_while_no_one_listens() {
+        let live_events = LiveEvents::starting_at((10, 0));
+        let (_, receiver) = live_events.subscribe(Uuid::new_v4());
+
+        assert!(!live_events.skip_to_if_idle((20, i64::MAX)));
+        assert_eq!(live_events.position(), (10, 0));
+
+        drop(receiver);
+        assert!(live_events.skip_to_if_idle((20, i64::MAX)));
+        assert_eq!(live_events.position(), (20, i64::MAX));
+    }
+
+    #[test]
+    fn stream_that_falls_behind_is_closed() {
+        let live_events = LiveEvents::starting_at((10, 0));
+        let user_id = Uuid::new_v4();
+        let (_, mut receiver) = live_events.subscribe(user_id);
+
+        let changes: Vec<_> = (0..=STREAM_BUFFER).map(|_| event(user_id)).collect();
+        live_events.publish(&changes, (11, 1));
+
+        for _ in 0..STREAM_BUFFER {
+            assert!(receiver.try_recv().is_ok());
+        }
+        assert_eq!(receiver.try_recv().unwrap_err(), mpsc::error::TryRecvError::Disconnected);
+    }
+}
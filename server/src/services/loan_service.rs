@@ -0,0 +1,376 @@
+use chrono::{Months, NaiveDate, Utc};
+use diesel::prelude::*;
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::expense::Expense;
+use crate::models::loan::{AmortizationPeriod, AmortizationSchedule, Loan, LoanPayment, LoanStatus, NewLoan, UpdateLoan};
+use crate::models::schema::{expenses, loan_payments, loans};
+use crate::services::audit_service;
+
+/// Longest supported term, 100 years of monthly installments
+const MAX_TERM_MONTHS: i32 = 1200;
+
+pub fn get_loans_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Loan>, diesel::result::Error> {
+    loans::table
+        .filter(loans::user_id.eq(user_id))
+        .order(loans::created_at.asc())
+        .select(Loan::as_select())
+        .load(connection)
+}
+
+/// One of the user's loans; loans of other users are reported as not found
+pub fn get_loan(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid) -> Result<Loan, diesel::result::Error> {
+    loans::table
+        .find(loan_id)
+        .filter(loans::user_id.eq(user_id))
+        .select(Loan::as_select())
+        .first(connection)
+}
+
+pub fn create_loan(connection: &mut DbConnection, user_id: Uuid, new_loan: NewLoan, audit: &AuditContext) -> Result<Loan, AppError> {
+    let loan = new_loan.into_loan(user_id);
+    validate_loan(&loan)?;
+
+    let loan = connection.transaction(|connection| {
+        let loan = diesel::insert_into(loans::table)
+            .values(&loan)
+            .returning(Loan::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::LOAN, loan.id, user_id, &loan))?;
+        Ok::<_, diesel::result::Error>(loan)
+    })?;
+
+    Ok(loan)
+}
+
+pub fn update_loan(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid, update_loan: UpdateLoan, audit: &AuditContext) -> Result<Loan, AppError> {
+    connection.transaction(|connection| {
+        let before = loans::table
+            .find(loan_id)
+            .filter(loans::user_id.eq(user_id))
+            .select(Loan::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let mut updated = before.clone();
+        if let Some(name) = &update_loan.name {
+            updated.name = name.clone();
+        }
+        updated.principal = update_loan.principal.unwrap_or(updated.principal);
+        updated.annual_rate = update_loan.annual_rate.unwrap_or(updated.annual_rate);
+        updated.term_months = update_loan.term_months.unwrap_or(updated.term_months);
+        validate_loan(&updated)?;
+
+        let now = Utc::now().naive_utc();
+        let loan = diesel::update(loans::table.find(loan_id))
+            .set((update_loan, loans::updated_at.eq(now)))
+            .returning(Loan::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::LOAN, loan.id, user_id, &before, &loan))?;
+        Ok(loan)
+    })
+}
+
+/// Delete a loan. Linked expenses are kept, only the links are removed.
+pub fn delete_loan(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid, audit: &AuditContext) -> Result<Loan, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let loan = diesel::delete(loans::table.find(loan_id).filter(loans::user_id.eq(user_id)))
+            .returning(Loan::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::LOAN, loan.id, user_id, &loan))?;
+        Ok(loan)
+    })
+}
+
+/// Expenses linked to one of the user's loans, oldest first. Expenses in the
+/// trash are left out.
+pub fn get_payments(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid) -> Result<Vec<Expense>, diesel::result::Error> {
+    let loan = get_loan(connection, user_id, loan_id)?;
+
+    loan_payments::table
+        .inner_join(expenses::table)
+        .filter(loan_payments::loan_id.eq(loan.id))
+        .filter(expenses::deleted_at.is_null())
+        .order((expenses::date.asc(), expenses::created_at.asc()))
+        .select(Expense::as_select())
+        .load(connection)
+}
+
+/// Record one of the user's expenses as a payment towards a loan
+pub fn link_payment(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid, expense_id: Uuid, audit: &AuditContext) -> Result<Expense, AppError> {
+    connection.transaction(|connection| {
+        let loan = get_loan(connection, user_id, loan_id)?;
+        let expense = expenses::table
+            .find(expense_id)
+            .filter(expenses::user_id.eq(user_id))
+            .filter(expenses::deleted_at.is_null())
+            .select(Expense::as_select())
+            .first(connection)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Expense not found".to_string()))?;
+
+        let linked = loan_payments::table
+            .find(expense.id)
+            .select(LoanPayment::as_select())
+            .first(connection)
+            .optional()?;
+        if let Some(linked) = linked {
+            return Err(if linked.loan_id == loan.id {
+                AppError::Conflict("Expense is already linked to this loan".to_string())
+            } else {
+                AppError::Conflict("Expense is already linked to another loan".to_string())
+            });
+        }
+
+        let payment = diesel::insert_into(loan_payments::table)
+            .values(LoanPayment {
+                expense_id: expense.id,
+                loan_id: loan.id,
+                created_at: Utc::now().naive_utc(),
+            })
+            .returning(LoanPayment::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::LOAN_PAYMENT, payment.expense_id, user_id, &payment))?;
+        Ok(expense)
+    })
+}
+
+/// Remove the link between a loan and an expense. The expense itself is kept.
+pub fn unlink_payment(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid, expense_id: Uuid, audit: &AuditContext) -> Result<LoanPayment, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let loan = get_loan(connection, user_id, loan_id)?;
+
+        let payment = diesel::delete(
+            loan_payments::table
+                .find(expense_id)
+                .filter(loan_payments::loan_id.eq(loan.id)),
+        )
+        .returning(LoanPayment::as_returning())
+        .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::LOAN_PAYMENT, payment.expense_id, user_id, &payment))?;
+        Ok(payment)
+    })
+}
+
+pub fn get_schedule(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid) -> Result<AmortizationSchedule, diesel::result::Error> {
+    let loan = get_loan(connection, user_id, loan_id)?;
+    Ok(amortization_schedule(&loan))
+}
+
+/// Compare the linked payments made by `as_of` with the schedule
+///
+/// The actual balance is computed like the schedule, with interest accruing
+/// each period on what was actually owed and the payments dated within the
+/// period paying it down. Payments made after the last elapsed due date
+/// reduce the balance directly.
+pub fn get_status(connection: &mut DbConnection, user_id: Uuid, loan_id: Uuid, as_of: NaiveDate) -> Result<LoanStatus, diesel::result::Error> {
+    let loan = get_loan(connection, user_id, loan_id)?;
+
+    let payments: Vec<(NaiveDate, Decimal)> = loan_payments::table
+        .inner_join(expenses::table)
+        .filter(loan_payments::loan_id.eq(loan.id))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::date.le(as_of))
+        .order(expenses::date.asc())
+        .select((expenses::date, expenses::amount))
+        .load(connection)?;
+
+    let schedule = amortization_schedule(&loan);
+    let elapsed: Vec<&AmortizationPeriod> = schedule.periods.iter().take_while(|period| period.due_date <= as_of).collect();
+    let scheduled_paid: Decimal = elapsed.iter().map(|period| period.payment).sum();
+    let scheduled_balance = elapsed.last().map_or(loan.principal, |period| period.remaining_balance);
+
+    let actual_paid: Decimal = payments.iter().map(|(_, amount)| *amount).sum();
+    let rate = monthly_rate(&loan);
+    let mut payments = payments.into_iter().peekable();
+    let mut actual_balance = loan.principal;
+    for period in &elapsed {
+        actual_balance += round_money(actual_balance.max(Decimal::ZERO) * rate);
+        while let Some((_, amount)) = payments.next_if(|(date, _)| *date <= period.due_date) {
+            actual_balance -= amount;
+        }
+    }
+    for (_, amount) in payments {
+        actual_balance -= amount;
+    }
+    let actual_balance = actual_balance.max(Decimal::ZERO);
+
+    Ok(LoanStatus {
+        loan_id: loan.id,
+        as_of,
+        periods_elapsed: elapsed.len() as u32,
+        scheduled_paid,
+        actual_paid,
+        scheduled_balance,
+        actual_balance,
+        difference: actual_balance - scheduled_balance,
+    })
+}
+
+/// Equal monthly installments paying the loan off over its term
+///
+/// Every amount is rounded to the cent, half away from zero. Interest is
+/// charged on the rounded balance each period and the last installment
+/// absorbs the rounding residue so the balance ends at exactly zero.
+pub fn amortization_schedule(loan: &Loan) -> AmortizationSchedule {
+    let rate = monthly_rate(loan);
+    let term = loan.term_months as u32;
+    let monthly_payment = monthly_payment(loan.principal, rate, term);
+
+    let mut balance = loan.principal;
+    let mut periods = Vec::with_capacity(term as usize);
+    for period in 1..=term {
+        let interest = round_money(balance * rate);
+        let (payment, principal) = if period == term {
+            (balance + interest, balance)
+        } else {
+            (monthly_payment, monthly_payment - interest)
+        };
+        balance -= principal;
+
+        periods.push(AmortizationPeriod {
+            period,
+            due_date: loan.start_date + Months::new(period),
+            payment,
+            interest,
+            principal,
+            remaining_balance: balance,
+        });
+    }
+
+    let total_interest = periods.iter().map(|period| period.interest).sum();
+    AmortizationSchedule {
+        loan_id: loan.id,
+        monthly_payment,
+        total_interest,
+        total_paid: loan.principal + total_interest,
+        periods,
+    }
+}
+
+fn monthly_rate(loan: &Loan) -> Decimal {
+    loan.annual_rate / Decimal::from(1200)
+}
+
+/// Annuity installment `P * r / (1 - (1 + r)^-n)`, or `P / n` without
+/// interest. Working with the discount factor keeps long terms from
+/// overflowing.
+fn monthly_payment(principal: Decimal, rate: Decimal, term: u32) -> Decimal {
+    if rate.is_zero() {
+        return round_money(principal / Decimal::from(term));
+    }
+
+    let discount = Decimal::ONE / (Decimal::ONE + rate);
+    let mut discount_total = Decimal::ONE;
+    for _ in 0..term {
+        discount_total *= discount;
+    }
+    round_money(principal * rate / (Decimal::ONE - discount_total))
+}
+
+fn round_money(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+fn validate_loan(loan: &Loan) -> Result<(), AppError> {
+    if loan.name.trim().is_empty() {
+        return Err(AppError::Validation("Loan name must not be empty".to_string()));
+    }
+    if loan.principal <= Decimal::ZERO {
+        return Err(AppError::Validation("Principal must be positive".to_string()));
+    }
+    if loan.annual_rate < Decimal::ZERO || loan.annual_rate > Decimal::ONE_HUNDRED {
+        return Err(AppError::Validation("Annual rate must be between 0 and 100 percent".to_string()));
+    }
+    if loan.term_months <= 0 || loan.term_months > MAX_TERM_MONTHS {
+        return Err(AppError::Validation(format!("Term must be between 1 and {} months", MAX_TERM_MONTHS)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn loan(principal: i64, annual_rate: Decimal, term_months: i32, start_date: NaiveDate) -> Loan {
+        let now = Utc::now().naive_utc();
+        Loan {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "Car".to_string(),
+            principal: Decimal::from(principal),
+            annual_rate,
+            term_months,
+            start_date,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn cents(amount: i64) -> Decimal {
+        Decimal::new(amount, 2)
+    }
+
+    #[test]
+    fn amortizes_with_equal_installments() {
+        let schedule = amortization_schedule(&loan(10_000, Decimal::from(6), 12, date(2025, 1, 15)));
+
+        assert_eq!(schedule.monthly_payment, cents(86066));
+        assert_eq!(schedule.periods.len(), 12);
+
+        let first = &schedule.periods[0];
+        assert_eq!(first.due_date, date(2025, 2, 15));
+        assert_eq!(first.interest, cents(5000));
+        assert_eq!(first.principal, cents(81066));
+        assert_eq!(first.remaining_balance, cents(918934));
+
+        // The last installment absorbs the rounding residue
+        let last = &schedule.periods[11];
+        assert_eq!(last.due_date, date(2026, 1, 15));
+        assert_eq!(last.payment, cents(86070));
+        assert_eq!(last.interest, cents(428));
+        assert_eq!(last.remaining_balance, Decimal::ZERO);
+
+        assert_eq!(schedule.total_interest, cents(32796));
+        assert_eq!(schedule.total_paid, cents(1_032_796));
+    }
+
+    #[test]
+    fn amortizes_long_terms_without_overflow() {
+        let schedule = amortization_schedule(&loan(100_000, Decimal::from(5), 360, date(2025, 1, 1)));
+
+        assert_eq!(schedule.monthly_payment, cents(53682));
+        assert_eq!(schedule.periods[359].remaining_balance, Decimal::ZERO);
+        assert_eq!(schedule.total_interest, cents(9_325_652));
+    }
+
+    #[test]
+    fn splits_interest_free_loans_evenly() {
+        let schedule = amortization_schedule(&loan(1000, Decimal::ZERO, 3, date(2025, 1, 1)));
+
+        let payments: Vec<Decimal> = schedule.periods.iter().map(|period| period.payment).collect();
+        assert_eq!(payments, vec![cents(33333), cents(33333), cents(33334)]);
+        assert_eq!(schedule.total_interest, Decimal::ZERO);
+    }
+
+    #[test]
+    fn clamps_due_dates_to_month_end() {
+        let schedule = amortization_schedule(&loan(1000, Decimal::ZERO, 3, date(2025, 1, 31)));
+
+        let due_dates: Vec<NaiveDate> = schedule.periods.iter().map(|period| period.due_date).collect();
+        assert_eq!(due_dates, vec![date(2025, 2, 28), date(2025, 3, 31), date(2025, 4, 30)]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use crate::config;
+
+/// A plain text email to one recipient
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Something that can send emails
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// The mailer selected by `MAIL_TRANSPORT`
+pub fn from_config() -> Arc<dyn Mailer> {
+    match config::get_mail_transport().as_str() {
+        "sendmail" => Arc::new(SendmailMailer {
+            program: config::get_sendmail_path(),
+            from: config::get_mail_from(),
+        }),
+        "log" => Arc::new(LogMailer),
+        other => {
+            log::warn!("Unknown MAIL_TRANSPORT {}, emails are only logged", other);
+            Arc::new(LogMailer)
+        }
+    }
+}
+
+/// Writes emails to the log instead of sending them, for development
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        log::info!("Email to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// Hands emails to the local sendmail program, which postfix, exim and
+/// msmtp all provide
+pub struct SendmailMailer {
+    pub program: String,
+    pub from: String,
+}
+
+impl Mailer for SendmailMailer {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        // Header values must not contain line breaks, or they could add headers
+        let header = |value: &str| value.replace(['\r', '\n'], " ");
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}\r\n",
+            header(&self.from),
+            header(&email.to),
+            header(&email.subject),
+            email.body.replace('\n', "\r\n"),
+        );
+
+        let mut child = Command::new(&self.program)
+            .args(["-t", "-i"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", self.program, e))?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| "sendmail has no stdin".to_string())?
+            .write_all(message.as_bytes())
+            .map_err(|e| format!("Failed to write to {}: {}", self.program, e))?;
+
+        let status = child.wait().map_err(|e| format!("Failed to wait for {}: {}", self.program, e))?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", self.program, status));
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,21 @@
 pub mod income_service;
 pub mod expense_service;
-pub mod auth_service;
\ No newline at end of file
+pub mod auth_service;
+pub mod two_factor_service;
+pub mod admin_service;
+pub mod api_token_service;
+pub mod audit_service;
+pub mod batch_service;
+pub mod idempotency_service;
+pub mod goal_service;
+pub mod loan_service;
+pub mod report_service;
+pub mod net_worth_service;
+pub mod categorization_service;
+pub mod suggestion_service;
+pub mod duplicate_service;
+pub mod reconciliation_service;
+pub mod mailer;
+pub mod notification_service;
+pub mod webhook_service;
+pub mod live_event_service;
\ No newline at end of file
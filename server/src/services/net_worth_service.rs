@@ -0,0 +1,445 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{Datelike, Duration, Months, NaiveDate, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::sql_types::{Date, Numeric};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::net_worth::{
+    NetWorth, NetWorthItem, NetWorthItemKind, NetWorthItemValue, NetWorthSnapshot, NetWorthSnapshotQuery, NewNetWorthItem, NewValuation,
+    UpdateNetWorthItem, Valuation,
+};
+use crate::models::schema::{expenses, incomes, net_worth_items, net_worth_snapshots, valuations};
+use crate::services::audit_service;
+
+pub fn get_items_by_user_id(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<NetWorthItem>, diesel::result::Error> {
+    net_worth_items::table
+        .filter(net_worth_items::user_id.eq(user_id))
+        .order(net_worth_items::created_at.asc())
+        .select(NetWorthItem::as_select())
+        .load(connection)
+}
+
+/// One of the user's items; items of other users are reported as not found
+pub fn get_item(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid) -> Result<NetWorthItem, diesel::result::Error> {
+    net_worth_items::table
+        .find(item_id)
+        .filter(net_worth_items::user_id.eq(user_id))
+        .select(NetWorthItem::as_select())
+        .first(connection)
+}
+
+pub fn create_item(connection: &mut DbConnection, user_id: Uuid, new_item: NewNetWorthItem, audit: &AuditContext) -> Result<NetWorthItem, AppError> {
+    validate_name(&new_item.name)?;
+
+    let item = connection.transaction(|connection| {
+        let item = diesel::insert_into(net_worth_items::table)
+            .values(new_item.into_item(user_id))
+            .returning(NetWorthItem::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::NET_WORTH_ITEM, item.id, user_id, &item))?;
+        Ok::<_, diesel::result::Error>(item)
+    })?;
+
+    Ok(item)
+}
+
+pub fn update_item(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid, update_item: UpdateNetWorthItem, audit: &AuditContext) -> Result<NetWorthItem, AppError> {
+    if let Some(name) = &update_item.name {
+        validate_name(name)?;
+    }
+
+    let item = connection.transaction(|connection| {
+        let before = net_worth_items::table
+            .find(item_id)
+            .filter(net_worth_items::user_id.eq(user_id))
+            .select(NetWorthItem::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let item = diesel::update(net_worth_items::table.find(item_id))
+            .set((update_item, net_worth_items::updated_at.eq(Utc::now().naive_utc())))
+            .returning(NetWorthItem::as_returning())
+            .get_result(connection)?;
+
+        // Turning an asset into a liability changes every month it was valued
+        if item.kind != before.kind {
+            if let Some(first_valuation) = first_valuation_date(connection, item.id)? {
+                invalidate_snapshots(connection, user_id, first_valuation)?;
+            }
+        }
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::NET_WORTH_ITEM, item.id, user_id, &before, &item))?;
+        Ok::<_, diesel::result::Error>(item)
+    })?;
+
+    Ok(item)
+}
+
+/// Delete an item together with its valuations
+pub fn delete_item(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid, audit: &AuditContext) -> Result<NetWorthItem, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let first_valuation = first_valuation_date(connection, item_id)?;
+        let item = diesel::delete(net_worth_items::table.find(item_id).filter(net_worth_items::user_id.eq(user_id)))
+            .returning(NetWorthItem::as_returning())
+            .get_result(connection)?;
+
+        if let Some(first_valuation) = first_valuation {
+            invalidate_snapshots(connection, user_id, first_valuation)?;
+        }
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::NET_WORTH_ITEM, item.id, user_id, &item))?;
+        Ok(item)
+    })
+}
+
+/// Valuations of one of the user's items, most recent first
+pub fn get_valuations(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid) -> Result<Vec<Valuation>, diesel::result::Error> {
+    let item = get_item(connection, user_id, item_id)?;
+
+    valuations::table
+        .filter(valuations::item_id.eq(item.id))
+        .order(valuations::date.desc())
+        .select(Valuation::as_select())
+        .load(connection)
+}
+
+/// Record what an item was worth on a date, replacing an earlier valuation
+/// for the same date
+pub fn add_valuation(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid, new_valuation: NewValuation, audit: &AuditContext) -> Result<Valuation, AppError> {
+    if new_valuation.value < Decimal::ZERO {
+        return Err(AppError::Validation("Value must not be negative".to_string()));
+    }
+
+    let valuation = connection.transaction(|connection| {
+        let item = get_item(connection, user_id, item_id)?;
+
+        let valuation = diesel::insert_into(valuations::table)
+            .values(Valuation {
+                id: Uuid::new_v4(),
+                item_id: item.id,
+                date: new_valuation.date,
+                value: new_valuation.value,
+                created_at: Utc::now().naive_utc(),
+            })
+            .on_conflict((valuations::item_id, valuations::date))
+            .do_update()
+            .set((valuations::value.eq(new_valuation.value), valuations::created_at.eq(Utc::now().naive_utc())))
+            .returning(Valuation::as_returning())
+            .get_result(connection)?;
+
+        invalidate_snapshots(connection, user_id, valuation.date)?;
+        audit_service::record(connection, audit, AuditChange::created(entity::VALUATION, valuation.id, user_id, &valuation))?;
+        Ok::<_, diesel::result::Error>(valuation)
+    })?;
+
+    Ok(valuation)
+}
+
+pub fn delete_valuation(connection: &mut DbConnection, user_id: Uuid, item_id: Uuid, valuation_id: Uuid, audit: &AuditContext) -> Result<Valuation, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let item = get_item(connection, user_id, item_id)?;
+
+        let valuation = diesel::delete(valuations::table.find(valuation_id).filter(valuations::item_id.eq(item.id)))
+            .returning(Valuation::as_returning())
+            .get_result(connection)?;
+
+        invalidate_snapshots(connection, user_id, valuation.date)?;
+        audit_service::record(connection, audit, AuditChange::deleted(entity::VALUATION, valuation.id, user_id, &valuation))?;
+        Ok(valuation)
+    })
+}
+
+/// Drop the snapshots of the month containing `changed_on` and every month
+/// after it, so they are recomputed on the next read. Called whenever data
+/// dated `changed_on` is added, changed or removed.
+pub fn invalidate_snapshots(connection: &mut DbConnection, user_id: Uuid, changed_on: NaiveDate) -> Result<usize, diesel::result::Error> {
+    diesel::delete(
+        net_worth_snapshots::table
+            .filter(net_worth_snapshots::user_id.eq(user_id))
+            .filter(net_worth_snapshots::month_end.ge(changed_on)),
+    )
+    .execute(connection)
+}
+
+/// Net worth of a user at the end of `date`
+pub fn get_net_worth(connection: &mut DbConnection, user_id: Uuid, date: NaiveDate) -> Result<NetWorth, diesel::result::Error> {
+    let income = incomes::table
+        .filter(incomes::user_id.eq(user_id))
+        .filter(incomes::deleted_at.is_null())
+        .filter(incomes::date.le(date))
+        .select(dsl::sum(incomes::amount))
+        .first::<Option<Decimal>>(connection)?
+        .unwrap_or(Decimal::ZERO);
+    let spent = expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::date.le(date))
+        .select(dsl::sum(expenses::amount))
+        .first::<Option<Decimal>>(connection)?
+        .unwrap_or(Decimal::ZERO);
+
+    let items = get_items_by_user_id(connection, user_id)?;
+    let valuations = load_valuations(connection, user_id)?;
+
+    let items: Vec<NetWorthItemValue> = items
+        .into_iter()
+        .map(|item| {
+            let latest = valuations.get(&item.id).and_then(|history| valuation_at(history, date));
+            NetWorthItemValue {
+                id: item.id,
+                name: item.name,
+                kind: item.kind,
+                value: latest.map_or(Decimal::ZERO, |(_, value)| value),
+                valued_on: latest.map(|(valued_on, _)| valued_on),
+            }
+        })
+        .collect();
+
+    let cash = income - spent;
+    let assets = total_of(&items, NetWorthItemKind::Asset);
+    let liabilities = total_of(&items, NetWorthItemKind::Liability);
+
+    Ok(NetWorth {
+        date,
+        cash,
+        assets,
+        liabilities,
+        net_worth: cash + assets - liabilities,
+        items,
+    })
+}
+
+/// Month-end snapshots of every completed month since the user's first
+/// transaction or valuation. Months whose snapshot is missing or was
+/// invalidated are computed first.
+pub fn get_snapshots(connection: &mut DbConnection, user_id: Uuid, query: &NetWorthSnapshotQuery, today: NaiveDate) -> Result<Vec<NetWorthSnapshot>, diesel::result::Error> {
+    connection.transaction(|connection| {
+        fill_snapshots(connection, user_id, today)?;
+
+        let mut snapshots = net_worth_snapshots::table
+            .filter(net_worth_snapshots::user_id.eq(user_id))
+            .order(net_worth_snapshots::month_end.asc())
+            .select(NetWorthSnapshot::as_select())
+            .into_boxed();
+        if let Some(from) = query.from {
+            snapshots = snapshots.filter(net_worth_snapshots::month_end.ge(from));
+        }
+        if let Some(to) = query.to {
+            snapshots = snapshots.filter(net_worth_snapshots::month_end.le(to));
+        }
+        snapshots.load(connection)
+    })
+}
+
+/// Throw away all of a user's snapshots and compute them again
+pub fn recompute_snapshots(connection: &mut DbConnection, user_id: Uuid, today: NaiveDate) -> Result<Vec<NetWorthSnapshot>, diesel::result::Error> {
+    connection.transaction(|connection| {
+        diesel::delete(net_worth_snapshots::table.filter(net_worth_snapshots::user_id.eq(user_id))).execute(connection)?;
+        get_snapshots(connection, user_id, &NetWorthSnapshotQuery { from: None, to: None }, today)
+    })
+}
+
+#[derive(QueryableByName)]
+struct MonthlyTotal {
+    #[diesel(sql_type = Date)]
+    month_end: NaiveDate,
+    #[diesel(sql_type = Numeric)]
+    total: Decimal,
+}
+
+fn fill_snapshots(connection: &mut DbConnection, user_id: Uuid, today: NaiveDate) -> Result<(), diesel::result::Error> {
+    let monthly_totals = |table: &str| {
+        format!(
+            "SELECT (date_trunc('month', date) + interval '1 month - 1 day')::date AS month_end, sum(amount) AS total
+             FROM {} WHERE user_id = $1 AND deleted_at IS NULL GROUP BY 1",
+            table
+        )
+    };
+    let income: Vec<MonthlyTotal> = diesel::sql_query(monthly_totals("incomes"))
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .load(connection)?;
+    let spent: Vec<MonthlyTotal> = diesel::sql_query(monthly_totals("expenses"))
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .load(connection)?;
+    let items: HashMap<Uuid, NetWorthItemKind> = get_items_by_user_id(connection, user_id)?
+        .into_iter()
+        .map(|item| (item.id, item.kind))
+        .collect();
+    let valuations = load_valuations(connection, user_id)?;
+
+    let first_activity = income
+        .iter()
+        .chain(&spent)
+        .map(|month| month.month_end)
+        .chain(valuations.values().filter_map(|history| history.first().map(|(date, _)| *date)))
+        .min();
+    let Some(first_activity) = first_activity else {
+        return Ok(());
+    };
+
+    let existing: HashSet<NaiveDate> = net_worth_snapshots::table
+        .filter(net_worth_snapshots::user_id.eq(user_id))
+        .select(net_worth_snapshots::month_end)
+        .load::<NaiveDate>(connection)?
+        .into_iter()
+        .collect();
+
+    let mut cash_flow: BTreeMap<NaiveDate, Decimal> = BTreeMap::new();
+    for month in income {
+        *cash_flow.entry(month.month_end).or_default() += month.total;
+    }
+    for month in spent {
+        *cash_flow.entry(month.month_end).or_default() -= month.total;
+    }
+
+    let now = Utc::now().naive_utc();
+    let mut missing = Vec::new();
+    let mut cash = Decimal::ZERO;
+    for month_end in completed_month_ends(first_activity, today) {
+        cash += cash_flow.get(&month_end).copied().unwrap_or_default();
+
+        if !existing.contains(&month_end) {
+            let (assets, liabilities) = totals_at(&items, &valuations, month_end);
+            missing.push(NetWorthSnapshot {
+                user_id,
+                month_end,
+                cash,
+                assets,
+                liabilities,
+                net_worth: cash + assets - liabilities,
+                computed_at: now,
+            });
+        }
+    }
+
+    diesel::insert_into(net_worth_snapshots::table)
+        .values(&missing)
+        .on_conflict_do_nothing()
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Ends of the months from the one of `first_activity` through the last one
+/// completed before `today`
+fn completed_month_ends(first_activity: NaiveDate, today: NaiveDate) -> Vec<NaiveDate> {
+    let last_month_end = today.with_day(1).unwrap_or(today) - Duration::days(1);
+    let mut month_ends = Vec::new();
+    let mut month_start = first_activity.with_day(1).unwrap_or(first_activity);
+    loop {
+        let month_end = month_start + Months::new(1) - Duration::days(1);
+        if month_end > last_month_end {
+            return month_ends;
+        }
+        month_ends.push(month_end);
+        month_start = month_start + Months::new(1);
+    }
+}
+
+/// Latest valuation on or before `date` from a history sorted oldest first
+fn valuation_at(history: &[(NaiveDate, Decimal)], date: NaiveDate) -> Option<(NaiveDate, Decimal)> {
+    history.iter().rev().find(|(valued_on, _)| *valued_on <= date).copied()
+}
+
+/// Total assets and liabilities as valued at the end of `date`. Valuations
+/// of items that no longer exist are ignored.
+fn totals_at(items: &HashMap<Uuid, NetWorthItemKind>, valuations: &HashMap<Uuid, Vec<(NaiveDate, Decimal)>>, date: NaiveDate) -> (Decimal, Decimal) {
+    let (mut assets, mut liabilities) = (Decimal::ZERO, Decimal::ZERO);
+    for (item_id, history) in valuations {
+        let value = valuation_at(history, date).map_or(Decimal::ZERO, |(_, value)| value);
+        match items.get(item_id) {
+            Some(NetWorthItemKind::Asset) => assets += value,
+            Some(NetWorthItemKind::Liability) => liabilities += value,
+            None => {}
+        }
+    }
+    (assets, liabilities)
+}
+
+/// Valuations of all of a user's items, oldest first per item
+fn load_valuations(connection: &mut DbConnection, user_id: Uuid) -> Result<HashMap<Uuid, Vec<(NaiveDate, Decimal)>>, diesel::result::Error> {
+    let rows: Vec<(Uuid, NaiveDate, Decimal)> = valuations::table
+        .inner_join(net_worth_items::table)
+        .filter(net_worth_items::user_id.eq(user_id))
+        .order(valuations::date.asc())
+        .select((valuations::item_id, valuations::date, valuations::value))
+        .load(connection)?;
+
+    let mut by_item: HashMap<Uuid, Vec<(NaiveDate, Decimal)>> = HashMap::new();
+    for (item_id, date, value) in rows {
+        by_item.entry(item_id).or_default().push((date, value));
+    }
+    Ok(by_item)
+}
+
+fn first_valuation_date(connection: &mut DbConnection, item_id: Uuid) -> Result<Option<NaiveDate>, diesel::result::Error> {
+    valuations::table
+        .filter(valuations::item_id.eq(item_id))
+        .select(dsl::min(valuations::date))
+        .first(connection)
+}
+
+fn total_of(items: &[NetWorthItemValue], kind: NetWorthItemKind) -> Decimal {
+    items.iter().filter(|item| item.kind == kind).map(|item| item.value).sum()
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::Validation("Item name must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn lists_completed_month_ends_only() {
+        assert_eq!(
+            completed_month_ends(date(2024, 11, 15), date(2025, 2, 10)),
+            vec![date(2024, 11, 30), date(2024, 12, 31), date(2025, 1, 31)]
+        );
+        assert_eq!(completed_month_ends(date(2024, 1, 31), date(2024, 3, 1)), vec![date(2024, 1, 31), date(2024, 2, 29)]);
+        assert!(completed_month_ends(date(2025, 2, 3), date(2025, 2, 28)).is_empty());
+    }
+
+    #[test]
+    fn values_items_at_their_latest_valuation() {
+        let history = vec![(date(2025, 1, 1), Decimal::from(100)), (date(2025, 3, 1), Decimal::from(150))];
+
+        assert_eq!(valuation_at(&history, date(2024, 12, 31)), None);
+        assert_eq!(valuation_at(&history, date(2025, 2, 28)), Some((date(2025, 1, 1), Decimal::from(100))));
+        assert_eq!(valuation_at(&history, date(2025, 3, 1)), Some((date(2025, 3, 1), Decimal::from(150))));
+    }
+
+    #[test]
+    fn totals_assets_and_liabilities_separately() {
+        let (house, mortgage, car, removed) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let items = HashMap::from([
+            (house, NetWorthItemKind::Asset),
+            (mortgage, NetWorthItemKind::Liability),
+            (car, NetWorthItemKind::Asset),
+        ]);
+        let valuations = HashMap::from([
+            (house, vec![(date(2025, 1, 10), Decimal::from(300_000))]),
+            (mortgage, vec![(date(2025, 1, 10), Decimal::from(200_000)), (date(2025, 2, 10), Decimal::from(199_000))]),
+            (car, vec![(date(2025, 3, 5), Decimal::from(15_000))]),
+            (removed, vec![(date(2025, 1, 1), Decimal::from(1_000))]),
+        ]);
+
+        assert_eq!(totals_at(&items, &valuations, date(2025, 2, 28)), (Decimal::from(300_000), Decimal::from(199_000)));
+        assert_eq!(totals_at(&items, &valuations, date(2025, 3, 31)), (Decimal::from(315_000), Decimal::from(199_000)));
+        assert_eq!(totals_at(&items, &valuations, date(2024, 12, 31)), (Decimal::ZERO, Decimal::ZERO));
+    }
+}
@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::notification::{
+    EmailRun, Notification, NotificationKind, NotificationPreferences, NotificationQuery, NotificationRun, NotificationsRead,
+    UpdateNotificationPreferences,
+};
+use crate::models::report::{ForecastQuery, RecurringItem};
+use crate::models::schema::{expenses, incomes, notification_preferences, notifications, users};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::{audit_service, report_service, webhook_service};
+
+pub const DEFAULT_NOTIFICATION_LIMIT: i64 = 50;
+pub const MAX_NOTIFICATION_LIMIT: i64 = 200;
+
+const MAX_BILL_REMINDER_DAYS: i32 = 14;
+/// Categories named in a weekly summary
+const SUMMARY_CATEGORIES: usize = 3;
+/// Attempts before the email of a notification is given up on
+const MAX_EMAIL_ATTEMPTS: i32 = 5;
+/// Wait after the first failed email; it doubles after every further one
+const FIRST_EMAIL_RETRY_MINUTES: i64 = 5;
+/// Emails sent per run of the email job
+const EMAIL_BATCH_SIZE: i64 = 50;
+/// A claimed email is tried again after this long if the server stopped
+/// before its attempt was recorded
+const EMAIL_CLAIM_TIMEOUT_SECONDS: i64 = 5 * 60;
+
+/// The user's notifications, newest first
+pub fn get_notifications(connection: &mut DbConnection, user_id: Uuid, query: &NotificationQuery) -> Result<Vec<Notification>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_NOTIFICATION_LIMIT);
+    if !(1..=MAX_NOTIFICATION_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!("limit must be between 1 and {}", MAX_NOTIFICATION_LIMIT)));
+    }
+
+    let mut notifications_query = notifications::table
+        .filter(notifications::user_id.eq(user_id))
+        .select(Notification::as_select())
+        .into_boxed();
+    if query.unread {
+        notifications_query = notifications_query.filter(notifications::read_at.is_null());
+    }
+
+    Ok(notifications_query
+        .order(notifications::created_at.desc())
+        .limit(limit)
+        .load(connection)?)
+}
+
+pub fn mark_read(connection: &mut DbConnection, user_id: Uuid, notification_id: Uuid) -> Result<Notification, diesel::result::Error> {
+    let notification = notifications::table
+        .find(notification_id)
+        .filter(notifications::user_id.eq(user_id))
+        .select(Notification::as_select())
+        .first(connection)?;
+    if notification.read_at.is_some() {
+        return Ok(notification);
+    }
+
+    diesel::update(notifications::table.find(notification.id))
+        .set(notifications::read_at.eq(Utc::now().naive_utc()))
+        .returning(Notification::as_returning())
+        .get_result(connection)
+}
+
+pub fn mark_all_read(connection: &mut DbConnection, user_id: Uuid) -> Result<NotificationsRead, diesel::result::Error> {
+    let read = diesel::update(notifications::table.filter(notifications::user_id.eq(user_id)).filter(notifications::read_at.is_null()))
+        .set(notifications::read_at.eq(Utc::now().naive_utc()))
+        .execute(connection)?;
+    Ok(NotificationsRead { read })
+}
+
+pub fn delete_notification(connection: &mut DbConnection, user_id: Uuid, notification_id: Uuid) -> Result<Notification, diesel::result::Error> {
+    diesel::delete(notifications::table.find(notification_id).filter(notifications::user_id.eq(user_id)))
+        .returning(Notification::as_returning())
+        .get_result(connection)
+}
+
+/// The user's notification preferences, or the defaults if they never
+/// changed them
+pub fn get_preferences(connection: &mut DbConnection, user_id: Uuid) -> Result<NotificationPreferences, diesel::result::Error> {
+    Ok(notification_preferences::table
+        .find(user_id)
+        .select(NotificationPreferences::as_select())
+        .first(connection)
+        .optional()?
+        .unwrap_or_else(|| NotificationPreferences::defaults(user_id, Utc::now().naive_utc())))
+}
+
+pub fn update_preferences(connection: &mut DbConnection, user_id: Uuid, update: UpdateNotificationPreferences, audit: &AuditContext) -> Result<NotificationPreferences, AppError> {
+    if let Some(days) = update.bill_reminder_days {
+        if !(1..=MAX_BILL_REMINDER_DAYS).contains(&days) {
+            return Err(AppError::Validation(format!("bill_reminder_days must be between 1 and {}", MAX_BILL_REMINDER_DAYS)));
+        }
+    }
+    if let Some(Some(budget)) = update.monthly_budget {
+        if budget <= Decimal::ZERO {
+            return Err(AppError::Validation("monthly_budget must be positive".to_string()));
+        }
+    }
+    if let Some(percent) = update.budget_alert_percent {
+        if !(1..=100).contains(&percent) {
+            return Err(AppError::Validation("budget_alert_percent must be between 1 and 100".to_string()));
+        }
+    }
+
+    connection.transaction(|connection| {
+        let now = Utc::now().naive_utc();
+        diesel::insert_into(notification_preferences::table)
+            .values(NotificationPreferences::defaults(user_id, now))
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+        let before: NotificationPreferences = notification_preferences::table
+            .find(user_id)
+            .select(NotificationPreferences::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let preferences = diesel::update(notification_preferences::table.find(user_id))
+            .set((update, notification_preferences::updated_at.eq(now)))
+            .returning(NotificationPreferences::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::NOTIFICATION_PREFERENCES, user_id, user_id, &before, &preferences))?;
+        Ok(preferences)
+    })
+}
+
+/// A notification that is due, before it is stored
+struct Pending {
+    kind: NotificationKind,
+    dedup_key: String,
+    title: String,
+    body: String,
+    data: serde_json::Value,
+}
+
+/// Create the notifications that are due for every active user. Emails and
+/// webhook deliveries are queued with them and sent by their own jobs. Every
+/// occasion is notified about once, so this can run as often as needed.
+pub fn run_scheduled(connection: &mut DbConnection, now: NaiveDateTime) -> Result<NotificationRun, diesel::result::Error> {
+    let today = now.date();
+    let active_users: Vec<(Uuid, NaiveDateTime)> = users::table
+        .filter(users::disabled_at.is_null())
+        .select((users::id, users::created_at))
+        .load(connection)?;
+    let mut preferences: HashMap<Uuid, NotificationPreferences> = notification_preferences::table
+        .select(NotificationPreferences::as_select())
+        .load(connection)?
+        .into_iter()
+        .map(|preferences| (preferences.user_id, preferences))
+        .collect();
+
+    let mut run = NotificationRun::default();
+    for (user_id, created_at) in active_users {
+        let preferences = preferences.remove(&user_id).unwrap_or_else(|| NotificationPreferences::defaults(user_id, now));
+
+        let due = match due_notifications(connection, &preferences, created_at.date(), today) {
+            Ok(due) => due,
+            Err(e) => {
+                log::error!("Failed to check notifications of user {}: {}", user_id, e);
+                continue;
+            }
+        };
+
+        for pending in due {
+            let kind = pending.kind;
+            let stored = connection.transaction(|connection| {
+                let Some(notification) = store(connection, user_id, pending, preferences.email_enabled, now)? else {
+                    return Ok(false);
+                };
+                webhook_service::enqueue_notification(connection, &notification)?;
+                Ok::<_, diesel::result::Error>(true)
+            })?;
+            if !stored {
+                continue;
+            }
+            match kind {
+                NotificationKind::WeeklySummary => run.weekly_summaries += 1,
+                NotificationKind::BillReminder => run.bill_reminders += 1,
+                NotificationKind::BudgetThreshold => run.budget_alerts += 1,
+            }
+        }
+    }
+
+    Ok(run)
+}
+
+/// Email the notifications whose email is due and record the outcome of
+/// each attempt
+pub fn send_due_emails(connection: &mut DbConnection, mailer: &dyn Mailer, now: NaiveDateTime) -> Result<EmailRun, diesel::result::Error> {
+    // Claim the emails first so that they are not sent while holding row
+    // locks
+    let claimed: Vec<(Notification, String)> = connection.transaction(|connection| {
+        let due: Vec<(Notification, String)> = notifications::table
+            .inner_join(users::table)
+            .filter(notifications::email_next_attempt_at.le(now))
+            .filter(users::disabled_at.is_null())
+            .order(notifications::email_next_attempt_at.asc())
+            .limit(EMAIL_BATCH_SIZE)
+            .select((Notification::as_select(), users::email))
+            .for_update()
+            .skip_locked()
+            .load(connection)?;
+
+        let ids: Vec<Uuid> = due.iter().map(|(notification, _)| notification.id).collect();
+        diesel::update(notifications::table.filter(notifications::id.eq_any(ids)))
+            .set(notifications::email_next_attempt_at.eq(now + Duration::seconds(EMAIL_CLAIM_TIMEOUT_SECONDS)))
+            .execute(connection)?;
+        Ok::<_, diesel::result::Error>(due)
+    })?;
+
+    let mut run = EmailRun::default();
+    for (notification, to) in claimed {
+        let email = Email {
+            to,
+            subject: notification.title.clone(),
+            body: notification.body.clone(),
+        };
+        let result = mailer.send(&email);
+        let attempted_at = Utc::now().naive_utc();
+        let attempts = notification.email_attempts + 1;
+
+        let (emailed_at, next_attempt_at) = match result {
+            Ok(()) => {
+                run.sent += 1;
+                (Some(attempted_at), None)
+            }
+            Err(e) => match email_retry_delay(attempts) {
+                Some(delay) => {
+                    log::warn!("Failed to email notification {}, will retry: {}", notification.id, e);
+                    run.retrying += 1;
+                    (None, Some(attempted_at + delay))
+                }
+                None => {
+                    log::error!("Failed to email notification {}, giving up: {}", notification.id, e);
+                    run.failed += 1;
+                    (None, None)
+                }
+            },
+        };
+
+        diesel::update(notifications::table.find(notification.id))
+            .set((
+                notifications::email_attempts.eq(attempts),
+                notifications::email_next_attempt_at.eq(next_attempt_at),
+                notifications::emailed_at.eq(emailed_at),
+            ))
+            .execute(connection)?;
+    }
+
+    Ok(run)
+}
+
+/// Wait before the next email attempt after `attempts` failed ones, or
+/// `None` once the email is given up on
+fn email_retry_delay(attempts: i32) -> Option<Duration> {
+    if attempts >= MAX_EMAIL_ATTEMPTS {
+        return None;
+    }
+    Some(Duration::minutes(FIRST_EMAIL_RETRY_MINUTES << (attempts - 1).max(0)))
+}
+
+fn due_notifications(connection: &mut DbConnection, preferences: &NotificationPreferences, member_since: NaiveDate, today: NaiveDate) -> Result<Vec<Pending>, AppError> {
+    let mut due = Vec::new();
+    if preferences.weekly_summary {
+        due.extend(weekly_summary(connection, preferences.user_id, member_since, today)?);
+    }
+    if preferences.bill_reminders {
+        due.extend(bill_reminders(connection, preferences.user_id, preferences.bill_reminder_days, today)?);
+    }
+    if let Some(budget) = preferences.monthly_budget {
+        due.extend(budget_alert(connection, preferences.user_id, budget, preferences.budget_alert_percent, today)?);
+    }
+    Ok(due)
+}
+
+/// Totals of the last full week, Monday to Sunday. Weeks without any
+/// transactions, or before the user signed up, are skipped.
+fn weekly_summary(connection: &mut DbConnection, user_id: Uuid, member_since: NaiveDate, today: NaiveDate) -> Result<Option<Pending>, diesel::result::Error> {
+    let (week_start, week_end) = last_full_week(today);
+    if member_since > week_end {
+        return Ok(None);
+    }
+
+    let (income_count, income) = incomes::table
+        .filter(incomes::user_id.eq(user_id))
+        .filter(incomes::deleted_at.is_null())
+        .filter(incomes::date.between(week_start, week_end))
+        .select((dsl::count_star(), dsl::sum(incomes::amount)))
+        .first::<(i64, Option<Decimal>)>(connection)?;
+    let spending: Vec<(Option<String>, Decimal)> = expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::date.between(week_start, week_end))
+        .select((expenses::category, expenses::amount))
+        .load(connection)?;
+    if income_count == 0 && spending.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(summarize_week(week_start, income.unwrap_or(Decimal::ZERO), spending)))
+}
+
+/// Monday and Sunday of the last week that ended before `today`
+fn last_full_week(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64 + 7);
+    (week_start, week_start + Duration::days(6))
+}
+
+/// Summary of the week starting on `week_start` from its income and the
+/// category and amount of each expense
+fn summarize_week(week_start: NaiveDate, income: Decimal, spending: Vec<(Option<String>, Decimal)>) -> Pending {
+    let week_end = week_start + Duration::days(6);
+    let spent: Decimal = spending.iter().map(|(_, amount)| *amount).sum();
+    let mut by_category: HashMap<String, Decimal> = HashMap::new();
+    for (category, amount) in spending {
+        *by_category.entry(category.unwrap_or_else(|| "Uncategorized".to_string())).or_insert(Decimal::ZERO) += amount;
+    }
+    let mut top_categories: Vec<(String, Decimal)> = by_category.into_iter().collect();
+    top_categories.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    top_categories.truncate(SUMMARY_CATEGORIES);
+
+    let mut body = format!(
+        "From {} to {} you received {:.2} and spent {:.2}, a net of {:.2}.",
+        week_start,
+        week_end,
+        income,
+        spent,
+        income - spent
+    );
+    if !top_categories.is_empty() {
+        let categories: Vec<String> = top_categories.iter().map(|(name, amount)| format!("{} {:.2}", name, amount)).collect();
+        body.push_str(&format!(" Most was spent on {}.", categories.join(", ")));
+    }
+
+    let week = week_start.iso_week();
+    Pending {
+        kind: NotificationKind::WeeklySummary,
+        dedup_key: format!("weekly_summary:{}-W{:02}", week.year(), week.week()),
+        title: format!("Your week of {}", week_start),
+        body,
+        data: json!({
+            "week_start": week_start,
+            "week_end": week_end,
+            "income": income.round_dp(2),
+            "expenses": spent.round_dp(2),
+            "net": (income - spent).round_dp(2),
+            "top_categories": top_categories
+                .iter()
+                .map(|(category, amount)| json!({ "category": category, "amount": amount.round_dp(2) }))
+                .collect::<Vec<_>>(),
+        }),
+    }
+}
+
+/// Recurring expenses, as found by the forecast, that are expected within
+/// the next `days` days
+fn bill_reminders(connection: &mut DbConnection, user_id: Uuid, days: i32, today: NaiveDate) -> Result<Vec<Pending>, AppError> {
+    let horizon = today + Duration::days(days as i64);
+
+    let mut reminders = Vec::new();
+    for month in reminder_months(today, horizon) {
+        let forecast = report_service::get_forecast(connection, user_id, &ForecastQuery { month, history_months: None }, today)?;
+        reminders.extend(forecast.expenses.upcoming_recurring.into_iter().filter_map(|bill| bill_reminder(bill, horizon)));
+    }
+    Ok(reminders)
+}
+
+/// Forecast months a reminder window from `today` to `horizon` reaches
+/// into: the current one, and the next when the window ends in it
+fn reminder_months(today: NaiveDate, horizon: NaiveDate) -> Vec<Option<String>> {
+    let mut months = vec![None];
+    if horizon.month() != today.month() {
+        let next_month = today.with_day(1).unwrap_or(today) + Months::new(1);
+        months.push(Some(next_month.format("%Y-%m").to_string()));
+    }
+    months
+}
+
+/// Reminder of a recurring expense, if it is expected by `horizon`
+fn bill_reminder(bill: RecurringItem, horizon: NaiveDate) -> Option<Pending> {
+    if bill.expected_date > horizon {
+        return None;
+    }
+    // Late bills move their expected date along, so the key has the month
+    // rather than the date
+    let dedup_key = format!("bill_reminder:{}:{}", bill.expected_date.format("%Y-%m"), bill.label.to_lowercase());
+    Some(Pending {
+        kind: NotificationKind::BillReminder,
+        dedup_key,
+        title: format!("{} is due on {}", bill.label, bill.expected_date),
+        body: format!("{} of about {:.2} is expected on {}.", bill.label, bill.amount, bill.expected_date),
+        data: json!({
+            "label": bill.label,
+            "expected_date": bill.expected_date,
+            "amount": bill.amount.round_dp(2),
+        }),
+    })
+}
+
+/// Warn once per month at the alert share of the budget and once when it is
+/// exceeded
+fn budget_alert(connection: &mut DbConnection, user_id: Uuid, budget: Decimal, alert_percent: i32, today: NaiveDate) -> Result<Option<Pending>, diesel::result::Error> {
+    let month_start = today.with_day(1).unwrap_or(today);
+    let spent = expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::date.between(month_start, today))
+        .select(dsl::sum(expenses::amount))
+        .first::<Option<Decimal>>(connection)?
+        .unwrap_or(Decimal::ZERO);
+
+    Ok(budget_threshold(month_start, spent, budget, alert_percent))
+}
+
+/// Alert for having spent `spent` of `budget` in the month starting on
+/// `month_start`, if a threshold was reached. The key names the threshold,
+/// so each is reported once a month however often it is checked.
+fn budget_threshold(month_start: NaiveDate, spent: Decimal, budget: Decimal, alert_percent: i32) -> Option<Pending> {
+    let used_percent = spent * Decimal::from(100) / budget;
+    let threshold = if spent > budget {
+        100
+    } else if used_percent >= Decimal::from(alert_percent) {
+        alert_percent
+    } else {
+        return None;
+    };
+
+    let month = month_start.format("%B %Y");
+    let title = if threshold == 100 {
+        format!("Budget for {} exceeded", month)
+    } else {
+        format!("{}% of the budget for {} spent", threshold, month)
+    };
+    Some(Pending {
+        kind: NotificationKind::BudgetThreshold,
+        dedup_key: format!("budget_threshold:{}:{}", month_start.format("%Y-%m"), threshold),
+        title,
+        body: format!(
+            "You spent {:.2} of your budget of {:.2} for {} ({:.0}%).",
+            spent,
+            budget,
+            month,
+            used_percent
+        ),
+        data: json!({
+            "month": month_start.format("%Y-%m").to_string(),
+            "budget": budget.round_dp(2),
+            "spent": spent.round_dp(2),
+            "threshold_percent": threshold,
+        }),
+    })
+}
+
+/// Put a notification in the inbox unless the user was already notified
+/// about the same occasion, with its email queued if `email` is set
+fn store(connection: &mut PgConnection, user_id: Uuid, pending: Pending, email: bool, now: NaiveDateTime) -> Result<Option<Notification>, diesel::result::Error> {
+    diesel::insert_into(notifications::table)
+        .values(Notification {
+            id: Uuid::new_v4(),
+            user_id,
+            kind: pending.kind,
+            dedup_key: pending.dedup_key,
+            title: pending.title,
+            body: pending.body,
+            data: pending.data,
+            read_at: None,
+            created_at: now,
+            email_attempts: 0,
+            email_next_attempt_at: email.then_some(now),
+            emailed_at: None,
+        })
+        .on_conflict((notifications::user_id, notifications::dedup_key))
+        .do_nothing()
+        .returning(Notification::as_returning())
+        .get_result(connection)
+        .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn bill(label: &str, expected_date: NaiveDate) -> RecurringItem {
+        RecurringItem {
+            label: label.to_string(),
+            expected_date,
+            amount: Decimal::from(1200),
+        }
+    }
+
+    #[test]
+    fn summarizes_the_last_full_week() {
+        assert_eq!(last_full_week(date(2025, 10, 6)), (date(2025, 9, 29), date(2025, 10, 5)));
+        assert_eq!(last_full_week(date(2025, 10, 8)), (date(2025, 9, 29), date(2025, 10, 5)));
+        assert_eq!(last_full_week(date(2025, 10, 5)), (date(2025, 9, 22), date(2025, 9, 28)));
+    }
+
+    #[test]
+    fn weekly_summary_lists_totals_and_top_categories() {
+        let spending = vec![
+            (Some("Food".to_string()), Decimal::from(50)),
+            (Some("Rent".to_string()), Decimal::from(600)),
+            (None, Decimal::from(20)),
+            (Some("Fun".to_string()), Decimal::from(30)),
+            (Some("Food".to_string()), Decimal::from(40)),
+        ];
+        let summary = summarize_week(date(2025, 9, 29), Decimal::from(1000), spending);
+
+        assert_eq!(summary.kind, NotificationKind::WeeklySummary);
+        assert_eq!(summary.dedup_key, "weekly_summary:2025-W40");
+        assert_eq!(summary.title, "Your week of 2025-09-29");
+        assert_eq!(
+            summary.body,
+            "From 2025-09-29 to 2025-10-05 you received 1000.00 and spent 740.00, a net of 260.00. Most was spent on Rent 600.00, Food 90.00, Fun 30.00."
+        );
+        assert_eq!(summary.data["net"], json!(260.0));
+        assert_eq!(summary.data["top_categories"].as_array().unwrap().len(), SUMMARY_CATEGORIES);
+    }
+
+    #[test]
+    fn weekly_summary_counts_uncategorized_spending() {
+        let summary = summarize_week(date(2025, 9, 29), Decimal::ZERO, vec![(None, Decimal::from(20))]);
+        assert!(summary.body.ends_with("Most was spent on Uncategorized 20.00."));
+
+        let summary = summarize_week(date(2025, 9, 29), Decimal::from(10), Vec::new());
+        assert!(summary.body.ends_with("a net of 10.00."));
+    }
+
+    #[test]
+    fn weekly_summary_key_uses_the_iso_week_year() {
+        let summary = summarize_week(date(2024, 12, 30), Decimal::ONE, Vec::new());
+        assert_eq!(summary.dedup_key, "weekly_summary:2025-W01");
+    }
+
+    #[test]
+    fn reminder_window_reaches_into_the_next_month() {
+        assert_eq!(reminder_months(date(2025, 10, 6), date(2025, 10, 13)), vec![None]);
+        assert_eq!(reminder_months(date(2025, 10, 25), date(2025, 11, 8)), vec![None, Some("2025-11".to_string())]);
+        assert_eq!(reminder_months(date(2025, 12, 31), date(2026, 1, 14)), vec![None, Some("2026-01".to_string())]);
+    }
+
+    #[test]
+    fn reminds_of_bills_within_the_window_only() {
+        let horizon = date(2025, 10, 13);
+        assert!(bill_reminder(bill("Rent", date(2025, 10, 14)), horizon).is_none());
+
+        let reminder = bill_reminder(bill("Rent", date(2025, 10, 13)), horizon).unwrap();
+        assert_eq!(reminder.kind, NotificationKind::BillReminder);
+        assert_eq!(reminder.title, "Rent is due on 2025-10-13");
+        assert_eq!(reminder.body, "Rent of about 1200.00 is expected on 2025-10-13.");
+    }
+
+    #[test]
+    fn bill_reminder_key_ignores_the_day_and_case() {
+        let horizon = date(2025, 10, 31);
+        let first = bill_reminder(bill("Rent", date(2025, 10, 1)), horizon).unwrap();
+        let late = bill_reminder(bill("RENT", date(2025, 10, 9)), horizon).unwrap();
+        assert_eq!(first.dedup_key, "bill_reminder:2025-10:rent");
+        assert_eq!(first.dedup_key, late.dedup_key);
+    }
+
+    #[test]
+    fn budget_alert_starts_at_the_alert_share() {
+        let month = date(2025, 10, 1);
+        let budget = Decimal::from(1000);
+        assert!(budget_threshold(month, Decimal::from(799), budget, 80).is_none());
+
+        let alert = budget_threshold(month, Decimal::from(800), budget, 80).unwrap();
+        assert_eq!(alert.kind, NotificationKind::BudgetThreshold);
+        assert_eq!(alert.title, "80% of the budget for October 2025 spent");
+        assert_eq!(alert.body, "You spent 800.00 of your budget of 1000.00 for October 2025 (80%).");
+        assert_eq!(alert.data["threshold_percent"], json!(80));
+    }
+
+    #[test]
+    fn budget_alert_reports_exceeding_the_budget() {
+        let month = date(2025, 10, 1);
+        let budget = Decimal::from(1000);
+
+        let spent_all = budget_threshold(month, budget, budget, 80).unwrap();
+        assert_eq!(spent_all.dedup_key, "budget_threshold:2025-10:80");
+
+        let exceeded = budget_threshold(month, Decimal::from(1250), budget, 80).unwrap();
+        assert_eq!(exceeded.title, "Budget for October 2025 exceeded");
+        assert_eq!(exceeded.dedup_key, "budget_threshold:2025-10:100");
+        assert_eq!(exceeded.data["threshold_percent"], json!(100));
+    }
+
+    #[test]
+    fn budget_alert_is_deduplicated_per_threshold_and_month() {
+        let budget = Decimal::from(1000);
+        let at_85 = budget_threshold(date(2025, 10, 1), Decimal::from(850), budget, 80).unwrap();
+        let at_95 = budget_threshold(date(2025, 10, 1), Decimal::from(950), budget, 80).unwrap();
+        let next_month = budget_threshold(date(2025, 11, 1), Decimal::from(850), budget, 80).unwrap();
+
+        assert_eq!(at_85.dedup_key, at_95.dedup_key);
+        assert_ne!(at_85.dedup_key, next_month.dedup_key);
+    }
+
+    #[test]
+    fn email_retries_back_off_and_give_up() {
+        assert_eq!(email_retry_delay(1), Some(Duration::minutes(5)));
+        assert_eq!(email_retry_delay(2), Some(Duration::minutes(10)));
+        assert_eq!(email_retry_delay(4), Some(Duration::minutes(40)));
+        assert_eq!(email_retry_delay(MAX_EMAIL_ATTEMPTS), None);
+    }
+}
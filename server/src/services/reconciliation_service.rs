@@ -0,0 +1,399 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use diesel::dsl;
+use diesel::prelude::*;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::expense::Expense;
+use crate::models::income::Income;
+use crate::models::reconciliation::{
+    ClearTransactions, NewReconciliation, Reconciliation, ReconciliationItem, ReconciliationStatus, ReconciliationSummary, ReconciliationTransaction, TransactionKind,
+    UpdateReconciliation,
+};
+use crate::models::schema::{expenses, incomes, reconciliation_items, reconciliations};
+use crate::services::audit_service;
+
+/// The user's reconciliations, latest statement first
+pub fn get_reconciliations(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Reconciliation>, diesel::result::Error> {
+    reconciliations::table
+        .filter(reconciliations::user_id.eq(user_id))
+        .order((reconciliations::period_end.desc(), reconciliations::created_at.desc()))
+        .select(Reconciliation::as_select())
+        .load(connection)
+}
+
+/// One of the user's reconciliations with its cleared balance
+pub fn get_summary(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid) -> Result<ReconciliationSummary, diesel::result::Error> {
+    let reconciliation = get_reconciliation(connection, user_id, reconciliation_id)?;
+    summarize(connection, reconciliation)
+}
+
+pub fn create_reconciliation(connection: &mut DbConnection, user_id: Uuid, new_reconciliation: NewReconciliation, audit: &AuditContext) -> Result<ReconciliationSummary, AppError> {
+    let account = new_reconciliation.account.trim().to_string();
+    if account.is_empty() {
+        return Err(AppError::Validation("Account must not be empty".to_string()));
+    }
+    if new_reconciliation.period_end < new_reconciliation.period_start {
+        return Err(AppError::Validation("Period must not end before it starts".to_string()));
+    }
+
+    connection.transaction(|connection| {
+        let open = reconciliations::table
+            .filter(reconciliations::user_id.eq(user_id))
+            .filter(reconciliations::account.eq(&account))
+            .filter(reconciliations::status.eq(ReconciliationStatus::Open))
+            .select(reconciliations::id)
+            .first::<Uuid>(connection)
+            .optional()?;
+        if open.is_some() {
+            return Err(AppError::Conflict(format!("A reconciliation of {} is already in progress", account)));
+        }
+
+        // Carry the balance over from the last statement
+        let opening_balance = match new_reconciliation.opening_balance {
+            Some(opening_balance) => opening_balance,
+            None => reconciliations::table
+                .filter(reconciliations::user_id.eq(user_id))
+                .filter(reconciliations::account.eq(&account))
+                .filter(reconciliations::status.eq(ReconciliationStatus::Finished))
+                .order(reconciliations::period_end.desc())
+                .select(reconciliations::statement_balance)
+                .first::<Decimal>(connection)
+                .optional()?
+                .unwrap_or(Decimal::ZERO),
+        };
+
+        let now = Utc::now().naive_utc();
+        let reconciliation = diesel::insert_into(reconciliations::table)
+            .values(Reconciliation {
+                id: Uuid::new_v4(),
+                user_id,
+                account,
+                period_start: new_reconciliation.period_start,
+                period_end: new_reconciliation.period_end,
+                opening_balance,
+                statement_balance: new_reconciliation.statement_balance,
+                status: ReconciliationStatus::Open,
+                finished_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .returning(Reconciliation::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::RECONCILIATION, reconciliation.id, user_id, &reconciliation))?;
+        Ok(summarize(connection, reconciliation)?)
+    })
+}
+
+pub fn update_reconciliation(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid, update_reconciliation: UpdateReconciliation, audit: &AuditContext) -> Result<ReconciliationSummary, AppError> {
+    connection.transaction(|connection| {
+        let before = get_open_reconciliation(connection, user_id, reconciliation_id)?;
+        let period_start = update_reconciliation.period_start.unwrap_or(before.period_start);
+        let period_end = update_reconciliation.period_end.unwrap_or(before.period_end);
+        if period_end < period_start {
+            return Err(AppError::Validation("Period must not end before it starts".to_string()));
+        }
+
+        let now = Utc::now().naive_utc();
+        let reconciliation = diesel::update(reconciliations::table.find(reconciliation_id))
+            .set((update_reconciliation, reconciliations::updated_at.eq(now)))
+            .returning(Reconciliation::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::RECONCILIATION, reconciliation.id, user_id, &before, &reconciliation))?;
+        Ok(summarize(connection, reconciliation)?)
+    })
+}
+
+/// Abandon an open reconciliation. Finished ones are kept as a record of
+/// what was checked.
+pub fn delete_reconciliation(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid, audit: &AuditContext) -> Result<Reconciliation, AppError> {
+    connection.transaction(|connection| {
+        let reconciliation = get_open_reconciliation(connection, user_id, reconciliation_id)?;
+        diesel::delete(reconciliations::table.find(reconciliation.id)).execute(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::RECONCILIATION, reconciliation.id, user_id, &reconciliation))?;
+        Ok(reconciliation)
+    })
+}
+
+/// Transactions of the account that can be cleared: everything up to the
+/// end of the period that was not reconciled before. For a finished
+/// reconciliation, the transactions that were cleared in it.
+pub fn get_transactions(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid) -> Result<Vec<ReconciliationTransaction>, diesel::result::Error> {
+    let reconciliation = get_reconciliation(connection, user_id, reconciliation_id)?;
+    let cleared = cleared_items(connection, reconciliation.id)?;
+    let cleared_ids = |kind: TransactionKind| -> Vec<Uuid> {
+        cleared.iter().filter(|(item_kind, _)| *item_kind == kind).map(|(_, id)| *id).collect()
+    };
+
+    let mut income_query = incomes::table
+        .filter(incomes::user_id.eq(user_id))
+        .filter(incomes::deleted_at.is_null())
+        .select(Income::as_select())
+        .into_boxed();
+    let mut expense_query = expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .select(Expense::as_select())
+        .into_boxed();
+    if reconciliation.status == ReconciliationStatus::Open {
+        income_query = income_query
+            .filter(incomes::account.eq(&reconciliation.account))
+            .filter(incomes::date.le(reconciliation.period_end))
+            .filter(incomes::reconciled_at.is_null());
+        expense_query = expense_query
+            .filter(expenses::account.eq(&reconciliation.account))
+            .filter(expenses::date.le(reconciliation.period_end))
+            .filter(expenses::reconciled_at.is_null());
+    } else {
+        income_query = income_query.filter(incomes::id.eq_any(cleared_ids(TransactionKind::Income)));
+        expense_query = expense_query.filter(expenses::id.eq_any(cleared_ids(TransactionKind::Expense)));
+    }
+
+    let mut transactions: Vec<(ReconciliationTransaction, chrono::NaiveDateTime)> = Vec::new();
+    for income in income_query.load(connection)? {
+        transactions.push((
+            ReconciliationTransaction {
+                kind: TransactionKind::Income,
+                id: income.id,
+                name: income.source,
+                amount: income.amount,
+                date: income.date,
+                description: income.description,
+                cleared: cleared.contains(&(TransactionKind::Income, income.id)),
+                cleared_balance: Decimal::ZERO,
+            },
+            income.created_at,
+        ));
+    }
+    for expense in expense_query.load(connection)? {
+        transactions.push((
+            ReconciliationTransaction {
+                kind: TransactionKind::Expense,
+                id: expense.id,
+                name: expense.item_name,
+                amount: -expense.amount,
+                date: expense.date,
+                description: expense.description,
+                cleared: cleared.contains(&(TransactionKind::Expense, expense.id)),
+                cleared_balance: Decimal::ZERO,
+            },
+            expense.created_at,
+        ));
+    }
+    transactions.sort_by_key(|(transaction, created_at)| (transaction.date, *created_at));
+
+    let mut balance = reconciliation.opening_balance;
+    Ok(transactions
+        .into_iter()
+        .map(|(mut transaction, _)| {
+            if transaction.cleared {
+                balance += transaction.amount;
+            }
+            transaction.cleared_balance = balance;
+            transaction
+        })
+        .collect())
+}
+
+/// Mark transactions of the account as cleared
+pub fn clear(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid, transactions: ClearTransactions) -> Result<ReconciliationSummary, AppError> {
+    connection.transaction(|connection| {
+        let reconciliation = get_open_reconciliation(connection, user_id, reconciliation_id)?;
+
+        let eligible_incomes: HashSet<Uuid> = incomes::table
+            .filter(incomes::id.eq_any(&transactions.incomes))
+            .filter(incomes::user_id.eq(user_id))
+            .filter(incomes::deleted_at.is_null())
+            .filter(incomes::account.eq(&reconciliation.account))
+            .filter(incomes::date.le(reconciliation.period_end))
+            .filter(incomes::reconciled_at.is_null())
+            .select(incomes::id)
+            .load::<Uuid>(connection)?
+            .into_iter()
+            .collect();
+        let eligible_expenses: HashSet<Uuid> = expenses::table
+            .filter(expenses::id.eq_any(&transactions.expenses))
+            .filter(expenses::user_id.eq(user_id))
+            .filter(expenses::deleted_at.is_null())
+            .filter(expenses::account.eq(&reconciliation.account))
+            .filter(expenses::date.le(reconciliation.period_end))
+            .filter(expenses::reconciled_at.is_null())
+            .select(expenses::id)
+            .load::<Uuid>(connection)?
+            .into_iter()
+            .collect();
+        let ineligible = transactions
+            .incomes
+            .iter()
+            .find(|id| !eligible_incomes.contains(id))
+            .or_else(|| transactions.expenses.iter().find(|id| !eligible_expenses.contains(id)));
+        if let Some(id) = ineligible {
+            return Err(AppError::Validation(format!(
+                "Transaction {} is not an unreconciled transaction of {} up to {}",
+                id, reconciliation.account, reconciliation.period_end
+            )));
+        }
+
+        let now = Utc::now().naive_utc();
+        let items: Vec<ReconciliationItem> = eligible_incomes
+            .into_iter()
+            .map(|id| (TransactionKind::Income, id))
+            .chain(eligible_expenses.into_iter().map(|id| (TransactionKind::Expense, id)))
+            .map(|(kind, record_id)| ReconciliationItem { reconciliation_id: reconciliation.id, kind, record_id, cleared_at: now })
+            .collect();
+        diesel::insert_into(reconciliation_items::table)
+            .values(&items)
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+
+        Ok(summarize(connection, reconciliation)?)
+    })
+}
+
+/// Take the cleared mark off transactions again
+pub fn unclear(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid, transactions: ClearTransactions) -> Result<ReconciliationSummary, AppError> {
+    connection.transaction(|connection| {
+        let reconciliation = get_open_reconciliation(connection, user_id, reconciliation_id)?;
+
+        for (kind, ids) in [(TransactionKind::Income, &transactions.incomes), (TransactionKind::Expense, &transactions.expenses)] {
+            diesel::delete(
+                reconciliation_items::table
+                    .filter(reconciliation_items::reconciliation_id.eq(reconciliation.id))
+                    .filter(reconciliation_items::kind.eq(kind))
+                    .filter(reconciliation_items::record_id.eq_any(ids)),
+            )
+            .execute(connection)?;
+        }
+
+        Ok(summarize(connection, reconciliation)?)
+    })
+}
+
+/// Finish a reconciliation whose cleared balance matches the statement and
+/// lock the cleared transactions against edits
+pub fn finish(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid, audit: &AuditContext) -> Result<ReconciliationSummary, AppError> {
+    connection.transaction(|connection| {
+        let before = get_open_reconciliation(connection, user_id, reconciliation_id)?;
+        let summary = summarize(connection, before.clone())?;
+        if !summary.difference.is_zero() {
+            return Err(AppError::Conflict(format!("Cleared balance differs from the statement by {}", summary.difference)));
+        }
+
+        let now = Utc::now().naive_utc();
+        let cleared = cleared_items(connection, before.id)?;
+        let ids_of = |kind: TransactionKind| -> Vec<Uuid> { cleared.iter().filter(|(item_kind, _)| *item_kind == kind).map(|(_, id)| *id).collect() };
+
+        let locked_incomes: Vec<Income> = incomes::table
+            .filter(incomes::id.eq_any(ids_of(TransactionKind::Income)))
+            .filter(incomes::deleted_at.is_null())
+            .select(Income::as_select())
+            .for_update()
+            .load(connection)?;
+        for income in locked_incomes {
+            let locked: Income = diesel::update(incomes::table.find(income.id))
+                .set((incomes::reconciled_at.eq(now), incomes::updated_at.eq(now)))
+                .get_result(connection)?;
+            audit_service::record(connection, audit, AuditChange::updated(entity::INCOME, locked.id, user_id, &income, &locked))?;
+        }
+
+        let locked_expenses: Vec<Expense> = expenses::table
+            .filter(expenses::id.eq_any(ids_of(TransactionKind::Expense)))
+            .filter(expenses::deleted_at.is_null())
+            .select(Expense::as_select())
+            .for_update()
+            .load(connection)?;
+        for expense in locked_expenses {
+            let locked: Expense = diesel::update(expenses::table.find(expense.id))
+                .set((expenses::reconciled_at.eq(now), expenses::updated_at.eq(now)))
+                .get_result(connection)?;
+            audit_service::record(connection, audit, AuditChange::updated(entity::EXPENSE, locked.id, user_id, &expense, &locked))?;
+        }
+
+        let reconciliation = diesel::update(reconciliations::table.find(before.id))
+            .set((
+                reconciliations::status.eq(ReconciliationStatus::Finished),
+                reconciliations::finished_at.eq(now),
+                reconciliations::updated_at.eq(now),
+            ))
+            .returning(Reconciliation::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::RECONCILIATION, reconciliation.id, user_id, &before, &reconciliation))?;
+        Ok(summarize(connection, reconciliation)?)
+    })
+}
+
+/// One of the user's reconciliations; those of other users are reported as
+/// not found
+fn get_reconciliation(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid) -> Result<Reconciliation, diesel::result::Error> {
+    reconciliations::table
+        .find(reconciliation_id)
+        .filter(reconciliations::user_id.eq(user_id))
+        .select(Reconciliation::as_select())
+        .first(connection)
+}
+
+fn get_open_reconciliation(connection: &mut DbConnection, user_id: Uuid, reconciliation_id: Uuid) -> Result<Reconciliation, AppError> {
+    let reconciliation = reconciliations::table
+        .find(reconciliation_id)
+        .filter(reconciliations::user_id.eq(user_id))
+        .select(Reconciliation::as_select())
+        .for_update()
+        .first(connection)?;
+
+    if reconciliation.status != ReconciliationStatus::Open {
+        return Err(AppError::Conflict("Reconciliation is already finished".to_string()));
+    }
+    Ok(reconciliation)
+}
+
+fn cleared_items(connection: &mut DbConnection, reconciliation_id: Uuid) -> Result<HashSet<(TransactionKind, Uuid)>, diesel::result::Error> {
+    Ok(reconciliation_items::table
+        .filter(reconciliation_items::reconciliation_id.eq(reconciliation_id))
+        .select((reconciliation_items::kind, reconciliation_items::record_id))
+        .load::<(TransactionKind, Uuid)>(connection)?
+        .into_iter()
+        .collect())
+}
+
+/// Totals of the cleared transactions that are not in the trash, with
+/// their current amounts
+fn summarize(connection: &mut DbConnection, reconciliation: Reconciliation) -> Result<ReconciliationSummary, diesel::result::Error> {
+    let cleared_of = |kind: TransactionKind| {
+        reconciliation_items::table
+            .filter(reconciliation_items::reconciliation_id.eq(reconciliation.id))
+            .filter(reconciliation_items::kind.eq(kind))
+            .select(reconciliation_items::record_id)
+    };
+
+    let (income_count, cleared_incomes) = incomes::table
+        .filter(incomes::id.eq_any(cleared_of(TransactionKind::Income)))
+        .filter(incomes::deleted_at.is_null())
+        .select((dsl::count_star(), dsl::sum(incomes::amount)))
+        .first::<(i64, Option<Decimal>)>(connection)?;
+    let (expense_count, cleared_expenses) = expenses::table
+        .filter(expenses::id.eq_any(cleared_of(TransactionKind::Expense)))
+        .filter(expenses::deleted_at.is_null())
+        .select((dsl::count_star(), dsl::sum(expenses::amount)))
+        .first::<(i64, Option<Decimal>)>(connection)?;
+
+    let cleared_incomes = cleared_incomes.unwrap_or(Decimal::ZERO);
+    let cleared_expenses = cleared_expenses.unwrap_or(Decimal::ZERO);
+    let cleared_balance = reconciliation.opening_balance + cleared_incomes - cleared_expenses;
+    Ok(ReconciliationSummary {
+        cleared_count: (income_count + expense_count) as usize,
+        cleared_incomes,
+        cleared_expenses,
+        cleared_balance,
+        difference: reconciliation.statement_balance - cleared_balance,
+        reconciliation,
+    })
+}
@@ -0,0 +1,611 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Date, Nullable, Numeric, Text};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::report::{
+    Forecast, ForecastPoint, ForecastQuery, ForecastSeries, RecurringItem, SpendingBreakdown, SpendingBreakdownQuery, SpendingGroup, SpendingGroupBy,
+    SpendingRemainder,
+};
+use crate::models::schema::{expenses, incomes};
+
+const DEFAULT_GROUP_LIMIT: i64 = 10;
+const MAX_GROUP_LIMIT: i64 = 100;
+
+const DEFAULT_HISTORY_MONTHS: u32 = 6;
+const MAX_HISTORY_MONTHS: u32 = 24;
+/// An item is considered recurring when it occurred in each of this many
+/// most recent history months
+const RECURRING_MONTHS: usize = 3;
+/// Days on either side of a day included in its moving average
+const SMOOTHING_RADIUS: u32 = 3;
+/// z-score of the 10th/90th percentile, giving an 80% range
+const RANGE_Z_SCORE: f64 = 1.2816;
+
+/// Totals of one group in the current and the previous period. Groups that
+/// only had spending in one of them have no total in the other.
+#[derive(QueryableByName)]
+struct GroupTotals {
+    #[diesel(sql_type = Text)]
+    key: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    label: Option<String>,
+    #[diesel(sql_type = Nullable<Numeric>)]
+    total: Option<Decimal>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    count: Option<i64>,
+    #[diesel(sql_type = Nullable<Numeric>)]
+    previous_total: Option<Decimal>,
+}
+
+/// Spending of a user grouped by normalized item name or category
+pub fn get_spending_breakdown(connection: &mut DbConnection, user_id: Uuid, query: &SpendingBreakdownQuery, today: NaiveDate) -> Result<SpendingBreakdown, AppError> {
+    let to = query.to.unwrap_or(today);
+    let from = query.from.unwrap_or_else(|| to.with_day(1).unwrap_or(to));
+    if from > to {
+        return Err(AppError::Validation("`from` must not be after `to`".to_string()));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_GROUP_LIMIT);
+    if !(1..=MAX_GROUP_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!("limit must be between 1 and {}", MAX_GROUP_LIMIT)));
+    }
+    let group_by = query.group_by.unwrap_or_default();
+
+    let (previous_from, previous_to) = previous_period(from, to)?;
+
+    // Only the grouping expressions vary and they are fixed strings, the
+    // user's input is always bound
+    let (key, label) = match group_by {
+        SpendingGroupBy::ItemName => (
+            r"lower(regexp_replace(btrim(item_name), '\s+', ' ', 'g'))",
+            r"regexp_replace(btrim(item_name), '\s+', ' ', 'g')",
+        ),
+        SpendingGroupBy::Category => (
+            "coalesce(lower(btrim(category)), '')",
+            "coalesce(btrim(category), 'Uncategorized')",
+        ),
+    };
+    let sql = format!(
+        "WITH current_period AS (
+            SELECT {key} AS key, mode() WITHIN GROUP (ORDER BY {label}) AS label,
+                   sum(amount) AS total, count(*) AS count
+            FROM expenses
+            WHERE user_id = $1 AND deleted_at IS NULL AND date BETWEEN $2 AND $3
+            GROUP BY 1
+        ), previous_period AS (
+            SELECT {key} AS key, sum(amount) AS total
+            FROM expenses
+            WHERE user_id = $1 AND deleted_at IS NULL AND date BETWEEN $4 AND $5
+            GROUP BY 1
+        )
+        SELECT key, c.label, c.total, c.count, p.total AS previous_total
+        FROM current_period c FULL OUTER JOIN previous_period p USING (key)
+        ORDER BY c.total DESC NULLS LAST, key",
+        key = key,
+        label = label,
+    );
+
+    let rows: Vec<GroupTotals> = diesel::sql_query(sql)
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Date, _>(from)
+        .bind::<Date, _>(to)
+        .bind::<Date, _>(previous_from)
+        .bind::<Date, _>(previous_to)
+        .load(connection)?;
+
+    let grouped = group_spending(rows, limit as usize);
+
+    Ok(SpendingBreakdown {
+        group_by,
+        from,
+        to,
+        previous_from,
+        previous_to,
+        total: grouped.total,
+        previous_total: grouped.previous_total,
+        groups: grouped.groups,
+        other: grouped.other,
+    })
+}
+
+/// The period of the same length right before `from`..=`to`
+fn previous_period(from: NaiveDate, to: NaiveDate) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let length = to - from + Duration::days(1);
+    from.checked_sub_signed(length)
+        .zip(from.checked_sub_signed(Duration::days(1)))
+        .ok_or_else(|| AppError::Validation("The period is too early to be compared with the one before it".to_string()))
+}
+
+/// Totals of both periods and the groups of a spending breakdown
+struct GroupedSpending {
+    total: Decimal,
+    previous_total: Decimal,
+    groups: Vec<SpendingGroup>,
+    other: SpendingRemainder,
+}
+
+/// Compare each group of `rows`, largest first, with the previous period,
+/// keeping the first `limit` groups and adding up the rest
+fn group_spending(rows: Vec<GroupTotals>, limit: usize) -> GroupedSpending {
+    let total: Decimal = rows.iter().filter_map(|row| row.total).sum();
+    let previous_total: Decimal = rows.iter().filter_map(|row| row.previous_total).sum();
+
+    let mut groups = Vec::new();
+    let mut other = SpendingRemainder {
+        groups: 0,
+        total: Decimal::ZERO,
+        count: 0,
+        share: Decimal::ZERO,
+    };
+    for row in rows {
+        let (Some(group_total), Some(count)) = (row.total, row.count) else {
+            continue;
+        };
+        if groups.len() < limit {
+            let group_previous_total = row.previous_total.unwrap_or(Decimal::ZERO);
+            let change = group_total - group_previous_total;
+            groups.push(SpendingGroup {
+                key: row.key,
+                label: row.label.unwrap_or_default(),
+                total: group_total,
+                count,
+                share: percentage(group_total, total),
+                previous_total: group_previous_total,
+                change,
+                change_percent: (group_previous_total > Decimal::ZERO).then(|| percentage(change, group_previous_total)),
+            });
+        } else {
+            other.groups += 1;
+            other.total += group_total;
+            other.count += count;
+        }
+    }
+    other.share = percentage(other.total, total);
+
+    GroupedSpending { total, previous_total, groups, other }
+}
+
+/// Project the current user's income, expenses and balance to the end of a
+/// month
+///
+/// The remaining days are projected from the average amount per day of the
+/// month over the history months, smoothed with a moving average, plus the
+/// recurring items that have not occurred yet. The range spans the 10th to
+/// 90th percentile of how the rest of the month went in the history months.
+pub fn get_forecast(connection: &mut DbConnection, user_id: Uuid, query: &ForecastQuery, today: NaiveDate) -> Result<Forecast, AppError> {
+    let month_start = match &query.month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| AppError::Validation("month must be formatted as YYYY-MM".to_string()))?,
+        None => today.with_day(1).unwrap_or(today),
+    };
+    let history_months = query.history_months.unwrap_or(DEFAULT_HISTORY_MONTHS);
+    if !(1..=MAX_HISTORY_MONTHS).contains(&history_months) {
+        return Err(AppError::Validation(format!("history_months must be between 1 and {}", MAX_HISTORY_MONTHS)));
+    }
+
+    let month_end = month_start + Months::new(1) - Duration::days(1);
+    let as_of = today.clamp(month_start - Duration::days(1), month_end);
+    let history_start = month_start - Months::new(history_months);
+
+    let income_entries: Vec<(NaiveDate, String, Decimal)> = incomes::table
+        .filter(incomes::user_id.eq(user_id))
+        .filter(incomes::deleted_at.is_null())
+        .filter(incomes::date.between(history_start, as_of))
+        .select((incomes::date, incomes::source, incomes::amount))
+        .load(connection)?;
+    let expense_entries: Vec<(NaiveDate, String, Decimal)> = expenses::table
+        .filter(expenses::user_id.eq(user_id))
+        .filter(expenses::deleted_at.is_null())
+        .filter(expenses::date.between(history_start, as_of))
+        .select((expenses::date, expenses::item_name, expenses::amount))
+        .load(connection)?;
+
+    let months: Vec<NaiveDate> = (0..history_months).rev().map(|back| month_start - Months::new(back + 1)).collect();
+    let income = project(&income_entries, &months, month_start, month_end, as_of);
+    let expenses = project(&expense_entries, &months, month_start, month_end, as_of);
+    let balance = combine_balance(&income, &expenses);
+
+    Ok(Forecast {
+        month_start,
+        month_end,
+        as_of,
+        history_months,
+        income: income.into_series(),
+        expenses: expenses.into_series(),
+        balance,
+    })
+}
+
+/// Total, latest date and spelling of an item within one month
+type MonthlyOccurrence = (Decimal, NaiveDate, String);
+
+/// Forecast of one kind of transaction, kept unrounded until the end
+struct Projection {
+    actual_to_date: Decimal,
+    upcoming_recurring: Vec<RecurringItem>,
+    /// (date, actual, projected, standard deviation) per day, cumulative
+    days: Vec<(NaiveDate, Option<Decimal>, Decimal, f64)>,
+}
+
+impl Projection {
+    fn into_series(self) -> ForecastSeries {
+        let points: Vec<ForecastPoint> = self
+            .days
+            .iter()
+            .map(|(date, actual, projected, deviation)| {
+                let (low, high) = range(*projected, *deviation);
+                ForecastPoint {
+                    date: *date,
+                    actual: actual.map(|actual| actual.round_dp(2)),
+                    projected: projected.round_dp(2),
+                    low,
+                    high,
+                }
+            })
+            .collect();
+        let last = points.last();
+
+        ForecastSeries {
+            actual_to_date: self.actual_to_date.round_dp(2),
+            projected_total: last.map_or(Decimal::ZERO, |point| point.projected),
+            low: last.map_or(Decimal::ZERO, |point| point.low),
+            high: last.map_or(Decimal::ZERO, |point| point.high),
+            upcoming_recurring: self.upcoming_recurring,
+            points,
+        }
+    }
+}
+
+fn project(entries: &[(NaiveDate, String, Decimal)], months: &[NaiveDate], month_start: NaiveDate, month_end: NaiveDate, as_of: NaiveDate) -> Projection {
+    let month_index = |date: NaiveDate| months.iter().position(|month| month.year() == date.year() && month.month() == date.month());
+    let days_in_month = month_end.day();
+
+    // Amount and latest occurrence of every item in every history month
+    let mut occurrences: HashMap<String, Vec<Option<MonthlyOccurrence>>> = HashMap::new();
+    let mut current_items: HashSet<String> = HashSet::new();
+    for (date, name, amount) in entries {
+        let key = normalize(name);
+        if *date >= month_start {
+            current_items.insert(key);
+            continue;
+        }
+        let Some(index) = month_index(*date) else {
+            continue;
+        };
+        let slot = &mut occurrences.entry(key).or_insert_with(|| vec![None; months.len()])[index];
+        match slot {
+            Some((total, latest, _)) => {
+                *total += *amount;
+                *latest = (*latest).max(*date);
+            }
+            None => *slot = Some((*amount, *date, name.trim().to_string())),
+        }
+    }
+
+    let recent = RECURRING_MONTHS.min(months.len());
+    let recurring: HashSet<&String> = if recent < 2 {
+        HashSet::new()
+    } else {
+        occurrences
+            .iter()
+            .filter(|(_, per_month)| per_month[months.len() - recent..].iter().all(Option::is_some))
+            .map(|(key, _)| key)
+            .collect()
+    };
+
+    // Recurring items are projected separately, so they are left out of the
+    // day-of-month averages
+    let mut daily = vec![vec![Decimal::ZERO; days_in_month as usize + 1]; months.len()];
+    for (date, name, amount) in entries {
+        if *date >= month_start || date.day() > days_in_month || recurring.contains(&normalize(name)) {
+            continue;
+        }
+        if let Some(index) = month_index(*date) {
+            daily[index][date.day() as usize] += *amount;
+        }
+    }
+
+    let mut upcoming: Vec<RecurringItem> = Vec::new();
+    if as_of < month_end {
+        for key in &recurring {
+            if current_items.contains(*key) {
+                continue;
+            }
+            let Some((amount, latest, label)) = occurrences[*key].last().cloned().flatten() else {
+                continue;
+            };
+            let expected = month_start.with_day(latest.day().min(days_in_month)).unwrap_or(month_end);
+            upcoming.push(RecurringItem {
+                label,
+                // Items that are late are still expected, from tomorrow on
+                expected_date: expected.max(as_of + Duration::days(1)),
+                amount,
+            });
+        }
+    }
+    upcoming.sort_by(|a, b| a.expected_date.cmp(&b.expected_date).then_with(|| a.label.cmp(&b.label)));
+
+    let count = Decimal::from(months.len().max(1));
+    let averages: Vec<Decimal> = (0..=days_in_month as usize)
+        .map(|day| daily.iter().map(|month| month[day]).sum::<Decimal>() / count)
+        .collect();
+    let smoothed = |day: u32| {
+        let from = day.saturating_sub(SMOOTHING_RADIUS).max(1);
+        let to = (day + SMOOTHING_RADIUS).min(days_in_month);
+        (from..=to).map(|day| averages[day as usize]).sum::<Decimal>() / Decimal::from(to - from + 1)
+    };
+
+    let mut actual = Decimal::ZERO;
+    let mut projected = Decimal::ZERO;
+    let mut rest_of_month = vec![Decimal::ZERO; months.len()];
+    let mut days = Vec::with_capacity(days_in_month as usize);
+    for day in 1..=days_in_month {
+        let date = month_start.with_day(day).unwrap_or(month_end);
+        if date <= as_of {
+            actual += entries.iter().filter(|(entry_date, _, _)| *entry_date == date).map(|(_, _, amount)| *amount).sum::<Decimal>();
+            projected = actual;
+            days.push((date, Some(actual), projected, 0.0));
+            continue;
+        }
+
+        projected += smoothed(day);
+        projected += upcoming.iter().filter(|item| item.expected_date == date).map(|item| item.amount).sum::<Decimal>();
+        for (month, total) in rest_of_month.iter_mut().enumerate() {
+            *total += daily[month][day as usize];
+        }
+        days.push((date, None, projected, standard_deviation(&rest_of_month)));
+    }
+
+    Projection {
+        actual_to_date: actual,
+        upcoming_recurring: upcoming,
+        days,
+    }
+}
+
+/// Income minus expenses. The range runs from low income and high expenses
+/// to high income and low expenses.
+fn combine_balance(income: &Projection, expenses: &Projection) -> ForecastSeries {
+    let points: Vec<ForecastPoint> = income
+        .days
+        .iter()
+        .zip(&expenses.days)
+        .map(|((date, income_actual, income_projected, income_deviation), (_, expense_actual, expense_projected, expense_deviation))| {
+            let (income_low, income_high) = range(*income_projected, *income_deviation);
+            let (expense_low, expense_high) = range(*expense_projected, *expense_deviation);
+            ForecastPoint {
+                date: *date,
+                actual: income_actual.zip(*expense_actual).map(|(income, expense)| (income - expense).round_dp(2)),
+                projected: (income_projected - expense_projected).round_dp(2),
+                low: income_low - expense_high,
+                high: income_high - expense_low,
+            }
+        })
+        .collect();
+    let last = points.last();
+
+    let mut upcoming_recurring: Vec<RecurringItem> = income
+        .upcoming_recurring
+        .iter()
+        .map(|item| RecurringItem { label: item.label.clone(), expected_date: item.expected_date, amount: item.amount })
+        .chain(expenses.upcoming_recurring.iter().map(|item| RecurringItem { label: item.label.clone(), expected_date: item.expected_date, amount: -item.amount }))
+        .collect();
+    upcoming_recurring.sort_by(|a, b| a.expected_date.cmp(&b.expected_date).then_with(|| a.label.cmp(&b.label)));
+
+    ForecastSeries {
+        actual_to_date: (income.actual_to_date - expenses.actual_to_date).round_dp(2),
+        projected_total: last.map_or(Decimal::ZERO, |point| point.projected),
+        low: last.map_or(Decimal::ZERO, |point| point.low),
+        high: last.map_or(Decimal::ZERO, |point| point.high),
+        upcoming_recurring,
+        points,
+    }
+}
+
+/// 80% range around a projection, never below zero
+fn range(projected: Decimal, deviation: f64) -> (Decimal, Decimal) {
+    let margin = Decimal::from_f64(deviation * RANGE_Z_SCORE).unwrap_or(Decimal::ZERO);
+    (
+        (projected - margin).max(Decimal::ZERO).round_dp(2),
+        (projected + margin).round_dp(2),
+    )
+}
+
+/// Sample standard deviation; zero with fewer than two values
+fn standard_deviation(values: &[Decimal]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let values: Vec<f64> = values.iter().map(|value| value.to_f64().unwrap_or(0.0)).collect();
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// Payee or source without differences in case and whitespace
+fn normalize(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// `part` as a percentage of `whole`, to two decimal places
+fn percentage(part: Decimal, whole: Decimal) -> Decimal {
+    if whole.is_zero() {
+        return Decimal::ZERO;
+    }
+    (part * Decimal::ONE_HUNDRED / whole).round_dp(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn entry(date: NaiveDate, name: &str, amount: i64) -> (NaiveDate, String, Decimal) {
+        (date, name.to_string(), Decimal::from(amount))
+    }
+
+    fn group(key: &str, total: Option<i64>, count: i64, previous_total: Option<i64>) -> GroupTotals {
+        GroupTotals {
+            key: key.to_string(),
+            label: total.map(|_| key.to_string()),
+            total: total.map(Decimal::from),
+            count: total.map(|_| count),
+            previous_total: previous_total.map(Decimal::from),
+        }
+    }
+
+    #[test]
+    fn compares_the_same_number_of_days_right_before() {
+        assert_eq!(previous_period(date(2025, 3, 1), date(2025, 3, 31)).unwrap(), (date(2025, 1, 29), date(2025, 2, 28)));
+        assert_eq!(previous_period(date(2025, 3, 1), date(2025, 3, 1)).unwrap(), (date(2025, 2, 28), date(2025, 2, 28)));
+    }
+
+    #[test]
+    fn refuses_periods_without_one_before() {
+        let error = previous_period(NaiveDate::MIN, date(2025, 3, 1)).unwrap_err();
+
+        assert_eq!(actix_web::ResponseError::status_code(&error), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn compares_groups_with_the_previous_period() {
+        let rows = vec![
+            group("rent", Some(1200), 1, Some(1000)),
+            group("coffee", Some(30), 6, None),
+            group("gym", None, 0, Some(50)),
+        ];
+
+        let grouped = group_spending(rows, 10);
+
+        assert_eq!(grouped.total, Decimal::from(1230));
+        assert_eq!(grouped.previous_total, Decimal::from(1050));
+        assert_eq!(grouped.groups.len(), 2);
+        assert_eq!(grouped.groups[0].previous_total, Decimal::from(1000));
+        assert_eq!(grouped.groups[0].change, Decimal::from(200));
+        assert_eq!(grouped.groups[0].change_percent, Some(Decimal::from(20)));
+        assert_eq!(grouped.groups[1].previous_total, Decimal::ZERO);
+        assert_eq!(grouped.groups[1].change, Decimal::from(30));
+        assert_eq!(grouped.groups[1].change_percent, None);
+        assert_eq!(grouped.other.groups, 0);
+    }
+
+    #[test]
+    fn adds_up_groups_beyond_the_limit() {
+        let rows = vec![
+            group("rent", Some(600), 1, None),
+            group("groceries", Some(300), 4, None),
+            group("coffee", Some(60), 12, None),
+            group("books", Some(40), 2, None),
+            group("gym", None, 0, Some(50)),
+        ];
+
+        let grouped = group_spending(rows, 2);
+
+        assert_eq!(grouped.groups.iter().map(|group| group.key.as_str()).collect::<Vec<_>>(), ["rent", "groceries"]);
+        assert_eq!(grouped.other.groups, 2);
+        assert_eq!(grouped.other.total, Decimal::from(100));
+        assert_eq!(grouped.other.count, 14);
+        assert_eq!(grouped.other.share, Decimal::from(10));
+    }
+
+    #[test]
+    fn rounds_shares_to_hundredths() {
+        let rows = vec![
+            group("rent", Some(1), 1, Some(3)),
+            group("groceries", Some(1), 1, None),
+            group("coffee", Some(1), 1, None),
+        ];
+
+        let grouped = group_spending(rows, 2);
+
+        assert_eq!(grouped.groups[0].share, Decimal::new(3333, 2));
+        assert_eq!(grouped.groups[0].change_percent, Some(Decimal::new(-6667, 2)));
+        assert_eq!(grouped.other.share, Decimal::new(3333, 2));
+    }
+
+    #[test]
+    fn shares_nothing_without_spending() {
+        let grouped = group_spending(vec![group("gym", None, 0, Some(50))], 10);
+
+        assert!(grouped.groups.is_empty());
+        assert_eq!(grouped.other.share, Decimal::ZERO);
+    }
+
+    /// The history months before `month_start`, oldest first, as in `get_forecast`
+    fn history(month_start: NaiveDate, count: u32) -> Vec<NaiveDate> {
+        (0..count).rev().map(|back| month_start - Months::new(back + 1)).collect()
+    }
+
+    #[test]
+    fn expects_month_end_recurring_items_on_the_last_day_of_a_short_month() {
+        let month_start = date(2025, 2, 1);
+        let entries = vec![
+            entry(date(2024, 11, 30), "Rent", 1200),
+            entry(date(2024, 12, 31), "Rent", 1200),
+            entry(date(2025, 1, 31), " rent ", 1250),
+            entry(date(2025, 2, 3), "Coffee", 5),
+        ];
+
+        let series = project(&entries, &history(month_start, 3), month_start, date(2025, 2, 28), date(2025, 2, 10)).into_series();
+
+        assert_eq!(series.points.len(), 28);
+        assert_eq!(series.actual_to_date, Decimal::from(5));
+        assert_eq!(series.upcoming_recurring.len(), 1);
+        assert_eq!(series.upcoming_recurring[0].label, "rent");
+        assert_eq!(series.upcoming_recurring[0].expected_date, date(2025, 2, 28));
+        assert_eq!(series.upcoming_recurring[0].amount, Decimal::from(1250));
+        assert_eq!(series.points[9].actual, Some(Decimal::from(5)));
+        assert_eq!(series.points[10].actual, None);
+        assert_eq!(series.points[26].projected, Decimal::from(5));
+        assert_eq!(series.projected_total, Decimal::from(1255));
+    }
+
+    #[test]
+    fn spreads_day_of_month_averages_and_drops_days_the_month_lacks() {
+        let month_start = date(2025, 4, 1);
+        let entries = vec![
+            entry(date(2025, 2, 15), "Groceries", 70),
+            entry(date(2025, 3, 15), "Hardware store", 70),
+            // April has no 31st, so this day has no counterpart to project
+            entry(date(2025, 3, 31), "Gift", 100),
+        ];
+
+        // Forecast made before the month started
+        let series = project(&entries, &history(month_start, 2), month_start, date(2025, 4, 30), date(2025, 3, 31)).into_series();
+
+        assert!(series.upcoming_recurring.is_empty());
+        assert!(series.points.iter().all(|point| point.actual.is_none()));
+        // The average of 70 on the 15th is smoothed over the 12th to the 18th
+        assert_eq!(series.points[10].projected, Decimal::ZERO);
+        assert_eq!(series.points[11].projected, Decimal::from(10));
+        assert_eq!(series.points[17].projected, Decimal::from(70));
+        assert_eq!(series.projected_total, Decimal::from(70));
+        // Both history months spent the same on the rest of the month
+        assert_eq!((series.low, series.high), (Decimal::from(70), Decimal::from(70)));
+    }
+
+    #[test]
+    fn projects_nothing_more_once_the_month_is_over() {
+        let month_start = date(2024, 12, 1);
+        let entries = vec![
+            entry(date(2024, 10, 5), "Gym", 30),
+            entry(date(2024, 11, 5), "Gym", 30),
+            entry(date(2024, 12, 20), "Gifts", 200),
+        ];
+
+        let series = project(&entries, &history(month_start, 2), month_start, date(2024, 12, 31), date(2024, 12, 31)).into_series();
+
+        assert!(series.upcoming_recurring.is_empty());
+        assert_eq!(series.points[30].actual, Some(Decimal::from(200)));
+        assert_eq!(series.projected_total, Decimal::from(200));
+    }
+}
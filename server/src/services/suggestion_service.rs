@@ -0,0 +1,278 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::database::db_connection::DbConnection;
+use crate::models::category_suggestion::{CategorySuggestion, CategorySuggestions};
+use crate::models::expense::NewExpense;
+use crate::models::schema::expenses;
+
+pub const DEFAULT_SUGGESTION_LIMIT: usize = 3;
+pub const MAX_SUGGESTION_LIMIT: usize = 10;
+
+/// Smallest probability for which a category is filled in without asking
+const MIN_PREFILL_PROBABILITY: f64 = 0.6;
+
+/// Laplace smoothing added to every word count
+const SMOOTHING: f64 = 1.0;
+
+/// Categories for the text of an expense, learned from the user's
+/// categorized expenses
+pub fn suggest_categories(connection: &mut DbConnection, user_id: Uuid, item_name: &str, description: Option<&str>, limit: usize) -> Result<CategorySuggestions, diesel::result::Error> {
+    let model = CategoryModel::train(connection, user_id)?;
+    let suggestions = model
+        .classify(item_name, description)
+        .into_iter()
+        .take(limit)
+        .map(|(category, probability)| CategorySuggestion {
+            category,
+            probability: Decimal::from_f64(probability).unwrap_or_default().round_dp(4),
+        })
+        .collect();
+
+    Ok(CategorySuggestions { trained_on: model.documents, suggestions })
+}
+
+/// Fills in the category of new expenses from the owner's history. Each
+/// user's model is trained once, on first use, so a batch import does not
+/// retrain for every expense.
+#[derive(Default)]
+pub struct CategoryPrefill {
+    models: HashMap<Uuid, CategoryModel>,
+}
+
+impl CategoryPrefill {
+    /// Set the category of an uncategorized expense if one category is
+    /// clearly the most likely
+    pub fn apply(&mut self, connection: &mut DbConnection, new_expense: &mut NewExpense) -> Result<(), diesel::result::Error> {
+        if new_expense.category.is_some() {
+            return Ok(());
+        }
+
+        let model = match self.models.entry(new_expense.user_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(CategoryModel::train(connection, new_expense.user_id)?),
+        };
+        new_expense.category = model.prefill(&new_expense.item_name, new_expense.description.as_deref());
+        Ok(())
+    }
+}
+
+/// Multinomial naive Bayes over the words of item names and descriptions
+struct CategoryModel {
+    /// Categorized expenses the model was trained on
+    documents: usize,
+    categories: Vec<CategoryStats>,
+    vocabulary: HashSet<String>,
+}
+
+struct CategoryStats {
+    /// Most common spelling among the expenses in the category
+    label: String,
+    documents: usize,
+    words: usize,
+    word_counts: HashMap<String, usize>,
+}
+
+impl CategoryModel {
+    fn train(connection: &mut DbConnection, user_id: Uuid) -> Result<CategoryModel, diesel::result::Error> {
+        let rows = expenses::table
+            .filter(expenses::user_id.eq(user_id))
+            .filter(expenses::deleted_at.is_null())
+            .filter(expenses::category.is_not_null())
+            .select((expenses::item_name, expenses::description, expenses::category))
+            .load::<(String, Option<String>, Option<String>)>(connection)?;
+
+        Ok(Self::from_expenses(rows))
+    }
+
+    /// Learn from (item name, description, category) of expenses
+    fn from_expenses(rows: Vec<(String, Option<String>, Option<String>)>) -> CategoryModel {
+        let mut model = CategoryModel { documents: 0, categories: Vec::new(), vocabulary: HashSet::new() };
+        let mut index_by_key: HashMap<String, usize> = HashMap::new();
+        let mut spellings: Vec<HashMap<String, usize>> = Vec::new();
+
+        for (item_name, description, category) in rows {
+            let Some(category) = category.map(|category| category.trim().to_string()).filter(|category| !category.is_empty()) else {
+                continue;
+            };
+            let index = *index_by_key.entry(category.to_lowercase()).or_insert_with(|| {
+                model.categories.push(CategoryStats { label: String::new(), documents: 0, words: 0, word_counts: HashMap::new() });
+                spellings.push(HashMap::new());
+                model.categories.len() - 1
+            });
+            *spellings[index].entry(category).or_insert(0) += 1;
+
+            let stats = &mut model.categories[index];
+            stats.documents += 1;
+            for word in tokenize(&item_name, description.as_deref()) {
+                stats.words += 1;
+                *stats.word_counts.entry(word.clone()).or_insert(0) += 1;
+                model.vocabulary.insert(word);
+            }
+            model.documents += 1;
+        }
+
+        for (stats, spellings) in model.categories.iter_mut().zip(spellings) {
+            // Ties go to the alphabetically first spelling so the label is stable
+            stats.label = spellings
+                .into_iter()
+                .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+                .map(|(label, _)| label)
+                .unwrap_or_default();
+        }
+
+        model
+    }
+
+    /// The most likely category if it is likely enough to fill in unasked
+    fn prefill(&self, item_name: &str, description: Option<&str>) -> Option<String> {
+        // With a single category every known word points to it
+        if self.categories.len() < 2 {
+            return None;
+        }
+
+        self.classify(item_name, description)
+            .into_iter()
+            .next()
+            .filter(|(_, probability)| *probability >= MIN_PREFILL_PROBABILITY)
+            .map(|(category, _)| category)
+    }
+
+    /// Categories with their posterior probability, most likely first.
+    /// Words never seen in training carry no information and are ignored;
+    /// if no word is known there is nothing to suggest.
+    fn classify(&self, item_name: &str, description: Option<&str>) -> Vec<(String, f64)> {
+        let words: Vec<String> = tokenize(item_name, description)
+            .into_iter()
+            .filter(|word| self.vocabulary.contains(word))
+            .collect();
+        if words.is_empty() || self.categories.is_empty() {
+            return Vec::new();
+        }
+
+        let vocabulary_size = self.vocabulary.len() as f64;
+        let log_scores: Vec<f64> = self
+            .categories
+            .iter()
+            .map(|stats| {
+                let prior = (stats.documents as f64 / self.documents as f64).ln();
+                let denominator = stats.words as f64 + SMOOTHING * vocabulary_size;
+                prior
+                    + words
+                        .iter()
+                        .map(|word| ((*stats.word_counts.get(word).unwrap_or(&0) as f64 + SMOOTHING) / denominator).ln())
+                        .sum::<f64>()
+            })
+            .collect();
+
+        // Normalize in log space so long texts do not underflow
+        let max_score = log_scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let total: f64 = log_scores.iter().map(|score| (score - max_score).exp()).sum();
+        let mut ranked: Vec<(String, f64)> = self
+            .categories
+            .iter()
+            .zip(log_scores)
+            .map(|(stats, score)| (stats.label.clone(), (score - max_score).exp() / total))
+            .collect();
+        ranked.sort_by(|(a_label, a), (b_label, b)| b.total_cmp(a).then_with(|| a_label.cmp(b_label)));
+        ranked
+    }
+}
+
+/// Lowercased words of the item name and description. Numbers are dropped,
+/// they are mostly card terminals, receipt numbers and dates.
+pub fn tokenize(item_name: &str, description: Option<&str>) -> Vec<String> {
+    [Some(item_name), description]
+        .into_iter()
+        .flatten()
+        .flat_map(|text| text.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.chars().count() > 1 && !word.chars().all(|c| c.is_numeric()))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(expenses: &[(&str, &str)]) -> CategoryModel {
+        CategoryModel::from_expenses(
+            expenses
+                .iter()
+                .map(|(item_name, category)| (item_name.to_string(), None, Some(category.to_string())))
+                .collect(),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} is not {}", actual, expected);
+    }
+
+    /// Dining has one expense with 2 words, Groceries two with 4 words, and
+    /// there are 5 distinct words
+    fn groceries_and_dining() -> CategoryModel {
+        model(&[("Starbucks coffee", "Dining"), ("Coffee beans", "Groceries"), ("Milk bread", "Groceries")])
+    }
+
+    #[test]
+    fn weighs_smoothed_word_likelihoods_by_category_prior() {
+        // Dining: 1/3 * (1+1)/(2+5) = 18/189, Groceries: 2/3 * (1+1)/(4+5) = 28/189
+        let ranked = groceries_and_dining().classify("Coffee", None);
+
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, "Groceries");
+        assert_close(ranked[0].1, 28.0 / 46.0);
+        assert_eq!(ranked[1].0, "Dining");
+        assert_close(ranked[1].1, 18.0 / 46.0);
+    }
+
+    #[test]
+    fn ignores_words_never_seen_in_training() {
+        let model = groceries_and_dining();
+
+        // Dining: 1/3 * 2/7 = 18/189, Groceries: 2/3 * 1/9 = 14/189
+        let ranked = model.classify("Starbucks Reserve", Some("airport"));
+        assert_eq!(ranked[0].0, "Dining");
+        assert_close(ranked[0].1, 18.0 / 32.0);
+
+        assert!(model.classify("Hardware store", None).is_empty());
+    }
+
+    #[test]
+    fn prefills_only_clear_winners() {
+        let model = groceries_and_dining();
+
+        // 28/46 = 0.61 clears the 0.6 threshold, 18/32 = 0.56 does not
+        assert_eq!(model.prefill("coffee", None).as_deref(), Some("Groceries"));
+        assert_eq!(model.prefill("starbucks", None), None);
+    }
+
+    #[test]
+    fn never_prefills_with_a_single_category() {
+        let model = model(&[("Coffee", "Dining"), ("Lunch", "Dining")]);
+
+        assert_close(model.classify("coffee", None)[0].1, 1.0);
+        assert_eq!(model.prefill("coffee", None), None);
+    }
+
+    #[test]
+    fn labels_categories_with_their_most_common_spelling() {
+        let model = model(&[("Milk", "groceries"), ("Bread", "Groceries "), ("Eggs", "groceries"), ("Coffee", "dining"), ("Tea", "Dining")]);
+
+        let mut labels: Vec<&str> = model.categories.iter().map(|stats| stats.label.as_str()).collect();
+        labels.sort();
+        // The tie between "Dining" and "dining" goes to the alphabetically first
+        assert_eq!(labels, vec!["Dining", "groceries"]);
+        assert_eq!(model.documents, 5);
+    }
+
+    #[test]
+    fn tokenizes_without_numbers_and_single_letters() {
+        assert_eq!(tokenize("POS 1234 Coffee-Shop #12 a", Some("Card 4x")), vec!["pos", "coffee", "shop", "card", "4x"]);
+    }
+}
@@ -0,0 +1,670 @@
+use actix_web::{web, HttpRequest};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::config;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::auth::AuthError;
+use crate::models::schema::{recovery_codes, user_totp};
+use crate::models::two_factor::{RecoveryCode, RecoveryCodesResponse, TotpEnrollmentResponse, UserTotp};
+use crate::models::user::User;
+use crate::services::audit_service;
+use crate::services::auth_service::{AuthService, DbPool};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const TOTP_SKEW: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Wrong codes in a row a user may enter before having to wait
+const FREE_CODE_ATTEMPTS: i32 = 5;
+/// Wait after the last free attempt, doubled with each further one
+const FIRST_LOCKOUT_SECONDS: i64 = 60;
+/// Longest wait between two attempts
+const MAX_LOCKOUT_SECONDS: i64 = 24 * 60 * 60;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    /// Start (or restart) TOTP enrollment for the current user
+    ///
+    /// A fresh secret is stored in a disabled state and returned together with
+    /// the `otpauth://` URI to render as a QR code. Nothing changes for login
+    /// until the user confirms the secret with [`Self::confirm_enrollment`].
+    pub async fn begin_enrollment(
+        pool: web::Data<DbPool>,
+        req: HttpRequest,
+    ) -> Result<TotpEnrollmentResponse, AuthError> {
+        let user = AuthService::get_current_user(pool.clone(), req).await?;
+        let mut conn = Self::connection(&pool)?;
+
+        if Self::is_enabled(&mut conn, user.id)? {
+            return Err(AuthError {
+                message: "Two-factor authentication is already enabled".to_string(),
+                code: "TWO_FACTOR_ALREADY_ENABLED".to_string(),
+            });
+        }
+
+        let secret = match Secret::generate_secret().to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+        };
+        let totp = Self::build_totp(&secret, &user.email)?;
+
+        let now = Utc::now().naive_utc();
+        let record = UserTotp {
+            user_id: user.id,
+            secret: secret.clone(),
+            enabled: false,
+            last_used_step: None,
+            created_at: now,
+            updated_at: now,
+            challenge_id: None,
+            failed_attempts: 0,
+            locked_until: None,
+        };
+
+        diesel::insert_into(user_totp::table)
+            .values(&record)
+            .on_conflict(user_totp::user_id)
+            .do_update()
+            .set((
+                user_totp::secret.eq(&record.secret),
+                user_totp::last_used_step.eq(None::<i64>),
+                user_totp::updated_at.eq(now),
+            ))
+            .execute(&mut conn)
+            .map_err(|_| Self::query_error())?;
+
+        Ok(TotpEnrollmentResponse {
+            secret,
+            otpauth_uri: totp.get_url(),
+        })
+    }
+
+    /// Confirm enrollment with a code from the authenticator app
+    ///
+    /// Enables two-factor authentication and returns a fresh set of recovery
+    /// codes. The plain codes are only ever shown in this response.
+    pub async fn confirm_enrollment(
+        pool: web::Data<DbPool>,
+        req: HttpRequest,
+        code: String,
+    ) -> Result<RecoveryCodesResponse, AuthError> {
+        let audit = AuditContext::from_http_request(&req);
+        let user = AuthService::get_current_user(pool.clone(), req).await?;
+        let audit = audit.with_actor(user.id);
+        let mut conn = Self::connection(&pool)?;
+        Self::count_attempt(&mut conn, user.id)?;
+
+        conn.transaction(|conn| {
+            let record = Self::find_totp(conn, user.id)?.ok_or_else(|| AuthError {
+                message: "Two-factor enrollment has not been started".to_string(),
+                code: "TWO_FACTOR_NOT_ENROLLED".to_string(),
+            })?;
+
+            if record.enabled {
+                return Err(AuthError {
+                    message: "Two-factor authentication is already enabled".to_string(),
+                    code: "TWO_FACTOR_ALREADY_ENABLED".to_string(),
+                });
+            }
+
+            if !Self::verify_totp(conn, &record, &user.email, &code)? {
+                return Err(Self::invalid_code());
+            }
+
+            diesel::update(user_totp::table.find(user.id))
+                .set((
+                    user_totp::enabled.eq(true),
+                    user_totp::failed_attempts.eq(0),
+                    user_totp::locked_until.eq(None::<NaiveDateTime>),
+                    user_totp::updated_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+                .map_err(|_| Self::query_error())?;
+
+            let recovery_codes = Self::replace_recovery_codes(conn, user.id)?;
+            audit_service::record(conn, &audit, AuditChange::created(entity::TWO_FACTOR, user.id, user.id, &Self::audit_snapshot(true)))?;
+            Ok(RecoveryCodesResponse { recovery_codes })
+        })
+    }
+
+    /// Turn two-factor authentication off, given a valid TOTP or recovery code
+    pub async fn disable(
+        pool: web::Data<DbPool>,
+        req: HttpRequest,
+        code: String,
+    ) -> Result<(), AuthError> {
+        let audit = AuditContext::from_http_request(&req);
+        let user = AuthService::get_current_user(pool.clone(), req).await?;
+        let audit = audit.with_actor(user.id);
+        let mut conn = Self::connection(&pool)?;
+        Self::count_attempt(&mut conn, user.id)?;
+
+        conn.transaction(|conn| {
+            Self::require_enabled(conn, &user)?;
+
+            if !Self::verify_second_factor(conn, &user, &code)? {
+                return Err(Self::invalid_code());
+            }
+
+            diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user.id)))
+                .execute(conn)
+                .map_err(|_| Self::query_error())?;
+            diesel::delete(user_totp::table.find(user.id))
+                .execute(conn)
+                .map_err(|_| Self::query_error())?;
+
+            audit_service::record(conn, &audit, AuditChange::deleted(entity::TWO_FACTOR, user.id, user.id, &Self::audit_snapshot(true)))?;
+            Ok(())
+        })
+    }
+
+    /// Invalidate all recovery codes and issue a new set
+    pub async fn regenerate_recovery_codes(
+        pool: web::Data<DbPool>,
+        req: HttpRequest,
+        code: String,
+    ) -> Result<RecoveryCodesResponse, AuthError> {
+        let audit = AuditContext::from_http_request(&req);
+        let user = AuthService::get_current_user(pool.clone(), req).await?;
+        let audit = audit.with_actor(user.id);
+        let mut conn = Self::connection(&pool)?;
+        Self::count_attempt(&mut conn, user.id)?;
+
+        conn.transaction(|conn| {
+            Self::require_enabled(conn, &user)?;
+
+            if !Self::verify_second_factor(conn, &user, &code)? {
+                return Err(Self::invalid_code());
+            }
+
+            let recovery_codes = Self::replace_recovery_codes(conn, user.id)?;
+            let snapshot = serde_json::json!({ "count": recovery_codes.len() });
+            audit_service::record(conn, &audit, AuditChange::created(entity::RECOVERY_CODES, user.id, user.id, &snapshot))?;
+            Ok(RecoveryCodesResponse { recovery_codes })
+        })
+    }
+
+    /// Whether the user has completed TOTP enrollment
+    pub fn is_enabled(conn: &mut PgConnection, user_id: Uuid) -> Result<bool, AuthError> {
+        Ok(Self::find_totp(conn, user_id)?.is_some_and(|record| record.enabled))
+    }
+
+    /// Check a second factor for the user, once the attempt has been counted
+    /// with [`Self::count_attempt`]
+    ///
+    /// Six digit codes are checked against the TOTP secret, anything else is
+    /// treated as a recovery code. Successful codes are consumed: a TOTP code
+    /// cannot be replayed within its window and a recovery code works once.
+    /// They also clear the user's failed attempts.
+    fn verify_second_factor(
+        conn: &mut PgConnection,
+        user: &User,
+        code: &str,
+    ) -> Result<bool, AuthError> {
+        let code = code.trim();
+
+        let verified = if code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+            match Self::find_totp(conn, user.id)? {
+                Some(record) if record.enabled => Self::verify_totp(conn, &record, &user.email, code)?,
+                _ => false,
+            }
+        } else {
+            Self::consume_recovery_code(conn, user.id, code)?
+        };
+
+        if verified {
+            diesel::update(user_totp::table.find(user.id))
+                .set((
+                    user_totp::failed_attempts.eq(0),
+                    user_totp::locked_until.eq(None::<NaiveDateTime>),
+                ))
+                .execute(conn)
+                .map_err(|_| Self::query_error())?;
+        }
+        Ok(verified)
+    }
+
+    /// Count an attempt at a code against the user's limit, or refuse it
+    /// while the user has to wait
+    ///
+    /// The attempt counts as failed until a correct code clears the count,
+    /// and the wait for the next attempt is set before the code is checked,
+    /// so concurrent guesses cannot get past the limit. Must not run inside a
+    /// transaction, which would roll the count back on a wrong code.
+    fn count_attempt(conn: &mut PgConnection, user_id: Uuid) -> Result<(), AuthError> {
+        conn.transaction(|conn| {
+            let attempts = user_totp::table
+                .find(user_id)
+                .select((user_totp::failed_attempts, user_totp::locked_until))
+                .for_update()
+                .first::<(i32, Option<NaiveDateTime>)>(conn)
+                .optional()
+                .map_err(|_| Self::query_error())?;
+            // Without TOTP there is nothing to guess; the check itself fails
+            let Some((failed_attempts, locked_until)) = attempts else {
+                return Ok(());
+            };
+
+            let now = Utc::now().naive_utc();
+            if let Some(locked_until) = locked_until.filter(|locked_until| *locked_until > now) {
+                return Err(AuthError {
+                    message: format!(
+                        "Too many invalid two-factor codes; try again in {} seconds",
+                        (locked_until - now).num_seconds() + 1
+                    ),
+                    code: "TWO_FACTOR_LOCKED".to_string(),
+                });
+            }
+
+            let failed_attempts = failed_attempts.saturating_add(1);
+            diesel::update(user_totp::table.find(user_id))
+                .set((
+                    user_totp::failed_attempts.eq(failed_attempts),
+                    user_totp::locked_until.eq(Self::lockout(failed_attempts).map(|wait| now + wait)),
+                ))
+                .execute(conn)
+                .map_err(|_| Self::query_error())?;
+            Ok(())
+        })
+    }
+
+    /// Wait before the next attempt once `failed_attempts` are counted
+    fn lockout(failed_attempts: i32) -> Option<Duration> {
+        let beyond_free = failed_attempts.checked_sub(FREE_CODE_ATTEMPTS).filter(|beyond| *beyond >= 0)?;
+        let seconds = FIRST_LOCKOUT_SECONDS
+            .checked_shl(beyond_free as u32)
+            .filter(|seconds| *seconds <= MAX_LOCKOUT_SECONDS)
+            .unwrap_or(MAX_LOCKOUT_SECONDS);
+        Some(Duration::seconds(seconds))
+    }
+
+    /// Issue the challenge for a password login, replacing any earlier one.
+    /// Failed attempts carry over from earlier challenges.
+    pub fn start_challenge(conn: &mut PgConnection, user_id: Uuid) -> Result<Uuid, AuthError> {
+        let challenge_id = Uuid::new_v4();
+        diesel::update(user_totp::table.find(user_id))
+            .set(user_totp::challenge_id.eq(challenge_id))
+            .execute(conn)
+            .map_err(|_| Self::query_error())?;
+        Ok(challenge_id)
+    }
+
+    /// Check the second factor given for a login challenge
+    ///
+    /// Only the latest challenge can be answered, and a correct answer uses
+    /// it up. Answers count against the user's attempts like every other
+    /// check of a code, see [`Self::count_attempt`]. Must not run inside a
+    /// transaction, which would roll the count back on failure.
+    pub fn answer_challenge(
+        conn: &mut PgConnection,
+        user: &User,
+        challenge_id: Uuid,
+        code: &str,
+    ) -> Result<bool, AuthError> {
+        let current = user_totp::table
+            .find(user.id)
+            .select(user_totp::challenge_id)
+            .first::<Option<Uuid>>(conn)
+            .optional()
+            .map_err(|_| Self::query_error())?
+            .flatten();
+
+        if current != Some(challenge_id) {
+            return Err(AuthError {
+                message: "Challenge is used up or was replaced; log in again".to_string(),
+                code: "INVALID_CHALLENGE".to_string(),
+            });
+        }
+
+        Self::count_attempt(conn, user.id)?;
+        if !Self::verify_second_factor(conn, user, code)? {
+            return Ok(false);
+        }
+
+        let cleared = diesel::update(
+            user_totp::table
+                .find(user.id)
+                .filter(user_totp::challenge_id.eq(challenge_id)),
+        )
+        .set(user_totp::challenge_id.eq(None::<Uuid>))
+        .execute(conn)
+        .map_err(|_| Self::query_error())?;
+
+        Ok(cleared > 0)
+    }
+
+    /// State recorded in the audit log; the secret itself never is
+    fn audit_snapshot(enabled: bool) -> serde_json::Value {
+        serde_json::json!({ "enabled": enabled })
+    }
+
+    fn require_enabled(conn: &mut PgConnection, user: &User) -> Result<(), AuthError> {
+        if Self::is_enabled(conn, user.id)? {
+            Ok(())
+        } else {
+            Err(AuthError {
+                message: "Two-factor authentication is not enabled".to_string(),
+                code: "TWO_FACTOR_NOT_ENABLED".to_string(),
+            })
+        }
+    }
+
+    fn find_totp(conn: &mut PgConnection, user_id: Uuid) -> Result<Option<UserTotp>, AuthError> {
+        user_totp::table
+            .find(user_id)
+            .select(UserTotp::as_select())
+            .first(conn)
+            .optional()
+            .map_err(|_| Self::query_error())
+    }
+
+    /// Check a TOTP code and record its time step so it cannot be used again
+    fn verify_totp(
+        conn: &mut PgConnection,
+        record: &UserTotp,
+        email: &str,
+        code: &str,
+    ) -> Result<bool, AuthError> {
+        let totp = Self::build_totp(&record.secret, email)?;
+        let current_step = Utc::now().timestamp() / TOTP_STEP as i64;
+
+        let matched_step = (-TOTP_SKEW..=TOTP_SKEW)
+            .map(|offset| current_step + offset)
+            .filter(|step| record.last_used_step.is_none_or(|last| *step > last))
+            .find(|step| totp.generate(*step as u64 * TOTP_STEP) == code);
+
+        let Some(step) = matched_step else {
+            return Ok(false);
+        };
+
+        // Only one of several requests with the same code may record its step
+        let recorded = diesel::update(
+            user_totp::table
+                .find(record.user_id)
+                .filter(user_totp::last_used_step.is_null().or(user_totp::last_used_step.lt(step))),
+        )
+        .set(user_totp::last_used_step.eq(step))
+        .execute(conn)
+        .map_err(|_| Self::query_error())?;
+
+        Ok(recorded > 0)
+    }
+
+    fn consume_recovery_code(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<bool, AuthError> {
+        let consumed = diesel::update(
+            recovery_codes::table
+                .filter(recovery_codes::user_id.eq(user_id))
+                .filter(recovery_codes::code_hash.eq(Self::hash_recovery_code(code)))
+                .filter(recovery_codes::used_at.is_null()),
+        )
+        .set(recovery_codes::used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+        .map_err(|_| Self::query_error())?;
+
+        Ok(consumed > 0)
+    }
+
+    fn replace_recovery_codes(conn: &mut PgConnection, user_id: Uuid) -> Result<Vec<String>, AuthError> {
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+            .execute(conn)
+            .map_err(|_| Self::query_error())?;
+
+        let now = Utc::now().naive_utc();
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| Self::generate_recovery_code())
+            .collect();
+        let records: Vec<RecoveryCode> = codes
+            .iter()
+            .map(|code| RecoveryCode {
+                id: Uuid::new_v4(),
+                user_id,
+                code_hash: Self::hash_recovery_code(code),
+                used_at: None,
+                created_at: now,
+            })
+            .collect();
+
+        diesel::insert_into(recovery_codes::table)
+            .values(&records)
+            .execute(conn)
+            .map_err(|_| Self::query_error())?;
+
+        Ok(codes)
+    }
+
+    /// Random code formatted as `xxxxx-xxxxx` for readability
+    fn generate_recovery_code() -> String {
+        let mut rng = rand::rng();
+        let chars: String = (0..10)
+            .map(|_| RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+            .collect();
+        format!("{}-{}", &chars[..5], &chars[5..])
+    }
+
+    /// Recovery codes are high-entropy random values, so a fast hash is
+    /// enough and lets us look them up directly instead of bcrypt-scanning.
+    fn hash_recovery_code(code: &str) -> String {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        format!("{:x}", Sha256::digest(normalized.as_bytes()))
+    }
+
+    fn build_totp(secret: &str, email: &str) -> Result<TOTP, AuthError> {
+        let secret_bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|_| Self::totp_error())?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            TOTP_SKEW as u8,
+            TOTP_STEP,
+            secret_bytes,
+            Some(config::get_totp_issuer()),
+            email.to_string(),
+        )
+        .map_err(|_| Self::totp_error())
+    }
+
+    fn connection(pool: &DbPool) -> Result<DbConnection, AuthError> {
+        pool.get().map_err(|_| AuthError {
+            message: "Database connection failed".to_string(),
+            code: "DB_CONNECTION_ERROR".to_string(),
+        })
+    }
+
+    fn invalid_code() -> AuthError {
+        AuthError {
+            message: "Invalid two-factor code".to_string(),
+            code: "INVALID_TWO_FACTOR_CODE".to_string(),
+        }
+    }
+
+    fn query_error() -> AuthError {
+        AuthError {
+            message: "Database query failed".to_string(),
+            code: "DB_QUERY_ERROR".to_string(),
+        }
+    }
+
+    fn totp_error() -> AuthError {
+        AuthError {
+            message: "Failed to set up TOTP".to_string(),
+            code: "TOTP_ERROR".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_database;
+
+    /// A user with TOTP enabled and a fresh set of recovery codes
+    fn enrolled(conn: &mut PgConnection) -> (User, Vec<String>) {
+        let user = test_database::user(conn);
+        let secret = match Secret::generate_secret().to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => unreachable!(),
+        };
+        let now = Utc::now().naive_utc();
+        diesel::insert_into(user_totp::table)
+            .values(UserTotp {
+                user_id: user.id,
+                secret,
+                enabled: true,
+                last_used_step: None,
+                created_at: now,
+                updated_at: now,
+                challenge_id: None,
+                failed_attempts: 0,
+                locked_until: None,
+            })
+            .execute(conn)
+            .unwrap();
+        let codes = TwoFactorService::replace_recovery_codes(conn, user.id).unwrap();
+        (user, codes)
+    }
+
+    fn current_code(conn: &mut PgConnection, user: &User) -> String {
+        let record = TwoFactorService::find_totp(conn, user.id).unwrap().unwrap();
+        TwoFactorService::build_totp(&record.secret, &user.email)
+            .unwrap()
+            .generate(Utc::now().timestamp() as u64)
+    }
+
+    fn check(conn: &mut PgConnection, user: &User, code: &str) -> Result<bool, AuthError> {
+        TwoFactorService::count_attempt(conn, user.id)?;
+        TwoFactorService::verify_second_factor(conn, user, code)
+    }
+
+    #[test]
+    fn lockout_starts_after_the_free_attempts_and_doubles() {
+        assert_eq!(TwoFactorService::lockout(1), None);
+        assert_eq!(TwoFactorService::lockout(FREE_CODE_ATTEMPTS - 1), None);
+        assert_eq!(TwoFactorService::lockout(FREE_CODE_ATTEMPTS), Some(Duration::seconds(FIRST_LOCKOUT_SECONDS)));
+        assert_eq!(TwoFactorService::lockout(FREE_CODE_ATTEMPTS + 1), Some(Duration::seconds(2 * FIRST_LOCKOUT_SECONDS)));
+        assert_eq!(TwoFactorService::lockout(FREE_CODE_ATTEMPTS + 3), Some(Duration::seconds(8 * FIRST_LOCKOUT_SECONDS)));
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(TwoFactorService::lockout(FREE_CODE_ATTEMPTS + 20), Some(Duration::seconds(MAX_LOCKOUT_SECONDS)));
+        assert_eq!(TwoFactorService::lockout(i32::MAX), Some(Duration::seconds(MAX_LOCKOUT_SECONDS)));
+    }
+
+    #[test]
+    fn new_logins_do_not_reset_the_lockout() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, _) = enrolled(&mut conn);
+
+        for _ in 0..FREE_CODE_ATTEMPTS {
+            let challenge = TwoFactorService::start_challenge(&mut conn, user.id).unwrap();
+            assert!(!TwoFactorService::answer_challenge(&mut conn, &user, challenge, "wrong-guess").unwrap());
+        }
+
+        let challenge = TwoFactorService::start_challenge(&mut conn, user.id).unwrap();
+        let code = current_code(&mut conn, &user);
+        let error = TwoFactorService::answer_challenge(&mut conn, &user, challenge, &code).unwrap_err();
+        assert_eq!(error.code, "TWO_FACTOR_LOCKED");
+    }
+
+    #[test]
+    fn every_check_of_a_code_shares_the_lockout() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, codes) = enrolled(&mut conn);
+
+        for _ in 0..FREE_CODE_ATTEMPTS {
+            assert!(!check(&mut conn, &user, "wrong-guess").unwrap());
+        }
+        assert_eq!(check(&mut conn, &user, &codes[0]).unwrap_err().code, "TWO_FACTOR_LOCKED");
+
+        let challenge = TwoFactorService::start_challenge(&mut conn, user.id).unwrap();
+        let error = TwoFactorService::answer_challenge(&mut conn, &user, challenge, &codes[0]).unwrap_err();
+        assert_eq!(error.code, "TWO_FACTOR_LOCKED");
+    }
+
+    #[test]
+    fn codes_can_be_tried_again_once_the_wait_is_over() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, codes) = enrolled(&mut conn);
+
+        for _ in 0..FREE_CODE_ATTEMPTS {
+            assert!(!check(&mut conn, &user, "wrong-guess").unwrap());
+        }
+        diesel::update(user_totp::table.find(user.id))
+            .set(user_totp::locked_until.eq(Utc::now().naive_utc() - Duration::seconds(1)))
+            .execute(&mut conn)
+            .unwrap();
+
+        assert!(check(&mut conn, &user, &codes[0]).unwrap());
+        let record = TwoFactorService::find_totp(&mut conn, user.id).unwrap().unwrap();
+        assert_eq!(record.failed_attempts, 0);
+        assert_eq!(record.locked_until, None);
+    }
+
+    #[test]
+    fn correct_code_clears_failed_attempts() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, codes) = enrolled(&mut conn);
+
+        for _ in 1..FREE_CODE_ATTEMPTS {
+            assert!(!check(&mut conn, &user, "wrong-guess").unwrap());
+        }
+        assert!(check(&mut conn, &user, &codes[0]).unwrap());
+        for _ in 1..FREE_CODE_ATTEMPTS {
+            assert!(!check(&mut conn, &user, "wrong-guess").unwrap());
+        }
+        assert!(check(&mut conn, &user, &codes[1]).unwrap());
+    }
+
+    #[test]
+    fn recovery_codes_work_once() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, codes) = enrolled(&mut conn);
+
+        assert!(check(&mut conn, &user, &codes[0]).unwrap());
+        assert!(!check(&mut conn, &user, &codes[0]).unwrap());
+        assert!(check(&mut conn, &user, &codes[1].to_uppercase().replace('-', " ")).unwrap());
+    }
+
+    #[test]
+    fn totp_codes_cannot_be_replayed() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, _) = enrolled(&mut conn);
+
+        let code = current_code(&mut conn, &user);
+        assert!(check(&mut conn, &user, &code).unwrap());
+        assert!(!check(&mut conn, &user, &code).unwrap());
+    }
+
+    #[test]
+    fn challenge_is_used_up_by_a_correct_answer() {
+        let Some(mut conn) = test_database::connection() else { return };
+        let (user, codes) = enrolled(&mut conn);
+
+        let replaced = TwoFactorService::start_challenge(&mut conn, user.id).unwrap();
+        let challenge = TwoFactorService::start_challenge(&mut conn, user.id).unwrap();
+        let error = TwoFactorService::answer_challenge(&mut conn, &user, replaced, &codes[0]).unwrap_err();
+        assert_eq!(error.code, "INVALID_CHALLENGE");
+
+        assert!(TwoFactorService::answer_challenge(&mut conn, &user, challenge, &codes[0]).unwrap());
+        let error = TwoFactorService::answer_challenge(&mut conn, &user, challenge, &codes[1]).unwrap_err();
+        assert_eq!(error.code, "INVALID_CHALLENGE");
+    }
+}
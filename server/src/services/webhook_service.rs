@@ -0,0 +1,649 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::config::errors::AppError;
+use crate::database::db_connection::DbConnection;
+use crate::models::audit::{entity, AuditChange, AuditContext};
+use crate::models::notification::Notification;
+use crate::models::schema::{webhook_deliveries, webhooks};
+use crate::models::webhook::{
+    CreatedWebhook, DeliveryRun, DeliveryStatus, NewWebhook, UpdateWebhook, Webhook, WebhookDelivery, WebhookDeliveryQuery, WebhookEvent,
+};
+use crate::services::audit_service;
+
+pub const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+pub const MAX_DELIVERY_LIMIT: i64 = 200;
+
+const SECRET_PREFIX: &str = "whsec_";
+const SECRET_RANDOM_LENGTH: usize = 32;
+const SECRET_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Attempts before a delivery is given up on
+const MAX_ATTEMPTS: i32 = 10;
+/// Wait after the first failed attempt; it doubles after every further one,
+/// so the last attempt is made about four hours after the first
+const FIRST_RETRY_DELAY_SECONDS: i64 = 30;
+/// Deliveries sent per run of the delivery job
+const DELIVERY_BATCH_SIZE: i64 = 50;
+/// A claimed delivery is tried again after this long if the server stopped
+/// before its attempt was recorded
+const CLAIM_TIMEOUT_SECONDS: i64 = 5 * 60;
+const REQUEST_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+pub fn get_webhooks(connection: &mut DbConnection, user_id: Uuid) -> Result<Vec<Webhook>, diesel::result::Error> {
+    webhooks::table
+        .filter(webhooks::user_id.eq(user_id))
+        .order(webhooks::created_at.asc())
+        .select(Webhook::as_select())
+        .load(connection)
+}
+
+/// One of the user's webhooks; those of other users are reported as not
+/// found
+pub fn get_webhook(connection: &mut DbConnection, user_id: Uuid, webhook_id: Uuid) -> Result<Webhook, diesel::result::Error> {
+    webhooks::table
+        .find(webhook_id)
+        .filter(webhooks::user_id.eq(user_id))
+        .select(Webhook::as_select())
+        .first(connection)
+}
+
+pub fn create_webhook(connection: &mut DbConnection, user_id: Uuid, new_webhook: NewWebhook, audit: &AuditContext) -> Result<CreatedWebhook, AppError> {
+    let url = validate_url(&new_webhook.url)?;
+    let events = validate_events(new_webhook.events)?;
+
+    let now = Utc::now().naive_utc();
+    let secret = generate_secret();
+    let webhook = connection.transaction(|connection| {
+        let webhook = diesel::insert_into(webhooks::table)
+            .values(Webhook {
+                id: Uuid::new_v4(),
+                user_id,
+                url,
+                secret: secret.clone(),
+                events,
+                enabled: true,
+                created_at: now,
+                updated_at: now,
+            })
+            .returning(Webhook::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::created(entity::WEBHOOK, webhook.id, user_id, &webhook))?;
+        Ok::<_, diesel::result::Error>(webhook)
+    })?;
+
+    Ok(CreatedWebhook { secret, webhook })
+}
+
+pub fn update_webhook(connection: &mut DbConnection, user_id: Uuid, webhook_id: Uuid, mut update_webhook: UpdateWebhook, audit: &AuditContext) -> Result<Webhook, AppError> {
+    if let Some(url) = &update_webhook.url {
+        update_webhook.url = Some(validate_url(url)?);
+    }
+    if let Some(events) = update_webhook.events.take() {
+        update_webhook.events = Some(validate_events(events)?);
+    }
+
+    connection.transaction(|connection| {
+        let before: Webhook = webhooks::table
+            .find(webhook_id)
+            .filter(webhooks::user_id.eq(user_id))
+            .select(Webhook::as_select())
+            .for_update()
+            .first(connection)?;
+
+        let webhook = diesel::update(webhooks::table.find(webhook_id))
+            .set((update_webhook, webhooks::updated_at.eq(Utc::now().naive_utc())))
+            .returning(Webhook::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::updated(entity::WEBHOOK, webhook.id, user_id, &before, &webhook))?;
+        Ok(webhook)
+    })
+}
+
+/// Delete a webhook together with its delivery log
+pub fn delete_webhook(connection: &mut DbConnection, user_id: Uuid, webhook_id: Uuid, audit: &AuditContext) -> Result<Webhook, diesel::result::Error> {
+    connection.transaction(|connection| {
+        let webhook = diesel::delete(webhooks::table.find(webhook_id).filter(webhooks::user_id.eq(user_id)))
+            .returning(Webhook::as_returning())
+            .get_result(connection)?;
+
+        audit_service::record(connection, audit, AuditChange::deleted(entity::WEBHOOK, webhook.id, user_id, &webhook))?;
+        Ok(webhook)
+    })
+}
+
+/// Deliveries of one of the user's webhooks, newest first
+pub fn get_deliveries(connection: &mut DbConnection, user_id: Uuid, webhook_id: Uuid, query: &WebhookDeliveryQuery) -> Result<Vec<WebhookDelivery>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT);
+    if !(1..=MAX_DELIVERY_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!("limit must be between 1 and {}", MAX_DELIVERY_LIMIT)));
+    }
+    let webhook = get_webhook(connection, user_id, webhook_id)?;
+
+    let mut deliveries = webhook_deliveries::table
+        .filter(webhook_deliveries::webhook_id.eq(webhook.id))
+        .select(WebhookDelivery::as_select())
+        .into_boxed();
+    if let Some(status) = query.status {
+        deliveries = deliveries.filter(webhook_deliveries::status.eq(status));
+    }
+
+    Ok(deliveries
+        .order(webhook_deliveries::created_at.desc())
+        .limit(limit)
+        .load(connection)?)
+}
+
+/// Queue a delivery again right away, with a fresh set of attempts
+pub fn retry_delivery(connection: &mut DbConnection, user_id: Uuid, webhook_id: Uuid, delivery_id: Uuid) -> Result<WebhookDelivery, AppError> {
+    connection.transaction(|connection| {
+        let webhook = get_webhook(connection, user_id, webhook_id)?;
+        let delivery: WebhookDelivery = webhook_deliveries::table
+            .find(delivery_id)
+            .filter(webhook_deliveries::webhook_id.eq(webhook.id))
+            .select(WebhookDelivery::as_select())
+            .for_update()
+            .first(connection)?;
+        if delivery.status == DeliveryStatus::Pending {
+            return Err(AppError::Conflict("Delivery is already queued".to_string()));
+        }
+
+        Ok(diesel::update(webhook_deliveries::table.find(delivery.id))
+            .set((
+                webhook_deliveries::status.eq(DeliveryStatus::Pending),
+                webhook_deliveries::attempts.eq(0),
+                webhook_deliveries::next_attempt_at.eq(Utc::now().naive_utc()),
+            ))
+            .returning(WebhookDelivery::as_returning())
+            .get_result(connection)?)
+    })
+}
+
+/// Queue deliveries of an audited change to the owner's webhooks that
+/// subscribed to it. Called from [`audit_service::record`], so deliveries
+/// are queued in the same transaction as the change.
+pub fn enqueue(connection: &mut PgConnection, change: &AuditChange) -> Result<(), diesel::result::Error> {
+    let (Some(event), Some(owner_id)) = (WebhookEvent::for_change(change.entity_type, change.action), change.owner_id) else {
+        return Ok(());
+    };
+    queue(connection, owner_id, event, &change.before, &change.after)
+}
+
+/// Queue deliveries of a new notification to the user's webhooks that
+/// subscribed to `notification.created`
+pub fn enqueue_notification(connection: &mut PgConnection, notification: &Notification) -> Result<(), diesel::result::Error> {
+    let data = serde_json::to_value(notification).ok();
+    queue(connection, notification.user_id, WebhookEvent::NotificationCreated, &None, &data)
+}
+
+fn queue(connection: &mut PgConnection, owner_id: Uuid, event: WebhookEvent, before: &Option<serde_json::Value>, after: &Option<serde_json::Value>) -> Result<(), diesel::result::Error> {
+    let subscribed: Vec<Uuid> = webhooks::table
+        .filter(webhooks::user_id.eq(owner_id))
+        .filter(webhooks::enabled.eq(true))
+        .filter(webhooks::events.contains(vec![event]))
+        .select(webhooks::id)
+        .load(connection)?;
+    if subscribed.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    let deliveries: Vec<WebhookDelivery> = subscribed
+        .into_iter()
+        .map(|webhook_id| {
+            let id = Uuid::new_v4();
+            let payload = payload(id, event, now, before, after);
+            WebhookDelivery {
+                id,
+                webhook_id,
+                event,
+                payload,
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                next_attempt_at: Some(now),
+                last_attempt_at: None,
+                response_status: None,
+                last_error: None,
+                delivered_at: None,
+                created_at: now,
+            }
+        })
+        .collect();
+
+    diesel::insert_into(webhook_deliveries::table)
+        .values(&deliveries)
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Send the deliveries that are due and record the outcome of each attempt
+pub fn deliver_due(connection: &mut DbConnection, now: NaiveDateTime) -> Result<DeliveryRun, diesel::result::Error> {
+    // Claim the deliveries first so the requests are not made while holding
+    // row locks
+    let claimed: Vec<(WebhookDelivery, String, String)> = connection.transaction(|connection| {
+        let due: Vec<(WebhookDelivery, String, String)> = webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending))
+            .filter(webhook_deliveries::next_attempt_at.le(now))
+            .filter(webhooks::enabled.eq(true))
+            .order(webhook_deliveries::next_attempt_at.asc())
+            .limit(DELIVERY_BATCH_SIZE)
+            .select((WebhookDelivery::as_select(), webhooks::url, webhooks::secret))
+            .for_update()
+            .skip_locked()
+            .load(connection)?;
+
+        let ids: Vec<Uuid> = due.iter().map(|(delivery, _, _)| delivery.id).collect();
+        diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(ids)))
+            .set(webhook_deliveries::next_attempt_at.eq(now + Duration::seconds(CLAIM_TIMEOUT_SECONDS)))
+            .execute(connection)?;
+        Ok::<_, diesel::result::Error>(due)
+    })?;
+
+    let mut run = DeliveryRun::default();
+    for (delivery, url, secret) in claimed {
+        // Resolved again for every attempt, as the address behind a name can
+        // change after the webhook was registered
+        let attempt = match resolve(&url) {
+            Ok(address) => send(&agent(address), &url, &secret, &delivery, Utc::now().timestamp()),
+            Err(error) => Attempt {
+                response_status: None,
+                error: Some(error),
+            },
+        };
+        let attempted_at = Utc::now().naive_utc();
+        let attempts = delivery.attempts + 1;
+
+        let (status, next_attempt_at) = if attempt.error.is_none() {
+            run.delivered += 1;
+            (DeliveryStatus::Delivered, None)
+        } else {
+            match retry_delay(attempts) {
+                Some(delay) => {
+                    run.retrying += 1;
+                    (DeliveryStatus::Pending, Some(attempted_at + delay))
+                }
+                None => {
+                    run.failed += 1;
+                    (DeliveryStatus::Failed, None)
+                }
+            }
+        };
+
+        diesel::update(webhook_deliveries::table.find(delivery.id))
+            .set((
+                webhook_deliveries::status.eq(status),
+                webhook_deliveries::attempts.eq(attempts),
+                webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+                webhook_deliveries::last_attempt_at.eq(attempted_at),
+                webhook_deliveries::response_status.eq(attempt.response_status),
+                webhook_deliveries::last_error.eq(attempt.error),
+                webhook_deliveries::delivered_at.eq((status == DeliveryStatus::Delivered).then_some(attempted_at)),
+            ))
+            .execute(connection)?;
+    }
+
+    Ok(run)
+}
+
+/// Body of an event as it is posted to webhooks and streamed to live
+/// dashboards: the record after the change, or before it for deletions, and
+/// for updates also its `previous` version. For `notification.created` the
+/// record is the notification.
+pub fn payload(id: Uuid, event: WebhookEvent, created_at: NaiveDateTime, before: &Option<serde_json::Value>, after: &Option<serde_json::Value>) -> serde_json::Value {
+    let mut payload = json!({
+        "id": id,
+        "event": event,
+        "created_at": created_at,
+        "data": if event.is_deletion() { before } else { after },
+    });
+    if event.is_update() {
+        payload["previous"] = before.clone().unwrap_or_default();
+    }
+    payload
+}
+
+/// Wait before the next attempt after `attempts` failed ones, or `None` once
+/// the delivery is given up on
+fn retry_delay(attempts: i32) -> Option<Duration> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    Some(Duration::seconds(FIRST_RETRY_DELAY_SECONDS << (attempts - 1).max(0)))
+}
+
+/// Result of posting a delivery once
+#[derive(Debug)]
+struct Attempt {
+    response_status: Option<i32>,
+    /// `None` when the receiver answered with a 2xx status
+    error: Option<String>,
+}
+
+/// Agent that only connects to `address`, whatever the URL's host resolves
+/// to by the time of the request, so a name cannot be pointed at an
+/// internal address after it was checked
+fn agent(address: SocketAddr) -> ureq::Agent {
+    // Redirects are not followed, so a delivery only goes to the registered
+    // URL
+    ureq::AgentBuilder::new()
+        .timeout(REQUEST_TIMEOUT)
+        .redirects(0)
+        .resolver(move |_: &str| -> io::Result<Vec<SocketAddr>> { Ok(vec![address]) })
+        .build()
+}
+
+/// Post a delivery, signed with the webhook's secret
+///
+/// The `Webhook-Signature` header is `sha256=` followed by the hex
+/// HMAC-SHA256 of `<Webhook-Timestamp>.<body>`, so receivers can check both
+/// the sender and that the request is recent.
+fn send(agent: &ureq::Agent, url: &str, secret: &str, delivery: &WebhookDelivery, timestamp: i64) -> Attempt {
+    let body = delivery.payload.to_string();
+    let signature = sign(secret, timestamp, &body);
+
+    let result = agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .set("Webhook-Id", &delivery.id.to_string())
+        .set("Webhook-Event", delivery.event.as_str())
+        .set("Webhook-Timestamp", &timestamp.to_string())
+        .set("Webhook-Signature", &format!("sha256={}", signature))
+        .send_string(&body);
+
+    // Only the status is kept: the receiver's answer is shown to whoever
+    // registered the URL and must not leak what a server would not
+    // otherwise tell them
+    match result {
+        Ok(response) if (200..300).contains(&response.status()) => Attempt {
+            response_status: Some(response.status() as i32),
+            error: None,
+        },
+        Ok(response) | Err(ureq::Error::Status(_, response)) => Attempt {
+            response_status: Some(response.status() as i32),
+            error: Some(format!("Receiver answered with status {}", response.status())),
+        },
+        Err(ureq::Error::Transport(transport)) => Attempt {
+            response_status: None,
+            error: Some(format!("Request failed: {}", transport.kind())),
+        },
+    }
+}
+
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Check that `url` is an http or https URL of a public address. Also used
+/// for the webhook channel of notifications.
+pub fn validate_url(url: &str) -> Result<String, AppError> {
+    let url = url.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://")) || url.contains(char::is_whitespace) {
+        return Err(AppError::Validation("url must be an http or https URL".to_string()));
+    }
+    resolve(url).map_err(AppError::Validation)?;
+    Ok(url.to_string())
+}
+
+/// The address to send to for `url`, provided that every address its host
+/// resolves to is public
+fn resolve(url: &str) -> Result<SocketAddr, String> {
+    let parsed = url::Url::parse(url).map_err(|_| "url is not a valid URL".to_string())?;
+    let (Some(host), Some(port)) = (parsed.host(), parsed.port_or_known_default()) else {
+        return Err("url must have a host".to_string());
+    };
+
+    let addresses: Vec<SocketAddr> = match host {
+        url::Host::Ipv4(ip) => vec![SocketAddr::new(ip.into(), port)],
+        url::Host::Ipv6(ip) => vec![SocketAddr::new(ip.into(), port)],
+        url::Host::Domain(domain) => (domain, port)
+            .to_socket_addrs()
+            .map_err(|_| format!("Could not resolve {}", domain))?
+            .collect(),
+    };
+    if addresses.iter().any(|address| !is_public(address.ip())) {
+        return Err("url must not point to a loopback, private, link-local or otherwise internal address".to_string());
+    }
+    addresses.first().copied().ok_or_else(|| format!("Could not resolve {}", host))
+}
+
+/// Whether an address is reachable on the internet rather than only from
+/// inside the server's own networks
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // "This network", carrier-grade NAT and reserved ranges
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public(IpAddr::V4(mapped)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+fn validate_events(events: Vec<WebhookEvent>) -> Result<Vec<WebhookEvent>, AppError> {
+    let mut unique: Vec<WebhookEvent> = Vec::new();
+    for event in events {
+        if !unique.contains(&event) {
+            unique.push(event);
+        }
+    }
+    if unique.is_empty() {
+        return Err(AppError::Validation("At least one event is required".to_string()));
+    }
+    Ok(unique)
+}
+
+fn generate_secret() -> String {
+    let mut rng = rand::rng();
+    let random: String = (0..SECRET_RANDOM_LENGTH)
+        .map(|_| SECRET_ALPHABET[rng.random_range(0..SECRET_ALPHABET.len())] as char)
+        .collect();
+    format!("{}{}", SECRET_PREFIX, random)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+
+    /// A request as seen by the test receiver
+    struct Received {
+        headers: HashMap<String, String>,
+        body: String,
+    }
+
+    /// Start an HTTP receiver on a free local port that answers one request
+    /// with `status` and hands the request over. Loopback receivers are
+    /// refused by [`resolve`], so tests send to the address directly.
+    fn receiver(status: &'static str) -> (String, SocketAddr, mpsc::Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let url = format!("http://receiver.test:{}/hook", address.port());
+        let (sender, received) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let Some((name, value)) = line.trim_end().split_once(':') else {
+                    break;
+                };
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            let length: usize = headers.get("content-length").map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut stream = stream;
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\nno", status).unwrap();
+            sender.send(Received { headers, body: String::from_utf8(body).unwrap() }).unwrap();
+        });
+
+        (url, address, received)
+    }
+
+    fn delivery() -> WebhookDelivery {
+        let now = Utc::now().naive_utc();
+        WebhookDelivery {
+            id: Uuid::new_v4(),
+            webhook_id: Uuid::new_v4(),
+            event: WebhookEvent::ExpenseCreated,
+            payload: json!({ "event": "expense.created", "data": { "item_name": "Coffee", "amount": 3.5 } }),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            response_status: None,
+            last_error: None,
+            delivered_at: None,
+            created_at: now,
+        }
+    }
+
+    #[test]
+    fn delivers_signed_payload() {
+        let (url, address, received) = receiver("204 No Content");
+        let delivery = delivery();
+
+        let attempt = send(&agent(address), &url, "whsec_test", &delivery, 1_700_000_000);
+        let request = received.recv().unwrap();
+
+        assert_eq!(attempt.response_status, Some(204));
+        assert!(attempt.error.is_none());
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&request.body).unwrap(), delivery.payload);
+        assert_eq!(request.headers["webhook-event"], "expense.created");
+        assert_eq!(request.headers["webhook-id"], delivery.id.to_string());
+        assert_eq!(request.headers["webhook-timestamp"], "1700000000");
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"whsec_test").unwrap();
+        mac.update(format!("1700000000.{}", request.body).as_bytes());
+        let expected = mac.finalize().into_bytes();
+        let signature = request.headers["webhook-signature"].strip_prefix("sha256=").unwrap();
+        assert_eq!(signature, format!("{:x}", expected));
+    }
+
+    #[test]
+    fn records_error_status() {
+        let (url, address, received) = receiver("503 Service Unavailable");
+
+        let attempt = send(&agent(address), &url, "whsec_test", &delivery(), 1_700_000_000);
+        received.recv().unwrap();
+
+        assert_eq!(attempt.response_status, Some(503));
+        // The receiver's body is not kept
+        assert_eq!(attempt.error.as_deref(), Some("Receiver answered with status 503"));
+    }
+
+    #[test]
+    fn does_not_follow_redirects() {
+        let (url, address, received) = receiver("302 Found");
+
+        let attempt = send(&agent(address), &url, "whsec_test", &delivery(), 1_700_000_000);
+        received.recv().unwrap();
+
+        assert_eq!(attempt.response_status, Some(302));
+        assert!(attempt.error.is_some());
+    }
+
+    #[test]
+    fn records_connection_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let attempt = send(&agent(address), &format!("http://{}/hook", address), "whsec_test", &delivery(), 1_700_000_000);
+
+        assert_eq!(attempt.response_status, None);
+        assert!(attempt.error.is_some());
+    }
+
+    #[test]
+    fn backs_off_exponentially_then_gives_up() {
+        assert_eq!(retry_delay(1), Some(Duration::seconds(30)));
+        assert_eq!(retry_delay(2), Some(Duration::seconds(60)));
+        assert_eq!(retry_delay(5), Some(Duration::seconds(480)));
+        assert_eq!(retry_delay(MAX_ATTEMPTS - 1), Some(Duration::seconds(7680)));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://10.1.2.3/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://100.64.0.1/hook",
+            "http://224.0.0.1/hook",
+            "http://255.255.255.255/hook",
+            "http://[::1]/hook",
+            "http://[::]/hook",
+            "http://[fe80::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[ff02::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[::ffff:169.254.169.254]/hook",
+        ] {
+            assert!(resolve(url).is_err(), "{} was accepted", url);
+            assert!(matches!(validate_url(url), Err(AppError::Validation(_))), "{} was accepted", url);
+        }
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        assert_eq!(resolve("https://93.184.216.34/hook"), Ok("93.184.216.34:443".parse().unwrap()));
+        assert_eq!(resolve("http://93.184.216.34:8080/hook"), Ok("93.184.216.34:8080".parse().unwrap()));
+        assert_eq!(resolve("https://[2606:2800:220:1::1]/hook"), Ok("[2606:2800:220:1::1]:443".parse().unwrap()));
+    }
+
+    #[test]
+    fn pinned_agent_ignores_what_the_host_resolves_to() {
+        // `receiver.test` does not resolve at all; the agent connects to the
+        // pinned address anyway
+        let (url, address, received) = receiver("200 OK");
+
+        let attempt = send(&agent(address), &url, "whsec_test", &delivery(), 1_700_000_000);
+        let request = received.recv().unwrap();
+
+        assert!(attempt.error.is_none());
+        assert_eq!(request.headers["host"], format!("receiver.test:{}", address.port()));
+    }
+}
@@ -1,10 +1,13 @@
-import { Component, OnInit } from '@angular/core';
+import { Component, OnDestroy, OnInit } from '@angular/core';
 import { CommonModule } from '@angular/common';
 import { RouterModule, ActivatedRoute } from '@angular/router';
 import { FormsModule } from '@angular/forms';
+import { HttpErrorResponse } from '@angular/common/http';
+import { Subscription } from 'rxjs';
 import { ExpenseService, Expense, NewExpense, UpdateExpense } from '../../services/expense.service';
 import { IncomeService, Income, NewIncome, UpdateIncome } from '../../services/income.service';
 import { User, UserWithIncomes, UserService } from '../../services/user.service';
+import { LiveEvent, LiveEventService } from '../../services/live-event.service';
 import { AddIncomeComponent } from '../add-income/add-income.component';
 import { AddExpenseComponent } from '../add-expense/add-expense.component';
 
@@ -14,7 +17,7 @@ import { AddExpenseComponent } from '../add-expense/add-expense.component';
   imports: [CommonModule, RouterModule, FormsModule, AddIncomeComponent, AddExpenseComponent],
   templateUrl: './dashboard.component.html'
 })
-export class DashboardComponent implements OnInit {
+export class DashboardComponent implements OnInit, OnDestroy {
   currentUser: User | null = null;
   expenses: Expense[] = [];
   incomes: Income[] = [];
@@ -32,11 +35,18 @@ export class DashboardComponent implements OnInit {
   showEditIncomeModal = false;
   editExpenseForm: Partial<Expense> = {};
   editIncomeForm: Partial<Income> = {};
+  // ETag of the record being edited, sent back as If-Match when saving
+  editEtag: string | null = null;
+  editError: string | null = null;
+
+  // Reloads what changed elsewhere, e.g. in another tab or through the API
+  private liveEvents: Subscription | null = null;
 
   constructor(
     private expenseService: ExpenseService,
     private incomeService: IncomeService,
     private userService: UserService,
+    private liveEventService: LiveEventService,
     private route: ActivatedRoute
   ) {}
 
@@ -50,6 +60,20 @@ export class DashboardComponent implements OnInit {
         console.error('No user ID found in route parameters');
       }
     });
+    this.liveEvents = this.liveEventService.events().subscribe(event => this.onLiveEvent(event));
+  }
+
+  ngOnDestroy() {
+    this.liveEvents?.unsubscribe();
+  }
+
+  onLiveEvent(event: LiveEvent) {
+    if (!this.currentUser) return;
+    if (event.type.startsWith('income.')) {
+      this.loadUserData(this.currentUser.id);
+    } else {
+      this.loadExpenses(this.currentUser.id);
+    }
   }
 
   loadUserData(userId: string) {
@@ -118,26 +142,48 @@ export class DashboardComponent implements OnInit {
 
   // Edit Expense
   startEditExpense(expense: Expense) {
-    this.editingExpense = { ...expense };
-    this.editExpenseForm = { ...expense };
-    this.showEditExpenseModal = true;
+    this.editError = null;
+    this.loadExpenseForEdit(expense.id, () => { this.showEditExpenseModal = true; });
+  }
+
+  private loadExpenseForEdit(expenseId: string, done: () => void) {
+    this.expenseService.getExpense(expenseId).subscribe({
+      next: ({ value, etag }) => {
+        this.editingExpense = value;
+        this.editExpenseForm = { ...value };
+        this.editEtag = etag;
+        done();
+      },
+      error: (error) => {
+        console.error('Error loading expense:', error);
+      }
+    });
   }
 
   saveEditExpense() {
-    if (!this.editingExpense) return;
+    if (!this.editingExpense || !this.editEtag) return;
     const update: UpdateExpense = {
       item_name: this.editExpenseForm.item_name,
       amount: this.editExpenseForm.amount,
       date: this.editExpenseForm.date,
       description: this.editExpenseForm.description
     };
-    this.expenseService.updateExpense(this.editingExpense.id, update).subscribe({
+    const expenseId = this.editingExpense.id;
+    this.expenseService.updateExpense(expenseId, update, this.editEtag).subscribe({
       next: () => {
         if (this.currentUser) this.loadExpenses(this.currentUser.id);
         this.cancelEditExpense();
       },
-      error: (error) => {
-        console.error('Error updating expense:', error);
+      error: (error: HttpErrorResponse) => {
+        if (error.status === 412) {
+          // Someone else saved first: show their version instead of overwriting it
+          this.loadExpenseForEdit(expenseId, () => {
+            this.editError = 'This expense was changed elsewhere. The latest version is shown; make your changes again.';
+          });
+        } else {
+          console.error('Error updating expense:', error);
+          this.editError = 'The expense could not be saved.';
+        }
       }
     });
   }
@@ -145,6 +191,8 @@ export class DashboardComponent implements OnInit {
   cancelEditExpense() {
     this.editingExpense = null;
     this.editExpenseForm = {};
+    this.editEtag = null;
+    this.editError = null;
     this.showEditExpenseModal = false;
   }
 
@@ -163,26 +211,48 @@ export class DashboardComponent implements OnInit {
 
   // Edit Income
   startEditIncome(income: Income) {
-    this.editingIncome = { ...income };
-    this.editIncomeForm = { ...income };
-    this.showEditIncomeModal = true;
+    this.editError = null;
+    this.loadIncomeForEdit(income.id, () => { this.showEditIncomeModal = true; });
+  }
+
+  private loadIncomeForEdit(incomeId: string, done: () => void) {
+    this.incomeService.getIncome(incomeId).subscribe({
+      next: ({ value, etag }) => {
+        this.editingIncome = value;
+        this.editIncomeForm = { ...value };
+        this.editEtag = etag;
+        done();
+      },
+      error: (error) => {
+        console.error('Error loading income:', error);
+      }
+    });
   }
 
   saveEditIncome() {
-    if (!this.editingIncome) return;
+    if (!this.editingIncome || !this.editEtag) return;
     const update: UpdateIncome = {
       source: this.editIncomeForm.source,
       amount: this.editIncomeForm.amount,
       date: this.editIncomeForm.date,
       description: this.editIncomeForm.description
     };
-    this.incomeService.updateIncome(this.editingIncome.id, update).subscribe({
+    const incomeId = this.editingIncome.id;
+    this.incomeService.updateIncome(incomeId, update, this.editEtag).subscribe({
       next: () => {
         if (this.currentUser) this.loadUserData(this.currentUser.id);
         this.cancelEditIncome();
       },
-      error: (error) => {
-        console.error('Error updating income:', error);
+      error: (error: HttpErrorResponse) => {
+        if (error.status === 412) {
+          // Someone else saved first: show their version instead of overwriting it
+          this.loadIncomeForEdit(incomeId, () => {
+            this.editError = 'This income was changed elsewhere. The latest version is shown; make your changes again.';
+          });
+        } else {
+          console.error('Error updating income:', error);
+          this.editError = 'The income could not be saved.';
+        }
       }
     });
   }
@@ -190,6 +260,8 @@ export class DashboardComponent implements OnInit {
   cancelEditIncome() {
     this.editingIncome = null;
     this.editIncomeForm = {};
+    this.editEtag = null;
+    this.editError = null;
     this.showEditIncomeModal = false;
   }
 
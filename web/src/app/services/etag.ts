@@ -0,0 +1,17 @@
+import { HttpHeaders, HttpResponse } from '@angular/common/http';
+
+// A response body with the ETag to send back as If-Match when updating it
+export interface WithEtag<T> {
+  value: T;
+  etag: string | null;
+}
+
+export function withEtag<T>(response: HttpResponse<T>): WithEtag<T> {
+  return { value: response.body as T, etag: response.headers.get('ETag') };
+}
+
+// Updates are refused with 428 without If-Match, and with 412 when the
+// record changed since `etag` was read
+export function ifMatch(etag: string): HttpHeaders {
+  return new HttpHeaders({ 'If-Match': etag });
+}
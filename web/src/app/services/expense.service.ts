@@ -1,7 +1,8 @@
 import { Injectable } from '@angular/core';
 import { HttpClient } from '@angular/common/http';
-import { Observable } from 'rxjs';
+import { Observable, map } from 'rxjs';
 import { environment } from '../../environments/environment';
+import { WithEtag, ifMatch, withEtag } from './etag';
 
 export interface Expense {
   id: string;
@@ -38,28 +39,39 @@ export class ExpenseService {
 
   constructor(private http: HttpClient) {}
 
-  // GET /api/expenses (all expenses)
+  // GET /api/v1/expenses (all expenses)
   getAllExpenses(): Observable<Expense[]> {
-    return this.http.get<Expense[]>(`${this.apiUrl}/api/expenses`);
+    return this.http.get<Expense[]>(`${this.apiUrl}/api/v1/expenses`);
   }
 
-  // GET /api/expenses/user/{user_id}
+  // GET /api/v1/users/{user_id}/expenses
   getExpensesByUserId(userId: string): Observable<Expense[]> {
-    return this.http.get<Expense[]>(`${this.apiUrl}/api/expenses/${userId}`);
+    return this.http.get<Expense[]>(`${this.apiUrl}/api/v1/users/${userId}/expenses`);
   }
 
-  // POST /api/expenses
-  createExpense(expense: NewExpense): Observable<Expense> {
-    return this.http.post<Expense>(`${this.apiUrl}/api/expenses`, expense);
+  // GET /api/v1/expenses/{expense_id}
+  getExpense(expenseId: string): Observable<WithEtag<Expense>> {
+    return this.http
+      .get<Expense>(`${this.apiUrl}/api/v1/expenses/${expenseId}`, { observe: 'response' })
+      .pipe(map(withEtag));
   }
 
-  // PUT /api/expenses/{expense_id}
-  updateExpense(expenseId: string, update: UpdateExpense): Observable<Expense> {
-    return this.http.put<Expense>(`${this.apiUrl}/api/expenses/${expenseId}`, update);
+  // POST /api/v1/expenses
+  createExpense(expense: NewExpense): Observable<WithEtag<Expense>> {
+    return this.http
+      .post<Expense>(`${this.apiUrl}/api/v1/expenses`, expense, { observe: 'response' })
+      .pipe(map(withEtag));
   }
 
-  // DELETE /api/expenses/{expense_id}
+  // PUT /api/v1/expenses/{expense_id}, only while the expense still has the version `etag`
+  updateExpense(expenseId: string, update: UpdateExpense, etag: string): Observable<WithEtag<Expense>> {
+    return this.http
+      .put<Expense>(`${this.apiUrl}/api/v1/expenses/${expenseId}`, update, { observe: 'response', headers: ifMatch(etag) })
+      .pipe(map(withEtag));
+  }
+
+  // DELETE /api/v1/expenses/{expense_id}
   deleteExpense(expenseId: string): Observable<void> {
-    return this.http.delete<void>(`${this.apiUrl}/api/expenses/${expenseId}`);
+    return this.http.delete<void>(`${this.apiUrl}/api/v1/expenses/${expenseId}`);
   }
 } 
\ No newline at end of file
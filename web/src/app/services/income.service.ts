@@ -1,7 +1,8 @@
 import { Injectable } from '@angular/core';
 import { HttpClient } from '@angular/common/http';
-import { Observable } from 'rxjs';
+import { Observable, map } from 'rxjs';
 import { environment } from '../../environments/environment';
+import { WithEtag, ifMatch, withEtag } from './etag';
 
 export interface Income {
   id: string;
@@ -38,28 +39,39 @@ export class IncomeService {
 
   constructor(private http: HttpClient) {}
 
-  // GET /api/incomes (all incomes)
+  // GET /api/v1/incomes (all incomes)
   getAllIncomes(): Observable<Income[]> {
-    return this.http.get<Income[]>(`${this.apiUrl}/api/incomes`);
+    return this.http.get<Income[]>(`${this.apiUrl}/api/v1/incomes`);
   }
 
-  // GET /api/incomes/user/{user_id}
+  // GET /api/v1/users/{user_id}/incomes
   getIncomesByUserId(userId: string): Observable<Income[]> {
-    return this.http.get<Income[]>(`${this.apiUrl}/api/incomes/user/${userId}`);
+    return this.http.get<Income[]>(`${this.apiUrl}/api/v1/users/${userId}/incomes`);
   }
 
-  // POST /api/incomes
-  createIncome(income: NewIncome): Observable<Income> {
-    return this.http.post<Income>(`${this.apiUrl}/api/incomes`, income);
+  // GET /api/v1/incomes/{income_id}
+  getIncome(incomeId: string): Observable<WithEtag<Income>> {
+    return this.http
+      .get<Income>(`${this.apiUrl}/api/v1/incomes/${incomeId}`, { observe: 'response' })
+      .pipe(map(withEtag));
   }
 
-  // PUT /api/incomes/{income_id}
-  updateIncome(incomeId: string, update: UpdateIncome): Observable<Income> {
-    return this.http.put<Income>(`${this.apiUrl}/api/incomes/${incomeId}`, update);
+  // POST /api/v1/incomes
+  createIncome(income: NewIncome): Observable<WithEtag<Income>> {
+    return this.http
+      .post<Income>(`${this.apiUrl}/api/v1/incomes`, income, { observe: 'response' })
+      .pipe(map(withEtag));
   }
 
-  // DELETE /api/incomes/{income_id}
+  // PUT /api/v1/incomes/{income_id}, only while the income still has the version `etag`
+  updateIncome(incomeId: string, update: UpdateIncome, etag: string): Observable<WithEtag<Income>> {
+    return this.http
+      .put<Income>(`${this.apiUrl}/api/v1/incomes/${incomeId}`, update, { observe: 'response', headers: ifMatch(etag) })
+      .pipe(map(withEtag));
+  }
+
+  // DELETE /api/v1/incomes/{income_id}
   deleteIncome(incomeId: string): Observable<void> {
-    return this.http.delete<void>(`${this.apiUrl}/api/incomes/${incomeId}`);
+    return this.http.delete<void>(`${this.apiUrl}/api/v1/incomes/${incomeId}`);
   }
 } 
\ No newline at end of file
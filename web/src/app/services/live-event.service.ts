@@ -0,0 +1,110 @@
+import { Injectable } from '@angular/core';
+import { HttpClient } from '@angular/common/http';
+import { Observable } from 'rxjs';
+import { environment } from '../../environments/environment';
+
+export type LiveEventType =
+  | 'income.created'
+  | 'income.updated'
+  | 'income.deleted'
+  | 'income.restored'
+  | 'expense.created'
+  | 'expense.updated'
+  | 'expense.deleted'
+  | 'expense.restored';
+
+const EVENT_TYPES: LiveEventType[] = [
+  'income.created',
+  'income.updated',
+  'income.deleted',
+  'income.restored',
+  'expense.created',
+  'expense.updated',
+  'expense.deleted',
+  'expense.restored'
+];
+
+// Wait before reconnecting after the stream was lost
+const RECONNECT_DELAY_MS = 3000;
+
+export interface LiveEvent {
+  id: string;
+  type: LiveEventType;
+  // Same JSON body as a webhook delivery of the event
+  data: unknown;
+}
+
+export interface EventStreamToken {
+  token: string;
+  expires_in: number;
+}
+
+@Injectable({
+  providedIn: 'root'
+})
+export class LiveEventService {
+  private apiUrl = environment.apiUrl;
+
+  constructor(private http: HttpClient) {}
+
+  // POST /api/events/token
+  createStreamToken(): Observable<EventStreamToken> {
+    return this.http.post<EventStreamToken>(`${this.apiUrl}/api/events/token`, null);
+  }
+
+  // GET /api/events?token=...&after=...
+  //
+  // Changes to the current user's incomes and expenses as they are committed.
+  // EventSource cannot send an Authorization header, so every connection
+  // opens with a new short-lived stream token. When the stream is lost it
+  // reconnects with the last event's ID as `after`, which replays the changes
+  // missed in between.
+  events(): Observable<LiveEvent> {
+    return new Observable<LiveEvent>(subscriber => {
+      let source: EventSource | null = null;
+      let lastEventId: string | null = null;
+      let retry: ReturnType<typeof setTimeout> | null = null;
+      let closed = false;
+
+      const reconnect = () => {
+        source?.close();
+        source = null;
+        if (!closed) {
+          retry = setTimeout(connect, RECONNECT_DELAY_MS);
+        }
+      };
+
+      const connect = () => {
+        this.createStreamToken().subscribe({
+          next: ({ token }) => {
+            if (closed) return;
+            const params = new URLSearchParams({ token });
+            if (lastEventId) {
+              params.set('after', lastEventId);
+            }
+            source = new EventSource(`${this.apiUrl}/api/events?${params}`);
+            for (const type of EVENT_TYPES) {
+              source.addEventListener(type, event => {
+                const message = event as MessageEvent<string>;
+                lastEventId = message.lastEventId;
+                subscriber.next({ id: message.lastEventId, type, data: JSON.parse(message.data) });
+              });
+            }
+            // The browser would retry with the expired token and without
+            // `after`, so reconnect here instead
+            source.onerror = reconnect;
+          },
+          error: reconnect
+        });
+      };
+
+      connect();
+
+      return () => {
+        closed = true;
+        if (retry) clearTimeout(retry);
+        source?.close();
+      };
+    });
+  }
+}